@@ -1,4 +1,56 @@
-use std::path::PathBuf;
+use crate::error::AppError;
+use crate::utils::common::TimeFormat;
+use crate::utils::regex::Regex;
+use crate::utils::tz::TimeZone;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// Default `max_output_bytes`: 10MB per stream
+const DEFAULT_MAX_OUTPUT_BYTES: u64 = 10 * 1024 * 1024;
+
+/// Default `io_buffer_size`: 64KB
+const DEFAULT_IO_BUFFER_SIZE: usize = 64 * 1024;
+
+/// Default `max_log_line_bytes`: 1MB
+const DEFAULT_MAX_LOG_LINE_BYTES: usize = 1024 * 1024;
+
+/// Default `http_idle_timeout`, in seconds
+const DEFAULT_HTTP_IDLE_TIMEOUT_SECS: u64 = 60;
+
+/// Default `log_broadcast_capacity`
+const DEFAULT_LOG_BROADCAST_CAPACITY: usize = 100;
+
+/// Default `temp_dir`, below the workspace
+const DEFAULT_TEMP_DIR_NAME: &str = ".devbox-tmp";
+
+/// Default `max_download_paths`
+const DEFAULT_MAX_DOWNLOAD_PATHS: usize = 1000;
+
+/// Default `max_download_bytes`: 10GB
+const DEFAULT_MAX_DOWNLOAD_BYTES: u64 = 10 * 1024 * 1024 * 1024;
+
+/// Default `max_subscriptions_per_socket`
+const DEFAULT_MAX_SUBSCRIPTIONS_PER_SOCKET: usize = 64;
+
+/// Owner label of requests made with the primary token, which may act on
+/// every process and session
+pub const ADMIN_LABEL: &str = "admin";
+
+/// Lowest and highest niceness accepted by setpriority(2)
+pub const NICE_MIN: i32 = -20;
+pub const NICE_MAX: i32 = 19;
+
+/// A named command template run by `process/run-preset`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ExecPreset {
+    pub command: String,
+    pub args: Option<Vec<String>>,
+    pub cwd: Option<String>,
+    pub env: Option<HashMap<String, String>>,
+    pub timeout: Option<u64>,
+}
 
 #[derive(Debug, Clone)]
 pub struct Config {
@@ -8,14 +60,103 @@ pub struct Config {
     /// Base workspace directory
     pub workspace_path: PathBuf,
 
+    /// Named workspaces selectable per request via the `X-Workspace` header
+    pub workspaces: HashMap<String, PathBuf>,
+
+    /// Directory for the process registry snapshot; unset disables persistence
+    pub state_dir: Option<PathBuf>,
+
+    /// Where uploads and atomic writes stage their data before it is renamed
+    /// into place; defaults to `.devbox-tmp` in the workspace
+    pub temp_dir: PathBuf,
+
+    /// Working directory for commands that don't specify one (relative to the
+    /// workspace); defaults to the workspace itself
+    pub default_cwd: Option<PathBuf>,
+
+    /// Shell command run once in the background after the server starts
+    /// listening, logged under the `startup` process ID
+    pub startup_command: Option<String>,
+
     /// Max file size in bytes
     pub max_file_size: u64,
 
+    /// Default cap on stdout and stderr captured by synchronous exec, each
+    pub max_output_bytes: u64,
+
+    /// Buffer size for streaming file contents in reads and downloads
+    pub io_buffer_size: usize,
+
     /// Authentication token
     pub token: Option<String>,
 
+    /// Accept requests without a token; no token is generated or checked
+    pub disable_auth: bool,
+
+    /// Extra tokens from `TOKENS`, each mapped to the label that owns the
+    /// processes and sessions started with it
+    pub tenant_tokens: HashMap<String, String>,
+
+    /// Close connections that go this long without a request being handled
+    /// or a byte being sent; `None` (0 on the command line) disables it
+    pub http_idle_timeout: Option<std::time::Duration>,
+
     /// Maximum concurrent file reads for search and replace operations
     pub max_concurrent_reads: usize,
+
+    /// Log lines buffered per process or session for live subscribers; slower
+    /// subscribers are told how many lines they missed
+    pub log_broadcast_capacity: usize,
+
+    /// Longest log line kept in one piece; longer output is split into
+    /// pieces of this size instead of being buffered until a newline
+    pub max_log_line_bytes: usize,
+
+    /// Pause reading a session's output while this many lines are still
+    /// queued for its slowest live subscriber; 0 disables backpressure
+    pub session_output_high_water: usize,
+
+    /// Niceness for background processes that don't set `nice`; unset keeps
+    /// the server's own
+    pub default_process_nice: Option<i32>,
+
+    /// Zone for timestamps in responses; UTC when unset
+    pub timezone: Option<TimeZone>,
+
+    /// Calendar dates or ISO week dates in response timestamps
+    pub time_format: TimeFormat,
+
+    /// Directory names skipped by search and find
+    pub search_ignored_dirs: Vec<String>,
+
+    /// Commands matching any of these patterns are refused
+    pub command_denylist: Vec<Regex>,
+
+    /// When non-empty, only commands matching one of these patterns may run
+    pub command_allowlist: Vec<Regex>,
+
+    /// Named command templates for `process/run-preset`
+    pub exec_presets: HashMap<String, ExecPreset>,
+
+    /// Ports hidden from `/ports` in addition to 22 and the listen port
+    pub excluded_ports: Vec<u16>,
+
+    /// API requests handled at once before new ones are refused with 503;
+    /// 0 means no limit
+    pub max_concurrent_requests: usize,
+
+    /// Most `paths` one `files/batch-download` request may name
+    pub max_download_paths: usize,
+
+    /// Largest total size of the files one batch download may cover
+    pub max_download_bytes: u64,
+
+    /// Log subscriptions one WebSocket connection may hold at once, each
+    /// backed by a forwarding task; 0 means no limit
+    pub max_subscriptions_per_socket: usize,
+
+    /// Suppress the informational startup and shutdown messages on stdout
+    pub quiet: bool,
 }
 
 impl Config {
@@ -24,62 +165,541 @@ impl Config {
         let mut workspace_path = PathBuf::from(
             std::env::var("WORKSPACE_PATH").unwrap_or_else(|_| "/home/devbox/project".to_string()),
         );
+        let mut workspaces = std::env::var("WORKSPACES")
+            .ok()
+            .map(|s| parse_workspaces(&s))
+            .unwrap_or_default();
+        let mut default_cwd = std::env::var("DEFAULT_CWD").ok().map(PathBuf::from);
+        let mut state_dir = std::env::var("STATE_DIR").ok().map(PathBuf::from);
+        let mut temp_dir = std::env::var("TEMP_DIR").ok().map(PathBuf::from);
+        let mut startup_command = std::env::var("STARTUP_COMMAND").ok();
         let mut max_file_size = std::env::var("MAX_FILE_SIZE")
             .ok()
             .and_then(|s| s.parse().ok())
             .unwrap_or(104857600);
+        let mut max_output_bytes = std::env::var("MAX_OUTPUT_BYTES")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(DEFAULT_MAX_OUTPUT_BYTES);
+        let mut io_buffer_size = std::env::var("IO_BUFFER_SIZE")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(DEFAULT_IO_BUFFER_SIZE);
         let mut token = std::env::var("TOKEN")
             .or_else(|_| std::env::var("DEVBOX_JWT_SECRET"))
             .ok();
 
+        let mut http_idle_timeout = std::env::var("HTTP_IDLE_TIMEOUT")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(DEFAULT_HTTP_IDLE_TIMEOUT_SECS);
+
         let mut max_concurrent_reads = std::env::var("MAX_CONCURRENT_READS")
             .ok()
             .and_then(|s| s.parse().ok())
             .unwrap_or(4);
 
+        let mut log_broadcast_capacity = std::env::var("LOG_BROADCAST_CAPACITY")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(DEFAULT_LOG_BROADCAST_CAPACITY);
+
+        let mut max_log_line_bytes = std::env::var("MAX_LOG_LINE_BYTES")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(DEFAULT_MAX_LOG_LINE_BYTES);
+
+        let mut session_output_high_water = std::env::var("SESSION_OUTPUT_HIGH_WATER")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(0);
+
+        let mut default_process_nice = std::env::var("DEFAULT_PROCESS_NICE")
+            .ok()
+            .and_then(|s| s.parse().ok());
+
+        let mut timezone = std::env::var("TIMEZONE").ok();
+
+        let mut time_format = std::env::var("TIME_FORMAT").unwrap_or_default();
+
+        let mut search_ignored_dirs = std::env::var("SEARCH_IGNORED_DIRS")
+            .ok()
+            .map(|s| parse_ignored_dirs(&s))
+            .unwrap_or_else(default_ignored_dirs);
+
+        let mut command_denylist = std::env::var("COMMAND_DENYLIST").unwrap_or_default();
+        let mut command_allowlist = std::env::var("COMMAND_ALLOWLIST").unwrap_or_default();
+
+        let mut exec_presets = std::env::var("EXEC_PRESETS").unwrap_or_default();
+        let mut excluded_ports = std::env::var("EXCLUDED_PORTS").unwrap_or_default();
+        let mut max_concurrent_requests = std::env::var("MAX_CONCURRENT_REQUESTS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(0);
+        let mut max_download_paths = std::env::var("MAX_DOWNLOAD_PATHS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(DEFAULT_MAX_DOWNLOAD_PATHS);
+        let mut max_download_bytes = std::env::var("MAX_DOWNLOAD_BYTES")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(DEFAULT_MAX_DOWNLOAD_BYTES);
+        let mut max_subscriptions_per_socket = std::env::var("MAX_SUBSCRIPTIONS_PER_SOCKET")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(DEFAULT_MAX_SUBSCRIPTIONS_PER_SOCKET);
+        let mut quiet = std::env::var("QUIET").is_ok_and(|v| parse_bool(&v));
+        let mut disable_auth = std::env::var("DISABLE_AUTH").is_ok_and(|v| parse_bool(&v));
+        let mut tenant_tokens = std::env::var("TOKENS").unwrap_or_default();
+
         // Check command line args for overrides (simple implementation)
         for arg in std::env::args() {
-            if arg.starts_with("--addr=") {
+            if arg == "--quiet" {
+                quiet = true;
+            } else if arg == "--disable-auth" {
+                disable_auth = true;
+            } else if arg.starts_with("--addr=") {
                 addr = arg.trim_start_matches("--addr=").to_string();
             } else if arg.starts_with("--token=") {
                 token = Some(arg.trim_start_matches("--token=").to_string());
+            } else if arg.starts_with("--tokens=") {
+                tenant_tokens = arg.trim_start_matches("--tokens=").to_string();
             } else if arg.starts_with("--workspace-path=") {
                 workspace_path = PathBuf::from(arg.trim_start_matches("--workspace-path="));
+            } else if arg.starts_with("--workspaces=") {
+                workspaces = parse_workspaces(arg.trim_start_matches("--workspaces="));
+            } else if arg.starts_with("--default-cwd=") {
+                default_cwd = Some(PathBuf::from(arg.trim_start_matches("--default-cwd=")));
+            } else if arg.starts_with("--state-dir=") {
+                state_dir = Some(PathBuf::from(arg.trim_start_matches("--state-dir=")));
+            } else if arg.starts_with("--temp-dir=") {
+                temp_dir = Some(PathBuf::from(arg.trim_start_matches("--temp-dir=")));
+            } else if arg.starts_with("--startup-command=") {
+                startup_command = Some(arg.trim_start_matches("--startup-command=").to_string());
             } else if arg.starts_with("--max-file-size=") {
                 if let Ok(size) = arg.trim_start_matches("--max-file-size=").parse::<u64>() {
                     max_file_size = size;
                 }
+            } else if arg.starts_with("--max-output-bytes=") {
+                if let Ok(bytes) = arg.trim_start_matches("--max-output-bytes=").parse::<u64>() {
+                    max_output_bytes = bytes;
+                }
+            } else if arg.starts_with("--io-buffer-size=") {
+                if let Ok(size) = arg.trim_start_matches("--io-buffer-size=").parse::<usize>() {
+                    io_buffer_size = size;
+                }
+            } else if arg.starts_with("--http-idle-timeout=") {
+                if let Ok(secs) = arg
+                    .trim_start_matches("--http-idle-timeout=")
+                    .parse::<u64>()
+                {
+                    http_idle_timeout = secs;
+                }
             } else if arg.starts_with("--max-concurrent-reads=") {
                 if let Ok(reads) = arg.trim_start_matches("--max-concurrent-reads=").parse::<usize>() {
                     max_concurrent_reads = reads;
                 }
+            } else if arg.starts_with("--log-broadcast-capacity=") {
+                if let Ok(capacity) = arg
+                    .trim_start_matches("--log-broadcast-capacity=")
+                    .parse::<usize>()
+                {
+                    log_broadcast_capacity = capacity;
+                }
+            } else if arg.starts_with("--max-log-line-bytes=") {
+                if let Ok(bytes) = arg
+                    .trim_start_matches("--max-log-line-bytes=")
+                    .parse::<usize>()
+                {
+                    max_log_line_bytes = bytes;
+                }
+            } else if arg.starts_with("--session-output-high-water=") {
+                if let Ok(lines) = arg
+                    .trim_start_matches("--session-output-high-water=")
+                    .parse::<usize>()
+                {
+                    session_output_high_water = lines;
+                }
+            } else if arg.starts_with("--default-process-nice=") {
+                if let Ok(nice) = arg
+                    .trim_start_matches("--default-process-nice=")
+                    .parse::<i32>()
+                {
+                    default_process_nice = Some(nice);
+                }
+            } else if arg.starts_with("--timezone=") {
+                timezone = Some(arg.trim_start_matches("--timezone=").to_string());
+            } else if arg.starts_with("--time-format=") {
+                time_format = arg.trim_start_matches("--time-format=").to_string();
+            } else if arg.starts_with("--search-ignored-dirs=") {
+                search_ignored_dirs =
+                    parse_ignored_dirs(arg.trim_start_matches("--search-ignored-dirs="));
+            } else if arg.starts_with("--command-denylist=") {
+                command_denylist = arg.trim_start_matches("--command-denylist=").to_string();
+            } else if arg.starts_with("--command-allowlist=") {
+                command_allowlist = arg.trim_start_matches("--command-allowlist=").to_string();
+            } else if arg.starts_with("--exec-presets=") {
+                exec_presets = arg.trim_start_matches("--exec-presets=").to_string();
+            } else if arg.starts_with("--excluded-ports=") {
+                excluded_ports = arg.trim_start_matches("--excluded-ports=").to_string();
+            } else if arg.starts_with("--max-concurrent-requests=") {
+                if let Ok(requests) = arg
+                    .trim_start_matches("--max-concurrent-requests=")
+                    .parse::<usize>()
+                {
+                    max_concurrent_requests = requests;
+                }
+            } else if arg.starts_with("--max-download-paths=") {
+                if let Ok(paths) = arg
+                    .trim_start_matches("--max-download-paths=")
+                    .parse::<usize>()
+                {
+                    max_download_paths = paths;
+                }
+            } else if arg.starts_with("--max-download-bytes=") {
+                if let Ok(bytes) = arg
+                    .trim_start_matches("--max-download-bytes=")
+                    .parse::<u64>()
+                {
+                    max_download_bytes = bytes;
+                }
+            } else if arg.starts_with("--max-subscriptions-per-socket=") {
+                if let Ok(subscriptions) = arg
+                    .trim_start_matches("--max-subscriptions-per-socket=")
+                    .parse::<usize>()
+                {
+                    max_subscriptions_per_socket = subscriptions;
+                }
             }
         }
 
-        if let Some(ref t) = token {
-            let masked = if t.len() > 6 {
-                format!("{}******{}", &t[..3], &t[t.len() - 3..])
-            } else {
-                "******".to_string()
-            };
-            println!("Token loaded from environment/args: {}", masked);
+        let mut tenant_tokens =
+            parse_tenant_tokens(&tenant_tokens).unwrap_or_else(|e| invalid_setting("TOKENS", &e));
+
+        if disable_auth {
+            // On stderr even in quiet mode: an open server must not go unnoticed
+            eprintln!("==============================================================");
+            eprintln!("WARNING: Authentication is disabled (DISABLE_AUTH).");
+            eprintln!("Anyone who can reach {} can read and write files", addr);
+            eprintln!("and run commands. Only use this on a trusted network.");
+            if token.take().is_some() {
+                eprintln!("The configured token is ignored.");
+            }
+            if !std::mem::take(&mut tenant_tokens).is_empty() {
+                eprintln!("The configured TOKENS are ignored.");
+            }
+            eprintln!("==============================================================");
+        } else if let Some(ref t) = token {
+            if !quiet {
+                println!("Token loaded from environment/args: {}", mask_token(t));
+            }
         } else {
             let random_token = crate::utils::common::generate_id();
-            println!(
+            // Clients can't connect without it, so quiet mode moves it to stderr
+            let message = format!(
                 "No token provided. Generated temporary token: {}",
                 random_token
             );
+            if quiet {
+                eprintln!("{}", message);
+            } else {
+                println!("{}", message);
+            }
             token = Some(random_token);
         }
+        if token
+            .as_ref()
+            .is_some_and(|t| tenant_tokens.contains_key(t))
+        {
+            invalid_setting("TOKENS", "a labelled token must differ from TOKEN");
+        }
+
+        // A broadcast channel needs room for at least one message
+        let log_broadcast_capacity = log_broadcast_capacity.max(1);
+
+        let temp_dir = temp_dir
+            .filter(|p| !p.as_os_str().is_empty())
+            .unwrap_or_else(|| workspace_path.join(DEFAULT_TEMP_DIR_NAME));
 
         Config {
             addr,
             workspace_path,
+            workspaces,
+            state_dir,
+            temp_dir,
+            default_cwd,
+            startup_command: startup_command.filter(|c| !c.trim().is_empty()),
             max_file_size,
+            max_output_bytes,
+            // A zero-sized buffer would never make progress
+            io_buffer_size: io_buffer_size.max(1),
             token,
+            disable_auth,
+            tenant_tokens,
+            http_idle_timeout: (http_idle_timeout > 0)
+                .then(|| std::time::Duration::from_secs(http_idle_timeout)),
             max_concurrent_reads,
+            log_broadcast_capacity,
+            // Leaves room for a whole UTF-8 character in every piece
+            max_log_line_bytes: max_log_line_bytes.max(4),
+            // Subscribers already lag once the channel is full, so a higher mark never triggers
+            session_output_high_water: session_output_high_water.min(log_broadcast_capacity),
+            default_process_nice: default_process_nice.map(clamp_nice),
+            timezone: timezone.filter(|name| !name.is_empty()).map(|name| {
+                TimeZone::load(&name).unwrap_or_else(|e| invalid_setting("TIMEZONE", &e))
+            }),
+            time_format: TimeFormat::parse(&time_format)
+                .unwrap_or_else(|e| invalid_setting("TIME_FORMAT", &e)),
+            search_ignored_dirs,
+            command_denylist: parse_patterns(&command_denylist)
+                .unwrap_or_else(|e| invalid_setting("COMMAND_DENYLIST", &e)),
+            command_allowlist: parse_patterns(&command_allowlist)
+                .unwrap_or_else(|e| invalid_setting("COMMAND_ALLOWLIST", &e)),
+            exec_presets: parse_presets(&exec_presets)
+                .unwrap_or_else(|e| invalid_setting("EXEC_PRESETS", &e)),
+            excluded_ports: parse_ports(&excluded_ports)
+                .unwrap_or_else(|e| invalid_setting("EXCLUDED_PORTS", &e)),
+            max_concurrent_requests,
+            max_download_paths,
+            max_download_bytes,
+            max_subscriptions_per_socket,
+            quiet,
+        }
+    }
+
+    /// Base directory for a request: the named workspace if one is given,
+    /// otherwise the default `workspace_path`.
+    pub fn resolve_workspace(&self, name: Option<&str>) -> Result<&Path, AppError> {
+        match name {
+            None | Some("") => Ok(&self.workspace_path),
+            Some(name) => self
+                .workspaces
+                .get(name)
+                .map(PathBuf::as_path)
+                .ok_or_else(|| AppError::BadRequest(format!("Unknown workspace: {}", name))),
+        }
+    }
+
+    /// Refuse a command line that matches the denylist or, when an allowlist
+    /// is configured, matches none of its patterns.
+    pub fn check_command(&self, command_line: &str) -> Result<(), AppError> {
+        if let Some(pattern) = self
+            .command_denylist
+            .iter()
+            .find(|p| p.is_match(command_line))
+        {
+            return Err(AppError::Forbidden(format!(
+                "Command blocked by denylist pattern: {}",
+                pattern.as_str()
+            )));
+        }
+        if !self.command_allowlist.is_empty()
+            && !self
+                .command_allowlist
+                .iter()
+                .any(|p| p.is_match(command_line))
+        {
+            return Err(AppError::Forbidden(
+                "Command is not in the allowlist".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Clamp a niceness value into the range the kernel accepts.
+pub fn clamp_nice(nice: i32) -> i32 {
+    nice.clamp(NICE_MIN, NICE_MAX)
+}
+
+/// Redacted form of a token for logs and the config endpoint: the first and
+/// last three characters of long tokens, nothing of short ones.
+pub fn mask_token(token: &str) -> String {
+    if token.chars().count() > 6 {
+        let head: String = token.chars().take(3).collect();
+        let mut tail: Vec<char> = token.chars().rev().take(3).collect();
+        tail.reverse();
+        format!("{}******{}", head, tail.into_iter().collect::<String>())
+    } else {
+        "******".to_string()
+    }
+}
+
+/// Exit with a plain message rather than a panic backtrace: an operator
+/// reading it needs the pattern at fault, not the source location.
+fn invalid_setting(name: &str, error: &str) -> ! {
+    eprintln!("Invalid {}: {}", name, error);
+    std::process::exit(2);
+}
+
+/// Parse labelled tokens given as comma-separated `label:token` pairs, e.g.
+/// `ci:s3cret,alice:t0ken`, into a map of token to label.
+fn parse_tenant_tokens(value: &str) -> Result<HashMap<String, String>, String> {
+    let mut tokens = HashMap::new();
+    let entries = value.split(',').map(str::trim).filter(|e| !e.is_empty());
+    for (i, entry) in entries.enumerate() {
+        let (label, token) = entry
+            .split_once(':')
+            .map(|(l, t)| (l.trim(), t.trim()))
+            .filter(|(l, t)| !l.is_empty() && !t.is_empty())
+            // The entry itself may be a secret, so only its position is named
+            .ok_or_else(|| format!("entry {} is not of the form label:token", i + 1))?;
+        if label == ADMIN_LABEL {
+            return Err(format!("{:?} is reserved for TOKEN", ADMIN_LABEL));
+        }
+        if tokens.values().any(|l| l == label) {
+            return Err(format!("label {:?} is used twice", label));
+        }
+        if tokens
+            .insert(token.to_string(), label.to_string())
+            .is_some()
+        {
+            return Err(format!("the token of {:?} is used twice", label));
+        }
+    }
+    Ok(tokens)
+}
+
+/// Parse a comma-separated pattern list. Write `\,` for a literal comma
+/// inside a pattern; blank entries are ignored.
+fn parse_patterns(value: &str) -> Result<Vec<Regex>, String> {
+    let mut patterns = Vec::new();
+    let mut current = String::new();
+    let mut chars = value.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if chars.peek() == Some(&',') => {
+                current.push_str("\\,");
+                chars.next();
+            }
+            ',' => patterns.push(std::mem::take(&mut current)),
+            c => current.push(c),
+        }
+    }
+    patterns.push(current);
+    patterns
+        .iter()
+        .map(|p| p.trim())
+        .filter(|p| !p.is_empty())
+        .map(|p| Regex::new(p).map_err(|e| format!("{:?}: {}", p, e)))
+        .collect()
+}
+
+/// Parse presets given as a JSON object of name to preset, e.g.
+/// `{"test":{"command":"npm","args":["test"],"cwd":"web"}}`. Empty means none.
+fn parse_presets(value: &str) -> Result<HashMap<String, ExecPreset>, String> {
+    if value.trim().is_empty() {
+        return Ok(HashMap::new());
+    }
+    let presets: HashMap<String, ExecPreset> =
+        serde_json::from_str(value).map_err(|e| e.to_string())?;
+    if let Some(name) = presets.keys().find(|name| name.is_empty()) {
+        return Err(format!("preset name must not be empty: {:?}", name));
+    }
+    Ok(presets)
+}
+
+/// Parse a comma-separated port list (e.g. `9090,9464`); blank entries are
+/// ignored.
+fn parse_ports(value: &str) -> Result<Vec<u16>, String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|p| !p.is_empty())
+        .map(|p| p.parse().map_err(|_| format!("not a port number: {:?}", p)))
+        .collect()
+}
+
+/// Parse a comma-separated `name=path` list (e.g. `api=/srv/api,web=/srv/web`).
+/// Entries without a name or path are ignored.
+fn parse_workspaces(value: &str) -> HashMap<String, PathBuf> {
+    value
+        .split(',')
+        .filter_map(|entry| {
+            let (name, path) = entry.split_once('=')?;
+            let (name, path) = (name.trim(), path.trim());
+            if name.is_empty() || path.is_empty() {
+                return None;
+            }
+            Some((name.to_string(), PathBuf::from(path)))
+        })
+        .collect()
+}
+
+/// `1`, `true`, `yes` or `on` (any case) enable a boolean env var; anything
+/// else leaves it off.
+fn parse_bool(value: &str) -> bool {
+    parse_flag(value).unwrap_or(false)
+}
+
+/// A boolean written as `1`/`true`/`yes`/`on` or `0`/`false`/`no`/`off` (any
+/// case), or `None` for anything else, for callers that reject typos.
+pub fn parse_flag(value: &str) -> Option<bool> {
+    match value.trim().to_ascii_lowercase().as_str() {
+        "1" | "true" | "yes" | "on" => Some(true),
+        "0" | "false" | "no" | "off" => Some(false),
+        _ => None,
+    }
+}
+
+fn default_ignored_dirs() -> Vec<String> {
+    crate::handlers::file::search::IGNORED_DIRS
+        .iter()
+        .map(|d| d.to_string())
+        .collect()
+}
+
+/// Parse a comma-separated directory list. A leading `+` appends to the
+/// default list instead of replacing it (e.g. `+.terraform,.gradle`).
+fn parse_ignored_dirs(value: &str) -> Vec<String> {
+    let (mut dirs, list) = match value.strip_prefix('+') {
+        Some(rest) => (default_ignored_dirs(), rest),
+        None => (Vec::new(), value),
+    };
+    for name in list.split(',').map(str::trim).filter(|n| !n.is_empty()) {
+        if !dirs.iter().any(|d| d == name) {
+            dirs.push(name.to_string());
         }
     }
+    dirs
+}
+
+/// A fixed configuration for unit tests that need one.
+#[cfg(test)]
+pub(crate) fn test_config() -> Config {
+    Config {
+        addr: "0.0.0.0:9757".to_string(),
+        workspace_path: PathBuf::from("/home/devbox/project"),
+        workspaces: HashMap::new(),
+        state_dir: None,
+        temp_dir: PathBuf::from("/home/devbox/project/.devbox-tmp"),
+        default_cwd: None,
+        startup_command: None,
+        max_file_size: 1024,
+        max_output_bytes: 1024,
+        io_buffer_size: 1024,
+        token: None,
+        disable_auth: false,
+        tenant_tokens: HashMap::new(),
+        http_idle_timeout: None,
+        max_concurrent_reads: 1,
+        log_broadcast_capacity: 16,
+        max_log_line_bytes: 1024,
+        session_output_high_water: 0,
+        default_process_nice: None,
+        timezone: None,
+        time_format: TimeFormat::Calendar,
+        search_ignored_dirs: Vec::new(),
+        command_denylist: Vec::new(),
+        command_allowlist: Vec::new(),
+        exec_presets: HashMap::new(),
+        excluded_ports: Vec::new(),
+        max_concurrent_requests: 0,
+        max_download_paths: 1000,
+        max_download_bytes: 1 << 30,
+        max_subscriptions_per_socket: 64,
+        quiet: false,
+    }
 }
 
 #[cfg(test)]
@@ -125,8 +745,135 @@ mod tests {
         let config = Config::load();
         assert_eq!(config.token, Some("test_jwt_2".to_string()));
 
+        // 3. Disabling auth drops the token instead of generating one
+        env::set_var("DISABLE_AUTH", "true");
+        let config = Config::load();
+        assert!(config.disable_auth);
+        assert_eq!(config.token, None);
+        env::remove_var("DISABLE_AUTH");
+
         // Cleanup
         env::remove_var("TOKEN");
         env::remove_var("DEVBOX_JWT_SECRET");
     }
+
+    #[test]
+    fn test_parse_ignored_dirs() {
+        assert_eq!(parse_ignored_dirs("a, b,,a"), vec!["a", "b"]);
+
+        let augmented = parse_ignored_dirs("+.terraform");
+        assert!(augmented.iter().any(|d| d == "node_modules"));
+        assert!(augmented.iter().any(|d| d == ".terraform"));
+    }
+
+    #[test]
+    fn test_resolve_workspace() {
+        let workspaces = parse_workspaces("api=/srv/api, web = /srv/web,broken,=/x");
+        assert_eq!(workspaces.len(), 2);
+
+        let config = Config {
+            workspaces,
+            ..test_config()
+        };
+        assert_eq!(
+            config.resolve_workspace(None).unwrap(),
+            Path::new("/home/devbox/project")
+        );
+        assert_eq!(
+            config.resolve_workspace(Some("web")).unwrap(),
+            Path::new("/srv/web")
+        );
+        assert!(matches!(
+            config.resolve_workspace(Some("nope")),
+            Err(AppError::BadRequest(_))
+        ));
+    }
+
+    #[test]
+    fn test_check_command() {
+        let config = test_config();
+        assert!(config.check_command("rm -rf /").is_ok());
+
+        let config = Config {
+            command_denylist: parse_patterns(r"rm\s+-rf\s+/(\s|$), mkfs").unwrap(),
+            command_allowlist: parse_patterns(r"^(git|npm|rm|echo)( |$), ^printf a\,b").unwrap(),
+            ..test_config()
+        };
+        assert!(config.check_command("git status").is_ok());
+        assert!(config.check_command("printf a,b").is_ok());
+        assert!(config.check_command("rm -rf ./build").is_ok());
+        assert!(matches!(
+            config.check_command("rm -rf /"),
+            Err(AppError::Forbidden(_))
+        ));
+        assert!(matches!(
+            config.check_command("curl example.com"),
+            Err(AppError::Forbidden(_))
+        ));
+        assert!(parse_patterns("ok,(broken").is_err());
+    }
+
+    #[test]
+    fn test_parse_presets() {
+        let presets =
+            parse_presets(r#"{"test": {"command": "npm", "args": ["test"], "env": {"CI": "1"}}}"#)
+                .unwrap();
+        assert_eq!(presets["test"].command, "npm");
+        assert_eq!(
+            presets["test"].args.as_deref(),
+            Some(&["test".to_string()][..])
+        );
+        assert!(parse_presets("").unwrap().is_empty());
+        assert!(parse_presets(r#"{"x": {"args": []}}"#).is_err());
+        assert!(parse_presets(r#"{"x": {"command": "ls", "shell": true}}"#).is_err());
+    }
+
+    #[test]
+    fn test_parse_ports() {
+        assert_eq!(parse_ports(" 9090, ,9464").unwrap(), vec![9090, 9464]);
+        assert!(parse_ports("").unwrap().is_empty());
+        assert!(parse_ports("9090,metrics").is_err());
+        assert!(parse_ports("70000").is_err());
+    }
+
+    #[test]
+    fn test_parse_bool() {
+        assert!(parse_bool("1"));
+        assert!(parse_bool(" TRUE "));
+        assert!(parse_bool("yes"));
+        assert!(!parse_bool("0"));
+        assert!(!parse_bool(""));
+        assert!(!parse_bool("quiet"));
+
+        assert_eq!(parse_flag("False"), Some(false));
+        assert_eq!(parse_flag("0"), Some(false));
+        assert_eq!(parse_flag(" no "), Some(false));
+        assert_eq!(parse_flag("ON"), Some(true));
+        assert_eq!(parse_flag("nope"), None);
+        assert_eq!(parse_flag(""), None);
+    }
+
+    #[test]
+    fn test_parse_tenant_tokens() {
+        let tokens = parse_tenant_tokens("ci:abc, alice : xyz,").unwrap();
+        assert_eq!(tokens.len(), 2);
+        assert_eq!(tokens["abc"], "ci");
+        assert_eq!(tokens["xyz"], "alice");
+        assert!(parse_tenant_tokens("").unwrap().is_empty());
+
+        let err = parse_tenant_tokens("ci:abc,s3cret").unwrap_err();
+        assert!(err.contains("entry 2") && !err.contains("s3cret"));
+        assert!(parse_tenant_tokens("admin:abc").is_err());
+        assert!(parse_tenant_tokens("ci:abc,ci:def").is_err());
+        assert!(parse_tenant_tokens("ci:abc,cd:abc").is_err());
+        assert!(parse_tenant_tokens("ci:").is_err());
+    }
+
+    #[test]
+    fn test_mask_token() {
+        assert_eq!(mask_token("abcdefghij"), "abc******hij");
+        assert_eq!(mask_token("abcdef"), "******");
+        assert_eq!(mask_token(""), "******");
+        assert_eq!(mask_token("äöü-ßéèñ"), "äöü******éèñ");
+    }
 }
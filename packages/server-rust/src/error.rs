@@ -1,4 +1,5 @@
 use crate::response::{ApiResponse, Status};
+use crate::utils::validate::FieldError;
 use axum::{
     http::StatusCode,
     response::{IntoResponse, Response},
@@ -16,8 +17,14 @@ pub enum AppError {
     Unauthorized(String),
     Forbidden(String),
     Conflict(String),
-    Validation(String),
+    /// Fields that deserialized but failed their checks; listed as `errors`
+    Validation(Vec<FieldError>),
     OperationError(String, serde_json::Value),
+    /// A file was expected but the path is a directory; reported as a conflict
+    /// with `isDir: true` so clients can fall back to listing it
+    IsADirectory(String),
+    /// Filesystem or OS failure; the response carries the errno name as `errorCode`
+    Io(std::io::Error),
 }
 
 impl std::error::Error for AppError {}
@@ -31,8 +38,12 @@ impl fmt::Display for AppError {
             AppError::Unauthorized(msg) => write!(f, "Unauthorized: {}", msg),
             AppError::Forbidden(msg) => write!(f, "Forbidden: {}", msg),
             AppError::Conflict(msg) => write!(f, "Conflict: {}", msg),
-            AppError::Validation(msg) => write!(f, "Validation Error: {}", msg),
+            AppError::Validation(errors) => {
+                write!(f, "Validation Error: {}", describe_field_errors(errors))
+            }
             AppError::OperationError(msg, _) => write!(f, "Operation Error: {}", msg),
+            AppError::IsADirectory(msg) => write!(f, "Is a Directory: {}", msg),
+            AppError::Io(err) => write!(f, "IO Error: {}", err),
         }
     }
 }
@@ -46,8 +57,25 @@ impl IntoResponse for AppError {
             AppError::Unauthorized(msg) => (Status::Unauthorized, msg, json!({})),
             AppError::Forbidden(msg) => (Status::Forbidden, msg, json!({})),
             AppError::Conflict(msg) => (Status::Conflict, msg, json!({})),
-            AppError::Validation(msg) => (Status::ValidationError, msg, json!({})),
+            AppError::Validation(errors) => (
+                Status::ValidationError,
+                format!("Invalid request: {}", describe_field_errors(&errors)),
+                json!({ "errors": errors }),
+            ),
             AppError::OperationError(msg, data) => (Status::OperationError, msg, data),
+            AppError::IsADirectory(msg) => (Status::Conflict, msg, json!({ "isDir": true })),
+            AppError::Io(err) => {
+                let status = match err.kind() {
+                    std::io::ErrorKind::NotFound => Status::NotFound,
+                    std::io::ErrorKind::PermissionDenied => Status::Forbidden,
+                    _ => Status::InternalError,
+                };
+                let data = match errno_name(&err) {
+                    Some(code) => json!({ "errorCode": code }),
+                    None => json!({}),
+                };
+                (status, err.to_string(), data)
+            }
         };
 
         let body = Json(ApiResponse::error(status, message, data));
@@ -61,14 +89,24 @@ impl IntoResponse for AppError {
     }
 }
 
+fn describe_field_errors(errors: &[FieldError]) -> String {
+    errors
+        .iter()
+        .map(|e| format!("{} {}", e.field, e.message))
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+/// Symbolic errno name (`ENOENT`, `EACCES`, ...) for errors that came from the OS.
+fn errno_name(err: &std::io::Error) -> Option<String> {
+    let errno = nix::errno::Errno::from_raw(err.raw_os_error()?);
+    (errno != nix::errno::Errno::UnknownErrno).then(|| format!("{:?}", errno))
+}
+
 // Helper to convert standard errors to AppError
 impl From<std::io::Error> for AppError {
     fn from(err: std::io::Error) -> Self {
-        match err.kind() {
-            std::io::ErrorKind::NotFound => AppError::NotFound(err.to_string()),
-            std::io::ErrorKind::PermissionDenied => AppError::Forbidden(err.to_string()),
-            _ => AppError::InternalServerError(err.to_string()),
-        }
+        AppError::Io(err)
     }
 }
 
@@ -77,3 +115,54 @@ impl From<serde_json::Error> for AppError {
         AppError::BadRequest(format!("JSON error: {}", err))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn body_json(err: AppError) -> serde_json::Value {
+        let body = err.into_response().into_body();
+        let bytes = axum::body::to_bytes(body, usize::MAX).await.unwrap();
+        serde_json::from_slice(&bytes).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_io_error_codes() {
+        let missing = std::fs::read("/nonexistent/devbox").unwrap_err();
+        let body = body_json(missing.into()).await;
+        assert_eq!(body["status"], 1404);
+        assert_eq!(body["errorCode"], "ENOENT");
+
+        let is_dir = std::fs::read("/").unwrap_err();
+        let body = body_json(is_dir.into()).await;
+        assert_eq!(body["status"], 1500);
+        assert_eq!(body["errorCode"], "EISDIR");
+
+        // Errors without an OS errno carry no code
+        let body = body_json(std::io::Error::other("boom").into()).await;
+        assert_eq!(body["message"], "boom");
+        assert!(body.get("errorCode").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_is_a_directory() {
+        let body = body_json(AppError::IsADirectory("Path is a directory".to_string())).await;
+        assert_eq!(body["status"], 1409);
+        assert_eq!(body["isDir"], true);
+    }
+
+    #[tokio::test]
+    async fn test_validation_lists_fields() {
+        let errors = ["command", "timeout"].map(|field| FieldError {
+            field: field.to_string(),
+            message: "is wrong".to_string(),
+        });
+        let body = body_json(AppError::Validation(errors.to_vec())).await;
+        assert_eq!(body["status"], 1400);
+        assert_eq!(
+            body["message"],
+            "Invalid request: command is wrong; timeout is wrong"
+        );
+        assert_eq!(body["errors"][1]["field"], "timeout");
+    }
+}
@@ -0,0 +1,115 @@
+use crate::config::mask_token;
+use crate::response::ApiResponse;
+use crate::state::AppState;
+use axum::{extract::State, Json};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConfigResponse {
+    addr: String,
+    workspace_path: String,
+    workspaces: BTreeMap<String, String>,
+    state_dir: Option<String>,
+    temp_dir: String,
+    default_cwd: Option<String>,
+    startup_command: Option<String>,
+    max_file_size: u64,
+    max_output_bytes: u64,
+    io_buffer_size: usize,
+    /// Seconds; `null` when idle connections are kept open
+    http_idle_timeout: Option<u64>,
+    max_concurrent_reads: usize,
+    log_broadcast_capacity: usize,
+    max_log_line_bytes: usize,
+    session_output_high_water: usize,
+    default_process_nice: Option<i32>,
+    /// IANA zone timestamps are rendered in; `null` means UTC
+    timezone: Option<String>,
+    /// `calendar` or `iso-week`
+    time_format: &'static str,
+    search_ignored_dirs: Vec<String>,
+    command_denylist: Vec<String>,
+    command_allowlist: Vec<String>,
+    /// Preset names only; their env may carry secrets
+    exec_presets: Vec<String>,
+    /// Only the configured extras; 22 and the listen port are always excluded
+    excluded_ports: Vec<u16>,
+    /// 0 when requests are not limited
+    max_concurrent_requests: usize,
+    max_download_paths: usize,
+    max_download_bytes: u64,
+    /// 0 when subscriptions are not limited
+    max_subscriptions_per_socket: usize,
+    quiet: bool,
+    token_set: bool,
+    /// Every request is accepted without a token
+    disable_auth: bool,
+    /// Labels of the `TOKENS` entries; their tokens are never returned
+    token_labels: Vec<String>,
+    /// Masked the same way as the startup log; the token itself is never returned
+    token: Option<String>,
+}
+
+/// Report the configuration the server is running with, after env vars and
+/// command-line flags have been applied.
+pub async fn get_config(State(state): State<Arc<AppState>>) -> Json<ApiResponse<ConfigResponse>> {
+    let config = &state.config;
+    let display = |p: &std::path::Path| p.to_string_lossy().to_string();
+    Json(ApiResponse::success(ConfigResponse {
+        addr: config.addr.clone(),
+        workspace_path: display(&config.workspace_path),
+        workspaces: config
+            .workspaces
+            .iter()
+            .map(|(name, path)| (name.clone(), display(path)))
+            .collect(),
+        state_dir: config.state_dir.as_deref().map(display),
+        temp_dir: display(&config.temp_dir),
+        default_cwd: config.default_cwd.as_deref().map(display),
+        startup_command: config.startup_command.clone(),
+        max_file_size: config.max_file_size,
+        max_output_bytes: config.max_output_bytes,
+        io_buffer_size: config.io_buffer_size,
+        http_idle_timeout: config.http_idle_timeout.map(|t| t.as_secs()),
+        max_concurrent_reads: config.max_concurrent_reads,
+        log_broadcast_capacity: config.log_broadcast_capacity,
+        max_log_line_bytes: config.max_log_line_bytes,
+        session_output_high_water: config.session_output_high_water,
+        default_process_nice: config.default_process_nice,
+        timezone: config.timezone.as_ref().map(|tz| tz.name().to_string()),
+        time_format: config.time_format.as_str(),
+        search_ignored_dirs: config.search_ignored_dirs.clone(),
+        command_denylist: config
+            .command_denylist
+            .iter()
+            .map(|p| p.as_str().to_string())
+            .collect(),
+        command_allowlist: config
+            .command_allowlist
+            .iter()
+            .map(|p| p.as_str().to_string())
+            .collect(),
+        exec_presets: {
+            let mut names: Vec<String> = config.exec_presets.keys().cloned().collect();
+            names.sort();
+            names
+        },
+        excluded_ports: config.excluded_ports.clone(),
+        max_concurrent_requests: config.max_concurrent_requests,
+        max_download_paths: config.max_download_paths,
+        max_download_bytes: config.max_download_bytes,
+        max_subscriptions_per_socket: config.max_subscriptions_per_socket,
+        quiet: config.quiet,
+        token_set: config.token.is_some(),
+        disable_auth: config.disable_auth,
+        token_labels: {
+            let mut labels: Vec<String> = config.tenant_tokens.values().cloned().collect();
+            labels.sort();
+            labels
+        },
+        token: config.token.as_deref().map(mask_token),
+    }))
+}
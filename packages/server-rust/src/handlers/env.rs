@@ -0,0 +1,131 @@
+use crate::error::AppError;
+use crate::middleware::auth::Caller;
+use crate::response::ApiResponse;
+use crate::state::AppState;
+use crate::utils::validate::is_env_name;
+use axum::{extract::State, Json};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+
+#[derive(Deserialize)]
+pub struct SetEnvDefaultsRequest {
+    env: HashMap<String, String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EnvDefaultsResponse {
+    env: BTreeMap<String, String>,
+}
+
+/// Replace the default environment that `process/exec`, `exec-sync`,
+/// `sync-stream` and `sessions/create` layer their own `env` over. It applies
+/// to every token, so only the admin may change it.
+pub async fn set_env_defaults(
+    State(state): State<Arc<AppState>>,
+    caller: Caller,
+    Json(req): Json<SetEnvDefaultsRequest>,
+) -> Result<Json<ApiResponse<EnvDefaultsResponse>>, AppError> {
+    caller.check_admin("change the default environment")?;
+    if let Some(key) = req.env.keys().find(|k| !is_env_name(k)) {
+        return Err(AppError::BadRequest(format!(
+            "Invalid environment variable name: {:?}",
+            key
+        )));
+    }
+    let env = req
+        .env
+        .iter()
+        .map(|(k, v)| (k.clone(), v.clone()))
+        .collect();
+    *state.env_defaults.write().await = req.env;
+    Ok(Json(ApiResponse::success(EnvDefaultsResponse { env })))
+}
+
+pub async fn get_env_defaults(
+    State(state): State<Arc<AppState>>,
+) -> Json<ApiResponse<EnvDefaultsResponse>> {
+    let defaults = state.env_defaults.read().await;
+    let env = defaults
+        .iter()
+        .map(|(k, v)| (k.clone(), v.clone()))
+        .collect();
+    Json(ApiResponse::success(EnvDefaultsResponse { env }))
+}
+
+pub async fn clear_env_defaults(
+    State(state): State<Arc<AppState>>,
+    caller: Caller,
+) -> Result<Json<ApiResponse<EnvDefaultsResponse>>, AppError> {
+    caller.check_admin("change the default environment")?;
+    state.env_defaults.write().await.clear();
+    Ok(Json(ApiResponse::success(EnvDefaultsResponse {
+        env: BTreeMap::new(),
+    })))
+}
+
+/// A request's `env` with the stored defaults underneath it.
+pub async fn with_env_defaults(
+    state: &AppState,
+    env: Option<HashMap<String, String>>,
+) -> Option<HashMap<String, String>> {
+    merge_env(&*state.env_defaults.read().await, env)
+}
+
+/// `env` wins over `defaults`; `None` stays `None` when there are no defaults.
+fn merge_env(
+    defaults: &HashMap<String, String>,
+    env: Option<HashMap<String, String>>,
+) -> Option<HashMap<String, String>> {
+    if defaults.is_empty() {
+        return env;
+    }
+    let mut merged = defaults.clone();
+    merged.extend(env.unwrap_or_default());
+    Some(merged)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merge_env() {
+        let pair = |k: &str, v: &str| (k.to_string(), v.to_string());
+        assert_eq!(merge_env(&HashMap::new(), None), None);
+
+        let defaults: HashMap<_, _> = [pair("CI", "1"), pair("NODE_ENV", "production")].into();
+        let merged = merge_env(&defaults, Some([pair("NODE_ENV", "test")].into())).unwrap();
+        assert_eq!(merged.len(), 2);
+        assert_eq!(merged["CI"], "1");
+        assert_eq!(merged["NODE_ENV"], "test");
+        assert_eq!(merge_env(&defaults, None).unwrap(), defaults);
+    }
+
+    #[tokio::test]
+    async fn test_only_admin_changes_defaults() {
+        let state = Arc::new(AppState::new(crate::config::test_config()));
+        let set = |caller: Caller, value: &str| {
+            set_env_defaults(
+                State(state.clone()),
+                caller,
+                Json(SetEnvDefaultsRequest {
+                    env: [("PATH".to_string(), value.to_string())].into(),
+                }),
+            )
+        };
+
+        assert!(set(Caller::admin(), "/usr/bin").await.is_ok());
+        let tenant = Caller("ci".to_string());
+        assert!(matches!(
+            set(tenant.clone(), "/tmp/evil").await,
+            Err(AppError::Forbidden(_))
+        ));
+        assert!(matches!(
+            clear_env_defaults(State(state.clone()), tenant).await,
+            Err(AppError::Forbidden(_))
+        ));
+        assert_eq!(state.env_defaults.read().await["PATH"], "/usr/bin");
+    }
+}
@@ -1,45 +1,159 @@
 use crate::error::AppError;
 use crate::response::ApiResponse;
+use crate::state::upload_limit::UploadLimit;
+use crate::state::workspace::Workspace;
 use crate::state::AppState;
-use crate::utils::path::{ensure_directory, validate_path};
+use crate::utils::hash::Sha256;
+use crate::utils::path::{
+    decoding_paths, ensure_directory, relative_to_workspace, validate_path, with_path_decoding,
+};
 use axum::{
     body::Body,
-    extract::{Multipart, State},
-    http::header,
-    response::{IntoResponse, Response},
+    extract::{multipart::Field, Multipart, Query, State},
+    http::{header, HeaderMap},
+    response::{
+        sse::{Event, Sse},
+        IntoResponse, Response,
+    },
     Json,
 };
 use flate2::write::GzEncoder;
 use flate2::Compression;
-use futures::StreamExt;
+use futures::{stream, StreamExt};
 use serde::{Deserialize, Serialize};
+use std::convert::Infallible;
 use std::io::Write;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use tokio::fs;
 use tokio::io::AsyncWriteExt;
 
+const NDJSON_CONTENT_TYPE: &str = "application/x-ndjson";
+
+/// Most entries `download_size` visits before giving up on a download
+const MAX_DOWNLOAD_ENTRIES: usize = 1_000_000;
+
+/// Forwards written bytes to the response body, collected into chunks of
+/// about `capacity` bytes so tar and gzip's small writes don't each become a
+/// channel message. `flush` sends whatever is pending.
 struct ChannelWriter {
     tx: tokio::sync::mpsc::Sender<Result<Vec<u8>, std::io::Error>>,
+    buf: Vec<u8>,
+    capacity: usize,
+}
+
+impl ChannelWriter {
+    fn new(
+        tx: tokio::sync::mpsc::Sender<Result<Vec<u8>, std::io::Error>>,
+        capacity: usize,
+    ) -> Self {
+        Self {
+            tx,
+            buf: Vec::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    fn send_pending(&mut self) -> std::io::Result<()> {
+        if self.buf.is_empty() {
+            return Ok(());
+        }
+        let data = std::mem::replace(&mut self.buf, Vec::with_capacity(self.capacity));
+        self.tx
+            .blocking_send(Ok(data))
+            .map_err(|_| std::io::Error::new(std::io::ErrorKind::BrokenPipe, "Channel closed"))
+    }
 }
 
 impl std::io::Write for ChannelWriter {
     fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
-        let data = buf.to_vec();
-        let len = data.len();
-        match self.tx.blocking_send(Ok(data)) {
-            Ok(_) => Ok(len),
-            Err(_) => Err(std::io::Error::new(
-                std::io::ErrorKind::BrokenPipe,
-                "Channel closed",
-            )),
+        self.buf.extend_from_slice(buf);
+        if self.buf.len() >= self.capacity {
+            self.send_pending()?;
         }
+        Ok(buf.len())
     }
 
     fn flush(&mut self) -> std::io::Result<()> {
-        Ok(())
+        self.send_pending()
+    }
+}
+
+/// Name an entry is stored under in a download: its path relative to the
+/// workspace, or just its base name when it lies outside the workspace.
+fn archive_name(path: &Path, workspace: &Path) -> PathBuf {
+    match path.strip_prefix(workspace) {
+        Ok(rel) => rel.to_path_buf(),
+        _ => PathBuf::from(path.file_name().unwrap_or(path.as_os_str())),
     }
 }
 
+/// Escape a value for use inside a quoted `Content-Disposition` parameter.
+fn quote_filename(name: &str) -> String {
+    name.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// Why `download_size` stopped short of a total
+#[derive(Debug, PartialEq)]
+enum DownloadSizeError {
+    /// The files add up to more than the limit; carries the size counted so far
+    TooLarge(u64),
+    TooManyEntries,
+}
+
+/// Total size of the files a download of `paths` would send. Symlinks to
+/// files count at their target's size, as multipart sends their contents
+/// (tar stores the link alone), but symlinked directories below the given
+/// paths are walked into by neither, so they count for nothing. Stops as soon as
+/// the total passes `limit` or `MAX_DOWNLOAD_ENTRIES` entries were seen, so a
+/// huge tree is not walked to the end just to be refused.
+fn download_size(paths: &[PathBuf], limit: u64) -> Result<u64, DownloadSizeError> {
+    let mut total = 0u64;
+    let mut entries = 0usize;
+    let mut stack: Vec<(PathBuf, std::fs::Metadata)> = paths
+        .iter()
+        .filter_map(|p| std::fs::metadata(p).ok().map(|m| (p.clone(), m)))
+        .collect();
+    while let Some((path, meta)) = stack.pop() {
+        entries += 1;
+        if entries > MAX_DOWNLOAD_ENTRIES {
+            return Err(DownloadSizeError::TooManyEntries);
+        }
+        if meta.is_dir() {
+            if let Ok(dir) = std::fs::read_dir(&path) {
+                for entry in dir.flatten() {
+                    let path = entry.path();
+                    let meta = match entry.metadata() {
+                        Ok(meta) if meta.file_type().is_symlink() => std::fs::metadata(&path)
+                            .ok()
+                            .filter(|target| target.is_file()),
+                        Ok(meta) => Some(meta),
+                        Err(_) => None,
+                    };
+                    if let Some(meta) = meta {
+                        stack.push((path, meta));
+                    }
+                }
+            }
+        } else if meta.is_file() {
+            total = total.saturating_add(meta.len());
+            if total > limit {
+                return Err(DownloadSizeError::TooLarge(total));
+            }
+        }
+    }
+    Ok(total)
+}
+
+/// A tar builder that stores symlinks as links. Following them would let a
+/// link to `/` inside the workspace archive the whole filesystem, past the
+/// `download_size` check, and a link cycle recurse forever.
+fn archive_builder<W: Write>(writer: W) -> tar::Builder<W> {
+    let mut tar = tar::Builder::new(writer);
+    tar.follow_symlinks(false);
+    tar
+}
+
 #[derive(Deserialize)]
 pub struct DownloadFilesRequest {
     paths: Vec<String>,
@@ -49,23 +163,57 @@ pub struct DownloadFilesRequest {
 
 pub async fn batch_download(
     State(state): State<Arc<AppState>>,
+    Workspace(workspace): Workspace,
     Json(req): Json<DownloadFilesRequest>,
 ) -> Result<Response, AppError> {
     if req.paths.is_empty() {
         return Err(AppError::BadRequest("No paths provided".to_string()));
     }
+    let max_paths = state.config.max_download_paths;
+    if req.paths.len() > max_paths {
+        return Err(AppError::BadRequest(format!(
+            "Too many paths: {}, limit is {}",
+            req.paths.len(),
+            max_paths
+        )));
+    }
 
     let mut valid_paths = Vec::new();
     for path in &req.paths {
-        let valid_path = validate_path(&state.config.workspace_path, path)?;
+        let valid_path = validate_path(&workspace, path)?;
         if !valid_path.exists() {
             return Err(AppError::NotFound(format!("File not found: {}", path)));
         }
         valid_paths.push(valid_path);
     }
 
+    // Refuse an oversized download up front rather than partway through the
+    // stream, when the client could only see a truncated archive.
+    let max_bytes = state.config.max_download_bytes;
+    let paths = valid_paths.clone();
+    match tokio::task::spawn_blocking(move || download_size(&paths, max_bytes))
+        .await
+        .map_err(|e| AppError::InternalServerError(e.to_string()))?
+    {
+        Ok(_) => {}
+        Err(DownloadSizeError::TooLarge(size)) => {
+            return Err(AppError::BadRequest(format!(
+                "Download too large: at least {} bytes, limit is {} bytes",
+                size, max_bytes
+            )));
+        }
+        Err(DownloadSizeError::TooManyEntries) => {
+            return Err(AppError::BadRequest(format!(
+                "Download too large: more than {} entries",
+                MAX_DOWNLOAD_ENTRIES
+            )));
+        }
+    }
+
     let format = req.format.as_deref().unwrap_or("tar.gz");
-    let workspace_path = state.config.workspace_path.clone();
+    let workspace_path = workspace.clone();
+    // Batch the many small writes from tar and gzip into fewer, larger chunks
+    let buffer_size = state.config.io_buffer_size;
 
     match format {
         "tar" => {
@@ -74,38 +222,33 @@ pub async fn batch_download(
             let tx_err = tx.clone();
 
             tokio::task::spawn_blocking(move || {
-                let writer = ChannelWriter { tx };
-                let mut tar = tar::Builder::new(writer);
+                let writer = ChannelWriter::new(tx, buffer_size);
+                let mut tar = archive_builder(writer);
                 for path in valid_paths {
-                    let rel_path = match path.strip_prefix(&workspace_path) {
-                        Ok(p) => p,
-                        Err(_) => {
-                            std::path::Path::new(path.file_name().unwrap_or(path.as_os_str()))
-                        }
-                    };
+                    let rel_path = archive_name(&path, &workspace_path);
                     if path.is_dir() {
-                        if let Err(e) = tar.append_dir_all(rel_path, &path) {
-                            let _ = tx_err.blocking_send(Err(std::io::Error::new(
-                                std::io::ErrorKind::Other,
-                                format!("Failed to append dir: {}", e),
-                            )));
+                        if let Err(e) = tar.append_dir_all(&rel_path, &path) {
+                            let _ = tx_err.blocking_send(Err(std::io::Error::other(format!(
+                                "Failed to append dir: {}",
+                                e
+                            ))));
                             return;
                         }
                     } else {
-                        if let Err(e) = tar.append_path_with_name(&path, rel_path) {
-                            let _ = tx_err.blocking_send(Err(std::io::Error::new(
-                                std::io::ErrorKind::Other,
-                                format!("Failed to append file: {}", e),
-                            )));
+                        if let Err(e) = tar.append_path_with_name(&path, &rel_path) {
+                            let _ = tx_err.blocking_send(Err(std::io::Error::other(format!(
+                                "Failed to append file: {}",
+                                e
+                            ))));
                             return;
                         }
                     }
                 }
-                if let Err(e) = tar.finish() {
-                    let _ = tx_err.blocking_send(Err(std::io::Error::new(
-                        std::io::ErrorKind::Other,
-                        format!("Failed to finish tar: {}", e),
-                    )));
+                if let Err(e) = tar.into_inner().and_then(|mut writer| writer.flush()) {
+                    let _ = tx_err.blocking_send(Err(std::io::Error::other(format!(
+                        "Failed to finish tar: {}",
+                        e
+                    ))));
                 }
             });
 
@@ -129,14 +272,28 @@ pub async fn batch_download(
             let tx_err = tx.clone();
 
             tokio::task::spawn_blocking(move || {
-                let mut writer = ChannelWriter { tx };
-                let mut stack = valid_paths.clone();
+                let mut writer = ChannelWriter::new(tx, buffer_size);
+                // Each entry carries the name it is sent under, so files found
+                // while walking a directory keep their path below it.
+                let mut stack: Vec<_> = valid_paths
+                    .into_iter()
+                    .map(|path| {
+                        let name = archive_name(&path, &workspace_path);
+                        (path, name)
+                    })
+                    .collect();
 
-                while let Some(path) = stack.pop() {
+                while let Some((path, name)) = stack.pop() {
                     if path.is_dir() {
                         if let Ok(entries) = std::fs::read_dir(&path) {
                             for entry in entries.flatten() {
-                                stack.push(entry.path());
+                                // Symlinked directories are not walked, as in
+                                // `download_size`
+                                let linked_dir = entry.file_type().is_ok_and(|t| t.is_symlink())
+                                    && entry.path().is_dir();
+                                if !linked_dir {
+                                    stack.push((entry.path(), name.join(entry.file_name())));
+                                }
                             }
                         }
                     } else {
@@ -144,7 +301,7 @@ pub async fn batch_download(
                         let header = format!(
                             "--{}\r\nContent-Disposition: attachment; filename=\"{}\"\r\nContent-Type: {}\r\n\r\n",
                             boundary_clone,
-                            path.to_string_lossy(),
+                            quote_filename(&name.to_string_lossy()),
                             mime
                         );
                         if writer.write_all(header.as_bytes()).is_err() {
@@ -153,8 +310,7 @@ pub async fn batch_download(
 
                         if let Ok(mut file) = std::fs::File::open(&path) {
                             if std::io::copy(&mut file, &mut writer).is_err() {
-                                let _ = tx_err.blocking_send(Err(std::io::Error::new(
-                                    std::io::ErrorKind::Other,
+                                let _ = tx_err.blocking_send(Err(std::io::Error::other(
                                     "Failed to read file",
                                 )));
                                 return;
@@ -166,6 +322,7 @@ pub async fn batch_download(
                     }
                 }
                 let _ = writer.write_all(format!("--{}--\r\n", boundary_clone).as_bytes());
+                let _ = writer.flush();
             });
 
             let stream = tokio_stream::wrappers::ReceiverStream::new(rx);
@@ -190,48 +347,43 @@ pub async fn batch_download(
             let tx_err = tx.clone();
 
             tokio::task::spawn_blocking(move || {
-                let writer = ChannelWriter { tx };
+                let writer = ChannelWriter::new(tx, buffer_size);
                 let mut enc = GzEncoder::new(writer, Compression::default());
                 {
-                    let mut tar = tar::Builder::new(&mut enc);
+                    let mut tar = archive_builder(&mut enc);
                     for path in valid_paths {
-                        let rel_path = match path.strip_prefix(&workspace_path) {
-                            Ok(p) => p,
-                            Err(_) => {
-                                std::path::Path::new(path.file_name().unwrap_or(path.as_os_str()))
-                            }
-                        };
+                        let rel_path = archive_name(&path, &workspace_path);
                         if path.is_dir() {
-                            if let Err(e) = tar.append_dir_all(rel_path, &path) {
-                                let _ = tx_err.blocking_send(Err(std::io::Error::new(
-                                    std::io::ErrorKind::Other,
-                                    format!("Failed to append dir: {}", e),
-                                )));
+                            if let Err(e) = tar.append_dir_all(&rel_path, &path) {
+                                let _ = tx_err.blocking_send(Err(std::io::Error::other(format!(
+                                    "Failed to append dir: {}",
+                                    e
+                                ))));
                                 return;
                             }
                         } else {
-                            if let Err(e) = tar.append_path_with_name(&path, rel_path) {
-                                let _ = tx_err.blocking_send(Err(std::io::Error::new(
-                                    std::io::ErrorKind::Other,
-                                    format!("Failed to append file: {}", e),
-                                )));
+                            if let Err(e) = tar.append_path_with_name(&path, &rel_path) {
+                                let _ = tx_err.blocking_send(Err(std::io::Error::other(format!(
+                                    "Failed to append file: {}",
+                                    e
+                                ))));
                                 return;
                             }
                         }
                     }
                     if let Err(e) = tar.finish() {
-                        let _ = tx_err.blocking_send(Err(std::io::Error::new(
-                            std::io::ErrorKind::Other,
-                            format!("Failed to finish tar: {}", e),
-                        )));
+                        let _ = tx_err.blocking_send(Err(std::io::Error::other(format!(
+                            "Failed to finish tar: {}",
+                            e
+                        ))));
                         return;
                     }
                 }
-                if let Err(e) = enc.finish() {
-                    let _ = tx_err.blocking_send(Err(std::io::Error::new(
-                        std::io::ErrorKind::Other,
-                        format!("Failed to finish gzip: {}", e),
-                    )));
+                if let Err(e) = enc.finish().and_then(|mut writer| writer.flush()) {
+                    let _ = tx_err.blocking_send(Err(std::io::Error::other(format!(
+                        "Failed to finish gzip: {}",
+                        e
+                    ))));
                 }
             });
 
@@ -250,15 +402,145 @@ pub async fn batch_download(
     }
 }
 
+#[derive(Deserialize)]
+pub struct ReadBatchRequest {
+    paths: Vec<String>,
+    /// `utf8` (default) or `base64`
+    encoding: Option<String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReadBatchEntry {
+    /// The path as requested
+    path: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    content: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    size: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReadBatchResponse {
+    files: Vec<ReadBatchEntry>,
+}
+
+/// Read several small files in one request. Each path succeeds or fails on its
+/// own; failures are reported in that entry's `error`.
+pub async fn read_batch(
+    State(state): State<Arc<AppState>>,
+    Workspace(workspace): Workspace,
+    Json(req): Json<ReadBatchRequest>,
+) -> Result<Json<ApiResponse<ReadBatchResponse>>, AppError> {
+    if req.paths.is_empty() {
+        return Err(AppError::BadRequest("No paths provided".to_string()));
+    }
+    let base64 = match req.encoding.as_deref() {
+        None | Some("utf8") | Some("utf-8") => false,
+        Some("base64") => true,
+        Some(other) => {
+            return Err(AppError::BadRequest(format!(
+                "Unsupported encoding: {} (expected utf8 or base64)",
+                other
+            )))
+        }
+    };
+
+    let mut files = Vec::with_capacity(req.paths.len());
+    for path in req.paths {
+        let entry = match read_entry(&workspace, &path, state.config.max_file_size).await {
+            Ok(data) => {
+                let size = data.len() as u64;
+                let content = if base64 {
+                    use base64::{engine::general_purpose, Engine as _};
+                    Ok(general_purpose::STANDARD.encode(&data))
+                } else {
+                    String::from_utf8(data)
+                        .map_err(|_| "File is not valid UTF-8; use encoding=base64".to_string())
+                };
+                match content {
+                    Ok(content) => ReadBatchEntry {
+                        path,
+                        content: Some(content),
+                        size: Some(size),
+                        error: None,
+                    },
+                    Err(error) => ReadBatchEntry {
+                        path,
+                        content: None,
+                        size: Some(size),
+                        error: Some(error),
+                    },
+                }
+            }
+            Err(error) => ReadBatchEntry {
+                path,
+                content: None,
+                size: None,
+                error: Some(error),
+            },
+        };
+        files.push(entry);
+    }
+
+    Ok(Json(ApiResponse::success(ReadBatchResponse { files })))
+}
+
+async fn read_entry(workspace: &Path, path: &str, max_size: u64) -> Result<Vec<u8>, String> {
+    let valid_path = validate_path(workspace, path).map_err(|e| e.to_string())?;
+    let metadata = fs::metadata(&valid_path)
+        .await
+        .map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => "File not found".to_string(),
+            _ => e.to_string(),
+        })?;
+    if metadata.is_dir() {
+        return Err("Path is a directory, not a file".to_string());
+    }
+    if metadata.len() > max_size {
+        return Err(format!(
+            "File too large: {} bytes, limit is {} bytes",
+            metadata.len(),
+            max_size
+        ));
+    }
+    fs::read(&valid_path).await.map_err(|e| e.to_string())
+}
+
 #[derive(Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct BatchUploadResult {
     path: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    relative_path: Option<String>,
     success: bool,
     #[serde(skip_serializing_if = "Option::is_none")]
     error: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     size: Option<u64>,
+    /// Hex SHA-256 of the bytes written (successful uploads only)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sha256: Option<String>,
+    /// What happened at the target path: created, overwritten, renamed or skipped
+    #[serde(skip_serializing_if = "Option::is_none")]
+    action: Option<&'static str>,
+}
+
+impl BatchUploadResult {
+    fn failure(path: String, error: impl ToString) -> Self {
+        Self {
+            path,
+            relative_path: None,
+            success: false,
+            error: Some(error.to_string()),
+            size: None,
+            sha256: None,
+            action: None,
+        }
+    }
 }
 
 #[derive(Serialize)]
@@ -266,9 +548,14 @@ pub struct BatchUploadResult {
 pub struct BatchUploadResponse {
     results: Vec<BatchUploadResult>,
     total_files: usize,
+    /// Files written; parts left alone by `onConflict=skip` count as skipped
     success_count: usize,
+    skipped_count: usize,
 }
 
+/// Optional per-part header carrying the expected hex SHA-256 of the part body.
+const CHECKSUM_HEADER: &str = "x-checksum-sha256";
+
 /// Mimics the Go server's behavior of manually parsing Content-Disposition
 /// to extract a filename, allowing for paths in the filename field.
 fn extract_full_filename(field: &axum::extract::multipart::Field) -> String {
@@ -296,122 +583,540 @@ fn extract_full_filename(field: &axum::extract::multipart::Field) -> String {
     default_filename
 }
 
-pub async fn batch_upload(
-    State(state): State<Arc<AppState>>,
-    mut multipart: Multipart,
-) -> Result<Json<ApiResponse<BatchUploadResponse>>, AppError> {
-    let mut results = Vec::new();
-    let mut success_count = 0;
-    let mut total_files = 0;
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BatchUploadParams {
+    on_conflict: Option<String>,
+}
+
+/// How `batch_upload` treats a target path that already exists.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum ConflictPolicy {
+    Overwrite,
+    Skip,
+    Rename,
+}
 
-    while let Some(field) = multipart
-        .next_field()
+impl ConflictPolicy {
+    fn parse(value: &str) -> Result<Self, AppError> {
+        match value.trim() {
+            "" | "overwrite" => Ok(Self::Overwrite),
+            "skip" => Ok(Self::Skip),
+            "rename" => Ok(Self::Rename),
+            other => Err(AppError::BadRequest(format!(
+                "Invalid onConflict: {} (expected overwrite, skip or rename)",
+                other
+            ))),
+        }
+    }
+}
+
+/// Upper bound on `name (n).ext` candidates tried by the rename policy.
+const MAX_RENAME_ATTEMPTS: usize = 1000;
+
+/// `report.txt` -> `report (n).txt`; names without an extension get the suffix appended.
+fn numbered_name(path: &Path, n: usize) -> PathBuf {
+    let stem = path
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let name = match path.extension() {
+        Some(ext) => format!("{} ({}).{}", stem, n, ext.to_string_lossy()),
+        None => format!("{} ({})", stem, n),
+    };
+    path.with_file_name(name)
+}
+
+async fn create_exclusive(path: &Path) -> std::io::Result<fs::File> {
+    fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(path)
         .await
-        .map_err(|e| AppError::BadRequest(e.to_string()))?
-    {
-        let name = field.name().unwrap_or("").to_string();
-        if name == "files" || name == "file" {
-            total_files += 1;
-            let filename = extract_full_filename(&field);
-
-            let target_path_res = validate_path(&state.config.workspace_path, &filename);
-
-            match target_path_res {
-                Ok(target_path) => {
-                    if let Some(parent) = target_path.parent() {
-                        if let Err(e) = ensure_directory(parent).await {
-                            results.push(BatchUploadResult {
-                                path: filename,
-                                success: false,
-                                error: Some(e.to_string()),
-                                size: None,
-                            });
-                            continue;
-                        }
+}
+
+/// Open the file an upload is written to according to `policy`. Returns
+/// `None` when the upload should be skipped. Skip and rename use exclusive
+/// creation so a file appearing concurrently is never clobbered.
+async fn open_upload_target(
+    path: &Path,
+    policy: ConflictPolicy,
+) -> std::io::Result<Option<(fs::File, PathBuf, &'static str)>> {
+    match policy {
+        ConflictPolicy::Overwrite => {
+            let existed = fs::symlink_metadata(path).await.is_ok();
+            let file = fs::File::create(path).await?;
+            let action = if existed { "overwritten" } else { "created" };
+            Ok(Some((file, path.to_path_buf(), action)))
+        }
+        ConflictPolicy::Skip => match create_exclusive(path).await {
+            Ok(file) => Ok(Some((file, path.to_path_buf(), "created"))),
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => Ok(None),
+            Err(e) => Err(e),
+        },
+        ConflictPolicy::Rename => {
+            for n in 0..=MAX_RENAME_ATTEMPTS {
+                let candidate = if n == 0 {
+                    path.to_path_buf()
+                } else {
+                    numbered_name(path, n)
+                };
+                match create_exclusive(&candidate).await {
+                    Ok(file) => {
+                        let action = if n == 0 { "created" } else { "renamed" };
+                        return Ok(Some((file, candidate, action)));
                     }
+                    Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => continue,
+                    Err(e) => return Err(e),
+                }
+            }
+            Err(std::io::Error::other(
+                "No free name found for renamed upload",
+            ))
+        }
+    }
+}
 
-                    let mut file = match fs::File::create(&target_path).await {
-                        Ok(f) => f,
-                        Err(e) => {
-                            results.push(BatchUploadResult {
-                                path: filename,
-                                success: false,
-                                error: Some(e.to_string()),
-                                size: None,
-                            });
-                            continue;
-                        }
-                    };
+/// Write a single `files` part to its target path and describe the outcome.
+async fn upload_part(
+    field: Field<'_>,
+    workspace: &Path,
+    max_size: u64,
+    policy: ConflictPolicy,
+) -> BatchUploadResult {
+    let filename = extract_full_filename(&field);
+    let expected_checksum = field
+        .headers()
+        .get(CHECKSUM_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.trim().to_ascii_lowercase());
 
-                    let mut size = 0;
-                    let mut stream = field;
-                    let mut failed = false;
-                    while let Some(chunk) = stream.next().await {
-                        match chunk {
-                            Ok(data) => {
-                                size += data.len() as u64;
-                                if size > state.config.max_file_size {
-                                    drop(file);
-                                    fs::remove_file(&target_path).await.ok();
-                                    results.push(BatchUploadResult {
-                                        path: filename.clone(),
-                                        success: false,
-                                        error: Some("File too large".to_string()),
-                                        size: None,
-                                    });
-                                    failed = true;
-                                    break;
-                                }
+    let target_path = match validate_path(workspace, &filename) {
+        Ok(path) => path,
+        Err(e) => return BatchUploadResult::failure(filename, e),
+    };
 
-                                if let Err(e) = file.write_all(&data).await {
-                                    results.push(BatchUploadResult {
-                                        path: filename.clone(),
-                                        success: false,
-                                        error: Some(e.to_string()),
-                                        size: None,
-                                    });
-                                    failed = true;
-                                    break;
-                                }
-                            }
-                            Err(e) => {
-                                results.push(BatchUploadResult {
-                                    path: filename.clone(),
-                                    success: false,
-                                    error: Some(e.to_string()),
-                                    size: None,
-                                });
-                                failed = true;
-                                break;
-                            }
-                        }
-                    }
+    if let Some(parent) = target_path.parent() {
+        if let Err(e) = ensure_directory(parent).await {
+            return BatchUploadResult::failure(filename, e);
+        }
+    }
 
-                    if !failed {
-                        success_count += 1;
-                        results.push(BatchUploadResult {
-                            path: target_path.to_string_lossy().to_string(),
-                            success: true,
-                            error: None,
-                            size: Some(size),
-                        });
-                    }
+    let (mut file, target_path, action) = match open_upload_target(&target_path, policy).await {
+        Ok(Some(opened)) => opened,
+        Ok(None) => {
+            return BatchUploadResult {
+                path: target_path.to_string_lossy().to_string(),
+                relative_path: relative_to_workspace(workspace, &target_path),
+                success: true,
+                error: None,
+                size: None,
+                sha256: None,
+                action: Some("skipped"),
+            };
+        }
+        Err(e) => return BatchUploadResult::failure(filename, e),
+    };
+
+    let mut size = 0;
+    let mut hasher = Sha256::new();
+    let mut stream = field;
+    while let Some(chunk) = stream.next().await {
+        let data = match chunk {
+            Ok(data) => data,
+            Err(e) => return BatchUploadResult::failure(filename, e),
+        };
+        size += data.len() as u64;
+        hasher.update(&data);
+        if size > max_size {
+            drop(file);
+            fs::remove_file(&target_path).await.ok();
+            return BatchUploadResult::failure(filename, "File too large");
+        }
+        if let Err(e) = file.write_all(&data).await {
+            return BatchUploadResult::failure(filename, e);
+        }
+    }
+    // tokio finishes the last write in the background; wait for it so the
+    // file is complete once the result is reported
+    if let Err(e) = file.flush().await {
+        return BatchUploadResult::failure(filename, e);
+    }
+
+    let checksum = hasher.finalize_hex();
+    if let Some(expected) = expected_checksum {
+        if expected != checksum {
+            fs::remove_file(&target_path).await.ok();
+            return BatchUploadResult::failure(
+                filename,
+                format!("Checksum mismatch: expected {}, got {}", expected, checksum),
+            );
+        }
+    }
+
+    BatchUploadResult {
+        path: target_path.to_string_lossy().to_string(),
+        relative_path: relative_to_workspace(workspace, &target_path),
+        success: true,
+        error: None,
+        size: Some(size),
+        sha256: Some(checksum),
+        action: Some(action),
+    }
+}
+
+/// Walks a batch upload form one file part at a time, so results can be
+/// either collected or streamed as they complete.
+struct UploadParts {
+    multipart: Multipart,
+    workspace: PathBuf,
+    max_size: u64,
+    policy: ConflictPolicy,
+    /// Whether the request opted into `encoded:` paths; streamed results are
+    /// produced after the handler has returned
+    decode_paths: bool,
+    total_files: usize,
+    success_count: usize,
+    skipped_count: usize,
+}
+
+impl UploadParts {
+    /// Upload the next file part, applying any `onConflict` fields seen on the
+    /// way. Returns `None` once the form is exhausted.
+    async fn next_result(&mut self) -> Result<Option<BatchUploadResult>, AppError> {
+        while let Some(field) = self
+            .multipart
+            .next_field()
+            .await
+            .map_err(|e| AppError::BadRequest(e.to_string()))?
+        {
+            match field.name().unwrap_or("") {
+                "onConflict" => {
+                    let value = field
+                        .text()
+                        .await
+                        .map_err(|e| AppError::BadRequest(e.to_string()))?;
+                    self.policy = ConflictPolicy::parse(&value)?;
                 }
-                Err(e) => {
-                    results.push(BatchUploadResult {
-                        path: filename,
-                        success: false,
-                        error: Some(e.to_string()),
-                        size: None,
-                    });
+                "files" | "file" => {
+                    let upload = upload_part(field, &self.workspace, self.max_size, self.policy);
+                    let result = with_path_decoding(self.decode_paths, upload).await;
+                    self.total_files += 1;
+                    if result.action == Some("skipped") {
+                        self.skipped_count += 1;
+                    } else if result.success {
+                        self.success_count += 1;
+                    }
+                    return Ok(Some(result));
                 }
+                _ => {}
             }
         }
+        Ok(None)
+    }
+}
+
+/// Upload files from `files`/`file` parts. Existing targets are handled per
+/// `onConflict`, taken from the query string or from an `onConflict` form
+/// field, which applies to the file parts that follow it.
+/// With `Accept: application/x-ndjson` each result is streamed as a JSON line
+/// as soon as its file is written; with `Accept: text/event-stream` results
+/// arrive as SSE `data` events followed by a `done` event carrying the totals.
+pub async fn batch_upload(
+    Workspace(workspace): Workspace,
+    UploadLimit(max_size): UploadLimit,
+    headers: HeaderMap,
+    Query(params): Query<BatchUploadParams>,
+    multipart: Multipart,
+) -> Result<Response, AppError> {
+    let mut parts = UploadParts {
+        multipart,
+        workspace,
+        max_size,
+        policy: ConflictPolicy::parse(params.on_conflict.as_deref().unwrap_or(""))?,
+        decode_paths: decoding_paths(),
+        total_files: 0,
+        success_count: 0,
+        skipped_count: 0,
+    };
+
+    let accept = headers
+        .get(header::ACCEPT)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("");
+
+    if accept.contains(NDJSON_CONTENT_TYPE) {
+        let stream = stream::unfold(Some(parts), |state| async move {
+            let mut parts = state?;
+            match parts.next_result().await {
+                Ok(Some(result)) => {
+                    let mut line = serde_json::to_vec(&result).unwrap_or_default();
+                    line.push(b'\n');
+                    Some((Ok(line), Some(parts)))
+                }
+                Ok(None) => None,
+                Err(e) => Some((Err(std::io::Error::other(e.to_string())), None)),
+            }
+        });
+        let headers = [(header::CONTENT_TYPE, NDJSON_CONTENT_TYPE)];
+        return Ok((headers, Body::from_stream(stream)).into_response());
+    }
+
+    if accept.contains("text/event-stream") {
+        let stream = stream::unfold(Some(parts), |state| async move {
+            let mut parts = state?;
+            let event = match parts.next_result().await {
+                Ok(Some(result)) => {
+                    let event = Event::default().json_data(&result).unwrap_or_default();
+                    return Some((Ok::<Event, Infallible>(event), Some(parts)));
+                }
+                Ok(None) => Event::default()
+                    .event("done")
+                    .json_data(serde_json::json!({
+                        "totalFiles": parts.total_files,
+                        "successCount": parts.success_count,
+                        "skippedCount": parts.skipped_count,
+                    }))
+                    .unwrap_or_default(),
+                Err(e) => Event::default().event("error").data(e.to_string()),
+            };
+            Some((Ok(event), None))
+        });
+        return Ok(Sse::new(stream).into_response());
+    }
+
+    let mut results = Vec::new();
+    while let Some(result) = parts.next_result().await? {
+        results.push(result);
     }
 
     Ok(Json(ApiResponse::success(BatchUploadResponse {
         results,
-        total_files,
-        success_count,
-    })))
+        total_files: parts.total_files,
+        success_count: parts.success_count,
+        skipped_count: parts.skipped_count,
+    }))
+    .into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_archive_name() {
+        let workspace = Path::new("/home/devbox/project");
+        assert_eq!(
+            archive_name(Path::new("/home/devbox/project/src/lib.rs"), workspace),
+            PathBuf::from("src/lib.rs")
+        );
+        assert_eq!(
+            archive_name(Path::new("/etc/hosts"), workspace),
+            PathBuf::from("hosts")
+        );
+        assert_eq!(quote_filename(r#"a"b\c"#), r#"a\"b\\c"#);
+    }
+
+    #[tokio::test]
+    async fn test_read_entry() {
+        let dir = std::env::temp_dir().join(format!(
+            "devbox-read-batch-{}",
+            crate::utils::common::generate_id()
+        ));
+        std::fs::create_dir_all(dir.join("sub")).unwrap();
+        std::fs::write(dir.join("package.json"), "{}").unwrap();
+        std::fs::write(dir.join("big.bin"), [0u8; 16]).unwrap();
+
+        assert_eq!(read_entry(&dir, "package.json", 8).await.unwrap(), b"{}");
+        assert_eq!(
+            read_entry(&dir, "big.bin", 8).await.unwrap_err(),
+            "File too large: 16 bytes, limit is 8 bytes"
+        );
+        assert_eq!(
+            read_entry(&dir, "missing", 8).await.unwrap_err(),
+            "File not found"
+        );
+        assert_eq!(
+            read_entry(&dir, "sub", 8).await.unwrap_err(),
+            "Path is a directory, not a file"
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_download_size() {
+        let dir = std::env::temp_dir().join(format!(
+            "devbox-download-size-{}",
+            crate::utils::common::generate_id()
+        ));
+        std::fs::create_dir_all(dir.join("src/nested")).unwrap();
+        std::fs::write(dir.join("README.md"), [0u8; 10]).unwrap();
+        std::fs::write(dir.join("src/main.rs"), [0u8; 20]).unwrap();
+        std::fs::write(dir.join("src/nested/mod.rs"), [0u8; 30]).unwrap();
+        std::os::unix::fs::symlink(&dir, dir.join("src/loop")).unwrap();
+        std::os::unix::fs::symlink(dir.join("README.md"), dir.join("src/readme")).unwrap();
+
+        let paths = vec![dir.join("README.md"), dir.join("src")];
+        assert_eq!(download_size(&paths, 70), Ok(70));
+        assert!(matches!(
+            download_size(&paths, 69),
+            Err(DownloadSizeError::TooLarge(size)) if size > 69
+        ));
+        assert_eq!(download_size(std::slice::from_ref(&dir), 100), Ok(70));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_archive_keeps_symlinks_as_links() {
+        let dir = std::env::temp_dir().join(format!(
+            "devbox-archive-links-{}",
+            crate::utils::common::generate_id()
+        ));
+        std::fs::create_dir_all(dir.join("outside")).unwrap();
+        std::fs::write(dir.join("outside/secret"), b"secret").unwrap();
+        std::fs::create_dir_all(dir.join("ws")).unwrap();
+        std::os::unix::fs::symlink(dir.join("outside"), dir.join("ws/root")).unwrap();
+        std::os::unix::fs::symlink(dir.join("ws"), dir.join("ws/loop")).unwrap();
+
+        let mut tar = archive_builder(Vec::new());
+        tar.append_dir_all("ws", dir.join("ws")).unwrap();
+        let data = tar.into_inner().unwrap();
+
+        let mut archive = tar::Archive::new(data.as_slice());
+        let mut entries: Vec<(String, tar::EntryType)> = archive
+            .entries()
+            .unwrap()
+            .map(|e| {
+                let e = e.unwrap();
+                (
+                    e.path().unwrap().to_string_lossy().to_string(),
+                    e.header().entry_type(),
+                )
+            })
+            .collect();
+        entries.sort_by(|a, b| a.0.cmp(&b.0));
+        assert_eq!(
+            entries,
+            vec![
+                ("ws/".to_string(), tar::EntryType::Directory),
+                ("ws/loop".to_string(), tar::EntryType::Symlink),
+                ("ws/root".to_string(), tar::EntryType::Symlink),
+            ]
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_channel_writer_coalesces_writes() {
+        use std::io::Write;
+        let (tx, mut rx) = tokio::sync::mpsc::channel(16);
+        let mut writer = ChannelWriter::new(tx, 8);
+
+        // Small writes wait until a chunk is full, then go out together
+        writer.write_all(b"abc").unwrap();
+        writer.write_all(b"def").unwrap();
+        assert!(rx.try_recv().is_err());
+        writer.write_all(b"ghi").unwrap();
+        assert_eq!(rx.try_recv().unwrap().unwrap(), b"abcdefghi");
+
+        // Finishing sends the remainder, and nothing when none is pending
+        writer.write_all(b"jk").unwrap();
+        assert!(rx.try_recv().is_err());
+        writer.flush().unwrap();
+        assert_eq!(rx.try_recv().unwrap().unwrap(), b"jk");
+        writer.flush().unwrap();
+        assert!(rx.try_recv().is_err());
+
+        // A whole archive arrives in a few buffer-sized chunks
+        let (tx, mut rx) = tokio::sync::mpsc::channel(64);
+        let mut tar = archive_builder(ChannelWriter::new(tx, 4096));
+        for i in 0..20 {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(3);
+            header.set_cksum();
+            tar.append_data(&mut header, format!("f{}", i), &b"abc"[..])
+                .unwrap();
+        }
+        tar.into_inner().and_then(|mut w| w.flush()).unwrap();
+        let mut chunks = Vec::new();
+        while let Ok(chunk) = rx.try_recv() {
+            chunks.push(chunk.unwrap());
+        }
+        // 20 entries of two blocks each plus the two-block trailer
+        assert_eq!(chunks.iter().map(Vec::len).sum::<usize>(), 42 * 512);
+        assert_eq!(chunks.len(), 6);
+        assert!(chunks[..5].iter().all(|c| c.len() == 4096));
+    }
+
+    #[tokio::test]
+    async fn test_skipped_uploads_are_counted_apart() {
+        use axum::extract::FromRequest;
+        let dir = std::env::temp_dir().join(format!(
+            "devbox-upload-skip-{}",
+            crate::utils::common::generate_id()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("existing.txt"), "old").unwrap();
+
+        let part = |name: &str| {
+            format!(
+                "--b\r\nContent-Disposition: form-data; name=\"files\"; filename=\"{}\"\r\n\r\nnew\r\n",
+                name
+            )
+        };
+        let body = format!("{}{}--b--\r\n", part("existing.txt"), part("fresh.txt"));
+        let request = axum::extract::Request::builder()
+            .header(header::CONTENT_TYPE, "multipart/form-data; boundary=b")
+            .body(Body::from(body))
+            .unwrap();
+        let mut parts = UploadParts {
+            multipart: Multipart::from_request(request, &()).await.unwrap(),
+            workspace: dir.clone(),
+            max_size: 1024,
+            policy: ConflictPolicy::Skip,
+            decode_paths: false,
+            total_files: 0,
+            success_count: 0,
+            skipped_count: 0,
+        };
+        while parts.next_result().await.unwrap().is_some() {}
+
+        assert_eq!(parts.total_files, 2);
+        assert_eq!(parts.success_count, 1);
+        assert_eq!(parts.skipped_count, 1);
+        assert_eq!(
+            std::fs::read_to_string(dir.join("existing.txt")).unwrap(),
+            "old"
+        );
+        assert_eq!(
+            std::fs::read_to_string(dir.join("fresh.txt")).unwrap(),
+            "new"
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_numbered_name() {
+        assert_eq!(
+            numbered_name(Path::new("/ws/docs/report.txt"), 1),
+            PathBuf::from("/ws/docs/report (1).txt")
+        );
+        assert_eq!(
+            numbered_name(Path::new("/ws/Makefile"), 2),
+            PathBuf::from("/ws/Makefile (2)")
+        );
+        assert_eq!(
+            numbered_name(Path::new("/ws/.env"), 1),
+            PathBuf::from("/ws/.env (1)")
+        );
+        assert!(ConflictPolicy::parse("clobber").is_err());
+        assert_eq!(
+            ConflictPolicy::parse("").unwrap(),
+            ConflictPolicy::Overwrite
+        );
+    }
 }
@@ -0,0 +1,174 @@
+use crate::error::AppError;
+use crate::response::ApiResponse;
+use crate::state::workspace::Workspace;
+use crate::utils::glob::Glob;
+use crate::utils::path::validate_path;
+use axum::Json;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use tokio::fs;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CleanRequest {
+    dir: String,
+    /// Wildcard matched against entry names, e.g. `*.log`
+    pattern: String,
+    /// Also remove matching directories, with everything inside them
+    #[serde(default)]
+    delete_dirs: bool,
+    /// Report what would be removed without removing anything
+    #[serde(default)]
+    dry_run: bool,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CleanFailure {
+    path: String,
+    error: String,
+}
+
+#[derive(Serialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct CleanResponse {
+    deleted_files: usize,
+    deleted_dirs: usize,
+    /// Removed (or, for a dry run, matching) paths
+    paths: Vec<String>,
+    failed: Vec<CleanFailure>,
+    dry_run: bool,
+}
+
+/// Delete every entry below `dir` whose name matches `pattern`. Symbolic
+/// links are neither followed nor removed, and matching directories are
+/// only removed with `deleteDirs`; otherwise they are searched like any other.
+pub async fn clean_files(
+    Workspace(workspace): Workspace,
+    Json(req): Json<CleanRequest>,
+) -> Result<Json<ApiResponse<CleanResponse>>, AppError> {
+    if req.pattern.trim().is_empty() {
+        return Err(AppError::BadRequest(
+            "pattern must not be empty".to_string(),
+        ));
+    }
+    if req.pattern.contains('/') {
+        return Err(AppError::BadRequest(
+            "pattern matches names, not paths; it must not contain '/'".to_string(),
+        ));
+    }
+    let glob = Glob::new(&req.pattern).map_err(AppError::BadRequest)?;
+
+    let root = validate_path(&workspace, &req.dir)?;
+    let metadata = fs::metadata(&root)
+        .await
+        .map_err(|_| AppError::NotFound(format!("Directory not found: {}", root.display())))?;
+    if !metadata.is_dir() {
+        return Err(AppError::BadRequest(format!(
+            "Path is not a directory: {}",
+            root.display()
+        )));
+    }
+
+    Ok(Json(ApiResponse::success(
+        clean_tree(root, &glob, req.delete_dirs, req.dry_run).await,
+    )))
+}
+
+async fn clean_tree(root: PathBuf, glob: &Glob, delete_dirs: bool, dry_run: bool) -> CleanResponse {
+    let mut result = CleanResponse {
+        dry_run,
+        ..Default::default()
+    };
+
+    // Iterative DFS to avoid stack overflow
+    let mut dirs = vec![root];
+    while let Some(dir) = dirs.pop() {
+        let mut entries = match fs::read_dir(&dir).await {
+            Ok(e) => e,
+            Err(_) => continue, // Removed or unreadable
+        };
+        while let Ok(Some(entry)) = entries.next_entry().await {
+            let Ok(file_type) = entry.file_type().await else {
+                continue;
+            };
+            if file_type.is_symlink() {
+                continue;
+            }
+            let path = entry.path();
+            let matched = glob.is_match(&entry.file_name().to_string_lossy());
+
+            if file_type.is_dir() && !(matched && delete_dirs) {
+                dirs.push(path);
+                continue;
+            }
+            if !matched {
+                continue;
+            }
+
+            let removed = if dry_run {
+                Ok(())
+            } else if file_type.is_dir() {
+                fs::remove_dir_all(&path).await
+            } else {
+                fs::remove_file(&path).await
+            };
+            let path = path.to_string_lossy().to_string();
+            match removed {
+                Ok(()) => {
+                    if file_type.is_dir() {
+                        result.deleted_dirs += 1;
+                    } else {
+                        result.deleted_files += 1;
+                    }
+                    result.paths.push(path);
+                }
+                Err(e) => result.failed.push(CleanFailure {
+                    path,
+                    error: e.to_string(),
+                }),
+            }
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_clean_tree() {
+        let root = std::env::temp_dir().join(format!(
+            "devbox-clean-{}",
+            crate::utils::common::generate_id()
+        ));
+        std::fs::create_dir_all(root.join("app/node_modules/pkg")).unwrap();
+        std::fs::create_dir_all(root.join("logs.log")).unwrap();
+        std::fs::write(root.join("a.log"), "").unwrap();
+        std::fs::write(root.join("app/b.log"), "").unwrap();
+        std::fs::write(root.join("app/node_modules/pkg/c.log"), "").unwrap();
+        std::fs::write(root.join("keep.txt"), "").unwrap();
+        std::os::unix::fs::symlink(root.join("a.log"), root.join("link.log")).unwrap();
+
+        let glob = Glob::new("*.log").unwrap();
+        let preview = clean_tree(root.clone(), &glob, false, true).await;
+        assert_eq!(preview.deleted_files, 3);
+        assert!(root.join("a.log").exists());
+
+        let result = clean_tree(root.clone(), &glob, false, false).await;
+        assert_eq!((result.deleted_files, result.deleted_dirs), (3, 0));
+        assert!(!root.join("app/node_modules/pkg/c.log").exists());
+        // Directories only go with deleteDirs; symlinks are left alone
+        assert!(root.join("logs.log").is_dir());
+        assert!(root.join("link.log").symlink_metadata().is_ok());
+        assert!(root.join("keep.txt").exists());
+
+        let glob = Glob::new("node_modules").unwrap();
+        let result = clean_tree(root.clone(), &glob, true, false).await;
+        assert_eq!((result.deleted_files, result.deleted_dirs), (0, 1));
+        assert!(!root.join("app/node_modules").exists());
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,123 @@
+use super::search::is_probably_text;
+use super::types::WriteFileResponse;
+use crate::error::AppError;
+use crate::response::ApiResponse;
+use crate::state::workspace::Workspace;
+use crate::state::AppState;
+use crate::utils::diff::{apply_patch, unified_diff, PatchError};
+use crate::utils::path::{relative_to_workspace, validate_path, write_atomic};
+use axum::{extract::State, Json};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::Arc;
+use tokio::fs;
+
+/// Default number of unchanged lines around each hunk (matches `diff -u`)
+const DEFAULT_CONTEXT_LINES: usize = 3;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiffRequest {
+    left: String,
+    right: String,
+    #[serde(default)]
+    context_lines: Option<usize>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiffResponse {
+    diff: String,
+    identical: bool,
+    additions: usize,
+    deletions: usize,
+}
+
+pub async fn diff_files(
+    State(state): State<Arc<AppState>>,
+    Workspace(workspace): Workspace,
+    Json(req): Json<DiffRequest>,
+) -> Result<Json<ApiResponse<DiffResponse>>, AppError> {
+    let left_path = validate_path(&workspace, &req.left)?;
+    let right_path = validate_path(&workspace, &req.right)?;
+
+    let left = read_text_file(&left_path, &req.left, state.config.max_file_size).await?;
+    let right = read_text_file(&right_path, &req.right, state.config.max_file_size).await?;
+
+    let context = req.context_lines.unwrap_or(DEFAULT_CONTEXT_LINES);
+    let result = unified_diff(&left, &right, &req.left, &req.right, context);
+
+    Ok(Json(ApiResponse::success(DiffResponse {
+        identical: result.diff.is_empty(),
+        diff: result.diff,
+        additions: result.additions,
+        deletions: result.deletions,
+    })))
+}
+
+#[derive(Deserialize)]
+pub struct PatchRequest {
+    path: String,
+    patch: String,
+}
+
+/// Apply a unified diff to a file. The file is only replaced if every hunk applies.
+pub async fn patch_file(
+    State(state): State<Arc<AppState>>,
+    Workspace(workspace): Workspace,
+    Json(req): Json<PatchRequest>,
+) -> Result<Json<ApiResponse<WriteFileResponse>>, AppError> {
+    let valid_path = validate_path(&workspace, &req.path)?;
+    let original = read_text_file(&valid_path, &req.path, state.config.max_file_size).await?;
+
+    let patched = apply_patch(&original, &req.patch).map_err(|e| match e {
+        PatchError::Malformed(msg) => AppError::BadRequest(format!("Invalid patch: {}", msg)),
+        PatchError::Mismatch(msg) => AppError::Conflict(msg),
+    })?;
+
+    if patched.len() as u64 > state.config.max_file_size {
+        return Err(AppError::BadRequest("File too large".to_string()));
+    }
+
+    write_atomic(&state.config.temp_dir, &valid_path, patched.as_bytes()).await?;
+
+    Ok(Json(ApiResponse::success(WriteFileResponse {
+        path: valid_path.to_string_lossy().to_string(),
+        relative_path: relative_to_workspace(&workspace, &valid_path),
+        size: patched.len() as u64,
+        received_size: None,
+    })))
+}
+
+/// Read a file as UTF-8 text, rejecting directories, oversized and binary files.
+pub(super) async fn read_text_file(
+    path: &Path,
+    display: &str,
+    max_file_size: u64,
+) -> Result<String, AppError> {
+    let metadata = fs::metadata(path)
+        .await
+        .map_err(|_| AppError::NotFound(format!("File not found: {}", display)))?;
+
+    if metadata.is_dir() {
+        return Err(AppError::BadRequest(format!(
+            "Path is a directory, not a file: {}",
+            display
+        )));
+    }
+
+    if metadata.len() > max_file_size {
+        return Err(AppError::BadRequest(format!("File too large: {}", display)));
+    }
+
+    let bytes = fs::read(path).await?;
+    if !is_probably_text(&bytes[..bytes.len().min(256)]) {
+        return Err(AppError::BadRequest(format!(
+            "Binary file not supported: {}",
+            display
+        )));
+    }
+
+    String::from_utf8(bytes)
+        .map_err(|_| AppError::BadRequest(format!("File is not valid UTF-8: {}", display)))
+}
@@ -0,0 +1,129 @@
+use super::diff::read_text_file;
+use crate::error::AppError;
+use crate::response::ApiResponse;
+use crate::state::workspace::Workspace;
+use crate::state::AppState;
+use crate::utils::path::{relative_to_workspace, validate_path, write_atomic};
+use axum::{extract::State, Json};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EditLinesRequest {
+    path: String,
+    /// First line to replace (1-based)
+    start_line: usize,
+    /// Line after the last one replaced; equal to `start_line` inserts without removing
+    end_line: usize,
+    replacement: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EditLinesResponse {
+    path: String,
+    relative_path: Option<String>,
+    size: u64,
+    line_count: usize,
+}
+
+/// Replace lines `[startLine, endLine)` of a text file and write it back atomically.
+pub async fn edit_lines(
+    State(state): State<Arc<AppState>>,
+    Workspace(workspace): Workspace,
+    Json(req): Json<EditLinesRequest>,
+) -> Result<Json<ApiResponse<EditLinesResponse>>, AppError> {
+    let valid_path = validate_path(&workspace, &req.path)?;
+    let original = read_text_file(&valid_path, &req.path, state.config.max_file_size).await?;
+
+    let (edited, line_count) =
+        splice_lines(&original, req.start_line, req.end_line, &req.replacement)?;
+    if edited.len() as u64 > state.config.max_file_size {
+        return Err(AppError::BadRequest("File too large".to_string()));
+    }
+
+    write_atomic(&state.config.temp_dir, &valid_path, edited.as_bytes()).await?;
+
+    Ok(Json(ApiResponse::success(EditLinesResponse {
+        path: valid_path.to_string_lossy().to_string(),
+        relative_path: relative_to_workspace(&workspace, &valid_path),
+        size: edited.len() as u64,
+        line_count,
+    })))
+}
+
+/// Splice `replacement` over lines `[start, end)` (1-based). A replacement
+/// without a trailing newline gets the file's line ending unless it becomes
+/// the unterminated last line. Returns the new text and its line count.
+fn splice_lines(
+    text: &str,
+    start: usize,
+    end: usize,
+    replacement: &str,
+) -> Result<(String, usize), AppError> {
+    let lines: Vec<&str> = text.split_inclusive('\n').collect();
+    if start == 0 || start > end || end > lines.len() + 1 {
+        return Err(AppError::BadRequest(format!(
+            "Invalid line range {}..{}: need 1 <= startLine <= endLine <= {}",
+            start,
+            end,
+            lines.len() + 1
+        )));
+    }
+
+    let newline = if text.contains("\r\n") { "\r\n" } else { "\n" };
+    let (before, rest) = lines.split_at(start - 1);
+    let after = &rest[end - start..];
+    // Replacing through an unterminated last line keeps the file unterminated
+    let ends_file_unterminated = after.is_empty() && !text.is_empty() && !text.ends_with('\n');
+
+    let mut edited = before.concat();
+    if !edited.is_empty() && !edited.ends_with('\n') && !replacement.is_empty() {
+        // Appending after an unterminated last line
+        edited.push_str(newline);
+    }
+    edited.push_str(replacement);
+    let needs_newline = !replacement.is_empty() && !replacement.ends_with('\n');
+    if needs_newline && !ends_file_unterminated && !(after.is_empty() && text.is_empty()) {
+        edited.push_str(newline);
+    }
+    edited.push_str(&after.concat());
+
+    let line_count = edited.split_inclusive('\n').count();
+    Ok((edited, line_count))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn splice(text: &str, start: usize, end: usize, replacement: &str) -> (String, usize) {
+        splice_lines(text, start, end, replacement).unwrap()
+    }
+
+    #[test]
+    fn test_splice_lines() {
+        let text = "a\nb\nc\n";
+        assert_eq!(splice(text, 2, 3, "B"), ("a\nB\nc\n".to_string(), 3));
+        assert_eq!(splice(text, 2, 4, ""), ("a\n".to_string(), 1));
+        assert_eq!(
+            splice(text, 1, 1, "x\ny\n"),
+            ("x\ny\na\nb\nc\n".to_string(), 5)
+        );
+        assert_eq!(splice(text, 4, 4, "d"), ("a\nb\nc\nd\n".to_string(), 4));
+
+        // Line endings and unterminated last lines are preserved
+        assert_eq!(
+            splice("a\r\nb\r\n", 1, 2, "z"),
+            ("z\r\nb\r\n".to_string(), 2)
+        );
+        assert_eq!(splice("a\nb", 2, 3, "c"), ("a\nc".to_string(), 2));
+        assert_eq!(splice("a\nb", 3, 3, "c"), ("a\nb\nc".to_string(), 3));
+        assert_eq!(splice("", 1, 1, "new"), ("new".to_string(), 1));
+
+        assert!(splice_lines(text, 0, 1, "").is_err());
+        assert!(splice_lines(text, 3, 2, "").is_err());
+        assert!(splice_lines(text, 1, 5, "").is_err());
+    }
+}
@@ -1,21 +1,33 @@
+use super::search::is_probably_text;
 use super::types::{FileOperationResponse, WriteFileResponse};
+use crate::config::parse_flag;
 use crate::error::AppError;
 use crate::response::ApiResponse;
+use crate::state::upload_limit::UploadLimit;
+use crate::state::workspace::Workspace;
 use crate::state::AppState;
-use crate::utils::path::{ensure_directory, validate_path};
+use crate::utils::common::{format_http_date, parse_http_date};
+use crate::utils::hash::HashAlgorithm;
+use crate::utils::mime;
+use crate::utils::path::{
+    ensure_directory, relative_to_workspace, replace_with, staging_path, validate_path,
+};
+use crate::utils::validate::Validator;
 use axum::{
     body::Body,
     extract::{Multipart, Query, State},
-    http::header,
+    http::{header, HeaderMap, HeaderName, HeaderValue, StatusCode},
     response::{IntoResponse, Response},
     Json,
 };
+use flate2::write::GzDecoder;
 use futures::StreamExt;
-use serde::Deserialize;
-use std::path::PathBuf;
+use serde::{Deserialize, Serialize};
+use std::io::{SeekFrom, Write};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use tokio::fs;
-use tokio::io::AsyncWriteExt;
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
 use tokio_util::io::ReaderStream;
 
 #[derive(Deserialize)]
@@ -26,10 +38,10 @@ pub struct DeleteFileRequest {
 }
 
 pub async fn delete_file(
-    State(state): State<Arc<AppState>>,
+    Workspace(workspace): Workspace,
     Json(req): Json<DeleteFileRequest>,
 ) -> Result<Json<ApiResponse<FileOperationResponse>>, AppError> {
-    let valid_path = validate_path(&state.config.workspace_path, &req.path)?;
+    let valid_path = validate_path(&workspace, &req.path)?;
 
     if !valid_path.exists() {
         return Err(AppError::NotFound("File not found".to_string()));
@@ -55,13 +67,52 @@ pub struct WriteFileRequest {
     path: String,
     content: String,
     encoding: Option<String>,
+    /// Create missing parent directories; `false` requires the parent to exist
+    #[serde(default = "default_create_dirs", rename = "createDirs")]
+    create_dirs: bool,
+}
+
+fn default_create_dirs() -> bool {
+    true
+}
+
+/// Make room for a file at `path`: create its parent directories, or with
+/// `create_dirs` off, insist that the parent already exists so a mistyped
+/// path fails instead of growing a new tree.
+async fn prepare_parent(path: &Path, create_dirs: bool) -> Result<(), AppError> {
+    let Some(parent) = path.parent() else {
+        return Ok(());
+    };
+    if create_dirs {
+        return ensure_directory(parent).await;
+    }
+    if fs::metadata(parent).await.is_ok_and(|m| m.is_dir()) {
+        Ok(())
+    } else {
+        Err(AppError::NotFound(format!(
+            "Parent directory does not exist: {}",
+            parent.display()
+        )))
+    }
+}
+
+/// Room for `path`, `encoding` and the JSON syntax around `content`
+const JSON_WRITE_OVERHEAD: u64 = 64 * 1024;
+
+/// Largest JSON body `/files/write` reads for an upload limit of `max_size`:
+/// the base64 encoding of a file that size plus the other fields. The route
+/// has no default body limit, so this is what stops an oversized JSON body
+/// before it is buffered.
+pub fn json_body_limit(max_size: u64) -> usize {
+    (max_size.div_ceil(3) * 4 + JSON_WRITE_OVERHEAD).min(usize::MAX as u64) as usize
 }
 
 pub async fn write_file_json(
-    State(state): State<Arc<AppState>>,
+    Workspace(workspace): Workspace,
+    UploadLimit(max_size): UploadLimit,
     Json(req): Json<WriteFileRequest>,
 ) -> Result<Json<ApiResponse<WriteFileResponse>>, AppError> {
-    let valid_path = validate_path(&state.config.workspace_path, &req.path)?;
+    let valid_path = validate_path(&workspace, &req.path)?;
 
     let content_bytes = if let Some(enc) = req.encoding {
         if enc == "base64" {
@@ -76,30 +127,75 @@ pub async fn write_file_json(
         req.content.into_bytes()
     };
 
-    if content_bytes.len() as u64 > state.config.max_file_size {
+    if content_bytes.len() as u64 > max_size {
         return Err(AppError::BadRequest("File too large".to_string()));
     }
 
-    if let Some(parent) = valid_path.parent() {
-        ensure_directory(parent).await?;
-    }
+    prepare_parent(&valid_path, req.create_dirs).await?;
 
     fs::write(&valid_path, content_bytes).await?;
 
     Ok(Json(ApiResponse::success(WriteFileResponse {
         path: valid_path.to_string_lossy().to_string(),
+        relative_path: relative_to_workspace(&workspace, &valid_path),
         size: fs::metadata(&valid_path).await?.len(),
+        received_size: None,
     })))
 }
 
+/// Save the single file of a multipart form. The upload is staged in
+/// `TEMP_DIR` (or next to its target) and only renamed into place once the whole form has been read, so a
+/// form with a second file field is rejected without touching anything; use
+/// `/files/batch-upload` for several files.
 pub async fn write_file_multipart(
     State(state): State<Arc<AppState>>,
+    Workspace(workspace): Workspace,
+    UploadLimit(max_size): UploadLimit,
     mut multipart: Multipart,
 ) -> Result<Json<ApiResponse<WriteFileResponse>>, AppError> {
+    let mut staged = None;
+    let temp_dir = &state.config.temp_dir;
+    let received = receive_upload(&workspace, temp_dir, max_size, &mut multipart, &mut staged);
+    if let Err(e) = received.await {
+        if let Some(upload) = staged {
+            fs::remove_file(&upload.tmp_path).await.ok();
+        }
+        return Err(e);
+    }
+
+    let Some(upload) = staged else {
+        return Err(AppError::BadRequest(
+            "No file found in multipart form".to_string(),
+        ));
+    };
+    if let Err(e) = replace_with(&upload.tmp_path, &upload.path).await {
+        fs::remove_file(&upload.tmp_path).await.ok();
+        return Err(e.into());
+    }
+
+    Ok(Json(ApiResponse::success(WriteFileResponse {
+        path: upload.path.to_string_lossy().to_string(),
+        relative_path: relative_to_workspace(&workspace, &upload.path),
+        size: upload.size,
+        received_size: None,
+    })))
+}
+
+/// A multipart file written to a staging path for its target.
+struct StagedUpload {
+    path: PathBuf,
+    tmp_path: PathBuf,
+    size: u64,
+}
+
+async fn receive_upload(
+    workspace: &Path,
+    temp_dir: &Path,
+    max_size: u64,
+    multipart: &mut Multipart,
+    staged: &mut Option<StagedUpload>,
+) -> Result<(), AppError> {
     let mut target_path = None;
-    let mut file_saved = false;
-    let mut saved_size = 0;
-    let mut saved_path = PathBuf::new();
 
     while let Some(field) = multipart
         .next_field()
@@ -115,78 +211,384 @@ pub async fn write_file_multipart(
                 .map_err(|e| AppError::BadRequest(e.to_string()))?;
             target_path = Some(val);
         } else if name == "file" || name == "files" {
+            if staged.is_some() {
+                return Err(AppError::BadRequest(
+                    "Multipart form has more than one file field; /files/write saves a single file, use /files/batch-upload for several".to_string(),
+                ));
+            }
             let filename = field.file_name().unwrap_or("unknown").to_string();
             let path_str = target_path.clone().unwrap_or_else(|| filename.clone());
-            let valid_path = validate_path(&state.config.workspace_path, &path_str)?;
+            let valid_path = validate_path(workspace, &path_str)?;
 
             if let Some(parent) = valid_path.parent() {
                 ensure_directory(parent).await?;
             }
 
-            let mut file = fs::File::create(&valid_path).await?;
-            let mut size = 0;
+            let tmp_path = staging_path(temp_dir, &valid_path).await;
+            let mut file = fs::File::create(&tmp_path).await?;
+            let upload = staged.insert(StagedUpload {
+                path: valid_path,
+                tmp_path,
+                size: 0,
+            });
 
             let mut stream = field;
             while let Some(chunk) = stream.next().await {
                 let chunk = chunk.map_err(|e| AppError::InternalServerError(e.to_string()))?;
-                size += chunk.len() as u64;
-                if size > state.config.max_file_size {
-                    drop(file);
-                    fs::remove_file(&valid_path).await.ok();
+                upload.size += chunk.len() as u64;
+                if upload.size > max_size {
                     return Err(AppError::BadRequest("File too large".to_string()));
                 }
                 file.write_all(&chunk).await?;
             }
-
-            file_saved = true;
-            saved_size = size;
-            saved_path = valid_path;
+            file.flush().await?;
         }
     }
-
-    if !file_saved {
-        return Err(AppError::BadRequest(
-            "No file found in multipart form".to_string(),
-        ));
-    }
-
-    Ok(Json(ApiResponse::success(WriteFileResponse {
-        path: saved_path.to_string_lossy().to_string(),
-        size: saved_size,
-    })))
+    Ok(())
 }
 
 pub async fn write_file_binary(
-    State(state): State<Arc<AppState>>,
+    Workspace(workspace): Workspace,
+    UploadLimit(max_size): UploadLimit,
     Query(params): Query<std::collections::HashMap<String, String>>,
+    headers: HeaderMap,
     body: Body,
 ) -> Result<Json<ApiResponse<WriteFileResponse>>, AppError> {
     let path_str = params
         .get("path")
         .ok_or_else(|| AppError::BadRequest("Path parameter required".to_string()))?;
-    let valid_path = validate_path(&state.config.workspace_path, path_str)?;
+    let valid_path = validate_path(&workspace, path_str)?;
+    let create_dirs = params.get("createDirs").map(|v| parse_flag(v));
+    let mut v = Validator::default();
+    v.check(
+        create_dirs != Some(None),
+        "createDirs",
+        "must be true or false",
+    );
+    v.finish()?;
+    let create_dirs = create_dirs.flatten().unwrap_or(true);
 
-    if let Some(parent) = valid_path.parent() {
-        ensure_directory(parent).await?;
+    let gzip = match headers
+        .get(header::CONTENT_ENCODING)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.trim().to_ascii_lowercase())
+    {
+        None => false,
+        Some(encoding) if encoding.is_empty() || encoding == "identity" => false,
+        Some(encoding) if encoding == "gzip" || encoding == "x-gzip" => true,
+        Some(encoding) => {
+            return Err(AppError::BadRequest(format!(
+                "Unsupported Content-Encoding: {} (only gzip is accepted)",
+                encoding
+            )))
+        }
+    };
+
+    // `encoding=base64` decodes the body while it is written, so large base64
+    // content never has to be held in memory the way a JSON write holds it
+    let mut base64 = match params.get("encoding").map(String::as_str) {
+        None | Some("") | Some("utf8") | Some("binary") => None,
+        Some("base64") => Some(Base64Stream::default()),
+        Some(other) => {
+            return Err(AppError::BadRequest(format!(
+                "Unsupported encoding: {} (only base64 is accepted)",
+                other
+            )))
+        }
+    };
+    let encoded = gzip || base64.is_some();
+
+    // Reject declared oversize uploads before reading the body; the streaming
+    // check below still covers chunked uploads without a length. An encoded
+    // body's length says nothing exact about what it decodes to, so only the
+    // decoded size is checked for those
+    let content_length = headers
+        .get(header::CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<u64>().ok());
+    if let Some(length) = content_length.filter(|&length| !encoded && length > max_size) {
+        return Err(AppError::BadRequest(format!(
+            "File too large: Content-Length is {} bytes, limit is {} bytes",
+            length, max_size
+        )));
     }
 
+    prepare_parent(&valid_path, create_dirs).await?;
+
     let mut file = fs::File::create(&valid_path).await?;
     let mut size = 0;
+    let mut received = 0;
+    // Decompresses into a capped buffer, which is drained to disk after every
+    // chunk. base64 text runs to 4/3 of its data plus line breaks, so twice
+    // the limit leaves room for any common wrapping
+    let inflate_limit = if base64.is_some() {
+        max_size.saturating_mul(2)
+    } else {
+        max_size
+    };
+    let mut decoder = gzip.then(|| GzDecoder::new(CappedBuffer::new(inflate_limit)));
 
     let mut stream = body.into_data_stream();
     while let Some(chunk) = stream.next().await {
         let chunk = chunk.map_err(|e| AppError::InternalServerError(e.to_string()))?;
-        size += chunk.len() as u64;
-        if size > state.config.max_file_size {
-            drop(file);
-            fs::remove_file(&valid_path).await.ok();
-            return Err(AppError::BadRequest("File too large".to_string()));
+        received += chunk.len() as u64;
+
+        let decoded;
+        let data: &[u8] = match decoder.as_mut() {
+            Some(d) => {
+                if let Err(e) = d.write_all(&chunk) {
+                    let err = inflate_error(d.get_ref(), e);
+                    return Err(discard_upload(file, &valid_path, err).await);
+                }
+                decoded = d.get_mut().take();
+                &decoded
+            }
+            None => &chunk,
+        };
+        let unwrapped;
+        let data: &[u8] = match base64.as_mut() {
+            Some(b) => match b.push(data) {
+                Ok(bytes) => {
+                    unwrapped = bytes;
+                    &unwrapped
+                }
+                Err(e) => return Err(discard_upload(file, &valid_path, e).await),
+            },
+            None => data,
+        };
+
+        size += data.len() as u64;
+        if size > max_size {
+            return Err(discard_upload(file, &valid_path, too_large()).await);
         }
-        file.write_all(&chunk).await?;
+        file.write_all(data).await?;
+    }
+
+    // Flush what the decoders still hold; this also catches truncated streams
+    let mut tail = Vec::new();
+    if let Some(mut d) = decoder {
+        if let Err(e) = d.try_finish() {
+            let err = inflate_error(d.get_ref(), e);
+            return Err(discard_upload(file, &valid_path, err).await);
+        }
+        tail = d.get_mut().take();
+    }
+    if let Some(mut b) = base64 {
+        match b.push(&tail).and_then(|mut bytes| {
+            bytes.extend(b.finish()?);
+            Ok(bytes)
+        }) {
+            Ok(bytes) => tail = bytes,
+            Err(e) => return Err(discard_upload(file, &valid_path, e).await),
+        }
+    }
+    size += tail.len() as u64;
+    if size > max_size {
+        return Err(discard_upload(file, &valid_path, too_large()).await);
     }
+    file.write_all(&tail).await?;
 
     Ok(Json(ApiResponse::success(WriteFileResponse {
         path: valid_path.to_string_lossy().to_string(),
+        relative_path: relative_to_workspace(&workspace, &valid_path),
+        size,
+        received_size: encoded.then_some(received),
+    })))
+}
+
+/// Incremental base64 decoding for `encoding=base64` binary uploads. Input
+/// is decoded in whole four-character groups and the rest carried over to
+/// the next chunk; whitespace, as in line-wrapped base64, is skipped.
+#[derive(Default)]
+struct Base64Stream {
+    pending: Vec<u8>,
+    /// A padded group was decoded, so nothing may follow
+    padded: bool,
+}
+
+impl Base64Stream {
+    fn push(&mut self, chunk: &[u8]) -> Result<Vec<u8>, AppError> {
+        self.pending
+            .extend(chunk.iter().filter(|b| !b.is_ascii_whitespace()));
+        if self.padded && !self.pending.is_empty() {
+            return Err(invalid_base64("data after padding"));
+        }
+        let whole = self.pending.len() / 4 * 4;
+        self.decode(whole)
+    }
+
+    fn finish(&mut self) -> Result<Vec<u8>, AppError> {
+        self.decode(self.pending.len())
+    }
+
+    fn decode(&mut self, len: usize) -> Result<Vec<u8>, AppError> {
+        use base64::{engine::general_purpose, Engine as _};
+        if len == 0 {
+            return Ok(Vec::new());
+        }
+        let decoded = general_purpose::STANDARD
+            .decode(&self.pending[..len])
+            .map_err(invalid_base64)?;
+        self.padded = self.pending[len - 1] == b'=';
+        self.pending.drain(..len);
+        Ok(decoded)
+    }
+}
+
+fn invalid_base64(e: impl std::fmt::Display) -> AppError {
+    AppError::BadRequest(format!("Invalid base64: {}", e))
+}
+
+fn invalid_gzip(e: std::io::Error) -> AppError {
+    AppError::BadRequest(format!("Invalid gzip body: {}", e))
+}
+
+/// Gzip output of an upload, refusing to grow once `limit` bytes have been
+/// decompressed in total, so a small compressed body cannot inflate past the
+/// size limit in memory before it is checked.
+struct CappedBuffer {
+    data: Vec<u8>,
+    written: u64,
+    limit: u64,
+    exceeded: bool,
+}
+
+impl CappedBuffer {
+    fn new(limit: u64) -> Self {
+        Self {
+            data: Vec::new(),
+            written: 0,
+            limit,
+            exceeded: false,
+        }
+    }
+
+    fn take(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.data)
+    }
+}
+
+impl Write for CappedBuffer {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if self.written + buf.len() as u64 > self.limit {
+            self.exceeded = true;
+            return Err(std::io::Error::other("decompressed size limit exceeded"));
+        }
+        self.written += buf.len() as u64;
+        self.data.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+fn inflate_error(buffer: &CappedBuffer, e: std::io::Error) -> AppError {
+    if buffer.exceeded {
+        too_large()
+    } else {
+        invalid_gzip(e)
+    }
+}
+
+fn too_large() -> AppError {
+    AppError::BadRequest("File too large".to_string())
+}
+
+/// Remove a partially written upload and hand back the error that stopped it.
+async fn discard_upload(file: fs::File, path: &Path, err: AppError) -> AppError {
+    drop(file);
+    fs::remove_file(path).await.ok();
+    err
+}
+
+#[derive(Deserialize)]
+pub struct WriteAtParams {
+    path: String,
+    offset: u64,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WriteAtResponse {
+    path: String,
+    relative_path: Option<String>,
+    offset: u64,
+    written: u64,
+    /// File size after the write
+    size: u64,
+}
+
+/// Overwrite the bytes of an existing file starting at `offset` with the
+/// request body, leaving everything else in place. Writing past the end
+/// extends the file, with a hole before `offset` if it starts beyond it.
+/// The write is not atomic: on failure the bytes written so far remain.
+pub async fn write_file_at(
+    Workspace(workspace): Workspace,
+    UploadLimit(max_size): UploadLimit,
+    Query(params): Query<WriteAtParams>,
+    headers: HeaderMap,
+    body: Body,
+) -> Result<Json<ApiResponse<WriteAtResponse>>, AppError> {
+    let valid_path = validate_path(&workspace, &params.path)?;
+    let limit_error = || {
+        AppError::BadRequest(format!(
+            "Write would end past the file size limit of {} bytes",
+            max_size
+        ))
+    };
+    let content_length = headers
+        .get(header::CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<u64>().ok());
+    if content_length.is_some_and(|length| params.offset.saturating_add(length) > max_size)
+        || params.offset > max_size
+    {
+        return Err(limit_error());
+    }
+
+    let mut file = match fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(&valid_path)
+        .await
+    {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            return Err(AppError::NotFound(format!(
+                "File not found: {}",
+                valid_path.display()
+            )))
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::IsADirectory => {
+            return Err(AppError::IsADirectory(format!(
+                "Path is a directory: {}",
+                valid_path.display()
+            )))
+        }
+        Err(e) => return Err(e.into()),
+    };
+    file.seek(SeekFrom::Start(params.offset)).await?;
+
+    let mut written = 0;
+    let mut stream = body.into_data_stream();
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk.map_err(|e| AppError::InternalServerError(e.to_string()))?;
+        written += chunk.len() as u64;
+        if params.offset + written > max_size {
+            return Err(limit_error());
+        }
+        file.write_all(&chunk).await?;
+    }
+    file.flush().await?;
+    let size = file.metadata().await?.len();
+
+    Ok(Json(ApiResponse::success(WriteAtResponse {
+        path: valid_path.to_string_lossy().to_string(),
+        relative_path: relative_to_workspace(&workspace, &valid_path),
+        offset: params.offset,
+        written,
         size,
     })))
 }
@@ -194,27 +596,69 @@ pub async fn write_file_binary(
 #[derive(Deserialize)]
 pub struct ReadFileParams {
     path: String,
+    /// Serve text files with `Content-Disposition: inline` instead of `attachment`
+    #[serde(default)]
+    inline: bool,
+    /// Start reading at this byte (default 0)
+    offset: Option<u64>,
+    /// Serve at most this many bytes (default: to the end of the file)
+    length: Option<u64>,
 }
 
+/// Response header telling clients whether the file looks like text.
+const FILE_IS_TEXT_HEADER: &str = "x-file-is-text";
+
+/// Response headers describing the served slice: its first byte and the
+/// size of the whole file (`Content-Length` is the slice length).
+const FILE_OFFSET_HEADER: &str = "x-file-offset";
+const FILE_SIZE_HEADER: &str = "x-file-size";
+
+/// How many leading bytes are sniffed for the text/binary hint.
+const TEXT_SNIFF_LEN: usize = 256;
+
 pub async fn read_file(
     State(state): State<Arc<AppState>>,
+    Workspace(workspace): Workspace,
     Query(params): Query<ReadFileParams>,
+    headers: HeaderMap,
 ) -> Result<Response, AppError> {
-    let valid_path = validate_path(&state.config.workspace_path, &params.path)?;
+    let valid_path = validate_path(&workspace, &params.path)?;
 
     if !valid_path.exists() {
         return Err(AppError::NotFound("File not found".to_string()));
     }
 
     if valid_path.is_dir() {
-        return Err(AppError::BadRequest(
+        return Err(AppError::IsADirectory(
             "Path is a directory, not a file".to_string(),
         ));
     }
 
-    let file = fs::File::open(&valid_path).await?;
+    let mut file = fs::File::open(&valid_path).await?;
     let metadata = file.metadata().await?;
     let size = metadata.len();
+    let (offset, length) = read_range(size, params.offset, params.length)?;
+
+    // HTTP dates have whole-second precision, so compare whole seconds
+    let modified = metadata
+        .modified()
+        .ok()
+        .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+        .map(|d| d.as_secs());
+    let last_modified = modified.map(format_http_date);
+    let not_modified = headers
+        .get(header::IF_MODIFIED_SINCE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(parse_http_date)
+        .zip(modified)
+        .is_some_and(|(since, modified)| modified <= since);
+    if not_modified {
+        return Ok(with_last_modified(
+            StatusCode::NOT_MODIFIED.into_response(),
+            last_modified,
+        ));
+    }
+
     let filename = valid_path
         .file_name()
         .unwrap_or_default()
@@ -222,19 +666,392 @@ pub async fn read_file(
         .to_string();
     let mime_type = "application/octet-stream".to_string();
 
-    let stream = ReaderStream::new(file);
+    let mut sniff = Vec::with_capacity(TEXT_SNIFF_LEN);
+    (&mut file)
+        .take(TEXT_SNIFF_LEN as u64)
+        .read_to_end(&mut sniff)
+        .await?;
+    file.seek(SeekFrom::Start(offset)).await?;
+    let is_text = is_probably_text(&sniff);
+    let disposition = if is_text && params.inline {
+        "inline"
+    } else {
+        "attachment"
+    };
+
+    let stream = ReaderStream::with_capacity(file.take(length), state.config.io_buffer_size);
     let body = Body::from_stream(stream);
 
     let headers = [
         (header::CONTENT_TYPE, mime_type),
-        (header::CONTENT_LENGTH, size.to_string()),
+        (header::CONTENT_LENGTH, length.to_string()),
+        (
+            HeaderName::from_static(FILE_OFFSET_HEADER),
+            offset.to_string(),
+        ),
+        (HeaderName::from_static(FILE_SIZE_HEADER), size.to_string()),
         (
             header::CONTENT_DISPOSITION,
-            format!("attachment; filename=\"{}\"", filename),
+            format!("{}; filename=\"{}\"", disposition, filename),
+        ),
+        (
+            HeaderName::from_static(FILE_IS_TEXT_HEADER),
+            is_text.to_string(),
         ),
     ];
 
-    Ok((headers, body).into_response())
+    Ok(with_last_modified(
+        (headers, body).into_response(),
+        last_modified,
+    ))
+}
+
+#[derive(Deserialize)]
+pub struct MimeTypeParams {
+    path: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MimeTypeResponse {
+    mime_type: &'static str,
+    is_text: bool,
+}
+
+/// Content type of a file from its extension, falling back to the magic
+/// bytes and the text heuristic, reading only the first few bytes.
+pub async fn get_mime_type(
+    Workspace(workspace): Workspace,
+    Query(params): Query<MimeTypeParams>,
+) -> Result<Json<ApiResponse<MimeTypeResponse>>, AppError> {
+    let valid_path = validate_path(&workspace, &params.path)?;
+    if !valid_path.exists() {
+        return Err(AppError::NotFound("File not found".to_string()));
+    }
+    if valid_path.is_dir() {
+        return Err(AppError::IsADirectory(
+            "Path is a directory, not a file".to_string(),
+        ));
+    }
+
+    let mut sniff = Vec::with_capacity(TEXT_SNIFF_LEN);
+    fs::File::open(&valid_path)
+        .await?
+        .take(TEXT_SNIFF_LEN as u64)
+        .read_to_end(&mut sniff)
+        .await?;
+    let is_text = is_probably_text(&sniff);
+    let mime_type = mime::from_extension(&valid_path)
+        .or_else(|| mime::from_magic(&sniff))
+        .unwrap_or(if is_text {
+            "text/plain"
+        } else {
+            "application/octet-stream"
+        });
+
+    Ok(Json(ApiResponse::success(MimeTypeResponse {
+        mime_type,
+        is_text,
+    })))
+}
+
+#[derive(Deserialize)]
+pub struct TailLinesParams {
+    path: String,
+    /// How many lines to return from the end (default 10)
+    lines: Option<usize>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TailLinesResponse {
+    lines: Vec<String>,
+    size: u64,
+}
+
+/// Default `lines` for `files/tail-lines`, as with `tail(1)`
+const DEFAULT_TAIL_LINES: usize = 10;
+
+/// Last lines of a file, read backwards from the end in `IO_BUFFER_SIZE`
+/// chunks so only the returned lines are read. The lines may not add up to
+/// more than `MAX_FILE_SIZE` bytes.
+pub async fn tail_file_lines(
+    State(state): State<Arc<AppState>>,
+    Workspace(workspace): Workspace,
+    Query(params): Query<TailLinesParams>,
+) -> Result<Json<ApiResponse<TailLinesResponse>>, AppError> {
+    let valid_path = validate_path(&workspace, &params.path)?;
+    if !valid_path.exists() {
+        return Err(AppError::NotFound("File not found".to_string()));
+    }
+    if valid_path.is_dir() {
+        return Err(AppError::IsADirectory(
+            "Path is a directory, not a file".to_string(),
+        ));
+    }
+
+    let mut file = fs::File::open(&valid_path).await?;
+    let size = file.metadata().await?.len();
+    let lines = read_last_lines(
+        &mut file,
+        size,
+        params.lines.unwrap_or(DEFAULT_TAIL_LINES),
+        state.config.io_buffer_size,
+        state.config.max_file_size,
+    )
+    .await?;
+
+    Ok(Json(ApiResponse::success(TailLinesResponse {
+        lines,
+        size,
+    })))
+}
+
+#[derive(Deserialize)]
+pub struct HeadParams {
+    path: String,
+    /// How many bytes to return from the start (default 4096)
+    bytes: Option<u64>,
+    /// `utf8` (default) or `base64`
+    encoding: Option<String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HeadResponse {
+    content: String,
+    encoding: &'static str,
+    /// Bytes of the file covered by `content`
+    length: u64,
+    /// Size of the whole file
+    size: u64,
+    truncated: bool,
+}
+
+/// Default `bytes` for `files/head`
+const DEFAULT_HEAD_BYTES: u64 = 4096;
+
+/// The first `bytes` bytes of a file, for previews and type detection
+/// without streaming the whole file. `bytes` may not exceed `MAX_FILE_SIZE`.
+pub async fn head_file(
+    State(state): State<Arc<AppState>>,
+    Workspace(workspace): Workspace,
+    Query(params): Query<HeadParams>,
+) -> Result<Json<ApiResponse<HeadResponse>>, AppError> {
+    let base64 = match params.encoding.as_deref() {
+        None | Some("utf8") | Some("utf-8") => false,
+        Some("base64") => true,
+        Some(other) => {
+            return Err(AppError::BadRequest(format!(
+                "Unsupported encoding: {} (expected utf8 or base64)",
+                other
+            )))
+        }
+    };
+    let bytes = params.bytes.unwrap_or(DEFAULT_HEAD_BYTES);
+    if bytes > state.config.max_file_size {
+        return Err(AppError::BadRequest(format!(
+            "bytes {} exceeds the maximum size of {} bytes",
+            bytes, state.config.max_file_size
+        )));
+    }
+
+    let valid_path = validate_path(&workspace, &params.path)?;
+    if !valid_path.exists() {
+        return Err(AppError::NotFound("File not found".to_string()));
+    }
+    if valid_path.is_dir() {
+        return Err(AppError::IsADirectory(
+            "Path is a directory, not a file".to_string(),
+        ));
+    }
+
+    let file = fs::File::open(&valid_path).await?;
+    let size = file.metadata().await?.len();
+    let mut data = Vec::with_capacity(bytes.min(size) as usize);
+    file.take(bytes).read_to_end(&mut data).await?;
+
+    let content = if base64 {
+        use base64::{engine::general_purpose, Engine as _};
+        general_purpose::STANDARD.encode(&data)
+    } else {
+        let text = utf8_prefix(&data).ok_or_else(|| {
+            AppError::BadRequest("File is not valid UTF-8; use encoding=base64".to_string())
+        })?;
+        text.to_string()
+    };
+    let length = if base64 { data.len() } else { content.len() } as u64;
+
+    Ok(Json(ApiResponse::success(HeadResponse {
+        content,
+        encoding: if base64 { "base64" } else { "utf8" },
+        length,
+        size,
+        truncated: length < size,
+    })))
+}
+
+/// `data` as text, dropping a character cut in half at the end. `None` when
+/// the bytes before that are not UTF-8.
+fn utf8_prefix(data: &[u8]) -> Option<&str> {
+    match std::str::from_utf8(data) {
+        Ok(text) => Some(text),
+        // `error_len` is `None` only for an incomplete sequence at the end
+        Err(e) if e.error_len().is_none() => {
+            Some(std::str::from_utf8(&data[..e.valid_up_to()]).unwrap_or_default())
+        }
+        Err(_) => None,
+    }
+}
+
+#[derive(Deserialize)]
+pub struct HashParams {
+    path: String,
+    /// `sha256` (default), `md5` or `blake3`
+    algo: Option<String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HashResponse {
+    algo: &'static str,
+    /// Lowercase hex
+    digest: String,
+    size: u64,
+}
+
+/// Digest of a file, so clients can verify an upload or decide whether to
+/// download without transferring the contents. Files of any size are
+/// accepted; hashing runs on the blocking pool.
+pub async fn hash_file(
+    State(state): State<Arc<AppState>>,
+    Workspace(workspace): Workspace,
+    Query(params): Query<HashParams>,
+) -> Result<Json<ApiResponse<HashResponse>>, AppError> {
+    let algorithm = match params.algo.as_deref() {
+        None => HashAlgorithm::Sha256,
+        Some(name) => HashAlgorithm::parse(name).ok_or_else(|| {
+            AppError::BadRequest(format!(
+                "Unsupported algo: {} (expected sha256, md5 or blake3)",
+                name
+            ))
+        })?,
+    };
+
+    let valid_path = validate_path(&workspace, &params.path)?;
+    if !valid_path.exists() {
+        return Err(AppError::NotFound("File not found".to_string()));
+    }
+    if valid_path.is_dir() {
+        return Err(AppError::IsADirectory(
+            "Path is a directory, not a file".to_string(),
+        ));
+    }
+
+    let buffer_size = state.config.io_buffer_size;
+    let (digest, size) = tokio::task::spawn_blocking(move || {
+        use std::io::Read;
+        let mut file = std::fs::File::open(&valid_path)?;
+        let mut hasher = algorithm.hasher();
+        let mut buf = vec![0u8; buffer_size];
+        let mut size = 0;
+        loop {
+            let n = file.read(&mut buf)?;
+            if n == 0 {
+                break;
+            }
+            hasher.update(&buf[..n]);
+            size += n as u64;
+        }
+        Ok::<_, std::io::Error>((hasher.finalize_hex(), size))
+    })
+    .await
+    .map_err(|e| AppError::InternalServerError(e.to_string()))??;
+
+    Ok(Json(ApiResponse::success(HashResponse {
+        algo: algorithm.name(),
+        digest,
+        size,
+    })))
+}
+
+/// Read chunks of `chunk_size` backwards from the end of `file` until `count`
+/// whole lines are in hand or the start of the file is reached. A final
+/// newline does not start an empty line; `\r\n` endings are stripped too.
+async fn read_last_lines(
+    file: &mut fs::File,
+    size: u64,
+    count: usize,
+    chunk_size: usize,
+    max_bytes: u64,
+) -> Result<Vec<String>, AppError> {
+    if count == 0 || size == 0 {
+        return Ok(Vec::new());
+    }
+
+    let mut tail: Vec<u8> = Vec::new();
+    let mut pos = size;
+    let mut newlines = 0;
+    // Newlines before the last line, ignoring the one ending the file
+    while pos > 0 && newlines < count {
+        if tail.len() as u64 >= max_bytes {
+            return Err(AppError::BadRequest(format!(
+                "The last {} lines exceed the maximum size of {} bytes",
+                count, max_bytes
+            )));
+        }
+        let len = (chunk_size.max(1) as u64).min(pos);
+        pos -= len;
+        let mut chunk = vec![0u8; len as usize];
+        file.seek(SeekFrom::Start(pos)).await?;
+        file.read_exact(&mut chunk).await?;
+
+        let ends_file = tail.is_empty();
+        newlines += chunk.iter().filter(|&&b| b == b'\n').count();
+        if ends_file && chunk.last() == Some(&b'\n') {
+            newlines -= 1;
+        }
+        chunk.extend_from_slice(&tail);
+        tail = chunk;
+    }
+
+    let text = tail.strip_suffix(b"\n").unwrap_or(&tail);
+    let mut lines: Vec<String> = text
+        .rsplit(|&b| b == b'\n')
+        .take(count)
+        .map(|line| String::from_utf8_lossy(line.strip_suffix(b"\r").unwrap_or(line)).into_owned())
+        .collect();
+    lines.reverse();
+    Ok(lines)
+}
+
+/// Resolve `offset`/`length` query parameters against a file of `size`
+/// bytes into the `(offset, length)` to serve. Either one reaching past the
+/// end of the file is rejected rather than silently shortened.
+fn read_range(size: u64, offset: Option<u64>, length: Option<u64>) -> Result<(u64, u64), AppError> {
+    let offset = offset.unwrap_or(0);
+    if offset > size {
+        return Err(AppError::BadRequest(format!(
+            "offset {} is beyond the end of the file ({} bytes)",
+            offset, size
+        )));
+    }
+    let remaining = size - offset;
+    match length {
+        Some(length) if length > remaining => Err(AppError::BadRequest(format!(
+            "offset {} + length {} is beyond the end of the file ({} bytes)",
+            offset, length, size
+        ))),
+        Some(length) => Ok((offset, length)),
+        None => Ok((offset, remaining)),
+    }
+}
+
+fn with_last_modified(mut response: Response, last_modified: Option<String>) -> Response {
+    if let Some(value) = last_modified.and_then(|v| HeaderValue::from_str(&v).ok()) {
+        response.headers_mut().insert(header::LAST_MODIFIED, value);
+    }
+    response
 }
 
 #[derive(Deserialize)]
@@ -243,19 +1060,64 @@ pub struct MoveFileRequest {
     destination: String,
     #[serde(default)]
     overwrite: bool,
+    /// Move a directory's contents into an existing destination directory
+    /// instead of replacing it
+    #[serde(default)]
+    merge: bool,
+    /// Keep access and modification times when the move has to copy across
+    /// filesystems; rename(2) keeps them anyway
+    #[serde(default = "default_preserve_timestamps", rename = "preserveTimestamps")]
+    preserve_timestamps: bool,
+}
+
+fn default_preserve_timestamps() -> bool {
+    true
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MoveFileResponse {
+    success: bool,
+    /// Entries moved into the destination by a merge; a directory that
+    /// did not exist there yet counts once
+    #[serde(skip_serializing_if = "Option::is_none")]
+    moved: Option<usize>,
+    /// Destination files a merge replaced
+    #[serde(skip_serializing_if = "Option::is_none")]
+    overwritten: Option<usize>,
 }
 
 pub async fn move_file(
-    State(state): State<Arc<AppState>>,
+    Workspace(workspace): Workspace,
     Json(req): Json<MoveFileRequest>,
-) -> Result<Json<ApiResponse<FileOperationResponse>>, AppError> {
-    let source_path = validate_path(&state.config.workspace_path, &req.source)?;
-    let dest_path = validate_path(&state.config.workspace_path, &req.destination)?;
+) -> Result<Json<ApiResponse<MoveFileResponse>>, AppError> {
+    let source_path = validate_path(&workspace, &req.source)?;
+    let dest_path = validate_path(&workspace, &req.destination)?;
 
     if !source_path.exists() {
         return Err(AppError::NotFound("Source file not found".to_string()));
     }
 
+    if req.merge && source_path.is_dir() && dest_path.is_dir() {
+        if dest_path.starts_with(&source_path) {
+            return Err(AppError::BadRequest(
+                "Cannot merge a directory into itself".to_string(),
+            ));
+        }
+        let (moved, overwritten) = merge_dirs(
+            &source_path,
+            &dest_path,
+            req.overwrite,
+            req.preserve_timestamps,
+        )
+        .await?;
+        return Ok(Json(ApiResponse::success(MoveFileResponse {
+            success: true,
+            moved: Some(moved),
+            overwritten: Some(overwritten),
+        })));
+    }
+
     if dest_path.exists() {
         if !req.overwrite {
             return Err(AppError::Conflict("Destination already exists".to_string()));
@@ -271,13 +1133,175 @@ pub async fn move_file(
         ensure_directory(parent).await?;
     }
 
-    fs::rename(source_path, dest_path).await?;
+    move_path(&source_path, &dest_path, req.preserve_timestamps).await?;
 
-    Ok(Json(ApiResponse::success(FileOperationResponse {
+    Ok(Json(ApiResponse::success(MoveFileResponse {
         success: true,
+        moved: None,
+        overwritten: None,
     })))
 }
 
+/// How many colliding paths a merge conflict names
+const MAX_REPORTED_CONFLICTS: usize = 5;
+
+/// Move the contents of `source` into the existing directory `dest`,
+/// descending into directories present on both sides, then remove the
+/// emptied source directories. Colliding files are replaced only with
+/// `overwrite`; a file never replaces a directory. All collisions are found
+/// before anything is moved, so a refused merge leaves both trees as they
+/// were. Returns the moved and overwritten counts.
+async fn merge_dirs(
+    source: &Path,
+    dest: &Path,
+    overwrite: bool,
+    preserve_timestamps: bool,
+) -> Result<(usize, usize), AppError> {
+    // (entry, target, whether a file is in the way)
+    let mut plan: Vec<(PathBuf, PathBuf, bool)> = Vec::new();
+    let mut conflicts = Vec::new();
+    let mut merged_dirs = Vec::new();
+    let mut stack = vec![(source.to_path_buf(), dest.to_path_buf())];
+    while let Some((src_dir, dst_dir)) = stack.pop() {
+        let mut entries = fs::read_dir(&src_dir).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let target = dst_dir.join(entry.file_name());
+            let is_dir = entry.file_type().await?.is_dir();
+            match fs::symlink_metadata(&target).await {
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                    plan.push((entry.path(), target, false))
+                }
+                Err(e) => return Err(e.into()),
+                Ok(meta) if meta.is_dir() && is_dir => stack.push((entry.path(), target)),
+                Ok(meta) if overwrite && !meta.is_dir() => plan.push((entry.path(), target, true)),
+                Ok(_) => conflicts.push(target),
+            }
+        }
+        merged_dirs.push(src_dir);
+    }
+
+    if !conflicts.is_empty() {
+        let shown: Vec<String> = conflicts
+            .iter()
+            .take(MAX_REPORTED_CONFLICTS)
+            .map(|p| p.display().to_string())
+            .collect();
+        let more = conflicts.len().saturating_sub(MAX_REPORTED_CONFLICTS);
+        return Err(AppError::Conflict(format!(
+            "{} entries already exist in the destination: {}{}",
+            conflicts.len(),
+            shown.join(", "),
+            if more > 0 {
+                format!(" and {} more", more)
+            } else {
+                String::new()
+            }
+        )));
+    }
+
+    let (mut moved, mut overwritten) = (0, 0);
+    for (entry, target, replace) in plan {
+        if replace {
+            // rename(2) replaces a file with a file, but not with a directory
+            if entry.is_dir() {
+                fs::remove_file(&target).await?;
+            }
+            overwritten += 1;
+        }
+        move_path(&entry, &target, preserve_timestamps).await?;
+        moved += 1;
+    }
+    // Children were pushed after their parents, so this empties bottom-up
+    for dir in merged_dirs.iter().rev() {
+        fs::remove_dir(dir).await.ok();
+    }
+    Ok((moved, overwritten))
+}
+
+/// rename(2), falling back to copy-then-delete when `source` and `dest` are
+/// on different filesystems. A failed copy is cleaned up and leaves the
+/// source in place.
+async fn move_path(source: &Path, dest: &Path, preserve_timestamps: bool) -> Result<(), AppError> {
+    match fs::rename(source, dest).await {
+        Err(e) if e.kind() == std::io::ErrorKind::CrossesDevices => {
+            move_across_devices(source, dest, preserve_timestamps).await
+        }
+        result => Ok(result?),
+    }
+}
+
+/// The copy-then-delete fallback of `move_path`.
+async fn move_across_devices(
+    source: &Path,
+    dest: &Path,
+    preserve_timestamps: bool,
+) -> Result<(), AppError> {
+    let (source, dest) = (source.to_path_buf(), dest.to_path_buf());
+    tokio::task::spawn_blocking(move || {
+        if let Err(e) = copy_tree(&source, &dest, preserve_timestamps) {
+            remove_tree(&dest).ok();
+            return Err(e);
+        }
+        remove_tree(&source)
+    })
+    .await
+    .map_err(|e| AppError::InternalServerError(e.to_string()))??;
+    Ok(())
+}
+
+/// Copy a file, symlink or directory tree, keeping permission bits and,
+/// if asked, access and modification times. Directory times are set after
+/// their contents are written, since writing them bumps the mtime.
+fn copy_tree(source: &Path, dest: &Path, preserve_timestamps: bool) -> std::io::Result<()> {
+    let meta = std::fs::symlink_metadata(source)?;
+    let file_type = meta.file_type();
+    if file_type.is_symlink() {
+        std::os::unix::fs::symlink(std::fs::read_link(source)?, dest)?;
+    } else if file_type.is_dir() {
+        std::fs::create_dir(dest)?;
+        for entry in std::fs::read_dir(source)? {
+            let entry = entry?;
+            copy_tree(
+                &entry.path(),
+                &dest.join(entry.file_name()),
+                preserve_timestamps,
+            )?;
+        }
+        std::fs::set_permissions(dest, meta.permissions())?;
+    } else {
+        std::fs::copy(source, dest)?;
+    }
+    if preserve_timestamps {
+        copy_times(&meta, dest)?;
+    }
+    Ok(())
+}
+
+/// Apply `meta`'s atime and mtime to `path` itself, not a symlink's target.
+fn copy_times(meta: &std::fs::Metadata, path: &Path) -> std::io::Result<()> {
+    use nix::sys::stat::{utimensat, UtimensatFlags};
+    use nix::sys::time::TimeSpec;
+    use std::os::unix::fs::MetadataExt;
+
+    utimensat(
+        nix::fcntl::AT_FDCWD,
+        path,
+        &TimeSpec::new(meta.atime(), meta.atime_nsec()),
+        &TimeSpec::new(meta.mtime(), meta.mtime_nsec()),
+        UtimensatFlags::NoFollowSymlink,
+    )
+    .map_err(std::io::Error::from)
+}
+
+fn remove_tree(path: &Path) -> std::io::Result<()> {
+    match std::fs::symlink_metadata(path) {
+        Ok(meta) if meta.is_dir() => std::fs::remove_dir_all(path),
+        Ok(_) => std::fs::remove_file(path),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(e),
+    }
+}
+
 #[derive(Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct RenameFileRequest {
@@ -286,11 +1310,11 @@ pub struct RenameFileRequest {
 }
 
 pub async fn rename_file(
-    State(state): State<Arc<AppState>>,
+    Workspace(workspace): Workspace,
     Json(req): Json<RenameFileRequest>,
 ) -> Result<Json<ApiResponse<FileOperationResponse>>, AppError> {
-    let old_path = validate_path(&state.config.workspace_path, &req.old_path)?;
-    let new_path = validate_path(&state.config.workspace_path, &req.new_path)?;
+    let old_path = validate_path(&workspace, &req.old_path)?;
+    let new_path = validate_path(&workspace, &req.new_path)?;
 
     if !old_path.exists() {
         return Err(AppError::NotFound("Old path not found".to_string()));
@@ -310,3 +1334,247 @@ pub async fn rename_file(
         success: true,
     })))
 }
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SymlinkRequest {
+    /// Stored verbatim; a relative target resolves against the link's directory
+    target: String,
+    link_path: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SymlinkResponse {
+    path: String,
+    relative_path: Option<String>,
+    target: String,
+}
+
+/// Create a symbolic link at `linkPath` pointing to `target`. The target
+/// does not have to exist, so dangling links can be created on purpose.
+pub async fn create_symlink(
+    Workspace(workspace): Workspace,
+    Json(req): Json<SymlinkRequest>,
+) -> Result<Json<ApiResponse<SymlinkResponse>>, AppError> {
+    if req.target.is_empty() {
+        return Err(AppError::BadRequest("Target cannot be empty".to_string()));
+    }
+    let link_path = validate_path(&workspace, &req.link_path)?;
+
+    // symlink_metadata so an existing (possibly dangling) link also counts
+    if fs::symlink_metadata(&link_path).await.is_ok() {
+        return Err(AppError::Conflict("Link path already exists".to_string()));
+    }
+
+    if let Some(parent) = link_path.parent() {
+        ensure_directory(parent).await?;
+    }
+
+    fs::symlink(&req.target, &link_path).await?;
+
+    Ok(Json(ApiResponse::success(SymlinkResponse {
+        path: link_path.to_string_lossy().to_string(),
+        relative_path: relative_to_workspace(&workspace, &link_path),
+        target: req.target,
+    })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_range() {
+        assert_eq!(read_range(100, None, None).unwrap(), (0, 100));
+        assert_eq!(read_range(100, Some(40), None).unwrap(), (40, 60));
+        assert_eq!(read_range(100, Some(40), Some(10)).unwrap(), (40, 10));
+        assert_eq!(read_range(100, Some(100), None).unwrap(), (100, 0));
+        assert!(read_range(100, Some(101), None).is_err());
+        assert!(read_range(100, Some(90), Some(11)).is_err());
+    }
+
+    #[test]
+    fn test_utf8_prefix() {
+        assert_eq!(utf8_prefix(b"hello"), Some("hello"));
+        let text = "na\u{ef}ve";
+        // Cut inside the two-byte 'ï'
+        assert_eq!(utf8_prefix(&text.as_bytes()[..3]), Some("na"));
+        assert_eq!(utf8_prefix(text.as_bytes()), Some(text));
+        assert_eq!(utf8_prefix(b"\xff\xfeab"), None);
+        assert_eq!(utf8_prefix(b""), Some(""));
+    }
+
+    #[test]
+    fn test_base64_stream() {
+        let decode = |chunks: &[&str]| {
+            let mut stream = Base64Stream::default();
+            let mut out = Vec::new();
+            for chunk in chunks {
+                out.extend(stream.push(chunk.as_bytes())?);
+            }
+            out.extend(stream.finish()?);
+            Ok::<_, AppError>(out)
+        };
+        assert_eq!(decode(&["aGVsbG8gd29ybGQ="]).unwrap(), b"hello world");
+        assert_eq!(
+            decode(&["aGV", "sbG8", "gd2\n9yb", "GQ", "=", "\n"]).unwrap(),
+            b"hello world"
+        );
+        assert_eq!(decode(&[]).unwrap(), b"");
+        assert!(decode(&["aGVsbG8"]).is_err());
+        assert!(decode(&["aGk=", "aGk="]).is_err());
+        assert!(decode(&["aG!s"]).is_err());
+    }
+
+    #[test]
+    fn test_capped_gzip_output() {
+        // 64 MiB of zeros compress to about 64 KiB
+        let mut bomb = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::best());
+        let zeros = vec![0u8; 1024 * 1024];
+        for _ in 0..64 {
+            bomb.write_all(&zeros).unwrap();
+        }
+        let bomb = bomb.finish().unwrap();
+
+        let limit = 1024 * 1024;
+        let mut decoder = GzDecoder::new(CappedBuffer::new(limit));
+        let err = decoder.write_all(&bomb).unwrap_err();
+        assert!(decoder.get_ref().data.len() as u64 <= limit);
+        assert!(matches!(
+            inflate_error(decoder.get_ref(), err),
+            AppError::BadRequest(msg) if msg == "File too large"
+        ));
+
+        let mut small = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::fast());
+        small.write_all(b"hello").unwrap();
+        let mut decoder = GzDecoder::new(CappedBuffer::new(5));
+        decoder.write_all(&small.finish().unwrap()).unwrap();
+        decoder.try_finish().unwrap();
+        assert_eq!(decoder.get_mut().take(), b"hello");
+    }
+
+    #[tokio::test]
+    async fn test_read_last_lines() {
+        let path = std::env::temp_dir().join(format!(
+            "devbox-tail-{}",
+            crate::utils::common::generate_id()
+        ));
+        let tail = |content: &'static [u8], count: usize, chunk: usize| {
+            let path = path.clone();
+            async move {
+                std::fs::write(&path, content).unwrap();
+                let mut file = fs::File::open(&path).await.unwrap();
+                read_last_lines(&mut file, content.len() as u64, count, chunk, 1024).await
+            }
+        };
+
+        assert_eq!(tail(b"a\nb\nc\n", 2, 1).await.unwrap(), ["b", "c"]);
+        assert_eq!(tail(b"a\nb\nc", 2, 3).await.unwrap(), ["b", "c"]);
+        assert_eq!(tail(b"a\r\nb\r\n", 5, 64).await.unwrap(), ["a", "b"]);
+        assert_eq!(tail(b"one\n\nthree\n", 2, 4).await.unwrap(), ["", "three"]);
+        assert_eq!(tail(b"\n", 3, 4).await.unwrap(), [""]);
+        assert!(tail(b"", 3, 4).await.unwrap().is_empty());
+        assert!(tail(b"a\nb", 0, 4).await.unwrap().is_empty());
+        assert!(matches!(
+            tail(&[b'x'; 4096], 1, 256).await,
+            Err(AppError::BadRequest(_))
+        ));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_prepare_parent() {
+        let root = std::env::temp_dir().join(format!(
+            "devbox-parent-{}",
+            crate::utils::common::generate_id()
+        ));
+        let file = root.join("typo/dir/file.txt");
+        assert!(matches!(
+            prepare_parent(&file, false).await,
+            Err(AppError::NotFound(_))
+        ));
+        assert!(!root.exists());
+
+        prepare_parent(&file, true).await.unwrap();
+        assert!(root.join("typo/dir").is_dir());
+        prepare_parent(&file, false).await.unwrap();
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_merge_dirs() {
+        let root = std::env::temp_dir().join(format!(
+            "devbox-merge-{}",
+            crate::utils::common::generate_id()
+        ));
+        let (src, dst) = (root.join("src"), root.join("dst"));
+        std::fs::create_dir_all(src.join("lib/new")).unwrap();
+        std::fs::create_dir_all(dst.join("lib")).unwrap();
+        std::fs::write(src.join("a.txt"), "new a").unwrap();
+        std::fs::write(src.join("lib/shared.rs"), "new").unwrap();
+        std::fs::write(src.join("lib/new/mod.rs"), "").unwrap();
+        std::fs::write(dst.join("a.txt"), "old a").unwrap();
+        std::fs::write(dst.join("lib/shared.rs"), "old").unwrap();
+        std::fs::write(dst.join("lib/keep.rs"), "keep").unwrap();
+
+        // Collisions are refused up front without moving anything
+        assert!(merge_dirs(&src, &dst, false, true).await.is_err());
+        assert!(src.join("lib/new/mod.rs").exists());
+        assert!(!dst.join("lib/new").exists());
+
+        assert_eq!(merge_dirs(&src, &dst, true, true).await.unwrap(), (3, 2));
+        assert_eq!(std::fs::read_to_string(dst.join("a.txt")).unwrap(), "new a");
+        assert_eq!(
+            std::fs::read_to_string(dst.join("lib/shared.rs")).unwrap(),
+            "new"
+        );
+        assert!(dst.join("lib/keep.rs").exists());
+        assert!(dst.join("lib/new/mod.rs").exists());
+        assert!(!src.exists());
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_move_across_devices_keeps_mtimes() {
+        use std::time::{Duration, SystemTime};
+
+        // The fallback is called directly, so it runs whether or not the test
+        // machine has a second filesystem to rename across
+        let name = format!("devbox-xdev-{}", crate::utils::common::generate_id());
+        let src = std::env::temp_dir().join(&name);
+        let dst = std::env::temp_dir().join(format!("{}-moved", name));
+        std::fs::create_dir_all(src.join("sub")).unwrap();
+        std::fs::write(src.join("sub/file.txt"), "data").unwrap();
+        std::os::unix::fs::symlink("sub/file.txt", src.join("link")).unwrap();
+        let old = SystemTime::UNIX_EPOCH + Duration::from_secs(1_500_000_000);
+        for path in [src.join("sub/file.txt"), src.join("sub"), src.clone()] {
+            std::fs::File::open(&path)
+                .unwrap()
+                .set_modified(old)
+                .unwrap();
+        }
+
+        move_across_devices(&src, &dst, true).await.unwrap();
+        assert!(!src.exists());
+        for path in ["sub/file.txt", "sub", ""] {
+            let meta = std::fs::metadata(dst.join(path)).unwrap();
+            assert_eq!(meta.modified().unwrap(), old, "{}", path);
+        }
+        assert_eq!(std::fs::read_to_string(dst.join("link")).unwrap(), "data");
+        std::fs::remove_dir_all(&dst).unwrap();
+
+        // Without preservation the copy gets fresh times
+        std::fs::write(&src, "data").unwrap();
+        std::fs::File::open(&src)
+            .unwrap()
+            .set_modified(old)
+            .unwrap();
+        move_across_devices(&src, &dst, false).await.unwrap();
+        assert_ne!(std::fs::metadata(&dst).unwrap().modified().unwrap(), old);
+        std::fs::remove_file(&dst).unwrap();
+    }
+}
@@ -1,16 +1,22 @@
 use super::types::FileInfo;
 use crate::error::AppError;
 use crate::response::ApiResponse;
-use crate::state::AppState;
-use crate::utils::path::validate_path;
+use crate::state::workspace::Workspace;
+use crate::utils::path::{encoded_name, path_string, relative_to_workspace, validate_path};
 use axum::{
-    extract::{Query, State},
+    body::Body,
+    extract::Query,
+    http::{header, HeaderMap},
+    response::{IntoResponse, Response},
     Json,
 };
+use futures::stream;
 use serde::{Deserialize, Serialize};
-use std::sync::Arc;
+use std::path::Path;
 use tokio::fs;
 
+const NDJSON_CONTENT_TYPE: &str = "application/x-ndjson";
+
 #[derive(Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ListFilesParams {
@@ -33,46 +39,113 @@ pub struct ListFilesResponse {
     files: Vec<FileInfo>,
 }
 
+/// Build the listing entry for `entry`, or `None` if it is hidden and hidden
+/// files were not requested.
+pub(super) async fn file_info(
+    entry: &fs::DirEntry,
+    workspace: &Path,
+    show_hidden: bool,
+) -> std::io::Result<Option<FileInfo>> {
+    let file_name = entry.file_name();
+    let name = file_name.to_string_lossy().to_string();
+    if !show_hidden && name.starts_with('.') {
+        return Ok(None);
+    }
+
+    let path = entry.path();
+    // DirEntry metadata describes the link itself; links are reported with
+    // their target's details, and broken ones with the link's own
+    let own_metadata = entry.metadata().await?;
+    let is_symlink = own_metadata.file_type().is_symlink();
+    let (target_metadata, link_target) = if is_symlink {
+        let link_target = fs::read_link(&path)
+            .await
+            .ok()
+            .map(|t| t.to_string_lossy().to_string());
+        (fs::metadata(&path).await.ok(), link_target)
+    } else {
+        (Some(own_metadata.clone()), None)
+    };
+    let is_dir = target_metadata.as_ref().is_some_and(|m| m.is_dir());
+    let size = target_metadata.as_ref().map(|m| m.len());
+    let metadata = target_metadata.unwrap_or(own_metadata);
+
+    #[cfg(unix)]
+    let permissions = {
+        use std::os::unix::fs::PermissionsExt;
+        Some(format!("0{:o}", metadata.permissions().mode() & 0o777))
+    };
+    #[cfg(not(unix))]
+    let permissions = None;
+    let modified = metadata.modified().ok().map(|t| {
+        let duration = t.duration_since(std::time::UNIX_EPOCH).unwrap_or_default();
+        crate::utils::common::format_time(duration.as_secs())
+    });
+
+    let encoded_name = encoded_name(&file_name);
+    let encoded_path = path.to_str().is_none().then(|| path_string(&path));
+    Ok(Some(FileInfo {
+        name,
+        encoded_name,
+        encoded_path,
+        relative_path: relative_to_workspace(workspace, &path),
+        path: path.to_string_lossy().to_string(),
+        size,
+        is_dir,
+        is_symlink,
+        link_target,
+        permissions,
+        modified,
+    }))
+}
+
+/// List a directory. With `Accept: application/x-ndjson` entries are streamed
+/// as one JSON object per line while the directory is read; `limit` and
+/// `offset` do not apply in that mode.
 pub async fn list_files(
-    State(state): State<Arc<AppState>>,
+    Workspace(workspace): Workspace,
+    headers: HeaderMap,
     Query(params): Query<ListFilesParams>,
-) -> Result<Json<ApiResponse<ListFilesResponse>>, AppError> {
+) -> Result<Response, AppError> {
     let path_str = params.path.as_deref().unwrap_or(".");
-    let valid_path = validate_path(&state.config.workspace_path, path_str)?;
+    let valid_path = validate_path(&workspace, path_str)?;
 
     let mut entries = fs::read_dir(&valid_path).await?;
-    let mut files = Vec::new();
-
-    while let Some(entry) = entries.next_entry().await? {
-        let name = entry.file_name().to_string_lossy().to_string();
-        if !params.show_hidden && name.starts_with('.') {
-            continue;
-        }
 
-        let metadata = entry.metadata().await?;
-        let is_dir = metadata.is_dir();
-        let size = metadata.len();
-
-        #[cfg(unix)]
-        let permissions = {
-            use std::os::unix::fs::PermissionsExt;
-            Some(format!("0{:o}", metadata.permissions().mode() & 0o777))
-        };
-        #[cfg(not(unix))]
-        let permissions = None;
-        let modified = metadata.modified().ok().map(|t| {
-            let duration = t.duration_since(std::time::UNIX_EPOCH).unwrap_or_default();
-            crate::utils::common::format_time(duration.as_secs())
+    let wants_ndjson = headers
+        .get(header::ACCEPT)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.contains(NDJSON_CONTENT_TYPE));
+    if wants_ndjson {
+        let show_hidden = params.show_hidden;
+        let stream = stream::unfold(Some((entries, workspace)), move |state| async move {
+            let (mut entries, workspace) = state?;
+            loop {
+                let entry = match entries.next_entry().await {
+                    Ok(Some(entry)) => entry,
+                    Ok(None) => return None,
+                    Err(e) => return Some((Err(e), None)),
+                };
+                match file_info(&entry, &workspace, show_hidden).await {
+                    Ok(Some(info)) => {
+                        let mut line = serde_json::to_vec(&info).unwrap_or_default();
+                        line.push(b'\n');
+                        return Some((Ok(line), Some((entries, workspace))));
+                    }
+                    Ok(None) => continue,
+                    Err(e) => return Some((Err(e), None)),
+                }
+            }
         });
+        let headers = [(header::CONTENT_TYPE, NDJSON_CONTENT_TYPE)];
+        return Ok((headers, Body::from_stream(stream)).into_response());
+    }
 
-        files.push(FileInfo {
-            name,
-            path: entry.path().to_string_lossy().to_string(),
-            size,
-            is_dir,
-            permissions,
-            modified,
-        });
+    let mut files = Vec::new();
+    while let Some(entry) = entries.next_entry().await? {
+        if let Some(info) = file_info(&entry, &workspace, params.show_hidden).await? {
+            files.push(info);
+        }
     }
 
     let total = files.len();
@@ -85,5 +158,46 @@ pub async fn list_files(
 
     Ok(Json(ApiResponse::success(ListFilesResponse {
         files: paged_files,
-    })))
+    }))
+    .into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_file_info_symlinks() {
+        let root = std::env::temp_dir().join(format!(
+            "devbox-list-{}",
+            crate::utils::common::generate_id()
+        ));
+        std::fs::create_dir_all(root.join("dir")).unwrap();
+        std::fs::write(root.join("file.txt"), "hello").unwrap();
+        std::os::unix::fs::symlink("file.txt", root.join("to-file")).unwrap();
+        std::os::unix::fs::symlink("dir", root.join("to-dir")).unwrap();
+        std::os::unix::fs::symlink("missing", root.join("broken")).unwrap();
+
+        let mut infos = std::collections::HashMap::new();
+        let mut entries = fs::read_dir(&root).await.unwrap();
+        while let Some(entry) = entries.next_entry().await.unwrap() {
+            let info = file_info(&entry, &root, false).await.unwrap().unwrap();
+            infos.insert(info.name.clone(), info);
+        }
+
+        let file = &infos["file.txt"];
+        assert!(!file.is_symlink);
+        assert_eq!(file.size, Some(5));
+        let to_file = &infos["to-file"];
+        assert!(to_file.is_symlink && !to_file.is_dir);
+        assert_eq!(to_file.size, Some(5));
+        assert_eq!(to_file.link_target.as_deref(), Some("file.txt"));
+        assert!(infos["to-dir"].is_dir);
+        let broken = &infos["broken"];
+        assert!(broken.is_symlink && !broken.is_dir);
+        assert_eq!(broken.size, None);
+        assert_eq!(broken.link_target.as_deref(), Some("missing"));
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
 }
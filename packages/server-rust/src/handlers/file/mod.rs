@@ -1,15 +1,26 @@
 pub mod batch;
+pub mod clean;
+pub mod diff;
+pub mod edit;
 pub mod io;
 pub mod list;
 pub mod perm;
 pub mod search;
+pub mod sync;
+pub mod tree;
 pub mod types;
 
-pub use batch::{batch_download, batch_upload};
+pub use batch::{batch_download, batch_upload, read_batch};
+pub use clean::clean_files;
+pub use diff::{diff_files, patch_file};
+pub use edit::edit_lines;
 pub use io::{
-    delete_file, move_file, read_file, rename_file, write_file_binary, write_file_json,
+    create_symlink, delete_file, get_mime_type, hash_file, head_file, json_body_limit, move_file,
+    read_file, rename_file, tail_file_lines, write_file_at, write_file_binary, write_file_json,
     write_file_multipart, WriteFileRequest,
 };
 pub use list::list_files;
-pub use perm::change_permissions;
+pub use perm::{change_permissions, get_permissions};
 pub use search::{find_in_files, replace_in_files, search_files};
+pub use sync::{list_watch, sync_files};
+pub use tree::file_tree;
@@ -1,15 +1,13 @@
 use crate::error::AppError;
 use crate::response::ApiResponse;
-use crate::state::AppState;
-use crate::utils::path::validate_path;
-use axum::{extract::State, Json};
-use serde::Deserialize;
+use crate::state::workspace::Workspace;
+use crate::utils::path::{relative_to_workspace, validate_path};
+use crate::utils::validate::{validate, Validate, Validator};
+use axum::{extract::Query, Json};
+use serde::{Deserialize, Serialize};
 use std::path::{Path, PathBuf};
-use std::sync::Arc;
 use tokio::fs;
 
-use super::types::FileOperationResponse;
-
 #[derive(Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ChmodRequest {
@@ -21,8 +19,92 @@ pub struct ChmodRequest {
     owner: Option<String>, // numeric forms: "uid" or "uid:gid"
 }
 
+impl Validate for ChmodRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.check(!self.path.trim().is_empty(), "path", "must not be empty");
+        v.check(
+            parse_mode(&self.mode).is_ok(),
+            "mode",
+            "must be octal like 755 or symbolic like u+x,g-w",
+        );
+        if let Some(owner) = &self.owner {
+            v.check(!owner.trim().is_empty(), "owner", "must not be empty");
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct PermissionsQuery {
+    path: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PermissionsResponse {
+    path: String,
+    mode: String,
+    uid: u32,
+    gid: u32,
+    /// Owner name, or null if the uid has no passwd entry
+    user: Option<String>,
+    /// Group name, or null if the gid has no group entry
+    group: Option<String>,
+}
+
+/// A parsed `mode` value: absolute octal bits, or symbolic clauses applied
+/// relative to each file's current mode (as `chmod u+x,g-w` does).
+#[cfg(unix)]
+#[derive(Debug, PartialEq)]
+enum ModeSpec {
+    Octal(u32),
+    Symbolic(Vec<SymbolicClause>),
+}
+
+/// One comma-separated clause such as `ug+rw` or `o=` (who mask plus actions).
+#[cfg(unix)]
+#[derive(Debug, PartialEq)]
+struct SymbolicClause {
+    who: u32,
+    actions: Vec<(char, String)>,
+}
+
 #[cfg(unix)]
-fn parse_mode(mode_str: &str) -> Result<u32, AppError> {
+impl ModeSpec {
+    /// Final permission bits for a file whose current mode is `current`.
+    fn resolve(&self, current: u32, is_dir: bool) -> u32 {
+        let clauses = match self {
+            ModeSpec::Octal(mode) => return *mode,
+            ModeSpec::Symbolic(clauses) => clauses,
+        };
+
+        let mut mode = current & 0o777;
+        for clause in clauses {
+            for (op, perms) in &clause.actions {
+                let mut bits = 0;
+                for p in perms.chars() {
+                    bits |= match p {
+                        'r' => 0o444,
+                        'w' => 0o222,
+                        'x' => 0o111,
+                        // Execute only for directories or files already executable by someone
+                        'X' if is_dir || mode & 0o111 != 0 => 0o111,
+                        _ => 0,
+                    };
+                }
+                bits &= clause.who;
+                match op {
+                    '+' => mode |= bits,
+                    '-' => mode &= !bits,
+                    _ => mode = (mode & !clause.who) | bits,
+                }
+            }
+        }
+        mode
+    }
+}
+
+#[cfg(unix)]
+fn parse_mode(mode_str: &str) -> Result<ModeSpec, AppError> {
     let s = mode_str.trim();
     if s.is_empty() {
         return Err(AppError::BadRequest("Mode cannot be empty".to_string()));
@@ -30,37 +112,77 @@ fn parse_mode(mode_str: &str) -> Result<u32, AppError> {
 
     // Accept forms like "755", "0755", or with 0o prefix
     let trimmed = s.strip_prefix("0o").or_else(|| s.strip_prefix("0O")).unwrap_or(s);
-    u32::from_str_radix(trimmed, 8).map_err(|_| AppError::BadRequest("Invalid mode (expect octal like 755)".to_string()))
-}
+    if let Ok(mode) = u32::from_str_radix(trimmed, 8) {
+        return Ok(ModeSpec::Octal(mode));
+    }
 
-#[cfg(unix)]
-async fn chmod_path(path: &Path, mode: u32) -> Result<(), AppError> {
-    use std::os::unix::fs::PermissionsExt;
-    let perms = std::fs::Permissions::from_mode(mode & 0o777);
-    fs::set_permissions(path, perms).await?;
-    Ok(())
+    parse_symbolic_mode(s)
+        .map(ModeSpec::Symbolic)
+        .ok_or_else(|| {
+            AppError::BadRequest(
+                "Invalid mode (expect octal like 755 or symbolic like u+x,g-w)".to_string(),
+            )
+        })
 }
 
+/// Parse `[ugoa]*([+-=][rwxX]*)+` clauses separated by commas. A clause
+/// without a who part applies to all classes (the umask is not consulted).
 #[cfg(unix)]
-async fn chmod_recursive(root: &Path, mode: u32) -> Result<(), AppError> {
-    let mut stack: Vec<PathBuf> = vec![root.to_path_buf()];
-    while let Some(p) = stack.pop() {
-        // Set permission for current path
-        let _ = chmod_path(&p, mode).await;
-
-        // If directory, push children
-        if let Ok(meta) = fs::metadata(&p).await {
-            if meta.is_dir() {
-                let mut rd = match fs::read_dir(&p).await {
-                    Ok(rd) => rd,
-                    Err(_) => continue,
-                };
-                while let Ok(Some(entry)) = rd.next_entry().await {
-                    stack.push(entry.path());
+fn parse_symbolic_mode(s: &str) -> Option<Vec<SymbolicClause>> {
+    let mut clauses = Vec::new();
+    for clause in s.split(',') {
+        let mut chars = clause.chars().peekable();
+
+        let mut who = 0;
+        while let Some(&c) = chars.peek() {
+            who |= match c {
+                'u' => 0o700,
+                'g' => 0o070,
+                'o' => 0o007,
+                'a' => 0o777,
+                _ => break,
+            };
+            chars.next();
+        }
+        if who == 0 {
+            who = 0o777;
+        }
+
+        let mut actions = Vec::new();
+        while let Some(op) = chars.next() {
+            if !matches!(op, '+' | '-' | '=') {
+                return None;
+            }
+            let mut perms = String::new();
+            while let Some(&c) = chars.peek() {
+                if !matches!(c, 'r' | 'w' | 'x' | 'X') {
+                    break;
                 }
+                perms.push(c);
+                chars.next();
             }
+            actions.push((op, perms));
+        }
+        if actions.is_empty() {
+            return None;
         }
+        clauses.push(SymbolicClause { who, actions });
     }
+    Some(clauses)
+}
+
+#[cfg(unix)]
+async fn chmod_path(path: &Path, spec: &ModeSpec) -> Result<(), AppError> {
+    use std::os::unix::fs::PermissionsExt;
+    let mode = match spec {
+        ModeSpec::Octal(mode) => *mode,
+        ModeSpec::Symbolic(_) => {
+            let meta = fs::metadata(path).await?;
+            spec.resolve(meta.permissions().mode(), meta.is_dir())
+        }
+    };
+    let perms = std::fs::Permissions::from_mode(mode & 0o777);
+    fs::set_permissions(path, perms).await?;
     Ok(())
 }
 
@@ -93,7 +215,7 @@ fn parse_owner(owner: &str) -> Result<(Option<nix::unistd::Uid>, Option<nix::uni
     // Resolve GID: try numeric first, else by name
     let gid = match group_part {
         None => None,
-        Some(g) if g.is_empty() => None,
+        Some("") => None,
         Some(g) => {
             if let Ok(val) = g.parse::<u32>() {
                 Some(Gid::from_raw(val))
@@ -113,36 +235,111 @@ fn parse_owner(owner: &str) -> Result<(Option<nix::unistd::Uid>, Option<nix::uni
 #[cfg(unix)]
 async fn chown_path(path: &Path, owner: Option<&str>) -> Result<(), AppError> {
     if let Some(o) = owner {
-        use nix::unistd::chown;
         let (uid, gid) = parse_owner(o)?;
-        chown(path, uid, gid).map_err(|e| AppError::InternalServerError(e.to_string()))?;
+        chown_ids(path, uid, gid)?;
     }
     Ok(())
 }
 
 #[cfg(unix)]
-async fn chown_recursive(root: &Path, owner: Option<&str>) -> Result<(), AppError> {
-    if owner.is_none() { return Ok(()); }
+fn chown_ids(
+    path: &Path,
+    uid: Option<nix::unistd::Uid>,
+    gid: Option<nix::unistd::Gid>,
+) -> Result<(), AppError> {
+    nix::unistd::chown(path, uid, gid).map_err(|e| AppError::InternalServerError(e.to_string()))
+}
+
+/// Most per-path failures listed in a recursive response; `failed` still
+/// counts them all.
+const MAX_REPORTED_FAILURES: usize = 100;
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PathFailure {
+    path: String,
+    error: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChmodResponse {
+    /// False if any path could not be changed
+    success: bool,
+    /// Recursive changes only: paths changed, paths that failed, and why
+    #[serde(skip_serializing_if = "Option::is_none")]
+    succeeded: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    failed: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    errors: Option<Vec<PathFailure>>,
+}
+
+/// Apply the mode and owner to `root` and everything below it, carrying on
+/// past failures. Unreadable directories count as failures too.
+#[cfg(unix)]
+async fn change_recursive(
+    workspace: &Path,
+    root: &Path,
+    spec: &ModeSpec,
+    owner: Option<(Option<nix::unistd::Uid>, Option<nix::unistd::Gid>)>,
+) -> ChmodResponse {
+    let mut succeeded = 0;
+    let mut failed = 0;
+    let mut errors = Vec::new();
+    let mut fail = |path: &Path, err: AppError| {
+        failed += 1;
+        if errors.len() < MAX_REPORTED_FAILURES {
+            errors.push(PathFailure {
+                path: relative_to_workspace(workspace, path)
+                    .unwrap_or_else(|| path.to_string_lossy().into_owned()),
+                error: err.to_string(),
+            });
+        }
+    };
+
     let mut stack: Vec<PathBuf> = vec![root.to_path_buf()];
     while let Some(p) = stack.pop() {
-        let _ = chown_path(&p, owner).await;
-        if let Ok(meta) = fs::metadata(&p).await {
-            if meta.is_dir() {
-                let mut rd = match fs::read_dir(&p).await { Ok(rd) => rd, Err(_) => continue };
-                while let Ok(Some(entry)) = rd.next_entry().await {
-                    stack.push(entry.path());
-                }
+        let mut result = chmod_path(&p, spec).await;
+        if let (Ok(()), Some((uid, gid))) = (&result, owner) {
+            result = chown_ids(&p, uid, gid);
+        }
+        match result {
+            Ok(()) => succeeded += 1,
+            Err(e) => fail(&p, e),
+        }
+
+        if fs::metadata(&p).await.is_ok_and(|meta| meta.is_dir()) {
+            match fs::read_dir(&p).await {
+                Ok(mut rd) => loop {
+                    match rd.next_entry().await {
+                        Ok(Some(entry)) => stack.push(entry.path()),
+                        Ok(None) => break,
+                        Err(e) => {
+                            fail(&p, e.into());
+                            break;
+                        }
+                    }
+                },
+                Err(e) => fail(&p, e.into()),
             }
         }
     }
-    Ok(())
+
+    ChmodResponse {
+        success: failed == 0,
+        succeeded: Some(succeeded),
+        failed: Some(failed),
+        errors: Some(errors),
+    }
 }
 
 pub async fn change_permissions(
-    State(state): State<Arc<AppState>>,
+    Workspace(workspace): Workspace,
     Json(req): Json<ChmodRequest>,
-) -> Result<Json<ApiResponse<FileOperationResponse>>, AppError> {
-    let target = validate_path(&state.config.workspace_path, &req.path)?;
+) -> Result<Json<ApiResponse<ChmodResponse>>, AppError> {
+    validate(&req)?;
+    let target = validate_path(&workspace, &req.path)?;
 
     if !target.exists() {
         return Err(AppError::NotFound("Path not found".to_string()));
@@ -151,12 +348,126 @@ pub async fn change_permissions(
     let mode = parse_mode(&req.mode)?;
 
     if req.recursive {
-        chmod_recursive(&target, mode).await?;
-        chown_recursive(&target, req.owner.as_deref()).await?;
-    } else {
-        chmod_path(&target, mode).await?;
-        chown_path(&target, req.owner.as_deref()).await?;
+        // An unknown user or group fails the request before anything changes
+        let owner = req.owner.as_deref().map(parse_owner).transpose()?;
+        let report = change_recursive(&workspace, &target, &mode, owner).await;
+        return Ok(Json(ApiResponse::success(report)));
     }
 
-    Ok(Json(ApiResponse::success(FileOperationResponse { success: true })))
+    chmod_path(&target, &mode).await?;
+    chown_path(&target, req.owner.as_deref()).await?;
+
+    Ok(Json(ApiResponse::success(ChmodResponse {
+        success: true,
+        succeeded: None,
+        failed: None,
+        errors: None,
+    })))
+}
+
+/// Octal permission bits including setuid, setgid and sticky, e.g. "4755".
+fn mode_string(mode: u32) -> String {
+    format!("{:04o}", mode & 0o7777)
+}
+
+pub async fn get_permissions(
+    Workspace(workspace): Workspace,
+    Query(params): Query<PermissionsQuery>,
+) -> Result<Json<ApiResponse<PermissionsResponse>>, AppError> {
+    use nix::unistd::{Gid, Group, Uid, User};
+    use std::os::unix::fs::MetadataExt;
+
+    let target = validate_path(&workspace, &params.path)?;
+    let metadata = fs::metadata(&target).await?;
+
+    let (uid, gid) = (metadata.uid(), metadata.gid());
+    // Lookup failures (e.g. no NSS entry) just leave the name unset
+    let user = User::from_uid(Uid::from_raw(uid))
+        .ok()
+        .flatten()
+        .map(|u| u.name);
+    let group = Group::from_gid(Gid::from_raw(gid))
+        .ok()
+        .flatten()
+        .map(|g| g.name);
+
+    Ok(Json(ApiResponse::success(PermissionsResponse {
+        path: target.to_string_lossy().to_string(),
+        mode: mode_string(metadata.mode()),
+        uid,
+        gid,
+        user,
+        group,
+    })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_mode() {
+        assert_eq!(parse_mode("0o755").unwrap(), ModeSpec::Octal(0o755));
+        assert_eq!(parse_mode("644").unwrap(), ModeSpec::Octal(0o644));
+        assert!(parse_mode("u+q").is_err());
+        assert!(parse_mode("u").is_err());
+        assert!(parse_mode("").is_err());
+
+        let resolve = |mode: &str, current: u32, is_dir: bool| {
+            parse_mode(mode).unwrap().resolve(current, is_dir)
+        };
+        assert_eq!(resolve("u+x", 0o644, false), 0o744);
+        assert_eq!(resolve("g-w,o-rwx", 0o777, false), 0o750);
+        assert_eq!(resolve("+x", 0o644, false), 0o755);
+        assert_eq!(resolve("go=r", 0o777, false), 0o744);
+        assert_eq!(resolve("u=rw-w", 0o000, false), 0o400);
+        // X only grants execute to directories and already-executable files
+        assert_eq!(resolve("a+rX", 0o600, false), 0o644);
+        assert_eq!(resolve("a+rX", 0o700, false), 0o755);
+        assert_eq!(resolve("a+rX", 0o600, true), 0o755);
+    }
+
+    #[test]
+    fn test_mode_string_keeps_special_bits() {
+        use std::os::unix::fs::{MetadataExt, PermissionsExt};
+        let path = std::env::temp_dir().join(format!(
+            "devbox-setuid-{}",
+            crate::utils::common::generate_id()
+        ));
+        std::fs::write(&path, "").unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o4755)).unwrap();
+        let mode = std::fs::metadata(&path).unwrap().mode();
+        assert_eq!(mode_string(mode), "4755");
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(mode_string(0o100644), "0644");
+        assert_eq!(mode_string(0o041777), "1777");
+    }
+
+    #[tokio::test]
+    async fn test_change_recursive_reports_failures() {
+        use std::os::unix::fs::PermissionsExt;
+        let root = std::env::temp_dir().join(format!(
+            "devbox-chmod-{}",
+            crate::utils::common::generate_id()
+        ));
+        std::fs::create_dir_all(root.join("sub")).unwrap();
+        std::fs::write(root.join("sub/a.txt"), "a").unwrap();
+        // chmod follows the link, so a dangling one fails
+        std::os::unix::fs::symlink(root.join("missing"), root.join("sub/broken")).unwrap();
+
+        let report = change_recursive(&root, &root, &ModeSpec::Octal(0o750), None).await;
+        assert!(!report.success);
+        assert_eq!(report.succeeded, Some(3));
+        assert_eq!(report.failed, Some(1));
+        let errors = report.errors.unwrap();
+        assert_eq!(errors[0].path, "sub/broken");
+        let mode = std::fs::metadata(root.join("sub/a.txt"))
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o777, 0o750);
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
 }
@@ -1,14 +1,18 @@
 use crate::error::AppError;
 use crate::response::ApiResponse;
+use crate::state::workspace::Workspace;
 use crate::state::AppState;
-use crate::utils::path::validate_path;
+use crate::utils::path::{path_string, validate_path};
 use axum::{extract::Json, extract::State};
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE, UTF_8};
 use futures::stream::{self, FuturesUnordered, StreamExt};
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use tokio::fs;
 use tokio::io::{AsyncBufReadExt, AsyncReadExt, BufReader};
+use tokio_util::sync::CancellationToken;
 
 // --- Constants ---
 
@@ -20,8 +24,8 @@ const BINARY_CHECK_SIZE: usize = 256;
 /// Threshold for small files: use full read + in-memory search instead of streaming
 const SMALL_FILE_THRESHOLD: u64 = 32 * 1024; // 32 KB
 
-/// Default ignored directories for search
-const IGNORED_DIRS: &[&str] = &[
+/// Default ignored directories for search (overridable via `Config::search_ignored_dirs`)
+pub const IGNORED_DIRS: &[&str] = &[
     "node_modules",
     ".git",
     ".svn",
@@ -56,12 +60,29 @@ const IGNORED_DIRS: &[&str] = &[
 pub struct SearchRequest {
     dir: String,
     pattern: String,
+    #[serde(default)]
+    include_hidden: bool,
+    #[serde(default)]
+    follow_symlinks: bool,
+    /// Additional directory names to skip for this request
+    #[serde(default)]
+    extra_ignored_dirs: Vec<String>,
+    /// Directory names to search even though they are ignored by default
+    #[serde(default)]
+    unignore: Vec<String>,
+    /// Stop the search once this many matches have been collected
+    #[serde(default)]
+    max_results: Option<usize>,
+    /// Descend at most this many directory levels below `dir` (0 searches only `dir` itself)
+    #[serde(default)]
+    max_depth: Option<usize>,
 }
 
 #[derive(Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct SearchResponse {
     files: Vec<String>,
+    truncated: bool,
 }
 
 // --- Find Types (content search) ---
@@ -71,22 +92,68 @@ pub struct SearchResponse {
 pub struct FindRequest {
     dir: String,
     keyword: String,
+    #[serde(default)]
+    include_hidden: bool,
+    #[serde(default)]
+    follow_symlinks: bool,
+    /// Additional directory names to skip for this request
+    #[serde(default)]
+    extra_ignored_dirs: Vec<String>,
+    /// Directory names to search even though they are ignored by default
+    #[serde(default)]
+    unignore: Vec<String>,
+    /// Stop the search once this many matches have been collected
+    #[serde(default)]
+    max_results: Option<usize>,
+    /// Descend at most this many directory levels below `dir` (0 searches only `dir` itself)
+    #[serde(default)]
+    max_depth: Option<usize>,
+    /// Sort files by descending number of keyword occurrences
+    #[serde(default)]
+    rank_by_match_count: bool,
+    /// Report the number of occurrences per file in `matches`
+    #[serde(default)]
+    include_matches: bool,
+    /// Also report the matching files grouped by parent directory in `groups`
+    #[serde(default)]
+    group_by_dir: bool,
 }
 
 #[derive(Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct FindResponse {
     files: Vec<String>,
+    truncated: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    matches: Option<Vec<FileMatchCount>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    groups: Option<Vec<DirGroup>>,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct DirGroup {
+    dir: String,
+    files: Vec<String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileMatchCount {
+    file: String,
+    count: usize,
 }
 
 // --- Replace Types ---
 
 /// Replace request structure
 ///
-/// **Encoding Limitation:**
-/// - Only UTF-8 encoded files are supported
+/// **Encoding:**
+/// - Files are treated as UTF-8 unless `encoding` is set
+/// - `encoding` accepts WHATWG labels (e.g. "utf-16le", "gbk", "latin1"); files are
+///   decoded, replaced in decoded form and re-encoded (a leading BOM is preserved)
 /// - Both `from` and `to` strings are transmitted as UTF-8 via HTTP/JSON
-/// - Files with other encodings (GBK, UTF-16, Latin1, etc.) will be skipped
+/// - Files that cannot be decoded with the selected encoding are skipped
 /// - Binary files are automatically detected and skipped
 #[derive(Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -94,6 +161,8 @@ pub struct ReplaceRequest {
     files: Vec<String>,
     from: String,
     to: String,
+    #[serde(default)]
+    encoding: Option<String>,
 }
 
 #[derive(Serialize)]
@@ -117,15 +186,17 @@ pub struct ReplaceResponse {
 /// Search for files by filename pattern (case-insensitive substring match)
 pub async fn search_files(
     State(state): State<Arc<AppState>>,
+    Workspace(workspace): Workspace,
     Json(req): Json<SearchRequest>,
 ) -> Result<Json<ApiResponse<SearchResponse>>, AppError> {
     // P0: Input validation - reject empty pattern
     if req.pattern.is_empty() {
         return Err(AppError::BadRequest("Pattern cannot be empty".to_string()));
     }
+    validate_max_results(req.max_results)?;
 
     // P0: Normalize workspace base (allow relative workspace path) and dir input
-    let workspace_base = state.config.workspace_path.clone();
+    let workspace_base = workspace.clone();
     let dir_trimmed = req.dir.trim();
     let dir_str = if dir_trimmed.is_empty() {
         "."
@@ -148,9 +219,21 @@ pub async fn search_files(
         )));
     }
 
-    let files = perform_filename_search(root_path, &req.pattern).await?;
+    let walk = WalkOptions {
+        include_hidden: req.include_hidden,
+        follow_symlinks: req.follow_symlinks,
+        ignored_dirs: effective_ignored_dirs(
+            &state.config.search_ignored_dirs,
+            &req.extra_ignored_dirs,
+            &req.unignore,
+        ),
+        max_results: req.max_results,
+        max_depth: req.max_depth,
+    };
+
+    let (files, truncated) = perform_filename_search(root_path, &req.pattern, &walk).await?;
 
-    let response = SearchResponse { files };
+    let response = SearchResponse { files, truncated };
 
     Ok(Json(ApiResponse::success(response)))
 }
@@ -158,15 +241,17 @@ pub async fn search_files(
 /// Find files by content keyword (searches inside text files)
 pub async fn find_in_files(
     State(state): State<Arc<AppState>>,
+    Workspace(workspace): Workspace,
     Json(req): Json<FindRequest>,
 ) -> Result<Json<ApiResponse<FindResponse>>, AppError> {
     // P0: Input validation - reject empty keyword
     if req.keyword.is_empty() {
         return Err(AppError::BadRequest("Keyword cannot be empty".to_string()));
     }
+    validate_max_results(req.max_results)?;
 
     // P0: Normalize workspace base (allow relative workspace path) and dir input
-    let workspace_base = state.config.workspace_path.clone();
+    let workspace_base = workspace.clone();
     let dir_trimmed = req.dir.trim();
     let dir_str = if dir_trimmed.is_empty() {
         "."
@@ -189,21 +274,73 @@ pub async fn find_in_files(
         )));
     }
 
-    let files = perform_content_search(
-        root_path,
-        &req.keyword,
+    let walk = WalkOptions {
+        include_hidden: req.include_hidden,
+        follow_symlinks: req.follow_symlinks,
+        ignored_dirs: effective_ignored_dirs(
+            &state.config.search_ignored_dirs,
+            &req.extra_ignored_dirs,
+            &req.unignore,
+        ),
+        max_results: req.max_results,
+        max_depth: req.max_depth,
+    };
+
+    // Counting every occurrence means reading whole files, so only do it on request
+    let count_matches = req.rank_by_match_count || req.include_matches;
+    // The walk runs in its own task so it can be stopped from outside: when
+    // the client disconnects, this handler's future is dropped and the guard
+    // cancels the token, which the walk checks between directories and files
+    let cancel = CancellationToken::new();
+    let _cancel_on_drop = cancel.clone().drop_guard();
+    let (max_concurrent, max_file_size) = (
         state.config.max_concurrent_reads,
         state.config.max_file_size,
-    )
-    .await?;
+    );
+    let keyword = req.keyword.clone();
+    let (mut hits, truncated) = tokio::spawn(async move {
+        perform_content_search(
+            root_path,
+            &keyword,
+            &walk,
+            max_concurrent,
+            max_file_size,
+            count_matches,
+            &cancel,
+        )
+        .await
+    })
+    .await
+    .map_err(|e| AppError::InternalServerError(format!("Search task failed: {}", e)))??;
 
-    let response = FindResponse { files };
+    if req.rank_by_match_count {
+        rank_by_count(&mut hits);
+    }
+
+    let matches = req.include_matches.then(|| {
+        hits.iter()
+            .map(|(file, count)| FileMatchCount {
+                file: file.clone(),
+                count: *count,
+            })
+            .collect()
+    });
+    let files: Vec<String> = hits.into_iter().map(|(file, _)| file).collect();
+    let groups = req.group_by_dir.then(|| group_by_dir(&files));
+
+    let response = FindResponse {
+        files,
+        truncated,
+        matches,
+        groups,
+    };
 
     Ok(Json(ApiResponse::success(response)))
 }
 
 pub async fn replace_in_files(
     State(state): State<Arc<AppState>>,
+    Workspace(workspace): Workspace,
     Json(req): Json<ReplaceRequest>,
 ) -> Result<Json<ApiResponse<ReplaceResponse>>, AppError> {
     // P0: Input validation - reject empty 'from' string
@@ -213,10 +350,25 @@ pub async fn replace_in_files(
         ));
     }
 
+    // Resolve the text encoding; UTF-8 keeps the default fast path
+    let encoding = match req.encoding.as_deref() {
+        None => None,
+        Some(label) => match Encoding::for_label(label.trim().as_bytes()) {
+            Some(enc) if enc == UTF_8 => None,
+            Some(enc) => Some(enc),
+            None => {
+                return Err(AppError::BadRequest(format!(
+                    "Unsupported encoding: {}",
+                    label
+                )))
+            }
+        },
+    };
+
     // P0: Validate all file paths before processing
     let mut validated_paths = Vec::with_capacity(req.files.len());
     for file_path_str in &req.files {
-        let valid_path = validate_path(&state.config.workspace_path, file_path_str)?;
+        let valid_path = validate_path(&workspace, file_path_str)?;
         validated_paths.push((file_path_str.clone(), valid_path));
     }
 
@@ -225,16 +377,23 @@ pub async fn replace_in_files(
     let to = req.to.clone();
     let max_file_size = state.config.max_file_size;
 
-    let replace_futs =
-        validated_paths
-            .into_iter()
-            .map(|(original_path, valid_path)| {
-                let from = from.clone();
-                let to = to.clone();
-                async move {
-                    perform_replace(valid_path, &original_path, &from, &to, max_file_size).await
-                }
-            });
+    let replace_futs = validated_paths
+        .into_iter()
+        .map(|(original_path, valid_path)| {
+            let from = from.clone();
+            let to = to.clone();
+            async move {
+                perform_replace(
+                    valid_path,
+                    &original_path,
+                    &from,
+                    &to,
+                    encoding,
+                    max_file_size,
+                )
+                .await
+            }
+        });
 
     let mut stream = stream::iter(replace_futs).buffer_unordered(state.config.max_concurrent_reads);
     let mut results = Vec::new();
@@ -250,27 +409,120 @@ pub async fn replace_in_files(
 
 // --- Helpers ---
 
+/// Directory traversal options shared by filename and content search
+struct WalkOptions {
+    /// Descend into dot-directories (entries in `ignored_dirs` are still skipped)
+    include_hidden: bool,
+    /// Follow symbolic links to files and directories
+    follow_symlinks: bool,
+    /// Directory names that are never descended into
+    ignored_dirs: HashSet<String>,
+    /// Maximum number of matches to collect before stopping
+    max_results: Option<usize>,
+    /// Maximum directory depth below the root; unlimited when `None`
+    max_depth: Option<usize>,
+}
+
+impl WalkOptions {
+    fn within_depth(&self, depth: usize) -> bool {
+        self.max_depth.is_none_or(|max| depth <= max)
+    }
+}
+
+enum EntryKind {
+    Dir,
+    File,
+}
+
+fn validate_max_results(max_results: Option<usize>) -> Result<(), AppError> {
+    if max_results == Some(0) {
+        return Err(AppError::BadRequest(
+            "maxResults must be greater than 0".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Build the ignored directory set for a request from the configured defaults
+fn effective_ignored_dirs(
+    configured: &[String],
+    extra: &[String],
+    unignore: &[String],
+) -> HashSet<String> {
+    let mut dirs: HashSet<String> = configured.iter().chain(extra).cloned().collect();
+    for name in unignore {
+        dirs.remove(name);
+    }
+    dirs
+}
+
 /// Check if a directory name should be ignored
-fn should_ignore_dir(name: &str) -> bool {
-    // Skip hidden directories
-    if name.starts_with('.') {
+fn should_ignore_dir(name: &str, walk: &WalkOptions) -> bool {
+    // Skip hidden directories unless explicitly requested
+    if !walk.include_hidden && name.starts_with('.') {
         return true;
     }
     // Skip known heavy directories
-    IGNORED_DIRS.contains(&name)
+    walk.ignored_dirs.contains(name)
 }
 
-/// Search files by filename pattern (case-insensitive substring)
+/// Classify a directory entry, resolving symbolic links when `follow_symlinks` is set.
+/// Returns `None` for entries that should not be visited.
+async fn classify_entry(entry: &fs::DirEntry, walk: &WalkOptions) -> Option<EntryKind> {
+    // Use async file_type() to avoid blocking
+    let file_type = entry.file_type().await.ok()?;
+
+    if file_type.is_symlink() {
+        // Skip symbolic links by default to avoid loops and escapes
+        if !walk.follow_symlinks {
+            return None;
+        }
+        // Dangling links fail to resolve and are skipped
+        let metadata = fs::metadata(entry.path()).await.ok()?;
+        if metadata.is_dir() {
+            return Some(EntryKind::Dir);
+        } else if metadata.is_file() {
+            return Some(EntryKind::File);
+        }
+        return None;
+    }
+
+    if file_type.is_dir() {
+        Some(EntryKind::Dir)
+    } else if file_type.is_file() {
+        Some(EntryKind::File)
+    } else {
+        None
+    }
+}
+
+/// Record a directory by its canonical path. Returns `false` if it was already visited,
+/// which is how symlink cycles are broken when following links.
+async fn mark_visited(visited: &mut HashSet<PathBuf>, dir: &Path) -> bool {
+    match fs::canonicalize(dir).await {
+        Ok(canonical) => visited.insert(canonical),
+        Err(_) => false,
+    }
+}
+
+/// Search files by filename pattern (case-insensitive substring).
+/// Returns at most `max_results` matches and whether more files matched.
 async fn perform_filename_search(
     root: PathBuf,
     pattern: &str,
-) -> Result<Vec<String>, AppError> {
+    walk: &WalkOptions,
+) -> Result<(Vec<String>, bool), AppError> {
+    let limit = walk.max_results.unwrap_or(usize::MAX);
     let mut matched_files: Vec<String> = Vec::new();
-    let mut dirs = vec![root];
+    let mut visited: HashSet<PathBuf> = HashSet::new();
+    if walk.follow_symlinks {
+        mark_visited(&mut visited, &root).await;
+    }
+    let mut dirs = vec![(root, 0)];
     let pattern_lower = pattern.to_lowercase();
 
     // Iterative DFS to avoid stack overflow
-    while let Some(current_dir) = dirs.pop() {
+    while let Some((current_dir, depth)) = dirs.pop() {
         let mut entries = match fs::read_dir(&current_dir).await {
             Ok(e) => e,
             Err(_) => continue, // Skip unreadable dirs
@@ -279,55 +531,91 @@ async fn perform_filename_search(
         while let Ok(Some(entry)) = entries.next_entry().await {
             let path = entry.path();
 
-            // Get file name for filtering
-            let file_name = match path.file_name().and_then(|n| n.to_str()) {
-                Some(name) => name,
-                None => continue,
-            };
-
-            // Use async file_type() to avoid blocking
-            let file_type = match entry.file_type().await {
-                Ok(ft) => ft,
-                Err(_) => continue, // Skip entries we can't stat
-            };
-
-            // Skip symbolic links to avoid loops and escapes
-            if file_type.is_symlink() {
-                continue;
-            }
+            // Get file name for filtering; names that are not UTF-8 are
+            // matched on their lossy form and reported by `path_string`
+            let os_name = entry.file_name();
+            let file_name = os_name.to_string_lossy();
 
-            if file_type.is_dir() {
-                // Check if directory should be ignored
-                if should_ignore_dir(file_name) {
-                    continue;
+            match classify_entry(&entry, walk).await {
+                Some(EntryKind::Dir) => {
+                    // Check if directory should be ignored
+                    if should_ignore_dir(&file_name, walk) {
+                        continue;
+                    }
+                    if !walk.within_depth(depth + 1) {
+                        continue;
+                    }
+                    if walk.follow_symlinks && !mark_visited(&mut visited, &path).await {
+                        continue;
+                    }
+                    dirs.push((path, depth + 1));
                 }
-                dirs.push(path);
-            } else if file_type.is_file() {
-                // Match filename (case-insensitive)
-                if file_name.to_lowercase().contains(&pattern_lower) {
-                    matched_files.push(path.to_string_lossy().to_string());
+                Some(EntryKind::File) => {
+                    // Match filename (case-insensitive)
+                    if file_name.to_lowercase().contains(&pattern_lower) {
+                        // One match past the cap shows that results were left out
+                        if matched_files.len() == limit {
+                            return Ok((matched_files, true));
+                        }
+                        matched_files.push(path_string(&path));
+                    }
                 }
+                None => continue,
             }
         }
     }
 
-    Ok(matched_files)
+    Ok((matched_files, false))
 }
 
-/// Search for keyword inside file contents (text files only)
+/// Group files by parent directory. Groups appear in the order their first
+/// file does, and keep the files' relative order.
+fn group_by_dir(files: &[String]) -> Vec<DirGroup> {
+    let mut groups: Vec<DirGroup> = Vec::new();
+    let mut index: HashMap<String, usize> = HashMap::new();
+    for file in files {
+        let dir = Path::new(file)
+            .parent()
+            .map(|p| p.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let i = *index.entry(dir.clone()).or_insert_with(|| {
+            groups.push(DirGroup {
+                dir,
+                files: Vec::new(),
+            });
+            groups.len() - 1
+        });
+        groups[i].files.push(file.clone());
+    }
+    groups
+}
+
+/// Search for keyword inside file contents (text files only).
+/// Returns at most `max_results` matches and whether more files matched.
 async fn perform_content_search(
     root: PathBuf,
     keyword: &str,
+    walk: &WalkOptions,
     max_concurrent: usize,
     max_file_size: u64,
-) -> Result<Vec<String>, AppError> {
-    let mut matched_files: Vec<String> = Vec::new();
-    let mut dirs = vec![root];
+    count_matches: bool,
+    cancel: &CancellationToken,
+) -> Result<(Vec<(String, usize)>, bool), AppError> {
+    let limit = walk.max_results.unwrap_or(usize::MAX);
+    let mut matched_files: Vec<(String, usize)> = Vec::new();
+    let mut visited: HashSet<PathBuf> = HashSet::new();
+    if walk.follow_symlinks {
+        mark_visited(&mut visited, &root).await;
+    }
+    let mut dirs = vec![(root, 0)];
     let keyword_owned = keyword.to_string();
     let mut futs: FuturesUnordered<_> = FuturesUnordered::new();
 
     // Iterative DFS to avoid stack overflow
-    while let Some(current_dir) = dirs.pop() {
+    'walk: while let Some((current_dir, depth)) = dirs.pop() {
+        if cancel.is_cancelled() {
+            break;
+        }
         let mut entries = match fs::read_dir(&current_dir).await {
             Ok(e) => e,
             Err(_) => continue, // Skip unreadable dirs
@@ -339,31 +627,27 @@ async fn perform_content_search(
         while let Ok(Some(entry)) = entries.next_entry().await {
             let path = entry.path();
 
-            // Get file name for filtering
-            let file_name = match path.file_name().and_then(|n| n.to_str()) {
-                Some(name) => name,
-                None => continue,
-            };
-
-            // P1: Use async file_type() to avoid blocking
-            let file_type = match entry.file_type().await {
-                Ok(ft) => ft,
-                Err(_) => continue, // Skip entries we can't stat
-            };
+            // Get file name for filtering; names that are not UTF-8 are
+            // matched on their lossy form and reported by `path_string`
+            let os_name = entry.file_name();
+            let file_name = os_name.to_string_lossy();
 
-            // P0: Skip symbolic links to avoid loops and escapes
-            if file_type.is_symlink() {
-                continue;
-            }
-
-            if file_type.is_dir() {
-                // P1: Check if directory should be ignored
-                if should_ignore_dir(file_name) {
-                    continue;
+            match classify_entry(&entry, walk).await {
+                Some(EntryKind::Dir) => {
+                    // P1: Check if directory should be ignored
+                    if should_ignore_dir(&file_name, walk) {
+                        continue;
+                    }
+                    if !walk.within_depth(depth + 1) {
+                        continue;
+                    }
+                    if walk.follow_symlinks && !mark_visited(&mut visited, &path).await {
+                        continue;
+                    }
+                    dirs.push((path, depth + 1));
                 }
-                dirs.push(path);
-            } else if file_type.is_file() {
-                files_in_dir.push(path);
+                Some(EntryKind::File) => files_in_dir.push(path),
+                None => continue,
             }
         }
 
@@ -400,27 +684,41 @@ async fn perform_content_search(
                         Ok(c) => c,
                         Err(_) => return None,
                     };
-                    if !kw.is_empty() && content.contains(&kw) {
-                        Some(path.to_string_lossy().to_string())
+                    let count = if kw.is_empty() {
+                        0
+                    } else if count_matches {
+                        content.matches(kw.as_str()).count()
                     } else {
-                        None
-                    }
+                        usize::from(content.contains(&kw))
+                    };
+                    (count > 0).then(|| (path_string(&path), count))
                 } else {
-                    file_contains_keyword_streaming(&path, &kw).await
+                    file_contains_keyword_streaming(&path, &kw, count_matches).await
                 }
             });
 
             // Bound concurrency
             while futs.len() >= max_concurrent {
-                if let Some(res) = futs.next().await {
-                    if let Some(file_path) = res {
-                        matched_files.push(file_path);
-                    }
+                if let Some(Some(file_path)) = futs.next().await {
+                    matched_files.push(file_path);
                 }
             }
+
+            // Stop enqueuing once a match past the cap shows more exist
+            if matched_files.len() > limit {
+                break 'walk;
+            }
+            if cancel.is_cancelled() {
+                break 'walk;
+            }
         }
     }
 
+    // Nobody is waiting for the result, so don't finish the queued reads
+    if cancel.is_cancelled() {
+        return Ok((matched_files, true));
+    }
+
     // Drain remaining
     while let Some(res) = futs.next().await {
         if let Some(file_path) = res {
@@ -428,10 +726,18 @@ async fn perform_content_search(
         }
     }
 
-    Ok(matched_files)
+    let truncated = matched_files.len() > limit;
+    matched_files.truncate(limit);
+    Ok((matched_files, truncated))
 }
 
-async fn file_contains_keyword_streaming(path: &PathBuf, keyword: &str) -> Option<String> {
+/// Stream a file line by line looking for `keyword`. Returns on the first hit
+/// unless `count_all` is set, in which case every occurrence is counted.
+async fn file_contains_keyword_streaming(
+    path: &PathBuf,
+    keyword: &str,
+    count_all: bool,
+) -> Option<(String, usize)> {
     let file = match fs::File::open(path).await {
         Ok(f) => f,
         Err(_) => return None,
@@ -439,6 +745,7 @@ async fn file_contains_keyword_streaming(path: &PathBuf, keyword: &str) -> Optio
 
     let mut reader = BufReader::new(file);
     let mut line_buf = String::new();
+    let mut count = 0;
 
     loop {
         line_buf.clear();
@@ -453,15 +760,26 @@ async fn file_contains_keyword_streaming(path: &PathBuf, keyword: &str) -> Optio
                         line = &line[..line.len() - 1];
                     }
                 }
-                if !keyword.is_empty() && line.contains(keyword) {
-                    return Some(path.to_string_lossy().to_string());
+                if keyword.is_empty() {
+                    continue;
+                }
+                if count_all {
+                    count += line.matches(keyword).count();
+                } else if line.contains(keyword) {
+                    return Some((path_string(path), 1));
                 }
             }
             Err(_) => break,
         }
     }
 
-    None
+    (count > 0).then(|| (path_string(path), count))
+}
+
+/// Sort by descending match count, breaking ties by path so results are stable
+/// regardless of the order concurrent reads completed in.
+fn rank_by_count(hits: &mut [(String, usize)]) {
+    hits.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
 }
 
 async fn perform_replace(
@@ -469,6 +787,7 @@ async fn perform_replace(
     original_path: &str,
     from: &str,
     to: &str,
+    encoding: Option<&'static Encoding>,
     max_file_size: u64,
 ) -> ReplaceResult {
     // P1: Use async metadata check instead of blocking exists()
@@ -547,7 +866,11 @@ async fn perform_replace(
     };
 
     // Check for binary content (custom 256B heuristic)
-    if !is_probably_text(&header) {
+    let is_text = match encoding {
+        Some(enc) => is_probably_text_in(&header, enc),
+        None => is_probably_text(&header),
+    };
+    if !is_text {
         return ReplaceResult {
             file: original_path.to_string(),
             status: "skipped".to_string(),
@@ -556,6 +879,10 @@ async fn perform_replace(
         };
     }
 
+    if let Some(enc) = encoding {
+        return perform_encoded_replace(&path, original_path, from, to, enc).await;
+    }
+
     // Now read the full content as UTF-8 text
     let content = match fs::read_to_string(&path).await {
         Ok(s) => s,
@@ -597,13 +924,117 @@ async fn perform_replace(
     }
 }
 
+/// Replace in a file stored in a non-UTF-8 encoding: decode, replace, re-encode.
+async fn perform_encoded_replace(
+    path: &Path,
+    original_path: &str,
+    from: &str,
+    to: &str,
+    encoding: &'static Encoding,
+) -> ReplaceResult {
+    let result = |status: &str, replacements: usize, error: Option<String>| ReplaceResult {
+        file: original_path.to_string(),
+        status: status.to_string(),
+        replacements,
+        error,
+    };
+
+    let bytes = match fs::read(path).await {
+        Ok(b) => b,
+        Err(e) => return result("error", 0, Some(format!("Failed to read file: {}", e))),
+    };
+
+    // A BOM overrides the requested encoding and is written back unchanged
+    let (encoding, bom_len) = Encoding::for_bom(&bytes).unwrap_or((encoding, 0));
+    let (content, had_errors) = encoding.decode_without_bom_handling(&bytes[bom_len..]);
+    if had_errors {
+        return result(
+            "skipped",
+            0,
+            Some(format!("File is not valid {}", encoding.name())),
+        );
+    }
+
+    let count = content.matches(from).count();
+    if count == 0 {
+        return result("skipped", 0, None);
+    }
+
+    let new_content = content.replace(from, to);
+    let Some(encoded) = encode_text(&new_content, encoding) else {
+        return result(
+            "error",
+            0,
+            Some(format!(
+                "Replacement text cannot be represented in {}",
+                encoding.name()
+            )),
+        );
+    };
+
+    let mut output = Vec::with_capacity(bom_len + encoded.len());
+    output.extend_from_slice(&bytes[..bom_len]);
+    output.extend_from_slice(&encoded);
+
+    match fs::write(path, output).await {
+        Ok(_) => result("success", count, None),
+        Err(e) => result("error", 0, Some(e.to_string())),
+    }
+}
+
+/// Encode text into `encoding`. Returns `None` if a character is unmappable.
+///
+/// encoding_rs only decodes UTF-16, so UTF-16LE/BE are encoded by hand.
+fn encode_text(text: &str, encoding: &'static Encoding) -> Option<Vec<u8>> {
+    if encoding == UTF_16LE {
+        return Some(text.encode_utf16().flat_map(u16::to_le_bytes).collect());
+    }
+    if encoding == UTF_16BE {
+        return Some(text.encode_utf16().flat_map(u16::to_be_bytes).collect());
+    }
+    let (bytes, _, had_unmappable) = encoding.encode(text);
+    if had_unmappable {
+        None
+    } else {
+        Some(bytes.into_owned())
+    }
+}
+
+/// Binary heuristic for files in an explicitly requested encoding.
+///
+/// The header is decoded (allowing a truncated trailing sequence) and rejected if it
+/// contains NUL characters or more than 10% control/replacement characters.
+fn is_probably_text_in(header: &[u8], encoding: &'static Encoding) -> bool {
+    if header.is_empty() {
+        return true;
+    }
+    let (encoding, bom_len) = Encoding::for_bom(header).unwrap_or((encoding, 0));
+    let mut decoder = encoding.new_decoder_without_bom_handling();
+    let mut decoded = String::with_capacity(header.len() * 3);
+    let _ = decoder.decode_to_string(&header[bom_len..], &mut decoded, false);
+
+    let mut total = 0usize;
+    let mut suspicious = 0usize;
+    for c in decoded.chars() {
+        total += 1;
+        match c {
+            '\0' => return false,
+            '\t' | '\n' | '\r' => {}
+            '\u{FFFD}' => suspicious += 1,
+            c if c.is_control() => suspicious += 1,
+            _ => {}
+        }
+    }
+    total == 0 || (suspicious as f32 / total as f32) <= 0.10
+}
+
 /// Determine whether the file header likely represents a UTF-8 text file.
 ///
 /// Heuristics on first up to 256 bytes:
-/// - Early null byte detection (including UTF-16, which we treat as non-UTF-8 text and skip)
+/// - Early null byte detection (UTF-16 needs an explicit `encoding`, see `is_probably_text_in`)
 /// - Control character density (excluding TAB/CR/LF); high density suggests binary
 /// - UTF-8 sequence validation allowing truncated trailing sequence
-fn is_probably_text(header: &[u8]) -> bool {
+pub(crate) fn is_probably_text(header: &[u8]) -> bool {
     if header.is_empty() {
         return true;
     }
@@ -694,3 +1125,265 @@ fn is_valid_utf8_prefix(bytes: &[u8]) -> bool {
     }
     true
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_root() -> PathBuf {
+        let root = std::env::temp_dir().join(format!(
+            "devbox-search-{}",
+            crate::utils::common::generate_id()
+        ));
+        std::fs::create_dir_all(&root).unwrap();
+        root
+    }
+
+    #[tokio::test]
+    async fn test_filename_search_hidden_and_symlinks() {
+        let root = temp_root();
+        std::fs::create_dir_all(root.join(".github/workflows")).unwrap();
+        std::fs::write(root.join(".github/workflows/ci.yml"), "on: push").unwrap();
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        std::fs::write(root.join("src/ci.rs"), "fn main() {}").unwrap();
+        // Cycle: src/loop -> root
+        std::os::unix::fs::symlink(&root, root.join("src/loop")).unwrap();
+
+        let ignored_dirs: HashSet<String> = IGNORED_DIRS.iter().map(|d| d.to_string()).collect();
+        let default_walk = WalkOptions {
+            include_hidden: false,
+            follow_symlinks: false,
+            ignored_dirs: ignored_dirs.clone(),
+            max_results: None,
+            max_depth: None,
+        };
+        let (files, truncated) = perform_filename_search(root.clone(), "ci", &default_walk)
+            .await
+            .unwrap();
+        assert_eq!(files.len(), 1);
+        assert!(!truncated);
+
+        let full_walk = WalkOptions {
+            include_hidden: true,
+            follow_symlinks: true,
+            ignored_dirs,
+            max_results: None,
+            max_depth: None,
+        };
+        let (files, _) = perform_filename_search(root.clone(), "ci", &full_walk)
+            .await
+            .unwrap();
+        assert_eq!(files.len(), 2, "cycle should be visited once: {:?}", files);
+
+        // Only a cap below the number of matches truncates
+        for (max_results, expected, expect_truncated) in [(1, 1, true), (2, 2, false)] {
+            let capped_walk = WalkOptions {
+                max_results: Some(max_results),
+                ignored_dirs: full_walk.ignored_dirs.clone(),
+                ..full_walk
+            };
+            let (files, truncated) = perform_filename_search(root.clone(), "ci", &capped_walk)
+                .await
+                .unwrap();
+            assert_eq!(files.len(), expected);
+            assert_eq!(truncated, expect_truncated, "maxResults {}", max_results);
+        }
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_search_max_depth() {
+        let root = temp_root();
+        std::fs::create_dir_all(root.join("a/b/c")).unwrap();
+        for dir in ["", "a", "a/b", "a/b/c"] {
+            std::fs::write(root.join(dir).join("note.txt"), "marker").unwrap();
+        }
+
+        let walk = |max_depth| WalkOptions {
+            include_hidden: false,
+            follow_symlinks: false,
+            ignored_dirs: HashSet::new(),
+            max_results: None,
+            max_depth,
+        };
+        for (max_depth, expected) in [(Some(0), 1), (Some(2), 3), (None, 4)] {
+            let (files, _) = perform_filename_search(root.clone(), "note", &walk(max_depth))
+                .await
+                .unwrap();
+            assert_eq!(files.len(), expected, "maxDepth {:?}", max_depth);
+            let (hits, _) = perform_content_search(
+                root.clone(),
+                "marker",
+                &walk(max_depth),
+                2,
+                1 << 20,
+                false,
+                &CancellationToken::new(),
+            )
+            .await
+            .unwrap();
+            assert_eq!(hits.len(), expected, "maxDepth {:?}", max_depth);
+        }
+
+        for (max_results, expected, expect_truncated) in [(3, 3, true), (4, 4, false)] {
+            let capped = WalkOptions {
+                max_results: Some(max_results),
+                ..walk(None)
+            };
+            let (hits, truncated) = perform_content_search(
+                root.clone(),
+                "marker",
+                &capped,
+                2,
+                1 << 20,
+                false,
+                &CancellationToken::new(),
+            )
+            .await
+            .unwrap();
+            assert_eq!(hits.len(), expected);
+            assert_eq!(truncated, expect_truncated, "maxResults {}", max_results);
+        }
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_content_search_cancellation() {
+        let root = temp_root();
+        for i in 0..200 {
+            let dir = root.join(format!("d{}", i));
+            std::fs::create_dir(&dir).unwrap();
+            std::fs::write(dir.join("note.txt"), "marker").unwrap();
+        }
+        let walk = WalkOptions {
+            include_hidden: false,
+            follow_symlinks: false,
+            ignored_dirs: HashSet::new(),
+            max_results: None,
+            max_depth: None,
+        };
+
+        let cancel = CancellationToken::new();
+        let token = cancel.clone();
+        let search_root = root.clone();
+        let search = tokio::spawn(async move {
+            perform_content_search(search_root, "marker", &walk, 1, 1 << 20, false, &token).await
+        });
+        // Let the walk get going, then drop the guard as a disconnect would
+        tokio::task::yield_now().await;
+        drop(cancel.drop_guard());
+        let (hits, truncated) = tokio::time::timeout(std::time::Duration::from_secs(1), search)
+            .await
+            .expect("walk did not stop after cancellation")
+            .unwrap()
+            .unwrap();
+        assert!(hits.len() < 200, "walk ran to completion");
+        assert!(truncated);
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_content_search_ranking() {
+        let root = temp_root();
+        std::fs::write(root.join("one.txt"), "todo once").unwrap();
+        std::fs::write(root.join("three.txt"), "todo todo\ntodo").unwrap();
+        // Large enough to take the streaming path
+        let big = "filler line\n".repeat(4096) + "todo todo\n";
+        std::fs::write(root.join("big.txt"), big).unwrap();
+
+        let walk = WalkOptions {
+            include_hidden: false,
+            follow_symlinks: false,
+            ignored_dirs: HashSet::new(),
+            max_results: None,
+            max_depth: None,
+        };
+        let (mut hits, _) = perform_content_search(
+            root.clone(),
+            "todo",
+            &walk,
+            2,
+            1 << 20,
+            true,
+            &CancellationToken::new(),
+        )
+        .await
+        .unwrap();
+        rank_by_count(&mut hits);
+        let ranked: Vec<_> = hits
+            .iter()
+            .map(|(f, c)| (Path::new(f).file_name().unwrap().to_str().unwrap(), *c))
+            .collect();
+        assert_eq!(
+            ranked,
+            vec![("three.txt", 3), ("big.txt", 2), ("one.txt", 1)]
+        );
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_replace_utf16_file() {
+        let root = temp_root();
+        let path = root.join("legacy.txt");
+        let mut bytes = vec![0xFF, 0xFE];
+        bytes.extend("hello world\r\n".encode_utf16().flat_map(u16::to_le_bytes));
+        std::fs::write(&path, &bytes).unwrap();
+
+        let result = perform_replace(
+            path.clone(),
+            "legacy.txt",
+            "world",
+            "devbox",
+            Some(UTF_16LE),
+            1024,
+        )
+        .await;
+        assert_eq!(result.status, "success");
+        assert_eq!(result.replacements, 1);
+
+        let mut expected = vec![0xFF, 0xFE];
+        expected.extend("hello devbox\r\n".encode_utf16().flat_map(u16::to_le_bytes));
+        assert_eq!(std::fs::read(&path).unwrap(), expected);
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_group_by_dir() {
+        let files: Vec<String> = ["/w/src/a.rs", "/w/lib.rs", "/w/src/b.rs"]
+            .iter()
+            .map(|f| f.to_string())
+            .collect();
+        let groups = group_by_dir(&files);
+        assert_eq!(
+            groups,
+            vec![
+                DirGroup {
+                    dir: "/w/src".to_string(),
+                    files: vec!["/w/src/a.rs".to_string(), "/w/src/b.rs".to_string()],
+                },
+                DirGroup {
+                    dir: "/w".to_string(),
+                    files: vec!["/w/lib.rs".to_string()],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_effective_ignored_dirs() {
+        let configured = vec!["vendor".to_string(), "target".to_string()];
+        let dirs = effective_ignored_dirs(
+            &configured,
+            &[".terraform".to_string()],
+            &["vendor".to_string()],
+        );
+        assert!(dirs.contains("target"));
+        assert!(dirs.contains(".terraform"));
+        assert!(!dirs.contains("vendor"));
+    }
+}
@@ -0,0 +1,474 @@
+use super::list::file_info;
+use super::types::FileInfo;
+use crate::error::AppError;
+use crate::state::workspace::Workspace;
+use crate::state::AppState;
+use crate::utils::hash::Sha256;
+use crate::utils::path::{relative_to_workspace, validate_path};
+use crate::utils::watch::{ChangeKind, DirWatcher, WatchEvent};
+use axum::{
+    extract::{Query, State},
+    response::sse::{Event, KeepAlive, Sse},
+};
+use futures::Stream;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::{BTreeMap, HashSet};
+use std::convert::Infallible;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::fs;
+use tokio::io::AsyncReadExt;
+use tokio::sync::mpsc;
+use tokio::time::{sleep, timeout, Duration};
+use tokio_stream::wrappers::ReceiverStream;
+
+type EventSender = mpsc::Sender<Result<Event, Infallible>>;
+
+#[derive(Deserialize)]
+pub struct SyncParams {
+    path: Option<String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SyncChange {
+    path: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    relative_path: Option<String>,
+    op: &'static str,
+    is_dir: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    size: Option<u64>,
+    /// SHA-256 of the new content, for files up to `MAX_FILE_SIZE`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sha256: Option<String>,
+}
+
+/// Stream a directory for mirroring: one `file` event per entry currently in
+/// the tree, a `ready` event, then a `change` event for every create, write
+/// and delete below it.
+pub async fn sync_files(
+    State(state): State<Arc<AppState>>,
+    Workspace(workspace): Workspace,
+    Query(params): Query<SyncParams>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, AppError> {
+    let root = validate_path(&workspace, params.path.as_deref().unwrap_or("."))?;
+    let metadata = fs::metadata(&root)
+        .await
+        .map_err(|_| AppError::NotFound(format!("Directory not found: {}", root.display())))?;
+    if !metadata.is_dir() {
+        return Err(AppError::BadRequest(format!(
+            "Path is not a directory: {}",
+            root.display()
+        )));
+    }
+
+    let watcher = DirWatcher::new(state.config.search_ignored_dirs.iter().cloned().collect())?;
+    let (tx, rx) = mpsc::channel(100);
+    tokio::spawn(run_sync(
+        watcher,
+        root,
+        workspace,
+        state.config.max_file_size,
+        tx,
+    ));
+
+    Ok(Sse::new(ReceiverStream::new(rx)).keep_alive(KeepAlive::default()))
+}
+
+async fn run_sync(
+    mut watcher: DirWatcher,
+    root: PathBuf,
+    workspace: PathBuf,
+    max_hash_size: u64,
+    tx: EventSender,
+) {
+    // Watches are in place before the snapshot is read, so nothing written
+    // during the walk is missed
+    if !send_snapshot(&mut watcher, &root, &workspace, &tx).await {
+        return;
+    }
+
+    loop {
+        let events = tokio::select! {
+            _ = tx.closed() => return,
+            events = watcher.next_events() => events,
+        };
+        let events = match events {
+            Ok(events) => events,
+            Err(e) => {
+                let _ = tx.send(Ok(error_event(e.to_string()))).await;
+                return;
+            }
+        };
+        if !watcher.is_watching() {
+            let _ = tx
+                .send(Ok(error_event(format!(
+                    "Directory removed: {}",
+                    root.display()
+                ))))
+                .await;
+            return;
+        }
+
+        for event in events {
+            let (path, kind, is_dir) = match event {
+                WatchEvent::Change { path, kind, is_dir } => (path, kind, is_dir),
+                WatchEvent::Overflow => {
+                    // Events were dropped; resend the whole tree
+                    let overflow = Event::default().event("overflow").data("{}");
+                    if tx.send(Ok(overflow)).await.is_err()
+                        || !send_snapshot(&mut watcher, &root, &workspace, &tx).await
+                    {
+                        return;
+                    }
+                    continue;
+                }
+            };
+
+            let mut changes = vec![(path.clone(), kind, is_dir)];
+            if kind == ChangeKind::Created && is_dir {
+                // Anything created inside before the watch existed is reported now
+                for entry in watcher.watch_tree(&path).await {
+                    let is_dir = entry.file_type().await.is_ok_and(|t| t.is_dir());
+                    changes.push((entry.path(), ChangeKind::Created, is_dir));
+                }
+            }
+
+            for (path, kind, is_dir) in changes {
+                let Some(change) = describe(&path, kind, is_dir, &workspace, max_hash_size).await
+                else {
+                    continue;
+                };
+                let event = Event::default()
+                    .event("change")
+                    .json_data(&change)
+                    .unwrap_or_default();
+                if tx.send(Ok(event)).await.is_err() {
+                    return;
+                }
+            }
+        }
+    }
+}
+
+/// Watch the tree and send its current entries followed by `ready`.
+/// Returns `false` once the client has gone away.
+async fn send_snapshot(
+    watcher: &mut DirWatcher,
+    root: &Path,
+    workspace: &Path,
+    tx: &EventSender,
+) -> bool {
+    let mut count = 0;
+    for entry in watcher.watch_tree(root).await {
+        let Ok(Some(info)) = file_info(&entry, workspace, true).await else {
+            continue;
+        };
+        let event = Event::default()
+            .event("file")
+            .json_data(&info)
+            .unwrap_or_default();
+        if tx.send(Ok(event)).await.is_err() {
+            return false;
+        }
+        count += 1;
+    }
+    let ready = Event::default()
+        .event("ready")
+        .data(json!({ "count": count }).to_string());
+    tx.send(Ok(ready)).await.is_ok()
+}
+
+/// Build the change record, or `None` for entries that vanished again before
+/// they could be inspected (their delete event follows) and for symlinks.
+async fn describe(
+    path: &Path,
+    kind: ChangeKind,
+    is_dir: bool,
+    workspace: &Path,
+    max_hash_size: u64,
+) -> Option<SyncChange> {
+    let (size, sha256) = if kind == ChangeKind::Deleted || is_dir {
+        (None, None)
+    } else {
+        let metadata = fs::symlink_metadata(path).await.ok()?;
+        if metadata.file_type().is_symlink() {
+            return None;
+        }
+        if metadata.len() <= max_hash_size {
+            // Size and hash come from the same read so they always agree
+            let (size, sha256) = hash_file(path).await.ok()?;
+            (Some(size), Some(sha256))
+        } else {
+            (Some(metadata.len()), None)
+        }
+    };
+
+    Some(SyncChange {
+        path: path.to_string_lossy().to_string(),
+        relative_path: relative_to_workspace(workspace, path),
+        op: kind.as_str(),
+        is_dir,
+        size,
+        sha256,
+    })
+}
+
+async fn hash_file(path: &Path) -> std::io::Result<(u64, String)> {
+    let mut file = fs::File::open(path).await?;
+    let mut hasher = Sha256::new();
+    let mut size = 0;
+    let mut buf = vec![0u8; 64 * 1024];
+    loop {
+        let n = file.read(&mut buf).await?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+        size += n as u64;
+    }
+    Ok((size, hasher.finalize_hex()))
+}
+
+fn error_event(message: String) -> Event {
+    Event::default().event("error").data(message)
+}
+
+/// Quiet period that lets a burst of events (an editor's save, a checkout)
+/// collapse into one diff
+const LIST_WATCH_COALESCE: Duration = Duration::from_millis(100);
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ListWatchParams {
+    path: Option<String>,
+    #[serde(default)]
+    show_hidden: bool,
+}
+
+#[derive(Serialize)]
+struct Listing<'a> {
+    files: Vec<&'a FileInfo>,
+}
+
+/// Stream one directory for an open folder view: a `listing` event with its
+/// entries, then `add`, `change` and `remove` events carrying the entry as
+/// `files/list` reports it. Subdirectories are not watched.
+pub async fn list_watch(
+    Workspace(workspace): Workspace,
+    Query(params): Query<ListWatchParams>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, AppError> {
+    let dir = validate_path(&workspace, params.path.as_deref().unwrap_or("."))?;
+    let metadata = fs::metadata(&dir)
+        .await
+        .map_err(|_| AppError::NotFound(format!("Directory not found: {}", dir.display())))?;
+    if !metadata.is_dir() {
+        return Err(AppError::BadRequest(format!(
+            "Path is not a directory: {}",
+            dir.display()
+        )));
+    }
+
+    let mut watcher = DirWatcher::new(HashSet::new())?;
+    watcher.watch_dir(&dir)?;
+    let (tx, rx) = mpsc::channel(100);
+    tokio::spawn(run_list_watch(
+        watcher,
+        dir,
+        workspace,
+        params.show_hidden,
+        tx,
+    ));
+
+    Ok(Sse::new(ReceiverStream::new(rx)).keep_alive(KeepAlive::default()))
+}
+
+async fn run_list_watch(
+    mut watcher: DirWatcher,
+    dir: PathBuf,
+    workspace: PathBuf,
+    show_hidden: bool,
+    tx: EventSender,
+) {
+    // The watch is in place before the first read, so nothing is missed
+    let mut listing = match read_listing(&dir, &workspace, show_hidden).await {
+        Ok(listing) => listing,
+        Err(e) => {
+            let _ = tx.send(Ok(error_event(e.to_string()))).await;
+            return;
+        }
+    };
+    let files = Listing {
+        files: listing.values().collect(),
+    };
+    let event = Event::default()
+        .event("listing")
+        .json_data(&files)
+        .unwrap_or_default();
+    if tx.send(Ok(event)).await.is_err() {
+        return;
+    }
+
+    loop {
+        // Entries written to, as their size and second-resolution mtime may
+        // not show it
+        let mut touched = HashSet::new();
+        let events = tokio::select! {
+            _ = tx.closed() => return,
+            events = watcher.next_events() => events,
+        };
+        let mut result = events.map(|events| note_modified(events, &mut touched));
+        if result.is_ok() {
+            tokio::select! {
+                _ = tx.closed() => return,
+                _ = sleep(LIST_WATCH_COALESCE) => {}
+            }
+            // Take whatever else arrived meanwhile without waiting for more
+            while let Ok(events) = timeout(Duration::ZERO, watcher.next_events()).await {
+                match events {
+                    Ok(events) if !events.is_empty() => note_modified(events, &mut touched),
+                    Ok(_) => break,
+                    Err(e) => {
+                        result = Err(e);
+                        break;
+                    }
+                }
+            }
+        }
+        if let Err(e) = result {
+            let _ = tx.send(Ok(error_event(e.to_string()))).await;
+            return;
+        }
+        if !watcher.is_watching() {
+            let _ = tx
+                .send(Ok(error_event(format!(
+                    "Directory removed: {}",
+                    dir.display()
+                ))))
+                .await;
+            return;
+        }
+
+        let current = match read_listing(&dir, &workspace, show_hidden).await {
+            Ok(current) => current,
+            Err(e) => {
+                let _ = tx.send(Ok(error_event(e.to_string()))).await;
+                return;
+            }
+        };
+        for (op, info) in diff_listing(&listing, &current, &touched) {
+            let event = Event::default()
+                .event(op)
+                .json_data(info)
+                .unwrap_or_default();
+            if tx.send(Ok(event)).await.is_err() {
+                return;
+            }
+        }
+        listing = current;
+    }
+}
+
+fn note_modified(events: Vec<WatchEvent>, touched: &mut HashSet<String>) {
+    for event in events {
+        if let WatchEvent::Change {
+            path,
+            kind: ChangeKind::Modified,
+            ..
+        } = event
+        {
+            if let Some(name) = path.file_name() {
+                touched.insert(name.to_string_lossy().into_owned());
+            }
+        }
+    }
+}
+
+/// The directory's entries by name; entries that vanish while being read
+/// are left out.
+async fn read_listing(
+    dir: &Path,
+    workspace: &Path,
+    show_hidden: bool,
+) -> std::io::Result<BTreeMap<String, FileInfo>> {
+    let mut listing = BTreeMap::new();
+    let mut entries = fs::read_dir(dir).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        if let Ok(Some(info)) = file_info(&entry, workspace, show_hidden).await {
+            listing.insert(info.name.clone(), info);
+        }
+    }
+    Ok(listing)
+}
+
+/// Events turning `old` into `new`: removals first, then additions and
+/// changes in name order. Entries in `touched` count as changed even when
+/// their listing looks the same.
+fn diff_listing<'a>(
+    old: &'a BTreeMap<String, FileInfo>,
+    new: &'a BTreeMap<String, FileInfo>,
+    touched: &HashSet<String>,
+) -> Vec<(&'static str, &'a FileInfo)> {
+    let mut changes: Vec<_> = old
+        .iter()
+        .filter(|(name, _)| !new.contains_key(*name))
+        .map(|(_, info)| ("remove", info))
+        .collect();
+    for (name, info) in new {
+        match old.get(name) {
+            None => changes.push(("add", info)),
+            Some(before) if before != info || touched.contains(name) => {
+                changes.push(("change", info))
+            }
+            Some(_) => {}
+        }
+    }
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diff_listing() {
+        let entry = |name: &str, size: u64| {
+            (
+                name.to_string(),
+                FileInfo {
+                    name: name.to_string(),
+                    path: format!("/ws/{}", name),
+                    encoded_name: None,
+                    encoded_path: None,
+                    relative_path: Some(name.to_string()),
+                    size: Some(size),
+                    is_dir: false,
+                    is_symlink: false,
+                    link_target: None,
+                    permissions: Some("0644".to_string()),
+                    modified: None,
+                },
+            )
+        };
+        let old = BTreeMap::from([entry("a", 1), entry("b", 2), entry("c", 3), entry("d", 4)]);
+        let new = BTreeMap::from([entry("b", 5), entry("c", 3), entry("d", 4), entry("e", 0)]);
+        let touched = HashSet::from(["d".to_string()]);
+
+        let changes: Vec<_> = diff_listing(&old, &new, &touched)
+            .into_iter()
+            .map(|(op, info)| (op, info.name.as_str()))
+            .collect();
+        assert_eq!(
+            changes,
+            vec![
+                ("remove", "a"),
+                ("change", "b"),
+                ("change", "d"),
+                ("add", "e")
+            ]
+        );
+        assert!(diff_listing(&new, &new, &HashSet::new()).is_empty());
+    }
+}
@@ -0,0 +1,231 @@
+use crate::error::AppError;
+use crate::response::ApiResponse;
+use crate::state::workspace::Workspace;
+use crate::state::AppState;
+use crate::utils::path::validate_path;
+use axum::{
+    extract::{Query, State},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashSet, VecDeque};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::fs;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileTreeParams {
+    path: Option<String>,
+    /// Levels below `path` to expand (default: 3)
+    #[serde(default = "default_max_depth")]
+    max_depth: usize,
+    /// Stop adding entries once this many nodes are in the tree (default: 1000)
+    #[serde(default = "default_max_nodes")]
+    max_nodes: usize,
+    #[serde(default)]
+    show_hidden: bool,
+}
+
+fn default_max_depth() -> usize {
+    3
+}
+
+fn default_max_nodes() -> usize {
+    1000
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TreeNode {
+    name: String,
+    path: String,
+    is_dir: bool,
+    /// Present for directories that were expanded
+    #[serde(skip_serializing_if = "Option::is_none")]
+    children: Option<Vec<TreeNode>>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileTreeResponse {
+    tree: TreeNode,
+    /// True when `maxNodes` was reached before the walk finished
+    truncated: bool,
+}
+
+pub async fn file_tree(
+    State(state): State<Arc<AppState>>,
+    Workspace(workspace): Workspace,
+    Query(params): Query<FileTreeParams>,
+) -> Result<Json<ApiResponse<FileTreeResponse>>, AppError> {
+    if params.max_nodes == 0 {
+        return Err(AppError::BadRequest(
+            "maxNodes must be greater than 0".to_string(),
+        ));
+    }
+    let root = validate_path(&workspace, params.path.as_deref().unwrap_or("."))?;
+    let metadata = fs::metadata(&root)
+        .await
+        .map_err(|_| AppError::NotFound(format!("Directory not found: {}", root.display())))?;
+    if !metadata.is_dir() {
+        return Err(AppError::BadRequest(format!(
+            "Path is not a directory: {}",
+            root.display()
+        )));
+    }
+
+    let ignored: HashSet<&str> = state
+        .config
+        .search_ignored_dirs
+        .iter()
+        .map(String::as_str)
+        .collect();
+    let (tree, truncated) = build_tree(root, &ignored, &params).await;
+    Ok(Json(ApiResponse::success(FileTreeResponse {
+        tree,
+        truncated,
+    })))
+}
+
+/// Walk breadth-first into a flat list where every node's parent has a lower
+/// index, then fold children into their parents from the back so no recursion
+/// is needed. Symbolic links are not listed.
+async fn build_tree(
+    root: PathBuf,
+    ignored: &HashSet<&str>,
+    params: &FileTreeParams,
+) -> (TreeNode, bool) {
+    let expand_root = params.max_depth > 0;
+    let mut nodes: Vec<(TreeNode, Option<usize>)> = vec![(dir_node(&root, expand_root), None)];
+    let mut queue = VecDeque::new();
+    if expand_root {
+        queue.push_back((0, root, 0));
+    }
+    let mut truncated = false;
+
+    'walk: while let Some((index, dir, depth)) = queue.pop_front() {
+        let mut entries = match fs::read_dir(&dir).await {
+            Ok(e) => e,
+            Err(_) => continue, // Unreadable dirs stay empty
+        };
+        let mut children = Vec::new();
+        while let Ok(Some(entry)) = entries.next_entry().await {
+            let Ok(file_type) = entry.file_type().await else {
+                continue;
+            };
+            let name = entry.file_name().to_string_lossy().to_string();
+            if file_type.is_symlink()
+                || (!params.show_hidden && name.starts_with('.'))
+                || (file_type.is_dir() && ignored.contains(name.as_str()))
+            {
+                continue;
+            }
+            children.push((name, file_type.is_dir(), entry.path()));
+        }
+        children.sort_by(|a, b| a.0.cmp(&b.0));
+
+        for (name, is_dir, path) in children {
+            if nodes.len() >= params.max_nodes {
+                truncated = true;
+                break 'walk;
+            }
+            let expand = is_dir && depth + 1 < params.max_depth;
+            let node = TreeNode {
+                name,
+                path: path.to_string_lossy().to_string(),
+                is_dir,
+                children: expand.then(Vec::new),
+            };
+            if expand {
+                queue.push_back((nodes.len(), path, depth + 1));
+            }
+            nodes.push((node, Some(index)));
+        }
+    }
+
+    while nodes.len() > 1 {
+        let (node, parent) = nodes.pop().expect("non-empty");
+        if let Some(children) = parent.and_then(|p| nodes[p].0.children.as_mut()) {
+            children.push(node);
+        }
+    }
+    let mut tree = nodes.pop().expect("root node").0;
+    reverse_children(&mut tree);
+    (tree, truncated)
+}
+
+fn dir_node(path: &Path, expand: bool) -> TreeNode {
+    TreeNode {
+        name: path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_else(|| "/".to_string()),
+        path: path.to_string_lossy().to_string(),
+        is_dir: true,
+        children: expand.then(Vec::new),
+    }
+}
+
+/// Children were folded in back to front; restore name order without recursion.
+fn reverse_children(root: &mut TreeNode) {
+    let mut stack = vec![root];
+    while let Some(node) = stack.pop() {
+        if let Some(children) = node.children.as_mut() {
+            children.reverse();
+            stack.extend(children.iter_mut());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(node: &TreeNode) -> Vec<&str> {
+        node.children
+            .iter()
+            .flatten()
+            .map(|c| c.name.as_str())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_build_tree() {
+        let root = std::env::temp_dir().join(format!(
+            "devbox-tree-{}",
+            crate::utils::common::generate_id()
+        ));
+        std::fs::create_dir_all(root.join("src/nested/deep")).unwrap();
+        std::fs::create_dir_all(root.join("node_modules/pkg")).unwrap();
+        std::fs::write(root.join("src/main.rs"), "").unwrap();
+        std::fs::write(root.join("src/nested/mod.rs"), "").unwrap();
+        std::fs::write(root.join("README.md"), "").unwrap();
+        std::fs::write(root.join(".env"), "").unwrap();
+        std::os::unix::fs::symlink(&root, root.join("src/loop")).unwrap();
+
+        let ignored = HashSet::from(["node_modules"]);
+        let params = |max_depth, max_nodes| FileTreeParams {
+            path: None,
+            max_depth,
+            max_nodes,
+            show_hidden: false,
+        };
+
+        let (tree, truncated) = build_tree(root.clone(), &ignored, &params(2, 100)).await;
+        assert!(!truncated);
+        assert_eq!(names(&tree), vec!["README.md", "src"]);
+        let src = &tree.children.as_ref().unwrap()[1];
+        assert_eq!(names(src), vec!["main.rs", "nested"]);
+        // Depth limit: `nested` is listed but not expanded
+        assert!(src.children.as_ref().unwrap()[1].children.is_none());
+        assert!(tree.children.as_ref().unwrap()[0].children.is_none());
+
+        let (tree, truncated) = build_tree(root.clone(), &ignored, &params(10, 3)).await;
+        assert!(truncated);
+        assert_eq!(names(&tree), vec!["README.md", "src"]);
+        assert!(names(&tree.children.as_ref().unwrap()[1]).is_empty());
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
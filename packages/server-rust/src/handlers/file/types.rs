@@ -1,12 +1,27 @@
 use serde::Serialize;
 
-#[derive(Serialize, Clone)]
+#[derive(Serialize, Clone, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct FileInfo {
     pub name: String,
     pub path: String,
-    pub size: u64,
+    /// Percent-encoded bytes of a name that is not valid UTF-8, which `name`
+    /// only approximates
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub encoded_name: Option<String>,
+    /// The `encoded:` form of `path` to pass back in path parameters, when
+    /// `path` is not valid UTF-8
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub encoded_path: Option<String>,
+    pub relative_path: Option<String>,
+    /// Absent for broken symbolic links
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub size: Option<u64>,
     pub is_dir: bool,
+    pub is_symlink: bool,
+    /// Where a symbolic link points, as stored in the link
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub link_target: Option<String>,
     pub permissions: Option<String>,
     pub modified: Option<String>,
 }
@@ -21,5 +36,10 @@ pub struct FileOperationResponse {
 #[serde(rename_all = "camelCase")]
 pub struct WriteFileResponse {
     pub path: String,
+    pub relative_path: Option<String>,
     pub size: u64,
+    /// Bytes received on the wire, when the upload was gzip-compressed or
+    /// base64-encoded
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub received_size: Option<u64>,
 }
@@ -0,0 +1,211 @@
+use crate::error::AppError;
+use crate::response::ApiResponse;
+use crate::state::workspace::Workspace;
+use crate::utils::path::validate_path;
+use axum::{extract::Query, Json};
+use serde::{Deserialize, Serialize};
+use std::process::Stdio;
+use tokio::process::Command;
+
+#[derive(Deserialize)]
+pub struct GitStatusParams {
+    /// Directory inside the repository (default: the workspace root)
+    path: Option<String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GitFileStatus {
+    path: String,
+    /// Original path for renamed or copied entries
+    #[serde(skip_serializing_if = "Option::is_none")]
+    orig_path: Option<String>,
+    /// Change recorded in the index, if any
+    staged: Option<&'static str>,
+    /// Change in the working tree relative to the index, if any
+    unstaged: Option<&'static str>,
+}
+
+#[derive(Serialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct GitStatusResponse {
+    /// Current branch; `None` when HEAD is detached
+    branch: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    upstream: Option<String>,
+    ahead: u32,
+    behind: u32,
+    files: Vec<GitFileStatus>,
+}
+
+pub async fn git_status(
+    Workspace(workspace): Workspace,
+    Query(params): Query<GitStatusParams>,
+) -> Result<Json<ApiResponse<GitStatusResponse>>, AppError> {
+    let dir = validate_path(&workspace, params.path.as_deref().unwrap_or("."))?;
+    if !dir.is_dir() {
+        return Err(AppError::NotFound("Directory not found".to_string()));
+    }
+
+    let output = Command::new("git")
+        .args(["status", "--porcelain=v2", "--branch", "-z"])
+        .current_dir(&dir)
+        .stdin(Stdio::null())
+        .output()
+        .await
+        .map_err(|e| AppError::InternalServerError(format!("Failed to run git: {}", e)))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if stderr.contains("not a git repository") {
+            return Err(AppError::BadRequest(format!(
+                "Not a git repository: {}",
+                dir.display()
+            )));
+        }
+        return Err(AppError::InternalServerError(format!(
+            "git status failed: {}",
+            stderr.trim()
+        )));
+    }
+
+    Ok(Json(ApiResponse::success(parse_porcelain_v2(
+        &String::from_utf8_lossy(&output.stdout),
+    ))))
+}
+
+/// Map a porcelain XY status letter to a change kind; `.` means unchanged.
+fn change_kind(code: char) -> Option<&'static str> {
+    match code {
+        'M' => Some("modified"),
+        'T' => Some("typeChanged"),
+        'A' => Some("added"),
+        'D' => Some("deleted"),
+        'R' => Some("renamed"),
+        'C' => Some("copied"),
+        'U' => Some("unmerged"),
+        _ => None,
+    }
+}
+
+/// Parse NUL-separated `git status --porcelain=v2 --branch -z` output.
+fn parse_porcelain_v2(output: &str) -> GitStatusResponse {
+    let mut status = GitStatusResponse::default();
+    let mut records = output.split('\0').filter(|r| !r.is_empty());
+
+    while let Some(record) = records.next() {
+        let (kind, rest) = record.split_once(' ').unwrap_or((record, ""));
+        match kind {
+            "#" => {
+                let (key, value) = rest.split_once(' ').unwrap_or((rest, ""));
+                match key {
+                    "branch.head" if value != "(detached)" => {
+                        status.branch = Some(value.to_string())
+                    }
+                    "branch.upstream" => status.upstream = Some(value.to_string()),
+                    "branch.ab" => {
+                        for part in value.split_whitespace() {
+                            if let Some(n) = part.strip_prefix('+') {
+                                status.ahead = n.parse().unwrap_or(0);
+                            } else if let Some(n) = part.strip_prefix('-') {
+                                status.behind = n.parse().unwrap_or(0);
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            // Ordinary (8 fields before the path), renamed/copied (9, followed
+            // by the original path as a separate record) and unmerged (10)
+            "1" | "2" | "u" => {
+                let skip = match kind {
+                    "1" => 7,
+                    "2" => 8,
+                    _ => 9,
+                };
+                let mut fields = rest.splitn(skip + 1, ' ');
+                let xy = fields.next().unwrap_or("");
+                let Some(path) = fields.nth(skip - 1) else {
+                    continue;
+                };
+                let mut codes = xy.chars();
+                let (x, y) = (codes.next().unwrap_or('.'), codes.next().unwrap_or('.'));
+                let orig_path = if kind == "2" {
+                    records.next().map(str::to_string)
+                } else {
+                    None
+                };
+                status.files.push(GitFileStatus {
+                    path: path.to_string(),
+                    orig_path,
+                    staged: change_kind(x),
+                    unstaged: change_kind(y),
+                });
+            }
+            "?" => status.files.push(GitFileStatus {
+                path: rest.to_string(),
+                orig_path: None,
+                staged: None,
+                unstaged: Some("untracked"),
+            }),
+            _ => {}
+        }
+    }
+
+    status
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_porcelain_v2() {
+        let output = [
+            "# branch.oid 1234abcd",
+            "# branch.head main",
+            "# branch.upstream origin/main",
+            "# branch.ab +2 -1",
+            "1 .M N... 100644 100644 100644 aaaa bbbb src/main.rs",
+            "1 A. N... 000000 100644 100644 0000 cccc new file.txt",
+            "2 R. N... 100644 100644 100644 dddd dddd R100 renamed.rs",
+            "old.rs",
+            "u UU N... 100644 100644 100644 100644 eeee ffff 0000 conflict.rs",
+            "? untracked.txt",
+            "",
+        ]
+        .join("\0");
+
+        let status = parse_porcelain_v2(&output);
+        assert_eq!(status.branch.as_deref(), Some("main"));
+        assert_eq!(status.upstream.as_deref(), Some("origin/main"));
+        assert_eq!((status.ahead, status.behind), (2, 1));
+
+        let summary: Vec<_> = status
+            .files
+            .iter()
+            .map(|f| {
+                (
+                    f.path.as_str(),
+                    f.orig_path.as_deref(),
+                    f.staged,
+                    f.unstaged,
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("src/main.rs", None, None, Some("modified")),
+                ("new file.txt", None, Some("added"), None),
+                ("renamed.rs", Some("old.rs"), Some("renamed"), None),
+                ("conflict.rs", None, Some("unmerged"), Some("unmerged")),
+                ("untracked.txt", None, None, Some("untracked")),
+            ]
+        );
+
+        let detached = parse_porcelain_v2("# branch.oid abcd\0# branch.head (detached)\0");
+        assert_eq!(detached.branch, None);
+        assert!(detached.files.is_empty());
+    }
+}
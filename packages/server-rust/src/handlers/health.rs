@@ -12,6 +12,39 @@ pub struct HealthCheckResponse {
     version: String,
 }
 
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CapabilitiesResponse {
+    version: String,
+    features: Vec<String>,
+}
+
+/// Optional features compiled into this build. Names are only ever added, so
+/// a client can gate a call on the name alone.
+const FEATURES: &[&str] = &[
+    "batchUpload",
+    "encodedPaths",
+    "envDefaults",
+    "execPresets",
+    "fileHash",
+    "fileSync",
+    "gitStatus",
+    "gzipUpload",
+    "lineEdit",
+    "listWatch",
+    "logAggregation",
+    "mergeMove",
+    "methodOverride",
+    "mimeDetection",
+    "multipartDownload",
+    "portWait",
+    "shellSessions",
+    "syncStream",
+    "tarDownload",
+    "websocket",
+    "writeAt",
+];
+
 #[derive(Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ReadinessCheckResponse {
@@ -45,3 +78,10 @@ pub async fn readiness_check(
         workspace: workspace_accessible,
     }))
 }
+
+pub async fn capabilities() -> Json<ApiResponse<CapabilitiesResponse>> {
+    Json(ApiResponse::success(CapabilitiesResponse {
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        features: FEATURES.iter().map(|f| f.to_string()).collect(),
+    }))
+}
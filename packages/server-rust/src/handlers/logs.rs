@@ -0,0 +1,218 @@
+use crate::error::AppError;
+use crate::handlers::websocket::{parse_log_entry, ParsedLogEntry};
+use crate::middleware::auth::Caller;
+use crate::response::ApiResponse;
+use crate::state::logs::LogLine;
+use crate::state::AppState;
+use crate::utils::common::format_time;
+use axum::{
+    extract::{Query, State},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+const LOG_LEVELS: &[&str] = &["stdout", "stderr", "system", "unknown"];
+
+#[derive(Deserialize)]
+pub struct AggregateLogsParams {
+    /// Only lines recorded after this time, in milliseconds since the Unix epoch
+    since: Option<u64>,
+    /// Comma-separated levels to keep, e.g. `stdout,stderr`
+    levels: Option<String>,
+    /// Only the last `tail` lines of the merged log
+    tail: Option<usize>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AggregateLogEntry {
+    /// `process` or `session`
+    source_type: &'static str,
+    source_id: String,
+    level: String,
+    content: String,
+    /// Set when the line was cut at `MAX_LOG_LINE_BYTES`; the next entry
+    /// from the same source continues it
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    continued: bool,
+    timestamp_ms: u64,
+    timestamp: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AggregateLogsResponse {
+    logs: Vec<AggregateLogEntry>,
+    count: usize,
+}
+
+/// Log lines of one process or session, oldest first.
+struct LogSource {
+    source_type: &'static str,
+    source_id: String,
+    lines: Vec<LogLine>,
+}
+
+/// Log lines of every process and session the caller owns, interleaved by
+/// the time each line was recorded. Lines recorded in the same millisecond
+/// keep their order within their source.
+pub async fn aggregate_logs(
+    State(state): State<Arc<AppState>>,
+    caller: Caller,
+    Query(params): Query<AggregateLogsParams>,
+) -> Result<Json<ApiResponse<AggregateLogsResponse>>, AppError> {
+    let levels = parse_levels(params.levels.as_deref())?;
+    let since = params.since.unwrap_or(0);
+    let recent = |line: &&LogLine| line.at_ms > since;
+
+    let mut sources = Vec::new();
+    {
+        let processes = state.processes.read().await;
+        for (id, proc) in processes.iter().filter(|(_, p)| caller.owns(&p.owner)) {
+            let logs = proc.logs.read().await;
+            sources.push(LogSource {
+                source_type: "process",
+                source_id: id.clone(),
+                lines: logs.iter().filter(recent).cloned().collect(),
+            });
+        }
+    }
+    {
+        let sessions = state.sessions.read().await;
+        for (id, sess) in sessions.iter().filter(|(_, s)| caller.owns(&s.owner)) {
+            let logs = sess.logs.read().await;
+            sources.push(LogSource {
+                source_type: "session",
+                source_id: id.clone(),
+                lines: logs.iter().filter(recent).cloned().collect(),
+            });
+        }
+    }
+
+    let logs = merge_logs(sources, &levels, params.tail);
+    Ok(Json(ApiResponse::success(AggregateLogsResponse {
+        count: logs.len(),
+        logs,
+    })))
+}
+
+fn parse_levels(levels: Option<&str>) -> Result<Vec<String>, AppError> {
+    let levels: Vec<String> = levels
+        .unwrap_or_default()
+        .split(',')
+        .map(|l| l.trim().to_ascii_lowercase())
+        .filter(|l| !l.is_empty())
+        .collect();
+    if let Some(level) = levels.iter().find(|l| !LOG_LEVELS.contains(&l.as_str())) {
+        return Err(AppError::BadRequest(format!(
+            "Unknown log level: {:?} (expected stdout, stderr, system or unknown)",
+            level
+        )));
+    }
+    Ok(levels)
+}
+
+fn merge_logs(
+    sources: Vec<LogSource>,
+    levels: &[String],
+    tail: Option<usize>,
+) -> Vec<AggregateLogEntry> {
+    let mut logs: Vec<AggregateLogEntry> = sources
+        .into_iter()
+        .flat_map(|source| {
+            let LogSource {
+                source_type,
+                source_id,
+                lines,
+            } = source;
+            lines.into_iter().filter_map(move |line| {
+                let ParsedLogEntry {
+                    level,
+                    content,
+                    continued,
+                } = parse_log_entry(&line.text);
+                if !levels.is_empty() && !levels.contains(&level) {
+                    return None;
+                }
+                Some(AggregateLogEntry {
+                    source_type,
+                    source_id: source_id.clone(),
+                    level,
+                    content,
+                    continued,
+                    timestamp_ms: line.at_ms,
+                    timestamp: format_time(line.at_ms / 1000),
+                })
+            })
+        })
+        .collect();
+    logs.sort_by_key(|entry| entry.timestamp_ms);
+    if let Some(tail) = tail {
+        logs.drain(..logs.len().saturating_sub(tail));
+    }
+    logs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn source(source_type: &'static str, id: &str, lines: &[(u64, &str)]) -> LogSource {
+        LogSource {
+            source_type,
+            source_id: id.to_string(),
+            lines: lines
+                .iter()
+                .map(|&(at_ms, text)| LogLine {
+                    at_ms,
+                    text: text.to_string(),
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_merge_logs() {
+        let sources = || {
+            vec![
+                source(
+                    "process",
+                    "p1",
+                    &[(10, "[stdout] a"), (30, "[stderr] c"), (30, "[stdout]+ d")],
+                ),
+                source("session", "s1", &[(20, "[exec] ls"), (30, "[stdout] e")]),
+            ]
+        };
+        let contents = |logs: &[AggregateLogEntry]| -> Vec<String> {
+            logs.iter().map(|l| l.content.clone()).collect()
+        };
+
+        let logs = merge_logs(sources(), &[], None);
+        assert_eq!(contents(&logs), vec!["a", "Executing: ls", "c", "d", "e"]);
+        assert_eq!(logs[1].source_type, "session");
+        assert_eq!(logs[1].level, "system");
+        assert!(logs[3].continued && !logs[2].continued);
+
+        let stdout = vec!["stdout".to_string()];
+        assert_eq!(
+            contents(&merge_logs(sources(), &stdout, None)),
+            vec!["a", "d", "e"]
+        );
+        assert_eq!(
+            contents(&merge_logs(sources(), &[], Some(2))),
+            vec!["d", "e"]
+        );
+        assert_eq!(merge_logs(sources(), &[], Some(10)).len(), 5);
+    }
+
+    #[test]
+    fn test_parse_levels() {
+        assert!(parse_levels(None).unwrap().is_empty());
+        assert_eq!(
+            parse_levels(Some("stdout, STDERR,")).unwrap(),
+            vec!["stdout", "stderr"]
+        );
+        assert!(parse_levels(Some("debug")).is_err());
+    }
+}
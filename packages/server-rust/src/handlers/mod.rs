@@ -1,5 +1,9 @@
+pub mod config;
+pub mod env;
 pub mod file;
+pub mod git;
 pub mod health;
+pub mod logs;
 pub mod port;
 pub mod process;
 pub mod session;
@@ -1,8 +1,18 @@
+use super::process::parse_signal;
 use crate::error::AppError;
+use crate::middleware::auth::Caller;
+use crate::monitor::port::PortMonitor;
 use crate::response::ApiResponse;
-use axum::Json;
-use serde::Serialize;
+use axum::{
+    extract::{Path, Query},
+    Json,
+};
+use serde::{Deserialize, Serialize};
 use std::sync::Arc;
+use std::time::Duration;
+
+/// Longest `timeout` accepted by `ports/wait`, in seconds
+const MAX_WAIT_SECS: u64 = 600;
 
 #[derive(Serialize)]
 #[serde(rename_all = "camelCase")]
@@ -21,3 +31,106 @@ pub async fn get_ports(
         last_updated_at: last_updated,
     })))
 }
+
+#[derive(Deserialize)]
+pub struct WaitForPortParams {
+    port: u16,
+    /// Seconds to wait before giving up (default: 30)
+    #[serde(default = "default_wait_secs")]
+    timeout: u64,
+}
+
+fn default_wait_secs() -> u64 {
+    30
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WaitForPortResponse {
+    port: u16,
+    waited_ms: u64,
+}
+
+/// Block until `port` is listening, so startup scripts don't have to poll `/ports`.
+pub async fn wait_for_port(
+    axum::extract::State(state): axum::extract::State<Arc<crate::state::AppState>>,
+    Query(params): Query<WaitForPortParams>,
+) -> Result<Json<ApiResponse<WaitForPortResponse>>, AppError> {
+    if params.timeout > MAX_WAIT_SECS {
+        return Err(AppError::BadRequest(format!(
+            "timeout must be at most {} seconds",
+            MAX_WAIT_SECS
+        )));
+    }
+
+    let start = std::time::Instant::now();
+    let open = state
+        .port_monitor
+        .wait_for_port(params.port, Duration::from_secs(params.timeout))
+        .await?;
+    if !open {
+        return Err(AppError::OperationError(
+            format!(
+                "Timed out after {}s waiting for port {}",
+                params.timeout, params.port
+            ),
+            serde_json::json!({ "port": params.port, "timeout": params.timeout }),
+        ));
+    }
+
+    Ok(Json(ApiResponse::success(WaitForPortResponse {
+        port: params.port,
+        waited_ms: crate::utils::common::elapsed_ms(start),
+    })))
+}
+
+#[derive(Deserialize)]
+pub struct KillPortParams {
+    /// SIGTERM, SIGINT, SIGHUP or SIGKILL (default)
+    signal: Option<String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KillPortResponse {
+    port: u16,
+    pids: Vec<u32>,
+    signal: String,
+}
+
+/// Signal whatever process is listening on `port`, so a stale dev server can
+/// be cleared without knowing its PID. The listener may belong to any token,
+/// so only the admin may do this.
+pub async fn kill_port(
+    caller: Caller,
+    Path(port): Path<u16>,
+    Query(params): Query<KillPortParams>,
+) -> Result<Json<ApiResponse<KillPortResponse>>, AppError> {
+    caller.check_admin("kill processes by port")?;
+    let signal = parse_signal(params.signal.as_deref().unwrap_or("SIGKILL"));
+    let pids = PortMonitor::find_owners(port).await;
+    if pids.is_empty() {
+        return Err(AppError::NotFound(format!(
+            "No process is listening on port {}",
+            port
+        )));
+    }
+    if pids.contains(&std::process::id()) {
+        return Err(AppError::Forbidden(format!(
+            "Port {} belongs to the server itself",
+            port
+        )));
+    }
+
+    for &pid in &pids {
+        nix::sys::signal::kill(nix::unistd::Pid::from_raw(pid as i32), signal).map_err(|e| {
+            AppError::InternalServerError(format!("Failed to signal process {}: {}", pid, e))
+        })?;
+    }
+
+    Ok(Json(ApiResponse::success(KillPortResponse {
+        port,
+        pids,
+        signal: signal.as_str().to_string(),
+    })))
+}
@@ -1,7 +1,14 @@
+use super::env::with_env_defaults;
+use crate::config::ExecPreset;
 use crate::error::AppError;
+use crate::middleware::auth::Caller;
 use crate::response::ApiResponse;
-use crate::state::{process::ProcessInfo, AppState};
-use crate::utils::path::validate_path;
+use crate::state::persist::FINISHED_RETENTION;
+use crate::state::{process::ProcessInfo, workspace::Workspace, AppState};
+use crate::utils::common::{read_line_capped, CONTINUATION_MARK};
+use crate::utils::diagnostics::{Diagnostic, DiagnosticParser, ParserKind};
+use crate::utils::path::{ensure_directory, expand_path, normalize_path, validate_path};
+use crate::utils::validate::{validate, Validate, Validator};
 use axum::response::sse::{Event, Sse};
 use axum::{
     extract::{Path, Query, State},
@@ -15,17 +22,51 @@ use std::io::ErrorKind;
 use std::os::unix::process::ExitStatusExt;
 use std::process::Stdio;
 use std::sync::Arc;
-use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::io::{AsyncReadExt, BufReader};
 use tokio::process::Command;
 use tokio::time::{timeout, Duration};
+use tokio_stream::wrappers::errors::BroadcastStreamRecvError;
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Clone)]
 pub struct ExecProcessRequest {
     command: String,
     args: Option<Vec<String>>,
     cwd: Option<String>,
     env: Option<std::collections::HashMap<String, String>>,
     timeout: Option<u64>,
+    #[serde(default, rename = "expandCwd")]
+    expand_cwd: bool,
+    /// Niceness (-20..19, clamped); defaults to `DEFAULT_PROCESS_NICE`
+    nice: Option<i32>,
+    /// Kill the process once it has gone this long without any output
+    #[serde(rename = "idleTimeoutSecs")]
+    idle_timeout_secs: Option<u64>,
+    /// Extract diagnostics from the output: `cargo`, `tsc` or `eslint`
+    parser: Option<String>,
+    /// Exit codes that mark the process `completed` rather than `failed`;
+    /// defaults to `[0]`
+    #[serde(rename = "successExitCodes")]
+    success_exit_codes: Option<Vec<i32>>,
+    /// Also write the raw stdout and stderr to this file (replaced if it
+    /// exists), which keeps everything the in-memory log drops
+    #[serde(rename = "outputFile")]
+    output_file: Option<String>,
+}
+
+impl Validate for ExecProcessRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.command(&self.command, self.args.as_deref());
+        v.env(self.env.as_ref());
+        v.timeout("timeout", self.timeout, false);
+        v.timeout("idleTimeoutSecs", self.idle_timeout_secs, true);
+        if let Some(codes) = &self.success_exit_codes {
+            v.check(
+                !codes.is_empty() && codes.iter().all(|c| (0..=255).contains(c)),
+                "successExitCodes",
+                "must list at least one exit code, each from 0 to 255",
+            );
+        }
+    }
 }
 
 #[derive(Serialize)]
@@ -34,6 +75,8 @@ pub struct ExecProcessResponse {
     process_id: String,
     pid: Option<u32>,
     process_status: String,
+    /// Pass to `/process/{id}/cancel` to cancel this process
+    cancel_token: String,
 }
 
 #[derive(Serialize)]
@@ -42,10 +85,25 @@ pub struct ListProcessesResponse {
     processes: Vec<crate::state::process::ProcessStatus>,
 }
 
+#[derive(Deserialize)]
+pub struct BatchStatusRequest {
+    ids: Vec<String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BatchStatusResponse {
+    /// Status per requested id; `null` for ids that are not (or no longer) known
+    statuses: std::collections::BTreeMap<String, Option<crate::state::process::ProcessStatus>>,
+}
+
 #[derive(Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ProcessOperationResponse {
     success: bool,
+    /// With `escalate=true`: whether SIGKILL had to follow the initial signal
+    #[serde(skip_serializing_if = "Option::is_none")]
+    escalated: Option<bool>,
 }
 
 #[derive(Serialize)]
@@ -69,13 +127,18 @@ pub struct StreamStartEvent {
 pub struct StreamOutputEvent {
     output: String,
     timestamp: String,
+    /// Set when the line was cut at `MAX_LOG_LINE_BYTES`; the next event continues it
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    continued: bool,
 }
 
 #[derive(Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct StreamCompleteEvent {
     exit_code: Option<i32>,
-    duration: i64,
+    /// Same as `duration_ms`, kept for older clients
+    duration: u64,
+    duration_ms: u64,
     timestamp: String,
 }
 
@@ -83,14 +146,41 @@ pub struct StreamCompleteEvent {
 #[serde(rename_all = "camelCase")]
 pub struct StreamErrorEvent {
     error: String,
-    duration_ms: i64,
+    duration_ms: u64,
     timestamp: String,
 }
 
 pub async fn exec_process(
     State(state): State<Arc<AppState>>,
-    Json(req): Json<ExecProcessRequest>,
+    Caller(owner): Caller,
+    Workspace(workspace): Workspace,
+    Json(mut req): Json<ExecProcessRequest>,
 ) -> Result<Json<ApiResponse<ExecProcessResponse>>, AppError> {
+    validate(&req)?;
+    state
+        .config
+        .check_command(&command_line(&req.command, req.args.as_deref()))?;
+    // Kept before the env defaults are merged, so a restart picks up the
+    // defaults current at that time
+    let spec = ExecSpec {
+        request: req.clone(),
+        workspace: workspace.clone(),
+    };
+    req.env = with_env_defaults(&state, req.env.take()).await;
+    let parser = req
+        .parser
+        .as_deref()
+        .map(|name| {
+            ParserKind::from_name(name).ok_or_else(|| {
+                AppError::BadRequest(format!(
+                    "Unknown parser: {} (expected one of {})",
+                    name,
+                    ParserKind::NAMES.join(", ")
+                ))
+            })
+        })
+        .transpose()?;
+
     let mut cmd = if let Some(args) = &req.args {
         let mut c = Command::new(&req.command);
         c.args(args);
@@ -110,58 +200,344 @@ pub async fn exec_process(
         }
     };
 
-    if let Some(cwd) = &req.cwd {
-        let valid_cwd = validate_path(&state.config.workspace_path, cwd)?;
-        cmd.current_dir(valid_cwd);
-    }
+    let valid_cwd = resolve_cwd(
+        &workspace,
+        state.config.default_cwd.as_deref(),
+        req.cwd.as_deref(),
+        req.expand_cwd,
+        req.env.as_ref(),
+    )?;
+    cmd.current_dir(valid_cwd);
 
     if let Some(env) = &req.env {
         cmd.envs(env);
     }
+    if let Some(nice) = req.nice.or(state.config.default_process_nice) {
+        set_niceness(&mut cmd, nice);
+    }
+
+    let output_file = match &req.output_file {
+        Some(path) => {
+            let path = validate_path(&workspace, path)?;
+            if path.is_dir() {
+                return Err(AppError::IsADirectory(
+                    "outputFile is a directory, not a file".to_string(),
+                ));
+            }
+            if let Some(parent) = path.parent() {
+                ensure_directory(parent).await?;
+            }
+            Some(path)
+        }
+        None => None,
+    };
+
+    let process_id = crate::utils::common::generate_id();
+    let options = BackgroundOptions {
+        time_limit: Some(Duration::from_secs(req.timeout.unwrap_or(7200))), // Default 2h
+        idle_limit: req
+            .idle_timeout_secs
+            .filter(|&secs| secs > 0)
+            .map(Duration::from_secs),
+        parser,
+        success_exit_codes: req.success_exit_codes.clone(),
+        output_file,
+        owner: Some(owner),
+    };
+    let (pid, cancel_token) = spawn_background(
+        &state,
+        cmd,
+        process_id.clone(),
+        req.command.clone(),
+        options,
+    )
+    .await
+    .map_err(|e| {
+        // Return error response instead of propagating error (matching Go behavior)
+        AppError::OperationError(
+            format!("Failed to spawn process: {}", e),
+            serde_json::Value::Object(serde_json::Map::new()),
+        )
+    })?;
+
+    if let Some(proc) = state.processes.write().await.get_mut(&process_id) {
+        proc.exec_spec = Some(Arc::new(spec));
+    }
+
+    Ok(Json(ApiResponse::success(ExecProcessResponse {
+        process_id,
+        pid,
+        process_status: "running".to_string(),
+        cancel_token,
+    })))
+}
+
+/// What `process/exec` was asked to run, and in which workspace.
+pub struct ExecSpec {
+    request: ExecProcessRequest,
+    workspace: std::path::PathBuf,
+}
+
+/// How long a restart waits for the old process to be reaped after it has
+/// been killed
+const RESTART_REAP_WAIT: Duration = Duration::from_secs(2);
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RestartProcessParams {
+    /// Stop the old process first if it is still running
+    #[serde(default = "default_restart_kill")]
+    kill: bool,
+    grace_ms: Option<u64>,
+}
+
+fn default_restart_kill() -> bool {
+    true
+}
+
+/// Start a fresh process from the request that started `id`, as if it had
+/// been sent to `process/exec` again. A still-running original is sent
+/// SIGTERM, then SIGKILL after `graceMs`, unless `kill=false`.
+pub async fn restart_process(
+    State(state): State<Arc<AppState>>,
+    caller: Caller,
+    Path(id): Path<String>,
+    Query(params): Query<RestartProcessParams>,
+) -> Result<Json<ApiResponse<ExecProcessResponse>>, AppError> {
+    // The new process keeps the original's owner, whoever restarts it
+    let (spec, running, mut exited, owner) = {
+        let processes = state.processes.read().await;
+        let proc = processes
+            .get(&id)
+            .ok_or_else(|| AppError::NotFound("Process not found".to_string()))?;
+        caller.check_owner(&proc.owner, "Process")?;
+        let spec = proc.exec_spec.clone().ok_or_else(|| {
+            AppError::BadRequest(
+                "Process cannot be restarted: it was not started by process/exec since the server started"
+                    .to_string(),
+            )
+        })?;
+        (
+            spec,
+            proc.status == "running",
+            proc.exited.subscribe(),
+            Caller(proc.owner.clone()),
+        )
+    };
+
+    if running && params.kill {
+        let mut kill_params =
+            std::collections::HashMap::from([("escalate".to_string(), "true".to_string())]);
+        if let Some(ms) = params.grace_ms {
+            kill_params.insert("graceMs".to_string(), ms.to_string());
+        }
+        match kill_process(State(state.clone()), caller, Path(id), Query(kill_params)).await {
+            // It exited on its own in the meantime
+            Ok(_) | Err(AppError::Conflict(_)) => {}
+            Err(e) => return Err(e),
+        }
+        let _ = timeout(RESTART_REAP_WAIT, exited.wait_for(|done| *done)).await;
+    }
+
+    exec_process(
+        State(state),
+        owner,
+        Workspace(spec.workspace.clone()),
+        Json(spec.request.clone()),
+    )
+    .await
+}
 
+/// Per-call changes to a preset. `args`, `cwd`, `timeout` and `nice` replace
+/// the preset's values; `env` is merged over the preset's, overriding keys it shares.
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct PresetOverrides {
+    args: Option<Vec<String>>,
+    cwd: Option<String>,
+    env: Option<std::collections::HashMap<String, String>>,
+    timeout: Option<u64>,
+    nice: Option<i32>,
+}
+
+#[derive(Deserialize)]
+pub struct RunPresetRequest {
+    name: String,
+    #[serde(default)]
+    overrides: PresetOverrides,
+}
+
+/// Run a command template from `EXEC_PRESETS` as if it had been sent to
+/// `process/exec`, including the allow/deny checks.
+pub async fn run_preset(
+    State(state): State<Arc<AppState>>,
+    caller: Caller,
+    Workspace(workspace): Workspace,
+    Json(req): Json<RunPresetRequest>,
+) -> Result<Json<ApiResponse<ExecProcessResponse>>, AppError> {
+    let preset = state
+        .config
+        .exec_presets
+        .get(&req.name)
+        .ok_or_else(|| AppError::NotFound(format!("Unknown preset: {}", req.name)))?;
+    let request = apply_overrides(preset, req.overrides);
+    exec_process(
+        State(state.clone()),
+        caller,
+        Workspace(workspace),
+        Json(request),
+    )
+    .await
+}
+
+fn apply_overrides(preset: &ExecPreset, overrides: PresetOverrides) -> ExecProcessRequest {
+    let env = match (preset.env.clone(), overrides.env) {
+        (Some(mut env), Some(extra)) => {
+            env.extend(extra);
+            Some(env)
+        }
+        (env, extra) => extra.or(env),
+    };
+    ExecProcessRequest {
+        command: preset.command.clone(),
+        args: overrides.args.or_else(|| preset.args.clone()),
+        cwd: overrides.cwd.or_else(|| preset.cwd.clone()),
+        env,
+        timeout: overrides.timeout.or(preset.timeout),
+        expand_cwd: false,
+        nice: overrides.nice,
+        idle_timeout_secs: None,
+        parser: None,
+        success_exit_codes: None,
+        output_file: None,
+    }
+}
+
+/// How `spawn_background` supervises a process.
+#[derive(Default)]
+pub(crate) struct BackgroundOptions {
+    /// Kill the process once this much time has passed
+    pub time_limit: Option<Duration>,
+    /// Kill the process once it has produced no output for this long
+    pub idle_limit: Option<Duration>,
+    /// Collect diagnostics found in either stream
+    pub parser: Option<ParserKind>,
+    /// Exit codes counted as `completed`; `None` means only 0
+    pub success_exit_codes: Option<Vec<i32>>,
+    /// Receives the raw output of both streams as well as the logs; opened
+    /// before the process starts but truncated only once it has
+    pub output_file: Option<std::path::PathBuf>,
+    /// Label of the token that started it; the admin when unset
+    pub owner: Option<String>,
+}
+
+/// Spawn `cmd` as a background process registered under `process_id`, with
+/// its stdout and stderr captured into the process logs and supervised as
+/// `options` say; the entry is dropped once retention expires. Returns the
+/// pid and the process's cancel token.
+pub(crate) async fn spawn_background(
+    state: &Arc<AppState>,
+    mut cmd: Command,
+    process_id: String,
+    command: String,
+    options: BackgroundOptions,
+) -> std::io::Result<(Option<u32>, String)> {
+    let BackgroundOptions {
+        time_limit,
+        idle_limit,
+        parser,
+        success_exit_codes,
+        output_file,
+        owner,
+    } = options;
+    let success_exit_codes = success_exit_codes.unwrap_or_else(|| vec![0]);
     cmd.stdout(Stdio::piped());
     cmd.stderr(Stdio::piped());
 
-    let child_result = cmd.spawn();
+    // Opened up front so a file that cannot be written never leaves a
+    // process running, but left intact until the command has started
+    let output_file = match output_file {
+        Some(path) => {
+            let existed = tokio::fs::try_exists(&path).await.unwrap_or(true);
+            let file = tokio::fs::OpenOptions::new()
+                .write(true)
+                .create(true)
+                .truncate(false)
+                .open(&path)
+                .await
+                .map_err(|e| {
+                    std::io::Error::new(e.kind(), format!("cannot create outputFile: {}", e))
+                })?;
+            Some((path, existed, file))
+        }
+        None => None,
+    };
 
-    let mut child = match child_result {
-        Ok(c) => c,
+    let mut child = match cmd.spawn() {
+        Ok(child) => child,
         Err(e) => {
-            // Return error response instead of propagating error (matching Go behavior)
-            return Err(AppError::OperationError(
-                format!("Failed to spawn process: {}", e),
-                serde_json::Value::Object(serde_json::Map::new()),
-            ));
+            if let Some((path, false, _)) = &output_file {
+                let _ = tokio::fs::remove_file(path).await;
+            }
+            return Err(e);
         }
     };
     let pid = child.id();
-    let process_id = crate::utils::common::generate_id();
+
+    // The process is running now, so a file that cannot be emptied only
+    // stops the copy on disk, as a failed write would
+    let mut output_error = None;
+    let output_file = match output_file {
+        Some((_, _, file)) => match file.set_len(0).await {
+            Ok(()) => Some(Arc::new(tokio::sync::Mutex::new(file))),
+            Err(e) => {
+                output_error = Some(format!("[system] Failed to write outputFile: {}", e));
+                None
+            }
+        },
+        None => None,
+    };
 
     let stdout = child.stdout.take().expect("stdout piped");
     let stderr = child.stderr.take().expect("stderr piped");
 
-    let (tx, _rx) = tokio::sync::broadcast::channel(100);
+    let (tx, _rx) = tokio::sync::broadcast::channel(state.config.log_broadcast_capacity);
 
-    let process_info = ProcessInfo::new(
-        process_id.clone(),
-        pid,
-        req.command.clone(),
-        Some(child),
-        tx.clone(),
-    );
+    let mut process_info =
+        ProcessInfo::new(process_id.clone(), pid, command, Some(child), tx.clone());
+    process_info.parser = parser;
+    if let Some(owner) = owner {
+        process_info.owner = owner;
+    }
+    if let Some(message) = output_error {
+        process_info.logs.write().await.push(message);
+    }
+    let cancel_token = process_info.cancel_token.clone();
 
     {
         let mut processes = state.processes.write().await;
         processes.insert(process_id.clone(), process_info);
     }
+    let _ = state.process_created.send(process_id.clone());
+    state.mark_registry_changed();
 
     let state_clone = state.clone();
     let pid_clone = process_id.clone();
     let tx_clone = tx.clone();
+    let output_clone = output_file.clone();
 
     tokio::spawn(async move {
         let reader = BufReader::new(stdout);
-        pump_log(reader, pid_clone, state_clone, tx_clone, "[stdout]").await;
+        pump_log(
+            reader,
+            pid_clone,
+            state_clone,
+            tx_clone,
+            "[stdout]",
+            parser,
+            output_clone,
+        )
+        .await;
     });
 
     let state_clone_err = state.clone();
@@ -176,13 +552,15 @@ pub async fn exec_process(
             state_clone_err,
             tx_clone_err,
             "[stderr]",
+            parser,
+            output_file,
         )
         .await;
     });
 
     let state_clone_cleanup = state.clone();
     let pid_clone_cleanup = process_id.clone();
-    let timeout_val = req.timeout;
+    let output = tx.subscribe();
 
     tokio::spawn(async move {
         // Take the child process out of the state to wait on it
@@ -196,23 +574,48 @@ pub async fn exec_process(
         };
 
         if let Some(mut child) = child {
-            let timeout_duration = Duration::from_secs(timeout_val.unwrap_or(7200)); // Default 2h
-
-            let wait_result = match timeout(timeout_duration, child.wait()).await {
-                Ok(res) => res,
-                Err(_) => {
-                    let _ = child.start_kill();
-                    child.wait().await
+            let deadline = async {
+                match time_limit {
+                    Some(limit) => tokio::time::sleep(limit).await,
+                    None => std::future::pending().await,
                 }
             };
+            let idle = async {
+                match idle_limit {
+                    Some(limit) => wait_for_silence(output, limit).await,
+                    None => std::future::pending().await,
+                }
+            };
+            let kill_reason = tokio::select! {
+                _ = child.wait() => None,
+                _ = deadline => Some(format!(
+                    "Timed out after {}s",
+                    time_limit.unwrap_or_default().as_secs()
+                )),
+                _ = idle => Some(format!(
+                    "No output for {}s",
+                    idle_limit.unwrap_or_default().as_secs()
+                )),
+            };
+            if kill_reason.is_some() {
+                let _ = child.start_kill();
+            }
+            // Reaps the child, or returns the status already collected above
+            let wait_result = child.wait().await;
 
             // Update status
             {
                 let mut processes = state_clone_cleanup.processes.write().await;
                 if let Some(proc) = processes.get_mut(&pid_clone_cleanup) {
+                    let cancelled = proc.status == "cancelled";
                     match wait_result {
                         Ok(status) => {
-                            if status.success() {
+                            if cancelled {
+                                // Keep the status `cancel_process` set
+                            } else if status
+                                .code()
+                                .is_some_and(|code| success_exit_codes.contains(&code))
+                            {
                                 proc.status = "completed".to_string();
                             } else if status.signal().is_some() {
                                 proc.status = "killed".to_string();
@@ -221,38 +624,113 @@ pub async fn exec_process(
                             }
                             proc.exit_code =
                                 status.code().or_else(|| status.signal().map(|s| 128 + s));
+                            if !cancelled && status.signal().is_some() {
+                                proc.kill_reason = kill_reason;
+                            }
                         }
+                        Err(_) if cancelled => {}
                         Err(_) => {
                             proc.status = "failed".to_string();
                         }
                     }
                     proc.end_time = Some(std::time::SystemTime::now());
+                    proc.exited.send_replace(true);
                 }
             }
+            state_clone_cleanup.mark_registry_changed();
 
             // Cleanup logs and status after 4 hours
-            tokio::time::sleep(Duration::from_secs(4 * 60 * 60)).await;
+            tokio::time::sleep(FINISHED_RETENTION).await;
 
             let mut processes = state_clone_cleanup.processes.write().await;
             processes.remove(&pid_clone_cleanup);
+            state_clone_cleanup.mark_registry_changed();
         }
     });
 
-    Ok(Json(ApiResponse::success(ExecProcessResponse {
-        process_id,
-        pid,
-        process_status: "running".to_string(),
-    })))
+    Ok((pid, cancel_token))
+}
+
+/// Process ID `STARTUP_COMMAND` is registered under, so its output is at
+/// `/process/startup/logs`.
+pub const STARTUP_PROCESS_ID: &str = "startup";
+
+/// Run `STARTUP_COMMAND` through `sh -c` in the default working directory,
+/// without a time limit since it often starts a long-lived dev server.
+/// Failures are logged and never stop the server.
+pub async fn run_startup_command(state: Arc<AppState>) {
+    let Some(command) = state.config.startup_command.clone() else {
+        return;
+    };
+
+    let mut cmd = Command::new("sh");
+    cmd.arg("-c").arg(&command);
+    cmd.current_dir(match &state.config.default_cwd {
+        Some(dir) => normalize_path(&state.config.workspace_path.join(dir)),
+        None => state.config.workspace_path.clone(),
+    });
+    if let Some(nice) = state.config.default_process_nice {
+        set_niceness(&mut cmd, nice);
+    }
+
+    let id = STARTUP_PROCESS_ID.to_string();
+    match spawn_background(&state, cmd, id, command, BackgroundOptions::default()).await {
+        Ok((pid, _)) if !state.config.quiet => println!(
+            "Startup command running as process {} (pid {})",
+            STARTUP_PROCESS_ID,
+            pid.unwrap_or_default()
+        ),
+        Ok(_) => {}
+        Err(e) => {
+            eprintln!("Startup command failed to start: {}", e);
+            return;
+        }
+    }
+
+    let Some(mut exited) = state
+        .processes
+        .read()
+        .await
+        .get(STARTUP_PROCESS_ID)
+        .map(|p| p.exited.subscribe())
+    else {
+        return;
+    };
+    let _ = exited.wait_for(|done| *done).await;
+    if let Some(p) = state.processes.read().await.get(STARTUP_PROCESS_ID) {
+        if p.status != "completed" {
+            eprintln!(
+                "Startup command {} with exit code {}; see /api/v1/process/{}/logs",
+                p.status,
+                p.exit_code.map_or("unknown".to_string(), |c| c.to_string()),
+                STARTUP_PROCESS_ID
+            );
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct ProcessStatusParams {
+    /// Include `/proc` resource usage for running processes
+    #[serde(default)]
+    stats: bool,
 }
 
 pub async fn list_processes(
     State(state): State<Arc<AppState>>,
+    caller: Caller,
+    Query(params): Query<ProcessStatusParams>,
 ) -> Result<Json<ApiResponse<ListProcessesResponse>>, AppError> {
-    let processes = state.processes.read().await;
     let mut result = Vec::new();
+    {
+        let processes = state.processes.read().await;
+        for proc in processes.values().filter(|p| caller.owns(&p.owner)) {
+            result.push(proc.to_status());
+        }
+    }
 
-    for proc in processes.values() {
-        result.push(proc.to_status());
+    if params.stats {
+        result = futures::future::join_all(result.into_iter().map(|s| s.with_stats())).await;
     }
 
     Ok(Json(ApiResponse::success(ListProcessesResponse {
@@ -260,65 +738,367 @@ pub async fn list_processes(
     })))
 }
 
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ZombiesResponse {
+    zombies: Vec<crate::monitor::zombies::Zombie>,
+    /// Children nothing waited for that the reaper collected, newest last
+    reaped: Vec<crate::monitor::zombies::ReapedChild>,
+}
+
+/// Defunct processes in `/proc`, for diagnosing PID exhaustion. Zombies that
+/// are the server's own children are reaped in the background once they have
+/// lingered; the rest belong to parents that have yet to wait for them.
+/// Tokens other than the admin see only those below their own processes and
+/// sessions.
+pub async fn list_zombies(
+    State(state): State<Arc<AppState>>,
+    caller: Caller,
+) -> Json<ApiResponse<ZombiesResponse>> {
+    let mut zombies = crate::monitor::zombies::list_zombies().await;
+    let mut reaped = state.zombie_reaper.reaped().await;
+    if let Some(visible) = visible_pids(&state, &caller).await {
+        zombies.retain(|z| visible.contains(&z.pid));
+        reaped.retain(|r| visible.contains(&r.pid));
+    }
+    Json(ApiResponse::success(ZombiesResponse { zombies, reaped }))
+}
+
+#[derive(Deserialize)]
+pub struct ProcessTreeParams {
+    /// Root of the tree; defaults to the server itself
+    pid: Option<u32>,
+}
+
+/// Every descendant of a process as seen in `/proc`, including those the API
+/// did not spawn directly, which `list` cannot show. Tokens other than the
+/// admin see only their own processes and sessions, below the ancestors that
+/// lead to them.
+pub async fn process_tree(
+    State(state): State<Arc<AppState>>,
+    caller: Caller,
+    Query(params): Query<ProcessTreeParams>,
+) -> Result<Json<ApiResponse<crate::monitor::tree::ProcessNode>>, AppError> {
+    let root = params.pid.unwrap_or_else(std::process::id);
+    let mut tree = crate::monitor::tree::process_tree(root).await;
+    if let Some(visible) = visible_pids(&state, &caller).await {
+        tree = tree.and_then(|t| crate::monitor::tree::retain_visible(t, &visible));
+    }
+    let tree = tree.ok_or_else(|| AppError::NotFound(format!("Process {} not found", root)))?;
+    Ok(Json(ApiResponse::success(tree)))
+}
+
+/// PIDs of the running processes and active sessions `caller` owns, with
+/// their descendants; `None` for the admin, who sees every process.
+async fn visible_pids(state: &AppState, caller: &Caller) -> Option<std::collections::HashSet<u32>> {
+    if caller.is_admin() {
+        return None;
+    }
+    let mut roots: Vec<u32> = state
+        .processes
+        .read()
+        .await
+        .values()
+        .filter(|p| p.status == "running" && caller.owns(&p.owner))
+        .filter_map(|p| p.pid)
+        .collect();
+    roots.extend(
+        state
+            .sessions
+            .read()
+            .await
+            .values()
+            .filter(|s| s.status == "active" && caller.owns(&s.owner))
+            .filter_map(|s| s.pid),
+    );
+    Some(crate::monitor::tree::subtree_pids(&roots).await)
+}
+
 pub async fn get_process_status(
     State(state): State<Arc<AppState>>,
+    caller: Caller,
     Path(id): Path<String>,
+    Query(params): Query<ProcessStatusParams>,
 ) -> Result<Json<ApiResponse<crate::state::process::ProcessStatus>>, AppError> {
-    let processes = state.processes.read().await;
-    let proc = processes
-        .get(&id)
-        .ok_or_else(|| AppError::NotFound("Process not found".to_string()))?;
+    let status = {
+        let processes = state.processes.read().await;
+        let proc = processes
+            .get(&id)
+            .ok_or_else(|| AppError::NotFound("Process not found".to_string()))?;
+        caller.check_owner(&proc.owner, "Process")?;
+        proc.to_status()
+    };
+
+    let status = if params.stats {
+        status.with_stats().await
+    } else {
+        status
+    };
 
-    Ok(Json(ApiResponse::success(proc.to_status())))
+    Ok(Json(ApiResponse::success(status)))
 }
 
-pub async fn kill_process(
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProcessEnvResponse {
+    process_id: String,
+    pid: u32,
+    env: std::collections::BTreeMap<String, String>,
+}
+
+/// The environment a running process actually sees, read from `/proc`.
+pub async fn get_process_env(
     State(state): State<Arc<AppState>>,
+    caller: Caller,
     Path(id): Path<String>,
-    Query(params): Query<std::collections::HashMap<String, String>>,
-) -> Result<Json<ApiResponse<ProcessOperationResponse>>, AppError> {
-    let mut processes = state.processes.write().await;
-    let proc = processes
-        .get_mut(&id)
-        .ok_or_else(|| AppError::NotFound("Process not found".to_string()))?;
+) -> Result<Json<ApiResponse<ProcessEnvResponse>>, AppError> {
+    let (pid, running) = {
+        let processes = state.processes.read().await;
+        let proc = processes
+            .get(&id)
+            .ok_or_else(|| AppError::NotFound("Process not found".to_string()))?;
+        caller.check_owner(&proc.owner, "Process")?;
+        (proc.pid, proc.status == "running")
+    };
+    let pid = pid
+        .filter(|_| running)
+        .ok_or_else(|| AppError::NotFound("Process has exited".to_string()))?;
 
-    // Check if process is running
-    if proc.status != "running" {
-        return Err(AppError::Conflict("Process is not running".to_string()));
-    }
+    let env = crate::monitor::proc_stats::read_environ(pid)
+        .await
+        .ok_or_else(|| AppError::NotFound(format!("Environment of pid {} is not readable", pid)))?;
+
+    Ok(Json(ApiResponse::success(ProcessEnvResponse {
+        process_id: id,
+        pid,
+        env,
+    })))
+}
+
+/// Look up several processes with a single read of the store. Processes of
+/// other tokens map to `null`, like unknown ids.
+pub async fn get_process_statuses(
+    State(state): State<Arc<AppState>>,
+    caller: Caller,
+    Query(params): Query<ProcessStatusParams>,
+    Json(req): Json<BatchStatusRequest>,
+) -> Result<Json<ApiResponse<BatchStatusResponse>>, AppError> {
+    let found: Vec<(String, Option<crate::state::process::ProcessStatus>)> = {
+        let processes = state.processes.read().await;
+        req.ids
+            .into_iter()
+            .map(|id| {
+                let status = processes
+                    .get(&id)
+                    .filter(|p| caller.owns(&p.owner))
+                    .map(ProcessInfo::to_status);
+                (id, status)
+            })
+            .collect()
+    };
+
+    let statuses = if params.stats {
+        futures::future::join_all(found.into_iter().map(|(id, status)| async move {
+            match status {
+                Some(status) => (id, Some(status.with_stats().await)),
+                None => (id, None),
+            }
+        }))
+        .await
+    } else {
+        found
+    };
+
+    Ok(Json(ApiResponse::success(BatchStatusResponse {
+        statuses: statuses.into_iter().collect(),
+    })))
+}
 
-    let signal_str = params
-        .get("signal")
-        .map(|s| s.as_str())
-        .unwrap_or("SIGKILL");
-    let signal = match signal_str {
+/// Map a `signal` query value to a signal; anything unrecognised is SIGKILL.
+pub(crate) fn parse_signal(name: &str) -> nix::sys::signal::Signal {
+    match name {
         "SIGTERM" => nix::sys::signal::Signal::SIGTERM,
         "SIGINT" => nix::sys::signal::Signal::SIGINT,
         "SIGHUP" => nix::sys::signal::Signal::SIGHUP,
         _ => nix::sys::signal::Signal::SIGKILL,
+    }
+}
+
+/// Signal a running process (SIGKILL unless `signal` says otherwise).
+///
+/// With `escalate=true` the initial signal defaults to SIGTERM; if the process
+/// has not exited after `graceMs` (default 5000) it is sent SIGKILL.
+pub async fn kill_process(
+    State(state): State<Arc<AppState>>,
+    caller: Caller,
+    Path(id): Path<String>,
+    Query(params): Query<std::collections::HashMap<String, String>>,
+) -> Result<Json<ApiResponse<ProcessOperationResponse>>, AppError> {
+    let escalate = params.get("escalate").is_some_and(|v| v == "true");
+    let grace = match params.get("graceMs") {
+        Some(ms) => Duration::from_millis(
+            ms.parse()
+                .map_err(|_| AppError::BadRequest(format!("Invalid graceMs: {}", ms)))?,
+        ),
+        None => Duration::from_millis(5000),
     };
 
-    if let Some(pid) = proc.pid {
+    let (pid, mut exited) = {
+        let mut processes = state.processes.write().await;
+        let proc = processes
+            .get_mut(&id)
+            .ok_or_else(|| AppError::NotFound("Process not found".to_string()))?;
+        caller.check_owner(&proc.owner, "Process")?;
+
+        // Check if process is running
+        if proc.status != "running" {
+            return Err(AppError::Conflict("Process is not running".to_string()));
+        }
+
+        let default_signal = if escalate { "SIGTERM" } else { "SIGKILL" };
+        let signal_str = params
+            .get("signal")
+            .map(|s| s.as_str())
+            .unwrap_or(default_signal);
+        let signal = parse_signal(signal_str);
+
+        let Some(pid) = proc.pid else {
+            return Err(AppError::NotFound(
+                "Process PID not found (process might have exited)".to_string(),
+            ));
+        };
         nix::sys::signal::kill(nix::unistd::Pid::from_raw(pid as i32), signal).map_err(|e| {
             AppError::InternalServerError(format!("Failed to signal process: {}", e))
         })?;
 
         if signal == nix::sys::signal::Signal::SIGKILL {
             proc.status = "killed".to_string();
+            state.mark_registry_changed();
         }
-    } else {
+        if !escalate || signal == nix::sys::signal::Signal::SIGKILL {
+            return Ok(Json(ApiResponse::success(ProcessOperationResponse {
+                success: true,
+                escalated: escalate.then_some(false),
+            })));
+        }
+        (pid, proc.exited.subscribe())
+    };
+
+    // The waiter task records the final status before flipping `exited`
+    if timeout(grace, exited.wait_for(|done| *done)).await.is_ok() {
+        return Ok(Json(ApiResponse::success(ProcessOperationResponse {
+            success: true,
+            escalated: Some(false),
+        })));
+    }
+
+    let mut processes = state.processes.write().await;
+    if let Some(proc) = processes.get_mut(&id) {
+        if proc.status == "running" {
+            nix::sys::signal::kill(
+                nix::unistd::Pid::from_raw(pid as i32),
+                nix::sys::signal::Signal::SIGKILL,
+            )
+            .map_err(|e| {
+                AppError::InternalServerError(format!("Failed to signal process: {}", e))
+            })?;
+            proc.status = "killed".to_string();
+            state.mark_registry_changed();
+        }
+    }
+
+    Ok(Json(ApiResponse::success(ProcessOperationResponse {
+        success: true,
+        escalated: Some(true),
+    })))
+}
+
+#[derive(Deserialize)]
+pub struct CancelProcessParams {
+    token: String,
+}
+
+/// Cancel a running process on behalf of the client that started it: the
+/// process is sent SIGKILL and reported as `cancelled` rather than `killed`.
+pub async fn cancel_process(
+    State(state): State<Arc<AppState>>,
+    caller: Caller,
+    Path(id): Path<String>,
+    Query(params): Query<CancelProcessParams>,
+) -> Result<Json<ApiResponse<ProcessOperationResponse>>, AppError> {
+    let mut processes = state.processes.write().await;
+    let proc = processes
+        .get_mut(&id)
+        .ok_or_else(|| AppError::NotFound("Process not found".to_string()))?;
+    caller.check_owner(&proc.owner, "Process")?;
+
+    if params.token != proc.cancel_token {
+        return Err(AppError::Forbidden("Invalid cancel token".to_string()));
+    }
+    if proc.status != "running" {
+        return Err(AppError::Conflict("Process is not running".to_string()));
+    }
+    let Some(pid) = proc.pid else {
         return Err(AppError::NotFound(
             "Process PID not found (process might have exited)".to_string(),
         ));
-    }
+    };
+
+    nix::sys::signal::kill(
+        nix::unistd::Pid::from_raw(pid as i32),
+        nix::sys::signal::Signal::SIGKILL,
+    )
+    .map_err(|e| AppError::InternalServerError(format!("Failed to signal process: {}", e)))?;
+    proc.status = "cancelled".to_string();
+    state.mark_registry_changed();
 
     Ok(Json(ApiResponse::success(ProcessOperationResponse {
         success: true,
+        escalated: None,
+    })))
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProcessDiagnosticsResponse {
+    process_id: String,
+    process_status: String,
+    parser: &'static str,
+    diagnostics: Vec<Diagnostic>,
+    errors: usize,
+    warnings: usize,
+}
+
+/// Diagnostics the process's `parser` has found in its output so far.
+pub async fn get_process_diagnostics(
+    State(state): State<Arc<AppState>>,
+    caller: Caller,
+    Path(id): Path<String>,
+) -> Result<Json<ApiResponse<ProcessDiagnosticsResponse>>, AppError> {
+    let processes = state.processes.read().await;
+    let proc = processes
+        .get(&id)
+        .ok_or_else(|| AppError::NotFound("Process not found".to_string()))?;
+    caller.check_owner(&proc.owner, "Process")?;
+    let parser = proc
+        .parser
+        .ok_or_else(|| AppError::BadRequest("Process was not started with a parser".to_string()))?;
+
+    let diagnostics = proc.diagnostics.read().await.clone();
+    let errors = diagnostics.iter().filter(|d| d.severity == "error").count();
+    Ok(Json(ApiResponse::success(ProcessDiagnosticsResponse {
+        process_id: id,
+        process_status: proc.status.clone(),
+        parser: parser.name(),
+        warnings: diagnostics.len() - errors,
+        errors,
+        diagnostics,
     })))
 }
 
 pub async fn get_process_logs(
     State(state): State<Arc<AppState>>,
+    caller: Caller,
     Path(id): Path<String>,
     headers: axum::http::HeaderMap,
     Query(params): Query<std::collections::HashMap<String, String>>,
@@ -327,6 +1107,7 @@ pub async fn get_process_logs(
     let proc = processes
         .get(&id)
         .ok_or_else(|| AppError::NotFound("Process not found".to_string()))?;
+    caller.check_owner(&proc.owner, "Process")?;
 
     let tail = params.get("tail").and_then(|t| t.parse::<usize>().ok());
 
@@ -338,26 +1119,31 @@ pub async fn get_process_logs(
 
     if is_sse {
         let rx = proc.log_broadcast.subscribe();
-        let logs = proc.logs.read().await.clone();
+        let logs = proc.logs.read().await.tail_texts(tail);
 
-        let start_index = if let Some(t) = tail {
-            if t < logs.len() {
-                logs.len() - t
-            } else {
-                0
-            }
-        } else {
-            0
+        // Markers around the replay, like `isHistory` on WebSocket log messages,
+        // so clients can tell when they have caught up with live output
+        let history = logs.len();
+        let marker = |name: &str, data: serde_json::Value| {
+            Ok::<Event, Infallible>(Event::default().event(name).data(data.to_string()))
         };
-
         let existing_logs_stream = tokio_stream::iter(
-            logs.into_iter()
-                .skip(start_index)
-                .map(|l| Ok::<Event, Infallible>(Event::default().data(l))),
+            std::iter::once(marker(
+                "history-start",
+                serde_json::json!({ "count": history }),
+            ))
+            .chain(logs.into_iter().map(|l| Ok(Event::default().data(l))))
+            .chain([
+                marker("history-end", serde_json::json!({ "count": history })),
+                marker("live", serde_json::json!({})),
+            ]),
         );
         let broadcast_stream = tokio_stream::wrappers::BroadcastStream::new(rx).map(|r| match r {
             Ok(l) => Ok(Event::default().data(l)),
-            Err(_) => Ok(Event::default().event("error").data("stream error")),
+            // The subscriber fell behind the broadcast buffer; say how much was lost
+            Err(BroadcastStreamRecvError::Lagged(n)) => Ok(Event::default()
+                .event("dropped")
+                .data(serde_json::json!({ "dropped": n }).to_string())),
         });
 
         let stream = existing_logs_stream.chain(broadcast_stream);
@@ -367,16 +1153,7 @@ pub async fn get_process_logs(
             .into_response());
     }
 
-    let logs = proc.logs.read().await;
-    let result_logs: Vec<String> = if let Some(t) = tail {
-        if t < logs.len() {
-            logs.iter().skip(logs.len() - t).cloned().collect()
-        } else {
-            logs.clone().into()
-        }
-    } else {
-        logs.clone().into()
-    };
+    let result_logs = proc.logs.read().await.tail_texts(tail);
 
     let status = proc.to_status();
 
@@ -397,6 +1174,46 @@ pub struct SyncExecutionRequest {
     cwd: Option<String>,
     env: Option<std::collections::HashMap<String, String>>,
     timeout: Option<u64>,
+    #[serde(default, rename = "expandCwd")]
+    expand_cwd: bool,
+    /// Cap on captured stdout and stderr, each (default: `MAX_OUTPUT_BYTES`)
+    #[serde(rename = "maxOutputBytes")]
+    max_output_bytes: Option<u64>,
+    /// `utf8` (default, invalid sequences replaced) or `base64` for the raw bytes
+    #[serde(rename = "outputEncoding")]
+    output_encoding: Option<String>,
+}
+
+impl SyncExecutionRequest {
+    fn wants_base64(&self) -> bool {
+        self.output_encoding.as_deref() == Some("base64")
+    }
+}
+
+impl Validate for SyncExecutionRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.command(&self.command, self.args.as_deref());
+        v.env(self.env.as_ref());
+        v.timeout("timeout", self.timeout, false);
+        v.check(
+            matches!(
+                self.output_encoding.as_deref(),
+                None | Some("utf8") | Some("utf-8") | Some("base64")
+            ),
+            "outputEncoding",
+            "must be utf8 or base64",
+        );
+    }
+}
+
+/// Captured output as a JSON string: lossy UTF-8, or base64 of the exact bytes.
+fn encode_output(data: &[u8], base64: bool) -> String {
+    if base64 {
+        use base64::{engine::general_purpose, Engine as _};
+        general_purpose::STANDARD.encode(data)
+    } else {
+        String::from_utf8_lossy(data).into_owned()
+    }
 }
 
 #[derive(serde::Serialize, Clone)]
@@ -404,16 +1221,30 @@ pub struct SyncExecutionRequest {
 pub struct SyncExecutionResponse {
     stdout: String,
     stderr: String,
+    /// `base64` when `stdout` and `stderr` are base64-encoded
+    #[serde(skip_serializing_if = "Option::is_none")]
+    output_encoding: Option<&'static str>,
+    /// Output hit the cap; the command was killed and the rest discarded
+    stdout_truncated: bool,
+    stderr_truncated: bool,
     exit_code: Option<i32>,
-    duration_ms: u128,
+    duration_ms: u64,
     start_time: String,
     end_time: String,
 }
 
 pub async fn exec_process_sync(
     State(state): State<Arc<AppState>>,
-    Json(req): Json<SyncExecutionRequest>,
+    Workspace(workspace): Workspace,
+    Json(mut req): Json<SyncExecutionRequest>,
 ) -> Result<Json<ApiResponse<SyncExecutionResponse>>, AppError> {
+    validate(&req)?;
+    req.env = with_env_defaults(&state, req.env.take()).await;
+    state
+        .config
+        .check_command(&command_line(&req.command, req.args.as_deref()))?;
+    let base64 = req.wants_base64();
+
     let start_time = crate::utils::common::format_time(
         std::time::SystemTime::now()
             .duration_since(std::time::UNIX_EPOCH)
@@ -441,10 +1272,14 @@ pub async fn exec_process_sync(
         }
     };
 
-    if let Some(cwd) = req.cwd {
-        let valid_cwd = validate_path(&state.config.workspace_path, &cwd)?;
-        cmd.current_dir(valid_cwd);
-    }
+    let valid_cwd = resolve_cwd(
+        &workspace,
+        state.config.default_cwd.as_deref(),
+        req.cwd.as_deref(),
+        req.expand_cwd,
+        req.env.as_ref(),
+    )?;
+    cmd.current_dir(valid_cwd);
 
     if let Some(env) = req.env {
         cmd.envs(env);
@@ -458,8 +1293,25 @@ pub async fn exec_process_sync(
     let child_result = cmd.spawn();
 
     match child_result {
-        Ok(child) => {
-            let output_result = timeout(time_limit, child.wait_with_output()).await;
+        Ok(mut child) => {
+            // Requests may lower the server's cap but never raise it
+            let limit = req
+                .max_output_bytes
+                .map_or(state.config.max_output_bytes, |n| {
+                    n.min(state.config.max_output_bytes)
+                });
+            let pid = child.id();
+            let stdout = child.stdout.take();
+            let stderr = child.stderr.take();
+            let output_result = timeout(time_limit, async {
+                let (stdout, stderr) = tokio::join!(
+                    read_capped(stdout, limit, pid),
+                    read_capped(stderr, limit, pid)
+                );
+                let status = child.wait().await?;
+                Ok::<_, std::io::Error>((status, stdout?, stderr?))
+            })
+            .await;
 
             let end_time = crate::utils::common::format_time(
                 std::time::SystemTime::now()
@@ -467,17 +1319,22 @@ pub async fn exec_process_sync(
                     .expect("Time went backwards")
                     .as_secs(),
             );
-            let duration_ms = start_instant.elapsed().as_millis();
+            let duration_ms = crate::utils::common::elapsed_ms(start_instant);
 
             match output_result {
-                Ok(Ok(output)) => Ok(Json(ApiResponse::success(SyncExecutionResponse {
-                    stdout: String::from_utf8_lossy(&output.stdout).to_string(),
-                    stderr: String::from_utf8_lossy(&output.stderr).to_string(),
-                    exit_code: output.status.code(),
-                    duration_ms,
-                    start_time,
-                    end_time,
-                }))),
+                Ok(Ok((status, stdout, stderr))) => {
+                    Ok(Json(ApiResponse::success(SyncExecutionResponse {
+                        stdout: encode_output(&stdout.data, base64),
+                        stderr: encode_output(&stderr.data, base64),
+                        output_encoding: base64.then_some("base64"),
+                        stdout_truncated: stdout.truncated,
+                        stderr_truncated: stderr.truncated,
+                        exit_code: status.code(),
+                        duration_ms,
+                        start_time,
+                        end_time,
+                    })))
+                }
                 Ok(Err(e)) => Err(AppError::InternalServerError(format!(
                     "Failed to wait for process: {}",
                     e
@@ -503,10 +1360,13 @@ pub async fn exec_process_sync(
                     .expect("Time went backwards")
                     .as_secs(),
             );
-            let duration_ms = start_instant.elapsed().as_millis();
+            let duration_ms = crate::utils::common::elapsed_ms(start_instant);
             let response = SyncExecutionResponse {
                 stdout: "".to_string(),
-                stderr: stderr_message,
+                stderr: encode_output(stderr_message.as_bytes(), base64),
+                output_encoding: base64.then_some("base64"),
+                stdout_truncated: false,
+                stderr_truncated: false,
                 exit_code: Some(127),
                 duration_ms,
                 start_time,
@@ -527,15 +1387,42 @@ pub struct SyncStreamExecutionRequest {
     cwd: Option<String>,
     env: Option<std::collections::HashMap<String, String>>,
     timeout: Option<u64>,
+    #[serde(default, rename = "expandCwd")]
+    expand_cwd: bool,
+}
+
+impl Validate for SyncStreamExecutionRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.command(&self.command, self.args.as_deref());
+        v.env(self.env.as_ref());
+        v.timeout("timeout", self.timeout, false);
+    }
 }
 
 pub async fn exec_process_sync_stream(
     State(state): State<Arc<AppState>>,
-    Json(req): Json<SyncStreamExecutionRequest>,
-) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    Workspace(workspace): Workspace,
+    Json(mut req): Json<SyncStreamExecutionRequest>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, AppError> {
+    validate(&req)?;
+    state
+        .config
+        .check_command(&command_line(&req.command, req.args.as_deref()))?;
+    req.env = with_env_defaults(&state, req.env.take()).await;
+
+    // An unusable cwd leaves the child in the server's directory, as before
+    let cwd = resolve_cwd(
+        &workspace,
+        state.config.default_cwd.as_deref(),
+        req.cwd.as_deref(),
+        req.expand_cwd,
+        req.env.as_ref(),
+    )
+    .ok();
+    let max_line = state.config.max_log_line_bytes;
     let stream = stream::unfold(
-        (state, req, false), // state, req, has_started
-        move |(state, req, has_started)| async move {
+        (cwd, req, false), // cwd, req, has_started
+        move |(cwd, req, has_started)| async move {
             if has_started {
                 return None;
             }
@@ -544,7 +1431,7 @@ pub async fn exec_process_sync_stream(
             let tx_stdout = tx.clone();
             let tx_stderr = tx.clone();
 
-            let state_for_task = state.clone();
+            let cwd_for_task = cwd.clone();
             let req_for_task = req.clone();
 
             tokio::spawn(async move {
@@ -582,11 +1469,8 @@ pub async fn exec_process_sync_stream(
                     }
                 };
 
-                if let Some(cwd) = &req_for_task.cwd {
-                    if let Ok(valid_cwd) = validate_path(&state_for_task.config.workspace_path, cwd)
-                    {
-                        cmd.current_dir(valid_cwd);
-                    }
+                if let Some(valid_cwd) = &cwd_for_task {
+                    cmd.current_dir(valid_cwd);
                 }
 
                 if let Some(env) = &req_for_task.env {
@@ -608,26 +1492,25 @@ pub async fn exec_process_sync_stream(
                             let tx = tx_stdout.clone();
                             tokio::spawn(async move {
                                 let mut reader = BufReader::new(stdout);
-                                let mut line = String::new();
-                                while let Ok(n) = reader.read_line(&mut line).await {
-                                    if n == 0 {
-                                        break;
-                                    }
+                                let mut buf = Vec::new();
+                                while let Ok(Some((line, continued))) =
+                                    read_line_capped(&mut reader, &mut buf, max_line).await
+                                {
                                     let _ = tx
                                         .send(Ok(Event::default().event("stdout").data(
                                             serde_json::to_string(&StreamOutputEvent {
-                                                output: line.clone(),
+                                                output: line,
                                                 timestamp: crate::utils::common::format_time(
                                                     std::time::SystemTime::now()
                                                         .duration_since(std::time::UNIX_EPOCH)
                                                         .expect("Time went backwards")
                                                         .as_secs(),
                                                 ),
+                                                continued,
                                             })
                                             .unwrap(),
                                         )))
                                         .await;
-                                    line.clear();
                                 }
                             });
                         }
@@ -636,32 +1519,31 @@ pub async fn exec_process_sync_stream(
                             let tx = tx_stderr.clone();
                             tokio::spawn(async move {
                                 let mut reader = BufReader::new(stderr);
-                                let mut line = String::new();
-                                while let Ok(n) = reader.read_line(&mut line).await {
-                                    if n == 0 {
-                                        break;
-                                    }
+                                let mut buf = Vec::new();
+                                while let Ok(Some((line, continued))) =
+                                    read_line_capped(&mut reader, &mut buf, max_line).await
+                                {
                                     let _ = tx
                                         .send(Ok(Event::default().event("stderr").data(
                                             serde_json::to_string(&StreamOutputEvent {
-                                                output: line.clone(),
+                                                output: line,
                                                 timestamp: crate::utils::common::format_time(
                                                     std::time::SystemTime::now()
                                                         .duration_since(std::time::UNIX_EPOCH)
                                                         .expect("Time went backwards")
                                                         .as_secs(),
                                                 ),
+                                                continued,
                                             })
                                             .unwrap(),
                                         )))
                                         .await;
-                                    line.clear();
                                 }
                             });
                         }
 
                         let wait_result = timeout(time_limit, child.wait()).await;
-                        let duration = start_instant.elapsed().as_millis() as i64;
+                        let duration = crate::utils::common::elapsed_ms(start_instant);
 
                         match wait_result {
                             Ok(Ok(status)) => {
@@ -670,6 +1552,7 @@ pub async fn exec_process_sync_stream(
                                         serde_json::to_string(&StreamCompleteEvent {
                                             exit_code: status.code(),
                                             duration,
+                                            duration_ms: duration,
                                             timestamp: crate::utils::common::format_time(
                                                 std::time::SystemTime::now()
                                                     .duration_since(std::time::UNIX_EPOCH)
@@ -739,13 +1622,157 @@ pub async fn exec_process_sync_stream(
             });
 
             let stream = tokio_stream::wrappers::ReceiverStream::new(rx);
-            Some((stream, (state, req, true)))
+            Some((stream, (cwd, req, true)))
         },
     );
 
     // Flatten the stream of streams
     let flattened = stream.flatten();
-    Sse::new(flattened).keep_alive(axum::response::sse::KeepAlive::default())
+    Ok(Sse::new(flattened).keep_alive(axum::response::sse::KeepAlive::default()))
+}
+
+/// One captured output stream, cut off after the configured limit.
+struct CappedOutput {
+    data: Vec<u8>,
+    truncated: bool,
+}
+
+/// Read `reader` to EOF keeping at most `limit` bytes. When the limit is hit
+/// the child `pid` is killed so a runaway command can't exhaust memory.
+async fn read_capped<R: tokio::io::AsyncRead + Unpin>(
+    reader: Option<R>,
+    limit: u64,
+    pid: Option<u32>,
+) -> std::io::Result<CappedOutput> {
+    let mut output = CappedOutput {
+        data: Vec::new(),
+        truncated: false,
+    };
+    let Some(mut reader) = reader else {
+        return Ok(output);
+    };
+    let limit = usize::try_from(limit).unwrap_or(usize::MAX);
+    let mut buf = [0u8; 8192];
+    loop {
+        let n = reader.read(&mut buf).await?;
+        if n == 0 {
+            return Ok(output);
+        }
+        let room = limit - output.data.len();
+        if n > room {
+            output.data.extend_from_slice(&buf[..room]);
+            output.truncated = true;
+            if let Some(pid) = pid {
+                let _ = nix::sys::signal::kill(
+                    nix::unistd::Pid::from_raw(pid as i32),
+                    nix::sys::signal::Signal::SIGKILL,
+                );
+            }
+            return Ok(output);
+        }
+        output.data.extend_from_slice(&buf[..n]);
+    }
+}
+
+/// Command line as checked against the command allow/deny lists: the command
+/// followed by its explicit arguments, space-separated.
+fn command_line(command: &str, args: Option<&[String]>) -> String {
+    match args {
+        Some(args) if !args.is_empty() => format!("{} {}", command, args.join(" ")),
+        _ => command.to_string(),
+    }
+}
+
+/// Run the child at `nice`, clamped to the valid range. Going below the
+/// server's own niceness needs CAP_SYS_NICE; without it the spawn fails.
+fn set_niceness(cmd: &mut Command, nice: i32) {
+    let nice = crate::config::clamp_nice(nice);
+    // SAFETY: the closure only calls setpriority, which is async-signal-safe
+    unsafe {
+        cmd.pre_exec(move || {
+            if nix::libc::setpriority(nix::libc::PRIO_PROCESS, 0, nice) == -1 {
+                return Err(std::io::Error::last_os_error());
+            }
+            Ok(())
+        });
+    }
+}
+
+/// Working directory for a spawned command. Without a request `cwd` this is
+/// `default_cwd` (relative to the workspace) or the workspace itself.
+/// A request `cwd` is validated, expanding `~` and `$VAR` first when `expand`
+/// is set; `~` resolves to `$HOME`, falling back to the workspace path.
+fn resolve_cwd(
+    workspace: &std::path::Path,
+    default_cwd: Option<&std::path::Path>,
+    cwd: Option<&str>,
+    expand: bool,
+    env: Option<&std::collections::HashMap<String, String>>,
+) -> Result<std::path::PathBuf, AppError> {
+    let Some(cwd) = cwd else {
+        return Ok(match default_cwd {
+            Some(dir) => normalize_path(&workspace.join(dir)),
+            None => workspace.to_path_buf(),
+        });
+    };
+    if !expand {
+        return validate_path(workspace, cwd);
+    }
+    let home = std::env::var("HOME")
+        .map(std::path::PathBuf::from)
+        .unwrap_or_else(|_| workspace.to_path_buf());
+    let expanded = expand_path(cwd, &home, env);
+    validate_path(workspace, &expanded)
+}
+
+/// Return once `output` carries no line for `limit`; never returns if the
+/// process's log channel closes first.
+async fn wait_for_silence(mut output: tokio::sync::broadcast::Receiver<String>, limit: Duration) {
+    loop {
+        match timeout(limit, output.recv()).await {
+            Err(_) => return,
+            Ok(Err(tokio::sync::broadcast::error::RecvError::Closed)) => {
+                std::future::pending::<()>().await
+            }
+            Ok(_) => {}
+        }
+    }
+}
+
+/// A `BufReader` that keeps a copy of every byte consumed from it, so the
+/// exact output can be saved while lines are decoded lossily for the logs.
+struct TeeReader<R> {
+    inner: BufReader<R>,
+    consumed: Vec<u8>,
+}
+
+impl<R: tokio::io::AsyncRead + Unpin> tokio::io::AsyncRead for TeeReader<R> {
+    fn poll_read(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+        out: &mut tokio::io::ReadBuf<'_>,
+    ) -> std::task::Poll<std::io::Result<()>> {
+        let this = &mut *self;
+        let filled = out.filled().len();
+        let result = std::pin::Pin::new(&mut this.inner).poll_read(cx, out);
+        this.consumed.extend_from_slice(&out.filled()[filled..]);
+        result
+    }
+}
+
+impl<R: tokio::io::AsyncRead + Unpin> tokio::io::AsyncBufRead for TeeReader<R> {
+    fn poll_fill_buf(
+        self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<std::io::Result<&[u8]>> {
+        std::pin::Pin::new(&mut self.get_mut().inner).poll_fill_buf(cx)
+    }
+
+    fn consume(mut self: std::pin::Pin<&mut Self>, amt: usize) {
+        let this = &mut *self;
+        this.consumed.extend_from_slice(&this.inner.buffer()[..amt]);
+        std::pin::Pin::new(&mut this.inner).consume(amt);
+    }
 }
 
 async fn pump_log<R: tokio::io::AsyncRead + Unpin>(
@@ -754,24 +1781,331 @@ async fn pump_log<R: tokio::io::AsyncRead + Unpin>(
     state: Arc<AppState>,
     tx: tokio::sync::broadcast::Sender<String>,
     prefix: &str,
+    parser: Option<ParserKind>,
+    mut output_file: Option<Arc<tokio::sync::Mutex<tokio::fs::File>>>,
 ) {
-    let mut reader = reader;
-    let mut line = String::new();
-    const MAX_LOG_LINES: usize = 10000;
+    use tokio::io::AsyncWriteExt;
 
-    while let Ok(n) = reader.read_line(&mut line).await {
-        if n == 0 {
-            break;
+    let mut reader = TeeReader {
+        inner: reader,
+        consumed: Vec::new(),
+    };
+    let mut buf = Vec::new();
+    let max_line = state.config.max_log_line_bytes;
+    const MAX_DIAGNOSTICS: usize = 10000;
+    let mut parser = parser.map(DiagnosticParser::new);
+
+    while let Ok(Some((line, continued))) = read_line_capped(&mut reader, &mut buf, max_line).await
+    {
+        let raw = std::mem::take(&mut reader.consumed);
+        let written = match &output_file {
+            Some(file) => file.lock().await.write_all(&raw).await,
+            None => Ok(()),
+        };
+        if let Err(e) = written {
+            // Keep logging; only the copy on disk stops
+            output_file = None;
+            let message = format!("[system] Failed to write outputFile: {}", e);
+            if let Some(proc) = state.processes.read().await.get(&pid) {
+                proc.logs.write().await.push(message.clone());
+            }
+            let _ = tx.send(message);
         }
-        let log_entry = format!("{} {}", prefix, line);
+        let mark = if continued { CONTINUATION_MARK } else { "" };
+        let log_entry = format!("{}{} {}", prefix, mark, line);
         if let Some(proc) = state.processes.read().await.get(&pid) {
-            let mut logs = proc.logs.write().await;
-            if logs.len() >= MAX_LOG_LINES {
-                logs.pop_front();
+            proc.logs.write().await.push(log_entry.clone());
+            if let Some(found) = parser.as_mut().and_then(|p| p.push_line(&line)) {
+                let mut diagnostics = proc.diagnostics.write().await;
+                if diagnostics.len() < MAX_DIAGNOSTICS {
+                    diagnostics.push(found);
+                }
             }
-            logs.push_back(log_entry.clone());
         }
         let _ = tx.send(log_entry);
-        line.clear();
+    }
+    if let Some(file) = output_file {
+        let _ = file.lock().await.flush().await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exec_request_validation() {
+        let parse = |body: serde_json::Value| {
+            let req: ExecProcessRequest = serde_json::from_value(body).unwrap();
+            match validate(&req) {
+                Err(AppError::Validation(errors)) => errors,
+                other => panic!("expected validation errors, got {:?}", other),
+            }
+        };
+        let errors = parse(serde_json::json!({
+            "command": "  ",
+            "args": ["ok", "bad\0"],
+            "env": {"A=B": "1"},
+            "timeout": 0,
+            "idleTimeoutSecs": 0,
+        }));
+        let fields: Vec<&str> = errors.iter().map(|e| e.field.as_str()).collect();
+        assert_eq!(fields, ["command", "args[1]", "env.A=B", "timeout"]);
+
+        let errors = parse(serde_json::json!({"command": "ls", "timeout": 10_000_000}));
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].message, "must be between 1 and 604800 seconds");
+
+        for codes in [serde_json::json!([]), serde_json::json!([0, 256])] {
+            let errors = parse(serde_json::json!({"command": "grep", "successExitCodes": codes}));
+            assert_eq!(errors[0].field, "successExitCodes");
+        }
+
+        let ok: ExecProcessRequest = serde_json::from_value(
+            serde_json::json!({"command": "ls", "timeout": 60, "successExitCodes": [0, 1]}),
+        )
+        .unwrap();
+        assert!(validate(&ok).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_tee_reader_keeps_raw_bytes() {
+        let input: &[u8] = b"ok\n\xff\xfe bad utf-8\na line longer than eight\nno newline";
+        let mut reader = TeeReader {
+            inner: BufReader::with_capacity(5, input),
+            consumed: Vec::new(),
+        };
+        let mut buf = Vec::new();
+        let mut lines = Vec::new();
+        while let Some((line, _)) = read_line_capped(&mut reader, &mut buf, 8).await.unwrap() {
+            lines.push(line);
+        }
+        assert_eq!(reader.consumed, input);
+        assert_eq!(lines[1], "\u{fffd}\u{fffd} bad u");
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_silence() {
+        let (tx, rx) = tokio::sync::broadcast::channel(16);
+        let talker = tokio::spawn(async move {
+            for _ in 0..4 {
+                tokio::time::sleep(Duration::from_millis(20)).await;
+                let _ = tx.send("line".to_string());
+            }
+            tx
+        });
+        let started = tokio::time::Instant::now();
+        wait_for_silence(rx, Duration::from_millis(60)).await;
+        // Four lines 20ms apart keep it waiting until 60ms after the last one
+        assert!(started.elapsed() >= Duration::from_millis(130));
+        drop(talker.await.unwrap());
+    }
+
+    #[test]
+    fn test_apply_overrides() {
+        let preset = ExecPreset {
+            command: "npm".to_string(),
+            args: Some(vec!["test".to_string()]),
+            cwd: Some("web".to_string()),
+            env: Some(
+                [("CI", "1"), ("NODE_ENV", "test")]
+                    .map(|(k, v)| (k.to_string(), v.to_string()))
+                    .into(),
+            ),
+            timeout: Some(600),
+        };
+
+        let req = apply_overrides(&preset, PresetOverrides::default());
+        assert_eq!(req.command, "npm");
+        assert_eq!(req.args, preset.args);
+        assert_eq!(req.cwd.as_deref(), Some("web"));
+        assert_eq!(req.timeout, Some(600));
+
+        let overrides = PresetOverrides {
+            args: Some(vec!["run".to_string(), "lint".to_string()]),
+            env: Some([("NODE_ENV".to_string(), "ci".to_string())].into()),
+            ..Default::default()
+        };
+        let req = apply_overrides(&preset, overrides);
+        assert_eq!(req.args.unwrap(), ["run", "lint"]);
+        let env = req.env.unwrap();
+        assert_eq!(env["CI"], "1");
+        assert_eq!(env["NODE_ENV"], "ci");
+    }
+
+    #[test]
+    fn test_encode_output() {
+        use base64::{engine::general_purpose, Engine as _};
+        let png_header = b"\x89PNG\r\n\x1a\n\xff\x00";
+        let encoded = encode_output(png_header, true);
+        assert_eq!(
+            general_purpose::STANDARD.decode(encoded).unwrap(),
+            png_header
+        );
+        // The default stays lossy
+        assert_eq!(encode_output(b"ok\xff", false), "ok\u{fffd}");
+    }
+
+    #[tokio::test]
+    async fn test_read_capped() {
+        let output = read_capped(Some(&b"hello world"[..]), 5, None)
+            .await
+            .unwrap();
+        assert_eq!(output.data, b"hello");
+        assert!(output.truncated);
+
+        let output = read_capped(Some(&b"hello"[..]), 5, None).await.unwrap();
+        assert_eq!(output.data, b"hello");
+        assert!(!output.truncated);
+
+        let output = read_capped(None::<&[u8]>, 5, None).await.unwrap();
+        assert!(output.data.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_set_niceness() {
+        let mut cmd = Command::new("cat");
+        cmd.arg("/proc/self/stat").stdout(Stdio::piped());
+        // Raising niceness needs no privileges; 40 is clamped to 19
+        set_niceness(&mut cmd, 40);
+        let output = cmd.output().await.unwrap();
+        let stat = String::from_utf8(output.stdout).unwrap();
+        // Field 19 of /proc/<pid>/stat, counted after the parenthesised command name
+        let after_comm = &stat[stat.rfind(')').unwrap() + 2..];
+        let nice: i32 = after_comm.split(' ').nth(16).unwrap().parse().unwrap();
+        assert_eq!(nice, 19);
+    }
+
+    #[tokio::test]
+    async fn test_exec_output_file() {
+        let dir = std::env::temp_dir().join(format!(
+            "devbox-exec-output-{}",
+            crate::utils::common::generate_id()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("out.log"), "kept").unwrap();
+        let state = Arc::new(AppState::new(crate::config::test_config()));
+        let exec = |body: serde_json::Value| {
+            exec_process(
+                State(state.clone()),
+                Caller::admin(),
+                Workspace(dir.clone()),
+                Json(serde_json::from_value(body).unwrap()),
+            )
+        };
+
+        // A command that cannot start leaves the old file alone
+        let failed = exec(serde_json::json!({
+            "command": "/nonexistent/command",
+            "outputFile": "out.log",
+        }))
+        .await;
+        assert!(matches!(failed, Err(AppError::OperationError(..))));
+        assert_eq!(std::fs::read(dir.join("out.log")).unwrap(), b"kept");
+        let failed = exec(serde_json::json!({
+            "command": "/nonexistent/command",
+            "outputFile": "new.log",
+        }))
+        .await;
+        assert!(failed.is_err());
+        assert!(!dir.join("new.log").exists());
+
+        let started = exec(serde_json::json!({
+            "command": "printf",
+            "args": ["one\\n\\377\\ntwo"],
+            "outputFile": "out.log",
+        }))
+        .await
+        .unwrap();
+        let mut exited = state.processes.read().await[&started.0.data.process_id]
+            .exited
+            .subscribe();
+        exited.wait_for(|&done| done).await.unwrap();
+        // The streams are flushed shortly after the process is reaped
+        let expected: &[u8] = b"one\n\xff\ntwo";
+        for _ in 0..50 {
+            if std::fs::read(dir.join("out.log")).unwrap() == expected {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        assert_eq!(std::fs::read(dir.join("out.log")).unwrap(), expected);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_exec_sync_caps_max_output_bytes() {
+        let state = Arc::new(AppState::new(crate::config::test_config()));
+        let exec = |max: u64| {
+            exec_process_sync(
+                State(state.clone()),
+                Workspace(std::env::temp_dir()),
+                Json(
+                    serde_json::from_value(serde_json::json!({
+                        "command": "head -c 4096 /dev/zero",
+                        "maxOutputBytes": max,
+                    }))
+                    .unwrap(),
+                ),
+            )
+        };
+
+        let lowered = exec(100).await.unwrap().0.data;
+        assert_eq!(lowered.stdout.len(), 100);
+        assert!(lowered.stdout_truncated);
+        // The configured 1024 bytes still apply to a larger request
+        let raised = exec(1_000_000).await.unwrap().0.data;
+        assert_eq!(raised.stdout.len(), 1024);
+        assert!(raised.stdout_truncated);
+    }
+
+    #[tokio::test]
+    async fn test_tree_shows_only_own_processes() {
+        let state = Arc::new(AppState::new(crate::config::test_config()));
+        let started = exec_process(
+            State(state.clone()),
+            Caller("ci".to_string()),
+            Workspace(std::env::temp_dir()),
+            Json(
+                serde_json::from_value(serde_json::json!({"command": "sleep", "args": ["5"]}))
+                    .unwrap(),
+            ),
+        )
+        .await
+        .unwrap();
+        let pid = state.processes.read().await[&started.0.data.process_id]
+            .pid
+            .unwrap();
+        let tree = |caller: Caller| {
+            process_tree(
+                State(state.clone()),
+                caller,
+                Query(ProcessTreeParams { pid: None }),
+            )
+        };
+        fn pids(node: &crate::monitor::tree::ProcessNode, out: &mut Vec<u32>) {
+            out.push(node.pid);
+            node.children.iter().for_each(|c| pids(c, out));
+        }
+
+        let mut own = Vec::new();
+        pids(
+            &tree(Caller("ci".to_string())).await.unwrap().0.data,
+            &mut own,
+        );
+        assert_eq!(own, [std::process::id(), pid]);
+        let mut all = Vec::new();
+        pids(&tree(Caller::admin()).await.unwrap().0.data, &mut all);
+        assert!(all.contains(&pid));
+        assert!(matches!(
+            tree(Caller("web".to_string())).await,
+            Err(AppError::NotFound(_))
+        ));
+
+        nix::sys::signal::kill(
+            nix::unistd::Pid::from_raw(pid as i32),
+            nix::sys::signal::Signal::SIGKILL,
+        )
+        .unwrap();
     }
 }
@@ -1,6 +1,11 @@
+use super::env::with_env_defaults;
+use super::process::parse_signal;
 use crate::error::AppError;
+use crate::middleware::auth::Caller;
+use crate::monitor::proc_stats::process_group_members;
 use crate::response::ApiResponse;
-use crate::state::{session::SessionInfo, AppState};
+use crate::state::{session::SessionInfo, workspace::Workspace, AppState};
+use crate::utils::common::{read_line_capped, split_output_entry, CONTINUATION_MARK};
 use crate::utils::path::validate_path;
 use axum::{
     extract::{Path, Query, State},
@@ -8,9 +13,18 @@ use axum::{
 };
 use serde::{Deserialize, Serialize};
 use std::process::Stdio;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 use tokio::io::{AsyncWriteExt, BufReader};
 use tokio::process::Command;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::time::{timeout, Duration};
+
+/// Prefix of the line echoed after each batch command to report its exit code.
+const EXIT_MARKER_PREFIX: &str = "__DEVBOX_EXIT_";
+
+/// How often a throttled output reader checks whether subscribers caught up.
+const THROTTLE_POLL: Duration = Duration::from_millis(20);
 
 #[derive(Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -47,7 +61,9 @@ pub struct SessionExecResponse {
     exit_code: i32,
     stdout: String,
     stderr: String,
+    /// Same as `duration_ms`, kept for older clients
     duration: u64,
+    duration_ms: u64,
 }
 
 #[derive(Serialize)]
@@ -65,14 +81,17 @@ pub struct SessionLogsResponse {
 
 pub async fn create_session(
     State(state): State<Arc<AppState>>,
-    Json(req): Json<CreateSessionRequest>,
+    Caller(owner): Caller,
+    Workspace(workspace): Workspace,
+    Json(mut req): Json<CreateSessionRequest>,
 ) -> Result<Json<ApiResponse<CreateSessionResponse>>, AppError> {
+    req.env = with_env_defaults(&state, req.env.take()).await;
     let shell = req.shell.unwrap_or_else(|| "/bin/bash".to_string());
     let cwd = req
         .working_dir
-        .unwrap_or_else(|| state.config.workspace_path.to_string_lossy().to_string());
+        .unwrap_or_else(|| workspace.to_string_lossy().to_string());
 
-    let valid_cwd = validate_path(&state.config.workspace_path, &cwd)?;
+    let valid_cwd = validate_path(&workspace, &cwd)?;
 
     let mut cmd = Command::new(&shell);
     cmd.current_dir(&valid_cwd);
@@ -84,6 +103,8 @@ pub async fn create_session(
     cmd.stdin(Stdio::piped());
     cmd.stdout(Stdio::piped());
     cmd.stderr(Stdio::piped());
+    // Its own process group, so commands run by the shell can be signaled together
+    cmd.process_group(0);
 
     let mut child = cmd
         .spawn()
@@ -94,7 +115,7 @@ pub async fn create_session(
     let stdout = child.stdout.take().expect("stdout piped");
     let stderr = child.stderr.take().expect("stderr piped");
 
-    let (tx, _rx) = tokio::sync::broadcast::channel(100);
+    let (tx, _rx) = tokio::sync::broadcast::channel(state.config.log_broadcast_capacity);
 
     let pid = child.id();
 
@@ -107,8 +128,12 @@ pub async fn create_session(
         child: Some(child),
         stdin,
         log_broadcast: tx.clone(),
+        owner,
     });
 
+    let high_water = state.config.session_output_high_water;
+    let max_line = state.config.max_log_line_bytes;
+    let throttled = session_info.throttled_readers.clone();
     {
         let mut sessions = state.sessions.write().await;
         sessions.insert(session_id.clone(), session_info);
@@ -117,27 +142,22 @@ pub async fn create_session(
     let state_clone = state.clone();
     let sid_clone = session_id.clone();
     let tx_clone = tx.clone();
+    let throttled_out = throttled.clone();
 
     tokio::spawn(async move {
         let mut reader = BufReader::new(stdout);
-        let mut line = String::new();
-        use tokio::io::AsyncBufReadExt;
-        const MAX_LOG_LINES: usize = 10000;
+        let mut buf = Vec::new();
 
-        while let Ok(n) = reader.read_line(&mut line).await {
-            if n == 0 {
-                break;
-            }
-            let log_entry = format!("[stdout] {}", line);
+        while let Ok(Some((line, continued))) =
+            read_line_capped(&mut reader, &mut buf, max_line).await
+        {
+            let mark = if continued { CONTINUATION_MARK } else { "" };
+            let log_entry = format!("[stdout]{} {}", mark, line);
             if let Some(sess) = state_clone.sessions.read().await.get(&sid_clone) {
-                let mut logs = sess.logs.write().await;
-                if logs.len() >= MAX_LOG_LINES {
-                    logs.pop_front();
-                }
-                logs.push_back(log_entry.clone());
+                sess.logs.write().await.push(log_entry.clone());
             }
+            wait_for_subscribers(&tx_clone, high_water, &throttled_out).await;
             let _ = tx_clone.send(log_entry);
-            line.clear();
         }
     });
 
@@ -147,24 +167,18 @@ pub async fn create_session(
 
     tokio::spawn(async move {
         let mut reader = BufReader::new(stderr);
-        let mut line = String::new();
-        use tokio::io::AsyncBufReadExt;
-        const MAX_LOG_LINES: usize = 10000;
+        let mut buf = Vec::new();
 
-        while let Ok(n) = reader.read_line(&mut line).await {
-            if n == 0 {
-                break;
-            }
-            let log_entry = format!("[stderr] {}", line);
+        while let Ok(Some((line, continued))) =
+            read_line_capped(&mut reader, &mut buf, max_line).await
+        {
+            let mark = if continued { CONTINUATION_MARK } else { "" };
+            let log_entry = format!("[stderr]{} {}", mark, line);
             if let Some(sess) = state_clone_err.sessions.read().await.get(&sid_clone_err) {
-                let mut logs = sess.logs.write().await;
-                if logs.len() >= MAX_LOG_LINES {
-                    logs.pop_front();
-                }
-                logs.push_back(log_entry.clone());
+                sess.logs.write().await.push(log_entry.clone());
             }
+            wait_for_subscribers(&tx_clone_err, high_water, &throttled).await;
             let _ = tx_clone_err.send(log_entry);
-            line.clear();
         }
     });
 
@@ -209,13 +223,34 @@ pub async fn create_session(
     })))
 }
 
+/// Hold off reading more shell output while `high_water` lines are still
+/// queued for the slowest live subscriber. The pipe then fills up and the
+/// shell blocks on write, instead of subscribers missing lines.
+async fn wait_for_subscribers(
+    tx: &tokio::sync::broadcast::Sender<String>,
+    high_water: usize,
+    throttled: &AtomicUsize,
+) {
+    if high_water == 0 || tx.len() < high_water {
+        return;
+    }
+    throttled.fetch_add(1, Ordering::Relaxed);
+    // Dropped subscribers release their queued lines, so this ends once the
+    // slowest one reads on or goes away
+    while tx.len() >= high_water {
+        tokio::time::sleep(THROTTLE_POLL).await;
+    }
+    throttled.fetch_sub(1, Ordering::Relaxed);
+}
+
 pub async fn list_sessions(
     State(state): State<Arc<AppState>>,
+    caller: Caller,
 ) -> Result<Json<ApiResponse<ListSessionsResponse>>, AppError> {
     let sessions = state.sessions.read().await;
     let mut result = Vec::new();
 
-    for sess in sessions.values() {
+    for sess in sessions.values().filter(|s| caller.owns(&s.owner)) {
         result.push(sess.to_status());
     }
 
@@ -226,12 +261,14 @@ pub async fn list_sessions(
 
 pub async fn get_session(
     State(state): State<Arc<AppState>>,
+    caller: Caller,
     Path(id): Path<String>,
 ) -> Result<Json<ApiResponse<crate::state::session::SessionStatus>>, AppError> {
     let sessions = state.sessions.read().await;
     let sess = sessions
         .get(&id)
         .ok_or_else(|| AppError::NotFound("Session not found".to_string()))?;
+    caller.check_owner(&sess.owner, "Session")?;
 
     Ok(Json(ApiResponse::success(sess.to_status())))
 }
@@ -243,6 +280,7 @@ pub struct UpdateSessionEnvRequest {
 
 pub async fn update_session_env(
     State(state): State<Arc<AppState>>,
+    caller: Caller,
     Path(id): Path<String>,
     Json(req): Json<UpdateSessionEnvRequest>,
 ) -> Result<Json<ApiResponse<SessionOperationResponse>>, AppError> {
@@ -250,6 +288,7 @@ pub async fn update_session_env(
     let sess = sessions
         .get_mut(&id)
         .ok_or_else(|| AppError::NotFound("Session not found".to_string()))?;
+    caller.check_owner(&sess.owner, "Session")?;
 
     // Update environment variables in session info
     for (k, v) in &req.env {
@@ -279,13 +318,17 @@ pub struct SessionExecRequest {
 
 pub async fn session_exec(
     State(state): State<Arc<AppState>>,
+    caller: Caller,
     Path(id): Path<String>,
     Json(req): Json<SessionExecRequest>,
 ) -> Result<Json<ApiResponse<SessionExecResponse>>, AppError> {
+    state.config.check_command(&req.command)?;
+
     let mut sessions = state.sessions.write().await;
     let sess = sessions
         .get_mut(&id)
         .ok_or_else(|| AppError::NotFound("Session not found".to_string()))?;
+    caller.check_owner(&sess.owner, "Session")?;
 
     if let Some(stdin) = &mut sess.stdin {
         let cmd = format!("{}\n", req.command);
@@ -295,12 +338,7 @@ pub async fn session_exec(
 
         let log_entry = format!("[exec] {}", req.command);
         {
-            const MAX_LOG_LINES: usize = 10000;
-            let mut logs = sess.logs.write().await;
-            if logs.len() >= MAX_LOG_LINES {
-                logs.pop_front();
-            }
-            logs.push_back(log_entry.clone());
+            sess.logs.write().await.push(log_entry.clone());
         }
         let _ = sess.log_broadcast.send(log_entry);
     }
@@ -310,9 +348,224 @@ pub async fn session_exec(
         stdout: "".to_string(),
         stderr: "".to_string(),
         duration: 0,
+        duration_ms: 0,
     })))
 }
 
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionExecBatchRequest {
+    commands: Vec<String>,
+    #[serde(default)]
+    stop_on_error: bool,
+    /// Per-command timeout in seconds
+    timeout: Option<u64>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionCommandResult {
+    command: String,
+    exit_code: i32,
+    stdout: String,
+    stderr: String,
+    /// Same as `duration_ms`, kept for older clients
+    duration: u64,
+    duration_ms: u64,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionExecBatchResponse {
+    results: Vec<SessionCommandResult>,
+    /// True when `stopOnError` halted the batch before every command ran
+    stopped: bool,
+}
+
+/// Run commands one at a time in the session shell, capturing each exit code.
+///
+/// After every command the shell is asked to print a unique marker line
+/// carrying `$?`; output seen on the session's log broadcast until that marker
+/// is attributed to the command. With `stopOnError`, the remaining commands
+/// are not submitted once one exits nonzero.
+pub async fn session_exec_batch(
+    State(state): State<Arc<AppState>>,
+    caller: Caller,
+    Path(id): Path<String>,
+    Json(req): Json<SessionExecBatchRequest>,
+) -> Result<Json<ApiResponse<SessionExecBatchResponse>>, AppError> {
+    if req.commands.is_empty() {
+        return Err(AppError::BadRequest(
+            "commands must not be empty".to_string(),
+        ));
+    }
+    for command in &req.commands {
+        state.config.check_command(command)?;
+    }
+    {
+        let sessions = state.sessions.read().await;
+        let sess = sessions
+            .get(&id)
+            .ok_or_else(|| AppError::NotFound("Session not found".to_string()))?;
+        caller.check_owner(&sess.owner, "Session")?;
+    }
+
+    let time_limit = Duration::from_secs(req.timeout.unwrap_or(30));
+    let mut results = Vec::with_capacity(req.commands.len());
+    let mut stopped = false;
+
+    for (i, command) in req.commands.iter().enumerate() {
+        match run_session_command(&state, &id, command, time_limit).await? {
+            Some(result) => {
+                let failed = result.exit_code != 0;
+                results.push(result);
+                if failed && req.stop_on_error {
+                    stopped = i + 1 < req.commands.len();
+                    break;
+                }
+            }
+            None => {
+                let partial = SessionExecBatchResponse {
+                    results,
+                    stopped: true,
+                };
+                return Err(AppError::OperationError(
+                    format!("Session command timed out: {}", command),
+                    serde_json::to_value(partial).unwrap(),
+                ));
+            }
+        }
+    }
+
+    Ok(Json(ApiResponse::success(SessionExecBatchResponse {
+        results,
+        stopped,
+    })))
+}
+
+/// Submit one command followed by its exit marker and wait for the marker.
+/// Returns `None` if the marker does not arrive within `time_limit`.
+async fn run_session_command(
+    state: &AppState,
+    id: &str,
+    command: &str,
+    time_limit: Duration,
+) -> Result<Option<SessionCommandResult>, AppError> {
+    let marker = format!(
+        "{}{}:",
+        EXIT_MARKER_PREFIX,
+        crate::utils::common::generate_nanoid(12)
+    );
+
+    // Subscribe before writing so no output is missed, and release the lock
+    // before waiting: the stdout/stderr readers need it to record log lines.
+    let mut rx = {
+        let mut sessions = state.sessions.write().await;
+        let sess = sessions
+            .get_mut(id)
+            .ok_or_else(|| AppError::NotFound("Session not found".to_string()))?;
+        let rx = sess.log_broadcast.subscribe();
+        let stdin = sess
+            .stdin
+            .as_mut()
+            .ok_or_else(|| AppError::Conflict("Session is not accepting input".to_string()))?;
+
+        // The leading newline keeps the marker on its own line even when the
+        // command's output does not end with one.
+        let script = format!("{}\nprintf '\\n{}%d\\n' \"$?\"\n", command, marker);
+        stdin.write_all(script.as_bytes()).await.map_err(|e| {
+            AppError::InternalServerError(format!("Failed to write to stdin: {}", e))
+        })?;
+        sess.last_used_at = std::time::SystemTime::now();
+
+        let log_entry = format!("[exec] {}", command);
+        {
+            sess.logs.write().await.push(log_entry.clone());
+        }
+        let _ = sess.log_broadcast.send(log_entry);
+        rx
+    };
+
+    let start = std::time::Instant::now();
+    let mut output = CommandOutput::default();
+
+    let wait = async {
+        loop {
+            match rx.recv().await {
+                Ok(entry) => {
+                    if let Some(code) = output.push(&entry, &marker) {
+                        return code;
+                    }
+                }
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => return -1,
+            }
+        }
+    };
+
+    let exit_code = match timeout(time_limit, wait).await {
+        Ok(code) => code,
+        Err(_) => return Ok(None),
+    };
+
+    let CommandOutput {
+        mut stdout, stderr, ..
+    } = output;
+    // Drop the blank line contributed by the marker's leading newline.
+    if stdout.ends_with('\n') {
+        stdout.pop();
+    }
+
+    let duration_ms = crate::utils::common::elapsed_ms(start);
+    Ok(Some(SessionCommandResult {
+        command: command.to_string(),
+        exit_code,
+        stdout,
+        stderr,
+        duration: duration_ms,
+        duration_ms,
+    }))
+}
+
+/// Output of one batch command, gathered from the session's log entries.
+#[derive(Default)]
+struct CommandOutput {
+    stdout: String,
+    stderr: String,
+    /// Where the stdout line being received starts, while its pieces arrive
+    partial_line: Option<usize>,
+}
+
+impl CommandOutput {
+    /// Record one log entry; returns the exit code once the marker arrives.
+    /// Pieces of lines cut at `MAX_LOG_LINE_BYTES` are joined back together,
+    /// and only a whole line is taken for the marker.
+    fn push(&mut self, entry: &str, marker: &str) -> Option<i32> {
+        match split_output_entry(entry) {
+            Some(("stdout", piece, continued)) => {
+                let start = self.partial_line.take().unwrap_or(self.stdout.len());
+                self.stdout.push_str(piece);
+                if continued {
+                    self.partial_line = Some(start);
+                } else if let Some(code) = parse_exit_marker(&self.stdout[start..], marker) {
+                    self.stdout.truncate(start);
+                    return Some(code);
+                }
+            }
+            Some((_, line, _)) => self.stderr.push_str(line),
+            None => {}
+        }
+        None
+    }
+}
+
+/// Exit code carried by a marker line, if `line` is the expected marker.
+fn parse_exit_marker(line: &str, marker: &str) -> Option<i32> {
+    line.trim_end()
+        .strip_prefix(marker)
+        .map(|code| code.parse().unwrap_or(-1))
+}
+
 #[derive(Deserialize)]
 pub struct SessionCdRequest {
     path: String,
@@ -320,6 +573,7 @@ pub struct SessionCdRequest {
 
 pub async fn session_cd(
     State(state): State<Arc<AppState>>,
+    caller: Caller,
     Path(id): Path<String>,
     Json(req): Json<SessionCdRequest>,
 ) -> Result<Json<ApiResponse<SessionCdResponse>>, AppError> {
@@ -327,6 +581,7 @@ pub async fn session_cd(
     let sess = sessions
         .get_mut(&id)
         .ok_or_else(|| AppError::NotFound("Session not found".to_string()))?;
+    caller.check_owner(&sess.owner, "Session")?;
 
     let current_cwd = std::path::Path::new(&sess.cwd);
     let new_path = if std::path::Path::new(&req.path).is_absolute() {
@@ -345,12 +600,7 @@ pub async fn session_cd(
 
         let log_entry = format!("[cd] {}", new_path.to_string_lossy());
         {
-            const MAX_LOG_LINES: usize = 10000;
-            let mut logs = sess.logs.write().await;
-            if logs.len() >= MAX_LOG_LINES {
-                logs.pop_front();
-            }
-            logs.push_back(log_entry.clone());
+            sess.logs.write().await.push(log_entry.clone());
         }
         let _ = sess.log_broadcast.send(log_entry);
     }
@@ -362,12 +612,14 @@ pub async fn session_cd(
 
 pub async fn terminate_session(
     State(state): State<Arc<AppState>>,
+    caller: Caller,
     Path(id): Path<String>,
 ) -> Result<Json<ApiResponse<SessionOperationResponse>>, AppError> {
     let mut sessions = state.sessions.write().await;
     let sess = sessions
         .get_mut(&id)
         .ok_or_else(|| AppError::NotFound("Session not found".to_string()))?;
+    caller.check_owner(&sess.owner, "Session")?;
 
     if let Some(pid) = sess.pid {
         nix::sys::signal::kill(
@@ -387,8 +639,72 @@ pub async fn terminate_session(
     })))
 }
 
+#[derive(Deserialize)]
+pub struct SessionSignalParams {
+    /// SIGINT (default), SIGTERM, SIGHUP or SIGKILL
+    signal: Option<String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionSignalResponse {
+    signal: String,
+    /// Processes that were signaled; empty when the shell was idle
+    pids: Vec<u32>,
+}
+
+/// Send a signal, SIGINT (Ctrl-C) by default, to the commands running in a
+/// session. The shell leads its own process group and every other member gets
+/// the signal; the shell itself is skipped, because a non-interactive shell
+/// exits when it receives SIGINT.
+pub async fn signal_session(
+    State(state): State<Arc<AppState>>,
+    caller: Caller,
+    Path(id): Path<String>,
+    Query(params): Query<SessionSignalParams>,
+) -> Result<Json<ApiResponse<SessionSignalResponse>>, AppError> {
+    let signal = parse_signal(params.signal.as_deref().unwrap_or("SIGINT"));
+    let shell_pid = {
+        let sessions = state.sessions.read().await;
+        let sess = sessions
+            .get(&id)
+            .ok_or_else(|| AppError::NotFound("Session not found".to_string()))?;
+        caller.check_owner(&sess.owner, "Session")?;
+        if sess.status != "active" {
+            return Err(AppError::Conflict("Session is not active".to_string()));
+        }
+        sess.pid.ok_or_else(|| {
+            AppError::NotFound("Session PID not found (session might have exited)".to_string())
+        })?
+    };
+
+    let mut pids = Vec::new();
+    for pid in process_group_members(shell_pid).await {
+        if pid == shell_pid {
+            continue;
+        }
+        match nix::sys::signal::kill(nix::unistd::Pid::from_raw(pid as i32), signal) {
+            Ok(()) => pids.push(pid),
+            // Finished between the scan and the signal
+            Err(nix::errno::Errno::ESRCH) => {}
+            Err(e) => {
+                return Err(AppError::InternalServerError(format!(
+                    "Failed to signal process {}: {}",
+                    pid, e
+                )))
+            }
+        }
+    }
+
+    Ok(Json(ApiResponse::success(SessionSignalResponse {
+        signal: signal.as_str().to_string(),
+        pids,
+    })))
+}
+
 pub async fn get_session_logs(
     State(state): State<Arc<AppState>>,
+    caller: Caller,
     Path(id): Path<String>,
     Query(params): Query<std::collections::HashMap<String, String>>,
 ) -> Result<Json<ApiResponse<SessionLogsResponse>>, AppError> {
@@ -396,19 +712,10 @@ pub async fn get_session_logs(
     let sess = sessions
         .get(&id)
         .ok_or_else(|| AppError::NotFound("Session not found".to_string()))?;
+    caller.check_owner(&sess.owner, "Session")?;
 
     let tail = params.get("tail").and_then(|t| t.parse::<usize>().ok());
-    let logs = sess.logs.read().await;
-
-    let result_logs: Vec<String> = if let Some(t) = tail {
-        if t < logs.len() {
-            logs.iter().skip(logs.len() - t).cloned().collect()
-        } else {
-            logs.clone().into()
-        }
-    } else {
-        logs.clone().into()
-    };
+    let result_logs = sess.logs.read().await.tail_texts(tail);
 
     Ok(Json(ApiResponse::success(SessionLogsResponse {
         session_id: id,
@@ -420,6 +727,28 @@ pub async fn get_session_logs(
 mod tests {
     use super::*;
 
+    #[tokio::test]
+    async fn test_wait_for_subscribers() {
+        let (tx, mut rx) = tokio::sync::broadcast::channel(4);
+        let throttled = Arc::new(AtomicUsize::new(0));
+        tx.send("a".to_string()).unwrap();
+        // Below the mark nothing waits
+        wait_for_subscribers(&tx, 2, &throttled).await;
+        tx.send("b".to_string()).unwrap();
+
+        let waiter = {
+            let (tx, throttled) = (tx.clone(), throttled.clone());
+            tokio::spawn(async move { wait_for_subscribers(&tx, 2, &throttled).await })
+        };
+        tokio::time::sleep(THROTTLE_POLL * 2).await;
+        assert_eq!(throttled.load(Ordering::Relaxed), 1);
+        assert!(!waiter.is_finished());
+
+        rx.recv().await.unwrap();
+        waiter.await.unwrap();
+        assert_eq!(throttled.load(Ordering::Relaxed), 0);
+    }
+
     #[test]
     fn test_create_session_response_serialization() {
         let response = CreateSessionResponse {
@@ -437,4 +766,40 @@ mod tests {
         assert!(json.contains("\"shell\":\"/bin/bash\""));
         assert!(json.contains("\"cwd\":\"/home/devbox/project\""));
     }
+
+    #[test]
+    fn test_parse_exit_marker() {
+        let marker = "__DEVBOX_EXIT_abc:";
+        assert_eq!(parse_exit_marker("__DEVBOX_EXIT_abc:0\n", marker), Some(0));
+        assert_eq!(
+            parse_exit_marker("__DEVBOX_EXIT_abc:127\n", marker),
+            Some(127)
+        );
+        assert_eq!(parse_exit_marker("__DEVBOX_EXIT_xyz:1\n", marker), None);
+        assert_eq!(parse_exit_marker("hello\n", marker), None);
+    }
+
+    #[test]
+    fn test_command_output_joins_continued_lines() {
+        let marker = "__DEVBOX_EXIT_abc:";
+        let mut output = CommandOutput::default();
+        for entry in [
+            "[exec] long",
+            "[stdout]+ aaaa",
+            "[stdout]+ bbbb",
+            "[stdout] cc\n",
+            "[stderr]+ warn",
+            "[stderr] ing\n",
+            // The rest of a cut line is not the marker, though it looks like it
+            "[stdout]+ x",
+            "[stdout] __DEVBOX_EXIT_abc:9\n",
+        ] {
+            assert_eq!(output.push(entry, marker), None);
+        }
+        // A marker cut into pieces is recognised once its line is complete
+        assert_eq!(output.push("[stdout]+ __DEVBOX_", marker), None);
+        assert_eq!(output.push("[stdout] EXIT_abc:3\n", marker), Some(3));
+        assert_eq!(output.stdout, "aaaabbbbcc\nx__DEVBOX_EXIT_abc:9\n");
+        assert_eq!(output.stderr, "warning\n");
+    }
 }
@@ -1,4 +1,6 @@
+use crate::middleware::auth::Caller;
 use crate::state::AppState;
+use crate::utils::common::split_output_entry;
 use axum::{
     extract::{
         ws::{Message, WebSocket, WebSocketUpgrade},
@@ -6,11 +8,16 @@ use axum::{
     },
     response::IntoResponse,
 };
-use futures::{sink::SinkExt, stream::StreamExt};
+use futures::{
+    sink::SinkExt,
+    stream::{SelectAll, Stream, StreamExt},
+};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::broadcast::error::RecvError;
+use tokio_stream::wrappers::{errors::BroadcastStreamRecvError, BroadcastStream};
 
 #[derive(Deserialize)]
 struct SubscriptionOptions {
@@ -38,6 +45,9 @@ struct LogEntry {
     content: String,
     timestamp: i64,
     sequence: i64,
+    /// Set when the line was cut at `MAX_LOG_LINE_BYTES`; the next entry continues it
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    continued: bool,
     #[serde(skip_serializing_if = "Option::is_none")]
     source: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -61,6 +71,19 @@ struct LogMessage {
     is_history: Option<bool>,
 }
 
+/// Sent instead of log lines a subscriber missed by falling behind the
+/// broadcast buffer (`LOG_BROADCAST_CAPACITY`).
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct DroppedMessage {
+    #[serde(rename = "type")]
+    msg_type: String, // "dropped"
+    data_type: String,
+    target_id: String,
+    dropped: u64,
+    timestamp: i64,
+}
+
 #[derive(Serialize)]
 #[serde(rename_all = "camelCase")]
 struct SubscriptionResult {
@@ -104,39 +127,195 @@ struct SubscriptionInfo {
 
 struct ActiveSubscriptionEntry {
     info: SubscriptionInfo,
+    /// History lines requested so far, to tell whether a refresh asks for more
+    tail: usize,
     handle: tokio::task::JoinHandle<()>,
 }
 
 pub async fn ws_handler(
     ws: WebSocketUpgrade,
     State(state): State<Arc<AppState>>,
+    caller: Caller,
 ) -> impl IntoResponse {
-    ws.on_upgrade(|socket| handle_socket(socket, state))
+    ws.on_upgrade(|socket| handle_socket(socket, state, caller))
+}
+
+/// A stored log entry split into the fields clients see.
+pub(crate) struct ParsedLogEntry {
+    pub level: String,
+    pub content: String,
+    /// The line was cut at `MAX_LOG_LINE_BYTES` and the next entry of the
+    /// same stream continues it
+    pub continued: bool,
 }
 
-fn parse_log_entry(raw_log: &str) -> (String, String) {
-    if raw_log.starts_with("[stdout] ") {
-        ("stdout".to_string(), raw_log[9..].to_string())
-    } else if raw_log.starts_with("[stderr] ") {
-        ("stderr".to_string(), raw_log[9..].to_string())
-    } else if raw_log.starts_with("[system] ") {
-        ("system".to_string(), raw_log[9..].to_string())
-    } else if raw_log.starts_with("[exec] ") {
-        (
-            "system".to_string(),
-            format!("Executing: {}", &raw_log[7..]),
-        )
-    } else if raw_log.starts_with("[cd] ") {
-        (
-            "system".to_string(),
-            format!("Changed directory to: {}", &raw_log[5..]),
-        )
+pub(crate) fn parse_log_entry(raw_log: &str) -> ParsedLogEntry {
+    let entry = |level: &str, content: String| ParsedLogEntry {
+        level: level.to_string(),
+        content,
+        continued: false,
+    };
+    if let Some((stream, text, continued)) = split_output_entry(raw_log) {
+        ParsedLogEntry {
+            level: stream.to_string(),
+            content: text.to_string(),
+            continued,
+        }
+    } else if let Some(rest) = raw_log.strip_prefix("[system] ") {
+        entry("system", rest.to_string())
+    } else if let Some(rest) = raw_log.strip_prefix("[exec] ") {
+        entry("system", format!("Executing: {}", rest))
+    } else if let Some(rest) = raw_log.strip_prefix("[cd] ") {
+        entry("system", format!("Changed directory to: {}", rest))
     } else {
-        ("unknown".to_string(), raw_log.to_string())
+        entry("unknown", raw_log.to_string())
+    }
+}
+
+/// Target ID of a process subscription that follows every process.
+const ALL_TARGETS: &str = "*";
+
+fn dropped_message(data_type: &str, target_id: &str, dropped: u64) -> String {
+    serde_json::to_string(&DroppedMessage {
+        msg_type: "dropped".to_string(),
+        data_type: data_type.to_string(),
+        target_id: target_id.to_string(),
+        dropped,
+        timestamp: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs() as i64,
+    })
+    .unwrap()
+}
+
+/// Log lines from one process broadcast, tagged with the process ID. A lag
+/// yields `Err` with the number of lines skipped.
+fn tagged_logs(
+    process_id: String,
+    rx: tokio::sync::broadcast::Receiver<String>,
+) -> impl Stream<Item = (String, Result<String, u64>)> {
+    BroadcastStream::new(rx).map(move |log| {
+        let log = log.map_err(|BroadcastStreamRecvError::Lagged(n)| n);
+        (process_id.clone(), log)
+    })
+}
+
+fn process_log_message(
+    process_id: &str,
+    raw_log: &str,
+    sequence: i64,
+    is_history: bool,
+) -> (String, String) {
+    let ParsedLogEntry {
+        level,
+        content,
+        continued,
+    } = parse_log_entry(raw_log);
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs() as i64;
+    let msg = serde_json::to_string(&LogMessage {
+        msg_type: "log".to_string(),
+        data_type: "process".to_string(),
+        target_id: process_id.to_string(),
+        log: LogEntry {
+            level: level.clone(),
+            content,
+            timestamp,
+            sequence,
+            continued,
+            source: None,
+            target_id: Some(process_id.to_string()),
+            target_type: Some("process".to_string()),
+            message: None,
+        },
+        sequence,
+        is_history: Some(is_history),
+    })
+    .unwrap();
+    (level, msg)
+}
+
+/// Forward logs from every process in the store that `caller` owns,
+/// attaching to processes as `exec_process` announces them. Each message's
+/// `targetId` names its source.
+async fn forward_all_processes(
+    state: Arc<AppState>,
+    caller: Caller,
+    tx: tokio::sync::mpsc::Sender<String>,
+    levels: Vec<String>,
+    tail: usize,
+) {
+    // Listen for new processes before the first scan so none slip through
+    let mut created = state.process_created.subscribe();
+    let mut attached = HashSet::new();
+    let mut streams = SelectAll::new();
+    let mut sequence = 0;
+    let mut first_scan = true;
+
+    loop {
+        let mut history = Vec::new();
+        {
+            let processes = state.processes.read().await;
+            for (id, proc) in processes.iter() {
+                if !caller.owns(&proc.owner) || !attached.insert(id.clone()) {
+                    continue;
+                }
+                if first_scan && tail > 0 {
+                    let logs = proc.logs.read().await;
+                    history.extend(logs.tail(Some(tail)).map(|l| (id.clone(), l.text.clone())));
+                }
+                streams.push(tagged_logs(id.clone(), proc.log_broadcast.subscribe()));
+            }
+        }
+        first_scan = false;
+
+        for (id, log) in history {
+            let (level, msg) = process_log_message(&id, &log, sequence, true);
+            if !levels.is_empty() && !levels.contains(&level) {
+                continue;
+            }
+            if tx.send(msg).await.is_err() {
+                return;
+            }
+            sequence += 1;
+        }
+
+        // Forward logs until the next process is announced, then rescan
+        loop {
+            tokio::select! {
+                Some((id, log)) = streams.next() => {
+                    let log = match log {
+                        Ok(log) => log,
+                        Err(dropped) => {
+                            if tx.send(dropped_message("process", &id, dropped)).await.is_err() {
+                                return;
+                            }
+                            continue;
+                        }
+                    };
+                    let (level, msg) = process_log_message(&id, &log, sequence, false);
+                    if !levels.is_empty() && !levels.contains(&level) {
+                        continue;
+                    }
+                    if tx.send(msg).await.is_err() {
+                        return;
+                    }
+                    sequence += 1;
+                }
+                event = created.recv() => match event {
+                    Ok(_) | Err(RecvError::Lagged(_)) => break,
+                    Err(RecvError::Closed) => return,
+                },
+            }
+        }
     }
 }
 
-async fn handle_socket(socket: WebSocket, state: Arc<AppState>) {
+/// Processes and sessions of other tokens are reported as not found.
+async fn handle_socket(socket: WebSocket, state: Arc<AppState>, caller: Caller) {
     let (mut sender, mut receiver) = socket.split();
     let (tx, mut rx) = tokio::sync::mpsc::channel::<String>(100);
 
@@ -162,18 +341,32 @@ async fn handle_socket(socket: WebSocket, state: Arc<AppState>) {
                     .unwrap_or_default()
                     .as_secs() as i64;
 
+                // A process subscription without a targetId covers every process
+                let target_id = req.target_id.clone().or_else(|| {
+                    (req.target_type.as_deref() == Some("process")).then(|| ALL_TARGETS.to_string())
+                });
+
                 if req.action == "subscribe" {
                     if let (Some(target_type), Some(target_id)) =
-                        (req.target_type.clone(), req.target_id.clone())
+                        (req.target_type.clone(), target_id)
                     {
                         let sub_key = format!("{}:{}", target_type, target_id);
 
-                        if active_subscriptions.contains_key(&sub_key) {
+                        // Each subscription holds a task, so a connection may only
+                        // have so many; refreshing an existing one is always allowed
+                        let limit = state.config.max_subscriptions_per_socket;
+                        if limit > 0
+                            && !active_subscriptions.contains_key(&sub_key)
+                            && active_subscriptions.len() >= limit
+                        {
                             let _ = tx
                                 .send(
                                     serde_json::to_string(&ErrorMessage {
-                                        status: 1400,
-                                        message: "Subscription already exists".to_string(),
+                                        status: 1429,
+                                        message: format!(
+                                            "Subscription limit of {} reached; unsubscribe before subscribing to more",
+                                            limit
+                                        ),
                                     })
                                     .unwrap(),
                                 )
@@ -181,30 +374,92 @@ async fn handle_socket(socket: WebSocket, state: Arc<AppState>) {
                             continue;
                         }
 
-                        let state_clone = state.clone();
-                        let tx_clone = tx.clone();
-                        let levels = req
-                            .options
-                            .as_ref()
+                        // Subscribing again replaces the forwarding task, keeping
+                        // any option the new request leaves out
+                        let previous = active_subscriptions.remove(&sub_key);
+                        if let Some(previous) = &previous {
+                            previous.handle.abort();
+                        }
+                        let options = req.options.as_ref();
+                        let levels = options
                             .and_then(|o| o.levels.clone())
+                            .or_else(|| previous.as_ref().map(|p| p.info.log_levels.clone()))
                             .unwrap_or_default();
-                        let tail = req.options.as_ref().and_then(|o| o.tail).unwrap_or(0);
+                        let previous_tail = previous.as_ref().map(|p| p.tail);
+                        let tail = options.and_then(|o| o.tail).or(previous_tail).unwrap_or(0);
+                        // History is sent again only when the refresh asks for more of it
+                        let replay = match previous_tail {
+                            Some(sent) if tail <= sent => 0,
+                            _ => tail,
+                        };
+                        let created_at = previous.as_ref().map_or(timestamp, |p| p.info.created_at);
+                        let extra = previous.is_some().then(|| {
+                            HashMap::from([(
+                                "refreshed".to_string(),
+                                serde_json::Value::Bool(true),
+                            )])
+                        });
+
+                        let state_clone = state.clone();
+                        let tx_clone = tx.clone();
+
+                        if target_type == "process" && target_id == ALL_TARGETS {
+                            let handle = tokio::spawn(forward_all_processes(
+                                state_clone,
+                                caller.clone(),
+                                tx_clone,
+                                levels.clone(),
+                                replay,
+                            ));
+                            active_subscriptions.insert(
+                                sub_key.clone(),
+                                ActiveSubscriptionEntry {
+                                    info: SubscriptionInfo {
+                                        id: sub_key,
+                                        target_type: target_type.clone(),
+                                        target_id: target_id.clone(),
+                                        log_levels: levels.clone(),
+                                        created_at,
+                                        active: true,
+                                    },
+                                    tail,
+                                    handle,
+                                },
+                            );
+                            let _ = tx
+                                .send(
+                                    serde_json::to_string(&SubscriptionResult {
+                                        action: "subscribed".to_string(),
+                                        target_type,
+                                        target_id,
+                                        levels: Some(
+                                            levels.into_iter().map(|l| (l, true)).collect(),
+                                        ),
+                                        timestamp,
+                                        extra,
+                                    })
+                                    .unwrap(),
+                                )
+                                .await;
+                            continue;
+                        }
 
                         // Subscribe logic
                         let broadcast_rx = match target_type.as_str() {
                             "process" => {
                                 let processes = state_clone.processes.read().await;
-                                if let Some(proc) = processes.get(&target_id) {
+                                if let Some(proc) =
+                                    processes.get(&target_id).filter(|p| caller.owns(&p.owner))
+                                {
                                     // Send historical logs if requested
-                                    if tail > 0 {
+                                    if replay > 0 {
                                         let logs = proc.logs.read().await;
-                                        let start_idx = if logs.len() > tail {
-                                            logs.len() - tail
-                                        } else {
-                                            0
-                                        };
-                                        for (i, log) in logs.iter().skip(start_idx).enumerate() {
-                                            let (level, content) = parse_log_entry(log);
+                                        for (i, log) in logs.tail(Some(replay)).enumerate() {
+                                            let ParsedLogEntry {
+                                                level,
+                                                content,
+                                                continued,
+                                            } = parse_log_entry(&log.text);
                                             if !levels.is_empty() && !levels.contains(&level) {
                                                 continue;
                                             }
@@ -216,8 +471,9 @@ async fn handle_socket(socket: WebSocket, state: Arc<AppState>) {
                                                 log: LogEntry {
                                                     level,
                                                     content,
-                                                    timestamp, // Historical logs use current time for now as we don't store timestamp per log line
+                                                    timestamp: (log.at_ms / 1000) as i64,
                                                     sequence: i as i64,
+                                                    continued,
                                                     source: None,
                                                     target_id: Some(target_id.clone()),
                                                     target_type: Some(target_type.clone()),
@@ -237,17 +493,18 @@ async fn handle_socket(socket: WebSocket, state: Arc<AppState>) {
                             }
                             "session" => {
                                 let sessions = state_clone.sessions.read().await;
-                                if let Some(sess) = sessions.get(&target_id) {
+                                if let Some(sess) =
+                                    sessions.get(&target_id).filter(|s| caller.owns(&s.owner))
+                                {
                                     // Send historical logs if requested
-                                    if tail > 0 {
+                                    if replay > 0 {
                                         let logs = sess.logs.read().await;
-                                        let start_idx = if logs.len() > tail {
-                                            logs.len() - tail
-                                        } else {
-                                            0
-                                        };
-                                        for (i, log) in logs.iter().skip(start_idx).enumerate() {
-                                            let (level, content) = parse_log_entry(log);
+                                        for (i, log) in logs.tail(Some(replay)).enumerate() {
+                                            let ParsedLogEntry {
+                                                level,
+                                                content,
+                                                continued,
+                                            } = parse_log_entry(&log.text);
                                             if !levels.is_empty() && !levels.contains(&level) {
                                                 continue;
                                             }
@@ -259,8 +516,9 @@ async fn handle_socket(socket: WebSocket, state: Arc<AppState>) {
                                                 log: LogEntry {
                                                     level,
                                                     content,
-                                                    timestamp,
+                                                    timestamp: (log.at_ms / 1000) as i64,
                                                     sequence: i as i64,
+                                                    continued,
                                                     source: None,
                                                     target_id: Some(target_id.clone()),
                                                     target_type: Some(target_type.clone()),
@@ -305,8 +563,27 @@ async fn handle_socket(socket: WebSocket, state: Arc<AppState>) {
 
                             let handle = tokio::spawn(async move {
                                 let mut sequence = 0;
-                                while let Ok(log) = rx.recv().await {
-                                    let (level, content) = parse_log_entry(&log);
+                                loop {
+                                    let log = match rx.recv().await {
+                                        Ok(log) => log,
+                                        Err(RecvError::Lagged(dropped)) => {
+                                            let msg = dropped_message(
+                                                &target_type_inner,
+                                                &target_id_inner,
+                                                dropped,
+                                            );
+                                            if tx_clone.send(msg).await.is_err() {
+                                                break;
+                                            }
+                                            continue;
+                                        }
+                                        Err(RecvError::Closed) => break,
+                                    };
+                                    let ParsedLogEntry {
+                                        level,
+                                        content,
+                                        continued,
+                                    } = parse_log_entry(&log);
 
                                     if !levels_inner.is_empty() && !levels_inner.contains(&level) {
                                         continue;
@@ -327,6 +604,7 @@ async fn handle_socket(socket: WebSocket, state: Arc<AppState>) {
                                             content,
                                             timestamp,
                                             sequence,
+                                            continued,
                                             source: None,
                                             target_id: Some(target_id_inner.clone()),
                                             target_type: Some(target_type_inner.clone()),
@@ -353,9 +631,10 @@ async fn handle_socket(socket: WebSocket, state: Arc<AppState>) {
                                         target_type: target_type.clone(),
                                         target_id: target_id.clone(),
                                         log_levels: levels.clone(),
-                                        created_at: timestamp,
+                                        created_at,
                                         active: true,
                                     },
+                                    tail,
                                     handle,
                                 },
                             );
@@ -374,7 +653,7 @@ async fn handle_socket(socket: WebSocket, state: Arc<AppState>) {
                                         target_id: target_id.clone(),
                                         levels: Some(levels_map),
                                         timestamp,
-                                        extra: None,
+                                        extra,
                                     })
                                     .unwrap(),
                                 )
@@ -394,7 +673,7 @@ async fn handle_socket(socket: WebSocket, state: Arc<AppState>) {
                     }
                 } else if req.action == "unsubscribe" {
                     if let (Some(target_type), Some(target_id)) =
-                        (req.target_type.clone(), req.target_id.clone())
+                        (req.target_type.clone(), target_id)
                     {
                         let sub_key = format!("{}:{}", target_type, target_id);
                         if let Some(entry) = active_subscriptions.remove(&sub_key) {
@@ -446,3 +725,30 @@ async fn handle_socket(socket: WebSocket, state: Arc<AppState>) {
 
     send_task.abort();
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_log_entry() {
+        let parsed = parse_log_entry("[stdout] hello");
+        assert_eq!(
+            (parsed.level.as_str(), parsed.content.as_str()),
+            ("stdout", "hello")
+        );
+        assert!(!parsed.continued);
+
+        let parsed = parse_log_entry("[stderr]+ first part");
+        assert_eq!(
+            (parsed.level.as_str(), parsed.content.as_str()),
+            ("stderr", "first part")
+        );
+        assert!(parsed.continued);
+
+        let parsed = parse_log_entry("[exec] ls");
+        assert_eq!(parsed.level, "system");
+        assert_eq!(parsed.content, "Executing: ls");
+        assert_eq!(parse_log_entry("[stdout]+x").level, "unknown");
+    }
+}
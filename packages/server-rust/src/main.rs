@@ -8,7 +8,10 @@ mod router;
 mod state;
 mod utils;
 
+use middleware::idle::{ConnActivity, IdleTimeoutListener};
 use std::net::SocketAddr;
+use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+use std::path::Path;
 use std::process;
 
 #[tokio::main(flavor = "current_thread")]
@@ -29,10 +32,36 @@ async fn main() {
         println!("    server-rust [OPTIONS]");
         println!();
         println!("OPTIONS:");
-        println!("    --addr=<ADDRESS>            Sets the server listening address. [env: ADDR] [default: 0.0.0.0:9757]");
+        println!("    --addr=<ADDRESS>            Sets the server listening address, or unix:<PATH> for a Unix socket. [env: ADDR] [default: 0.0.0.0:9757]");
         println!("    --workspace-path=<PATH>     Sets the base workspace directory. [env: WORKSPACE_PATH] [default: /home/devbox/project]");
+        println!("    --workspaces=<NAME=PATH,..> Sets named workspaces selectable with the X-Workspace header. [env: WORKSPACES]");
+        println!("    --default-cwd=<PATH>        Sets the working directory for commands without a cwd. [env: DEFAULT_CWD] [default: the workspace]");
+        println!("    --state-dir=<PATH>          Persists the process registry across restarts in this directory. [env: STATE_DIR]");
+        println!("    --temp-dir=<PATH>           Stages uploads and atomic writes here when on the target's filesystem. [env: TEMP_DIR] [default: WORKSPACE_PATH/.devbox-tmp]");
+        println!("    --startup-command=<CMD>     Runs this shell command once the server is listening; logs are at process/startup/logs. [env: STARTUP_COMMAND]");
         println!("    --max-file-size=<BYTES>     Sets the maximum file size for uploads in bytes. [env: MAX_FILE_SIZE] [default: 104857600]");
+        println!("    --max-output-bytes=<BYTES>  Sets the default cap on captured exec-sync output per stream. [env: MAX_OUTPUT_BYTES] [default: 10485760]");
+        println!("    --io-buffer-size=<BYTES>    Sets the buffer size for streaming file reads and downloads. [env: IO_BUFFER_SIZE] [default: 65536]");
+        println!("    --http-idle-timeout=<SECS>  Closes connections idle this long between requests; SSE and WebSocket connections are exempt, 0 disables. [env: HTTP_IDLE_TIMEOUT] [default: 60]");
+        println!("    --log-broadcast-capacity=<N> Sets how many log lines are buffered for slow live subscribers. [env: LOG_BROADCAST_CAPACITY] [default: 100]");
+        println!("    --max-log-line-bytes=<BYTES> Splits longer output lines into pieces of this size. [env: MAX_LOG_LINE_BYTES] [default: 1048576]");
+        println!("    --session-output-high-water=<N> Pauses session output while N lines wait for a slow subscriber (0 disables). [env: SESSION_OUTPUT_HIGH_WATER] [default: 0]");
+        println!("    --default-process-nice=<N>   Sets the niceness (-20..19) of background processes that don't request one. [env: DEFAULT_PROCESS_NICE]");
+        println!("    --timezone=<ZONE>           Renders timestamps in this IANA time zone, e.g. Asia/Shanghai. [env: TIMEZONE] [default: UTC]");
+        println!("    --time-format=<FORMAT>      Writes timestamp dates as calendar dates or ISO week dates (iso-week). [env: TIME_FORMAT] [default: calendar]");
         println!("    --token=<TOKEN>             Sets the authentication token. [env: TOKEN / DEVBOX_JWT_SECRET] [default: a random token if not provided]");
+        println!("    --tokens=<LABEL:TOKEN,...>  Extra tokens, each owning the processes and sessions it starts; TOKEN may act on all. [env: TOKENS]");
+        println!("    --disable-auth              Accepts every request without a token; only for trusted networks. [env: DISABLE_AUTH]");
+        println!("    --search-ignored-dirs=<DIRS> Sets directories skipped by search (comma-separated, prefix with '+' to extend the defaults). [env: SEARCH_IGNORED_DIRS]");
+        println!("    --command-denylist=<REGEX,..> Refuses commands matching any pattern. [env: COMMAND_DENYLIST]");
+        println!("    --command-allowlist=<REGEX,..> Only runs commands matching one of the patterns. [env: COMMAND_ALLOWLIST]");
+        println!("    --exec-presets=<JSON>       Defines named commands for process/run-preset. [env: EXEC_PRESETS]");
+        println!("    --excluded-ports=<PORT,..>  Hides these ports from /api/v1/ports, besides 22 and the listen port. [env: EXCLUDED_PORTS]");
+        println!("    --max-concurrent-requests=<N> Refuses API requests with 503 while N are in progress (0 disables). [env: MAX_CONCURRENT_REQUESTS] [default: 0]");
+        println!("    --max-download-paths=<N>      Most paths one batch download may name. [env: MAX_DOWNLOAD_PATHS] [default: 1000]");
+        println!("    --max-download-bytes=<BYTES>  Largest total file size of one batch download. [env: MAX_DOWNLOAD_BYTES] [default: 10737418240]");
+        println!("    --max-subscriptions-per-socket=<N> Most log subscriptions one WebSocket connection may hold (0 disables). [env: MAX_SUBSCRIPTIONS_PER_SOCKET] [default: 64]");
+        println!("    --quiet                     Suppresses the startup and shutdown messages on stdout. [env: QUIET]");
         println!();
         println!("    --help                      Prints this help information.");
         println!("    --version                   Prints version information.");
@@ -43,29 +72,83 @@ async fn main() {
 
     // Load config
     let config = config::Config::load();
+    if let Some(zone) = &config.timezone {
+        utils::tz::set_display_zone(zone.clone());
+    }
+    utils::common::set_time_format(config.time_format);
 
     // Initialize logging
-    println!("Workspace path: {:?}", config.workspace_path);
+    if !config.quiet {
+        println!("Workspace path: {:?}", config.workspace_path);
+    }
 
     // Initialize state
     let state = state::AppState::new(config.clone());
 
     // Create router
-    let app = router::create_router(state);
+    let app = router::create_router(state.clone());
+    let state = std::sync::Arc::new(state);
+    tokio::spawn(monitor::zombies::run_reaper(
+        state.zombie_reaper.clone(),
+        config.quiet,
+    ));
 
     // Bind server
+    if let Some(path) = config.addr.strip_prefix(UNIX_ADDR_PREFIX) {
+        serve_unix(Path::new(path), app, state).await;
+        return;
+    }
     let addr: SocketAddr = config.addr.parse().expect("Invalid address");
     let listener = tokio::net::TcpListener::bind(addr)
         .await
         .expect("Failed to bind to address");
-    println!("Server running on {}", addr);
-    axum::serve(listener, app)
-        .with_graceful_shutdown(shutdown_signal())
-        .await
-        .expect("Failed to start server");
+    if !config.quiet {
+        println!("Server running on {}", addr);
+    }
+    tokio::spawn(handlers::process::run_startup_command(state));
+    axum::serve(
+        IdleTimeoutListener::new(listener, config.http_idle_timeout),
+        app.into_make_service_with_connect_info::<ConnActivity>(),
+    )
+    .with_graceful_shutdown(shutdown_signal(config.quiet))
+    .await
+    .expect("Failed to start server");
 }
 
-async fn shutdown_signal() {
+/// `--addr=unix:/path/to.sock` binds a Unix domain socket instead of TCP.
+const UNIX_ADDR_PREFIX: &str = "unix:";
+
+/// Socket file mode: owner and group only, so a sidecar sharing the group can connect.
+const UNIX_SOCKET_MODE: u32 = 0o660;
+
+async fn serve_unix(path: &Path, app: axum::Router, state: std::sync::Arc<state::AppState>) {
+    // Replace a socket left behind by a previous run, but never a regular file
+    if let Ok(meta) = std::fs::symlink_metadata(path) {
+        if !meta.file_type().is_socket() {
+            panic!("Refusing to replace non-socket file {:?}", path);
+        }
+        std::fs::remove_file(path).expect("Failed to remove stale socket");
+    }
+    let listener = tokio::net::UnixListener::bind(path).expect("Failed to bind to socket");
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(UNIX_SOCKET_MODE))
+        .expect("Failed to set socket permissions");
+    let quiet = state.config.quiet;
+    let idle_timeout = state.config.http_idle_timeout;
+    if !quiet {
+        println!("Server running on {}{}", UNIX_ADDR_PREFIX, path.display());
+    }
+    tokio::spawn(handlers::process::run_startup_command(state));
+    axum::serve(
+        IdleTimeoutListener::new(listener, idle_timeout),
+        app.into_make_service_with_connect_info::<ConnActivity>(),
+    )
+    .with_graceful_shutdown(shutdown_signal(quiet))
+    .await
+    .expect("Failed to start server");
+    let _ = std::fs::remove_file(path);
+}
+
+async fn shutdown_signal(quiet: bool) {
     #[cfg(unix)]
     {
         use tokio::signal::unix::{signal, SignalKind};
@@ -82,7 +165,9 @@ async fn shutdown_signal() {
         wait_for_ctrl_c().await;
     }
 
-    println!("Shutdown signal received, stopping server...");
+    if !quiet {
+        println!("Shutdown signal received, stopping server...");
+    }
 }
 
 async fn wait_for_ctrl_c() {
@@ -1,21 +1,93 @@
+use crate::config::ADMIN_LABEL;
+use crate::error::AppError;
 use axum::{
-    extract::Request,
-    http::{header, StatusCode},
+    extract::{FromRequestParts, Request},
+    http::{header, request::Parts, StatusCode},
     middleware::Next,
     response::Response,
 };
+use std::convert::Infallible;
 use std::sync::Arc;
 
+/// Label of the token a request was made with. Processes and sessions record
+/// the label that started them, and only that label or the admin may act on
+/// them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Caller(pub String);
+
+impl Caller {
+    pub fn admin() -> Self {
+        Self(ADMIN_LABEL.to_string())
+    }
+
+    pub fn is_admin(&self) -> bool {
+        self.0 == ADMIN_LABEL
+    }
+
+    /// Whether this caller may act on something owned by `owner`.
+    pub fn owns(&self, owner: &str) -> bool {
+        self.is_admin() || self.0 == owner
+    }
+
+    /// `Forbidden` unless this caller may act on `what`, owned by `owner`.
+    pub fn check_owner(&self, owner: &str, what: &str) -> Result<(), AppError> {
+        if self.owns(owner) {
+            return Ok(());
+        }
+        Err(AppError::Forbidden(format!(
+            "{} belongs to another token",
+            what
+        )))
+    }
+
+    /// `Forbidden` unless this is the admin, for actions that reach the
+    /// processes and sessions of every token.
+    pub fn check_admin(&self, action: &str) -> Result<(), AppError> {
+        if self.is_admin() {
+            return Ok(());
+        }
+        Err(AppError::Forbidden(format!(
+            "Only the admin token may {}",
+            action
+        )))
+    }
+}
+
+/// Set by `auth_middleware` on every authenticated request. Routes it does
+/// not guard carry no label and act as the admin, as do all requests when
+/// auth is disabled.
+impl<S: Send + Sync> FromRequestParts<S> for Caller {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Ok(parts
+            .extensions
+            .get::<Caller>()
+            .cloned()
+            .unwrap_or_else(Caller::admin))
+    }
+}
+
 pub async fn auth_middleware(
     // We can't easily extract State in middleware without some boilerplate or using `axum::middleware::from_fn_with_state`.
     // We'll assume this is used with `from_fn_with_state`.
     axum::extract::State(state): axum::extract::State<Arc<crate::state::AppState>>,
-    req: Request,
+    mut req: Request,
     next: Next,
 ) -> Result<Response, StatusCode> {
-    // Skip auth for health checks
+    if state.config.disable_auth {
+        return Ok(next.run(req).await);
+    }
+
+    // Skip auth for health checks, feature discovery and OPTIONS, which
+    // browsers send without credentials and which never reaches a handler
     let path = req.uri().path();
-    if path == "/health" || path == "/health/live" || path == "/health/ready" {
+    if req.method() == axum::http::Method::OPTIONS
+        || path == "/health"
+        || path == "/health/live"
+        || path == "/health/ready"
+        || path == "/api/v1/capabilities"
+    {
         return Ok(next.run(req).await);
     }
 
@@ -29,15 +101,18 @@ pub async fn auth_middleware(
         Some(header_value) if header_value.starts_with("Bearer ") => {
             let token = &header_value[7..];
             if let Some(expected_token) = &state.config.token {
-                if token == expected_token {
+                let caller = if token == expected_token {
+                    Some(Caller::admin())
+                } else {
+                    state.config.tenant_tokens.get(token).cloned().map(Caller)
+                };
+                if let Some(caller) = caller {
+                    req.extensions_mut().insert(caller);
                     return Ok(next.run(req).await);
                 }
             } else {
-                // If no token is configured (shouldn't happen with our config logic), allow?
-                // Or if we decided to allow no-auth mode.
-                // Our config logic generates a token if missing, so we should always have one.
-                // But if the user explicitly set it to empty string?
-                // Let's assume strict auth if token is present.
+                // Config only leaves the token unset when auth is disabled,
+                // which returned above; never treat a missing token as open
                 return Err(StatusCode::UNAUTHORIZED);
             }
         }
@@ -46,3 +121,24 @@ pub async fn auth_middleware(
 
     Err(StatusCode::UNAUTHORIZED)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_caller_ownership() {
+        let ci = Caller("ci".to_string());
+        assert!(ci.owns("ci"));
+        assert!(!ci.owns("alice"));
+        assert!(!ci.owns(ADMIN_LABEL));
+        assert!(matches!(
+            ci.check_owner("alice", "Process"),
+            Err(AppError::Forbidden(_))
+        ));
+        assert!(Caller::admin().owns("alice"));
+        assert!(Caller::admin().check_owner("ci", "Session").is_ok());
+        assert!(matches!(ci.check_admin("x"), Err(AppError::Forbidden(_))));
+        assert!(Caller::admin().check_admin("x").is_ok());
+    }
+}
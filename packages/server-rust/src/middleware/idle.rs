@@ -0,0 +1,273 @@
+//! Closing of idle HTTP connections. axum's `serve` gives no access to
+//! hyper's header-read and keep-alive timeouts, so the listener wraps every
+//! connection in `IdleTimeoutIo`, which fails the pending read once the
+//! connection has gone `timeout` without a request in flight or a byte
+//! written. Bytes read do not count: a client dribbling out request headers
+//! is as idle as one sending nothing. Connections that carried an SSE stream
+//! or a WebSocket upgrade are exempt for the rest of their life.
+
+use axum::{
+    extract::{connect_info::ConnectInfo, connect_info::Connected, Request},
+    http::{header, StatusCode},
+    middleware::Next,
+    response::Response,
+    serve::{IncomingStream, Listener},
+};
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::time::{Instant, Sleep};
+
+/// What the idle check needs to know about one connection, shared between
+/// its IO and the requests served on it.
+struct Activity {
+    opened: Instant,
+    /// Milliseconds after `opened` of the last write or finished request
+    last_ms: AtomicU64,
+    in_flight: AtomicUsize,
+    exempt: AtomicBool,
+}
+
+impl Activity {
+    fn touch(&self) {
+        self.last_ms
+            .store(self.opened.elapsed().as_millis() as u64, Ordering::Relaxed);
+    }
+
+    /// When the connection times out, or `None` while it is in use.
+    fn idle_deadline(&self, timeout: Duration) -> Option<Instant> {
+        if self.exempt.load(Ordering::Relaxed) || self.in_flight.load(Ordering::Relaxed) > 0 {
+            return None;
+        }
+        let last = Duration::from_millis(self.last_ms.load(Ordering::Relaxed));
+        Some(self.opened + last + timeout)
+    }
+}
+
+/// Per-connection handle made available to requests as `ConnectInfo`.
+#[derive(Clone)]
+pub struct ConnActivity(Arc<Activity>);
+
+impl<L: Listener> Connected<IncomingStream<'_, IdleTimeoutListener<L>>> for ConnActivity {
+    fn connect_info(stream: IncomingStream<'_, IdleTimeoutListener<L>>) -> Self {
+        stream.io().activity.clone()
+    }
+}
+
+/// Listener whose connections close after `timeout` of idleness; `None`
+/// leaves them open indefinitely.
+pub struct IdleTimeoutListener<L> {
+    inner: L,
+    timeout: Option<Duration>,
+}
+
+impl<L> IdleTimeoutListener<L> {
+    pub fn new(inner: L, timeout: Option<Duration>) -> Self {
+        Self { inner, timeout }
+    }
+}
+
+impl<L: Listener> Listener for IdleTimeoutListener<L> {
+    type Io = IdleTimeoutIo<L::Io>;
+    type Addr = L::Addr;
+
+    async fn accept(&mut self) -> (Self::Io, Self::Addr) {
+        let (io, addr) = self.inner.accept().await;
+        (IdleTimeoutIo::new(io, self.timeout), addr)
+    }
+
+    fn local_addr(&self) -> io::Result<Self::Addr> {
+        self.inner.local_addr()
+    }
+}
+
+pub struct IdleTimeoutIo<T> {
+    inner: T,
+    activity: ConnActivity,
+    timeout: Option<Duration>,
+    timer: Pin<Box<Sleep>>,
+}
+
+impl<T> IdleTimeoutIo<T> {
+    fn new(inner: T, timeout: Option<Duration>) -> Self {
+        let opened = Instant::now();
+        Self {
+            inner,
+            activity: ConnActivity(Arc::new(Activity {
+                opened,
+                last_ms: AtomicU64::new(0),
+                in_flight: AtomicUsize::new(0),
+                exempt: AtomicBool::new(false),
+            })),
+            timeout,
+            timer: Box::pin(tokio::time::sleep_until(opened)),
+        }
+    }
+
+    /// Called while a read is pending: arm the timer for the idle deadline
+    /// and fail the read once it passes. A busy connection re-arms it a
+    /// whole `timeout` ahead and checks again then.
+    fn poll_idle(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let Some(timeout) = self.timeout else {
+            return Poll::Pending;
+        };
+        loop {
+            let deadline = self
+                .activity
+                .0
+                .idle_deadline(timeout)
+                .unwrap_or_else(|| Instant::now() + timeout);
+            if self.timer.deadline() != deadline {
+                self.timer.as_mut().reset(deadline);
+            }
+            if self.timer.as_mut().poll(cx).is_pending() {
+                return Poll::Pending;
+            }
+            // Requests may have come and gone since the timer was armed
+            if self
+                .activity
+                .0
+                .idle_deadline(timeout)
+                .is_some_and(|d| d <= Instant::now())
+            {
+                return Poll::Ready(Err(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    "connection idle",
+                )));
+            }
+        }
+    }
+}
+
+impl<T: AsyncRead + Unpin> AsyncRead for IdleTimeoutIo<T> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        match Pin::new(&mut this.inner).poll_read(cx, buf) {
+            Poll::Pending => this.poll_idle(cx),
+            ready => ready,
+        }
+    }
+}
+
+impl<T: AsyncWrite + Unpin> AsyncWrite for IdleTimeoutIo<T> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        let result = Pin::new(&mut this.inner).poll_write(cx, buf);
+        if matches!(result, Poll::Ready(Ok(n)) if n > 0) {
+            this.activity.0.touch();
+        }
+        result
+    }
+
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[io::IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        let result = Pin::new(&mut this.inner).poll_write_vectored(cx, bufs);
+        if matches!(result, Poll::Ready(Ok(n)) if n > 0) {
+            this.activity.0.touch();
+        }
+        result
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.inner.is_write_vectored()
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_shutdown(cx)
+    }
+}
+
+/// Marks the connection busy until the handler has produced its response,
+/// even when the handler future is dropped instead.
+struct InFlight(ConnActivity);
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        self.0 .0.touch();
+        self.0 .0.in_flight.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Keep connections open while their requests are being handled, and for
+/// good once they carry an event stream or a WebSocket.
+pub async fn track_requests(req: Request, next: Next) -> Response {
+    let Some(ConnectInfo(conn)) = req.extensions().get::<ConnectInfo<ConnActivity>>().cloned()
+    else {
+        return next.run(req).await;
+    };
+    conn.0.in_flight.fetch_add(1, Ordering::Relaxed);
+    let _in_flight = InFlight(conn.clone());
+
+    let response = next.run(req).await;
+    let streaming = response.status() == StatusCode::SWITCHING_PROTOCOLS
+        || response
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|v| v.starts_with("text/event-stream"));
+    if streaming {
+        conn.0.exempt.store(true, Ordering::Relaxed);
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    #[tokio::test]
+    async fn test_idle_timeout_io() {
+        let timeout = Some(Duration::from_millis(50));
+
+        let (client, server) = tokio::io::duplex(64);
+        let mut io = IdleTimeoutIo::new(server, timeout);
+        let mut buf = [0u8; 8];
+        let err = io.read(&mut buf).await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+        drop(client);
+
+        // A request in flight keeps the connection open past the timeout
+        let (mut client, server) = tokio::io::duplex(64);
+        let mut io = IdleTimeoutIo::new(server, timeout);
+        let activity = io.activity.clone();
+        activity.0.in_flight.fetch_add(1, Ordering::Relaxed);
+        let writer = tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(120)).await;
+            client.write_all(b"ping").await.unwrap();
+            client
+        });
+        assert_eq!(io.read(&mut buf).await.unwrap(), 4);
+        drop(InFlight(activity));
+        let _client = writer.await.unwrap();
+        let err = io.read(&mut buf).await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+
+        // Without a timeout reads wait indefinitely
+        let (_client, server) = tokio::io::duplex(64);
+        let mut io = IdleTimeoutIo::new(server, None);
+        let read = tokio::time::timeout(Duration::from_millis(100), io.read(&mut buf)).await;
+        assert!(read.is_err());
+    }
+}
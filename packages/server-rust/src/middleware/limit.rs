@@ -0,0 +1,60 @@
+//! Load shedding with `MAX_CONCURRENT_REQUESTS`: once that many API requests
+//! are being handled, further ones are refused straight away with 503 rather
+//! than queued, so a misbehaving client cannot run the server out of file
+//! descriptors. A request holds its slot until the handler returns; the body
+//! of a streamed response does not keep it.
+
+use crate::response::{ApiResponse, Status};
+use crate::state::AppState;
+use axum::{
+    extract::{Request, State},
+    http::{header, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde_json::json;
+use std::sync::Arc;
+
+/// Long-lived requests (paths below `/api/v1`) that would otherwise hold a
+/// slot for as long as they wait
+const UNLIMITED_PATHS: &[&str] = &[
+    "/files/sync",
+    "/files/list-watch",
+    "/process/sync-stream",
+    "/ports/wait",
+];
+
+pub async fn limit_requests(
+    State(state): State<Arc<AppState>>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let Some(slots) = &state.request_slots else {
+        return next.run(req).await;
+    };
+    if UNLIMITED_PATHS.contains(&req.uri().path()) {
+        return next.run(req).await;
+    }
+    let Ok(_permit) = slots.clone().try_acquire_owned() else {
+        return busy_response(state.config.max_concurrent_requests);
+    };
+    next.run(req).await
+}
+
+fn busy_response(limit: usize) -> Response {
+    let body = Json(ApiResponse::error(
+        Status::ServiceUnavailable,
+        format!(
+            "Server busy: limit of {} concurrent requests reached",
+            limit
+        ),
+        json!({}),
+    ));
+    (
+        StatusCode::SERVICE_UNAVAILABLE,
+        [(header::RETRY_AFTER, "1")],
+        body,
+    )
+        .into_response()
+}
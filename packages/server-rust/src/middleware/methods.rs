@@ -0,0 +1,112 @@
+//! Method handling for browsers and restrictive proxies: `OPTIONS` on any
+//! route is answered with the methods it accepts (and what a CORS preflight
+//! needs), and a `POST` may carry `X-HTTP-Method-Override` for proxies that
+//! only pass GET and POST. This wraps the whole router rather than being one
+//! of its layers, since those run after a route's method was already chosen.
+//!
+//! Requests carry their token in `Authorization`, never cookies, so responses
+//! allow any origin.
+
+use crate::error::AppError;
+use axum::{
+    extract::Request,
+    http::{header, HeaderValue, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+
+const METHOD_OVERRIDE_HEADER: &str = "x-http-method-override";
+
+/// Methods a `POST` may be turned into
+const OVERRIDABLE_METHODS: &[Method] = &[Method::GET, Method::PUT, Method::PATCH, Method::DELETE];
+
+/// How long a browser may cache a preflight answer, in seconds
+const PREFLIGHT_MAX_AGE: &str = "600";
+
+pub async fn handle_methods(mut req: Request, next: Next) -> Response {
+    if req.method() == Method::POST {
+        if let Some(value) = req.headers().get(METHOD_OVERRIDE_HEADER) {
+            match override_method(value) {
+                Ok(method) => *req.method_mut() = method,
+                Err(e) => return e.into_response(),
+            }
+        }
+    }
+    if req.method() != Method::OPTIONS {
+        let has_origin = req.headers().contains_key(header::ORIGIN);
+        let mut response = next.run(req).await;
+        if has_origin {
+            response.headers_mut().insert(
+                header::ACCESS_CONTROL_ALLOW_ORIGIN,
+                HeaderValue::from_static("*"),
+            );
+        }
+        return response;
+    }
+
+    let requested_headers = req
+        .headers()
+        .get(header::ACCESS_CONTROL_REQUEST_HEADERS)
+        .cloned();
+    // No route has an OPTIONS handler, so a known path answers 405 with the
+    // methods it does have in `Allow`
+    let response = next.run(req).await;
+    if response.status() != StatusCode::METHOD_NOT_ALLOWED {
+        return response;
+    }
+    let allow = match response.headers().get(header::ALLOW) {
+        Some(methods) => format!("{},OPTIONS", methods.to_str().unwrap_or_default()),
+        None => "OPTIONS".to_string(),
+    };
+    let mut headers = vec![
+        (header::ALLOW, allow.clone()),
+        (header::ACCESS_CONTROL_ALLOW_ORIGIN, "*".to_string()),
+        (header::ACCESS_CONTROL_ALLOW_METHODS, allow),
+        (
+            header::ACCESS_CONTROL_MAX_AGE,
+            PREFLIGHT_MAX_AGE.to_string(),
+        ),
+    ];
+    if let Some(requested) = requested_headers.and_then(|v| v.to_str().ok().map(String::from)) {
+        headers.push((header::ACCESS_CONTROL_ALLOW_HEADERS, requested));
+    }
+    let mut response = StatusCode::NO_CONTENT.into_response();
+    for (name, value) in headers {
+        if let Ok(value) = HeaderValue::from_str(&value) {
+            response.headers_mut().insert(name, value);
+        }
+    }
+    response
+}
+
+fn override_method(value: &HeaderValue) -> Result<Method, AppError> {
+    let name = value
+        .to_str()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_uppercase();
+    OVERRIDABLE_METHODS
+        .iter()
+        .find(|m| m.as_str() == name)
+        .cloned()
+        .ok_or_else(|| {
+            AppError::BadRequest(format!(
+                "Unsupported X-HTTP-Method-Override: {:?} (expected GET, PUT, PATCH or DELETE)",
+                name
+            ))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_override_method() {
+        let value = |s: &'static str| HeaderValue::from_static(s);
+        assert_eq!(override_method(&value("DELETE")).unwrap(), Method::DELETE);
+        assert_eq!(override_method(&value(" patch ")).unwrap(), Method::PATCH);
+        assert!(override_method(&value("CONNECT")).is_err());
+        assert!(override_method(&value("POST")).is_err());
+    }
+}
@@ -1,2 +1,7 @@
 pub mod auth;
+pub mod idle;
+pub mod limit;
 pub mod logging;
+pub mod methods;
+pub mod path_encoding;
+pub mod rejection;
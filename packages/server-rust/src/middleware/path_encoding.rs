@@ -0,0 +1,23 @@
+use crate::error::AppError;
+use crate::utils::path::{with_path_decoding, PATH_ENCODING_HEADER};
+use axum::{
+    extract::Request,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+
+/// Decode `encoded:` path parameters for requests that send
+/// `X-Path-Encoding: percent`; other values are refused.
+pub async fn scope_path_encoding(req: Request, next: Next) -> Response {
+    let enabled = match req.headers().get(PATH_ENCODING_HEADER) {
+        None => false,
+        Some(value) if value.as_bytes().eq_ignore_ascii_case(b"percent") => true,
+        Some(_) => {
+            return AppError::BadRequest(
+                "Unsupported X-Path-Encoding header (expected \"percent\")".to_string(),
+            )
+            .into_response()
+        }
+    };
+    with_path_decoding(enabled, next.run(req)).await
+}
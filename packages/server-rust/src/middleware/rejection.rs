@@ -0,0 +1,100 @@
+use crate::error::AppError;
+use crate::response::{ApiResponse, Status};
+use axum::{
+    body::to_bytes,
+    extract::Request,
+    http::{header, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde_json::json;
+
+/// Longest rejection message read back from axum's plain-text body
+const MAX_REJECTION_BODY: usize = 64 * 1024;
+
+/// Rewrite axum's built-in extractor rejections (malformed JSON, a bad query
+/// string or path parameter, a missing `Content-Type`, ...) into the usual
+/// `ApiResponse` error body. Handlers report their own errors through
+/// `AppError`, which always answers 200, so a 4xx response with a plain-text
+/// body can only come from a rejection.
+pub async fn normalize_rejections(req: Request, next: Next) -> Response {
+    normalize(next.run(req).await).await
+}
+
+async fn normalize(response: Response) -> Response {
+    let is_rejection = response.status().is_client_error()
+        && response
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|v| v.starts_with("text/plain"));
+    if !is_rejection {
+        return response;
+    }
+
+    let status = response.status();
+    let message = match to_bytes(response.into_body(), MAX_REJECTION_BODY).await {
+        Ok(body) if !body.is_empty() => String::from_utf8_lossy(&body).into_owned(),
+        _ => status
+            .canonical_reason()
+            .unwrap_or("Invalid request")
+            .to_string(),
+    };
+    // An oversized body or the wrong content type keeps a status of its own,
+    // so clients can tell those from a malformed request
+    let code = match status {
+        StatusCode::PAYLOAD_TOO_LARGE => Status::PayloadTooLarge,
+        StatusCode::UNSUPPORTED_MEDIA_TYPE => Status::UnsupportedMediaType,
+        _ => return AppError::BadRequest(message).into_response(),
+    };
+    Json(ApiResponse::error(code, message, json!({}))).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::extract::rejection::JsonRejection;
+    use axum::extract::FromRequest;
+
+    async fn body_status(response: Response) -> (StatusCode, serde_json::Value) {
+        let response = normalize(response).await;
+        let status = response.status();
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&bytes).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_rejection_status_codes() {
+        let request = |content_type: &str, body: &'static str| {
+            Request::builder()
+                .header(header::CONTENT_TYPE, content_type)
+                .body(axum::body::Body::from(body))
+                .unwrap()
+        };
+        let reject = |result: Result<Json<serde_json::Value>, JsonRejection>| {
+            result.unwrap_err().into_response()
+        };
+
+        let malformed = reject(Json::from_request(request("application/json", "{"), &()).await);
+        let (http, body) = body_status(malformed).await;
+        assert_eq!(http, StatusCode::OK);
+        assert_eq!(body["status"], 1422);
+
+        let wrong_type = reject(Json::from_request(request("text/plain", "{}"), &()).await);
+        let (http, body) = body_status(wrong_type).await;
+        assert_eq!(http, StatusCode::OK);
+        assert_eq!(body["status"], 1415);
+        assert!(body["message"].as_str().unwrap().contains("Content-Type"));
+
+        let too_large = (StatusCode::PAYLOAD_TOO_LARGE, "length limit exceeded").into_response();
+        let (_, body) = body_status(too_large).await;
+        assert_eq!(body["status"], 1413);
+        assert_eq!(body["message"], "length limit exceeded");
+
+        // Handler errors pass through untouched
+        let handled = AppError::NotFound("gone".to_string()).into_response();
+        let (_, body) = body_status(handled).await;
+        assert_eq!(body["status"], 1404);
+    }
+}
@@ -1 +1,4 @@
 pub mod port;
+pub mod proc_stats;
+pub mod tree;
+pub mod zombies;
@@ -1,4 +1,5 @@
 use crate::error::AppError;
+use std::collections::HashSet;
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 use tokio::fs;
@@ -55,6 +56,23 @@ impl PortMonitor {
         Ok((ports, last_updated_ts))
     }
 
+    /// Poll until `port` is listening or `timeout` passes. Returns whether it
+    /// was seen.
+    pub async fn wait_for_port(&self, port: u16, timeout: Duration) -> Result<bool, AppError> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let (ports, _) = self.get_ports().await?;
+            if ports.contains(&port) {
+                return Ok(true);
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return Ok(false);
+            }
+            tokio::time::sleep(self.cache_ttl.min(deadline - now)).await;
+        }
+    }
+
     async fn refresh(&self) -> Result<(), AppError> {
         let ports = self.poll_ports().await?;
 
@@ -87,7 +105,7 @@ impl PortMonitor {
         }
 
         let mut filtered_ports = Vec::new();
-        let mut seen = std::collections::HashSet::new();
+        let mut seen = HashSet::new();
 
         for port in ports {
             if !self.excluded_ports.contains(&port) && !seen.contains(&port) {
@@ -99,6 +117,79 @@ impl PortMonitor {
         Ok(filtered_ports)
     }
 
+    /// PIDs of the processes holding a listening TCP socket on `port`, found
+    /// by matching socket inodes from `/proc/net/tcp{,6}` against the
+    /// `socket:[inode]` links under `/proc/<pid>/fd`. Processes whose fds
+    /// cannot be read are skipped.
+    pub async fn find_owners(port: u16) -> Vec<u32> {
+        let (tcp_res, tcp6_res) = tokio::join!(
+            fs::read_to_string("/proc/net/tcp"),
+            fs::read_to_string("/proc/net/tcp6")
+        );
+        let mut inodes = HashSet::new();
+        for content in [tcp_res, tcp6_res].into_iter().flatten() {
+            Self::parse_listening_inodes(&content, port, &mut inodes);
+        }
+        if inodes.is_empty() {
+            return Vec::new();
+        }
+
+        let mut owners = Vec::new();
+        let Ok(mut procs) = fs::read_dir("/proc").await else {
+            return owners;
+        };
+        while let Ok(Some(entry)) = procs.next_entry().await {
+            let Some(pid) = entry
+                .file_name()
+                .to_str()
+                .and_then(|s| s.parse::<u32>().ok())
+            else {
+                continue;
+            };
+            let Ok(mut fds) = fs::read_dir(entry.path().join("fd")).await else {
+                continue;
+            };
+            while let Ok(Some(fd)) = fds.next_entry().await {
+                let Ok(target) = fs::read_link(fd.path()).await else {
+                    continue;
+                };
+                let inode = target
+                    .to_str()
+                    .and_then(|t| t.strip_prefix("socket:["))
+                    .and_then(|t| t.strip_suffix(']'))
+                    .and_then(|t| t.parse::<u64>().ok());
+                if inode.is_some_and(|i| inodes.contains(&i)) {
+                    owners.push(pid);
+                    break;
+                }
+            }
+        }
+        owners.sort_unstable();
+        owners
+    }
+
+    /// Collect inodes of sockets in LISTEN state (`0A`) bound to `port` on any address.
+    fn parse_listening_inodes(content: &str, port: u16, inodes: &mut HashSet<u64>) {
+        for line in content.lines().skip(1) {
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            if parts.len() < 10 || parts[3] != "0A" {
+                continue;
+            }
+            let local_port = parts[1]
+                .rsplit(':')
+                .next()
+                .and_then(|p| u16::from_str_radix(p, 16).ok());
+            if local_port != Some(port) {
+                continue;
+            }
+            if let Ok(inode) = parts[9].parse::<u64>() {
+                if inode != 0 {
+                    inodes.insert(inode);
+                }
+            }
+        }
+    }
+
     fn parse_proc_net_tcp(content: &str, ports: &mut Vec<u16>) {
         for line in content.lines().skip(1) {
             let mut parts = line.split_whitespace();
@@ -128,3 +219,52 @@ impl PortMonitor {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_wait_for_port() {
+        let monitor = PortMonitor::new(Duration::from_millis(20), Vec::new());
+        let listener = std::net::TcpListener::bind("0.0.0.0:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        assert!(monitor
+            .wait_for_port(port, Duration::from_secs(2))
+            .await
+            .unwrap());
+
+        drop(listener);
+        // Closed ports are not reported once the cache expires
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!monitor
+            .wait_for_port(port, Duration::from_millis(100))
+            .await
+            .unwrap());
+    }
+
+    #[test]
+    fn test_parse_listening_inodes() {
+        let content = "  sl  local_address rem_address   st tx_queue rx_queue tr tm->when retrnsmt   uid  timeout inode
+   0: 0100007F:1F90 00000000:0000 0A 00000000:00000000 00:00000000 00000000  1000        0 4242 1 0 100 0 0 10 0
+   1: 0100007F:1F90 0100007F:C350 01 00000000:00000000 00:00000000 00000000  1000        0 4343 1 0 20 4 30 10 -1
+   2: 00000000:0016 00000000:0000 0A 00000000:00000000 00:00000000 00000000     0        0 77 1 0 100 0 0 10 0
+";
+        let mut inodes = HashSet::new();
+        PortMonitor::parse_listening_inodes(content, 8080, &mut inodes);
+        // Established connections on the same port are not owners
+        assert_eq!(inodes, HashSet::from([4242]));
+    }
+
+    #[tokio::test]
+    async fn test_find_owners() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        assert_eq!(
+            PortMonitor::find_owners(port).await,
+            vec![std::process::id()]
+        );
+        drop(listener);
+        assert!(PortMonitor::find_owners(port).await.is_empty());
+    }
+}
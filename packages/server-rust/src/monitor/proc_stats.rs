@@ -0,0 +1,134 @@
+use nix::unistd::{sysconf, SysconfVar};
+use std::collections::BTreeMap;
+use tokio::fs;
+
+/// Resource usage of a live process, read from `/proc/<pid>/stat` and `statm`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ProcStats {
+    /// User plus system CPU time
+    pub cpu_time_ms: u64,
+    pub rss_bytes: u64,
+    pub threads: u32,
+}
+
+/// Returns `None` if the process is gone or `/proc` is unavailable.
+pub async fn read_proc_stats(pid: u32) -> Option<ProcStats> {
+    let (stat, statm) = tokio::join!(
+        fs::read_to_string(format!("/proc/{}/stat", pid)),
+        fs::read_to_string(format!("/proc/{}/statm", pid))
+    );
+    let (cpu_ticks, threads) = parse_stat(&stat.ok()?)?;
+    let rss_pages = parse_statm(&statm.ok()?)?;
+
+    let ticks_per_sec = sysconf_or(SysconfVar::CLK_TCK, 100);
+    let page_size = sysconf_or(SysconfVar::PAGE_SIZE, 4096);
+
+    Some(ProcStats {
+        cpu_time_ms: cpu_ticks * 1000 / ticks_per_sec,
+        rss_bytes: rss_pages * page_size,
+        threads,
+    })
+}
+
+/// PIDs of all live processes in process group `pgid`, the leader included.
+pub async fn process_group_members(pgid: u32) -> Vec<u32> {
+    let mut members = Vec::new();
+    let Ok(mut procs) = fs::read_dir("/proc").await else {
+        return members;
+    };
+    while let Ok(Some(entry)) = procs.next_entry().await {
+        let Some(pid) = entry
+            .file_name()
+            .to_str()
+            .and_then(|s| s.parse::<u32>().ok())
+        else {
+            continue;
+        };
+        let Ok(stat) = fs::read_to_string(entry.path().join("stat")).await else {
+            continue; // Exited while scanning
+        };
+        if parse_pgrp(&stat) == Some(pgid) {
+            members.push(pid);
+        }
+    }
+    members.sort_unstable();
+    members
+}
+
+/// Environment of a live process from `/proc/<pid>/environ`. Returns `None`
+/// if the process is gone, belongs to another user, or `/proc` is unavailable.
+pub async fn read_environ(pid: u32) -> Option<BTreeMap<String, String>> {
+    let content = fs::read(format!("/proc/{}/environ", pid)).await.ok()?;
+    Some(parse_environ(&content))
+}
+
+fn sysconf_or(var: SysconfVar, default: u64) -> u64 {
+    match sysconf(var) {
+        Ok(Some(v)) if v > 0 => v as u64,
+        _ => default,
+    }
+}
+
+/// Extract `utime + stime` (clock ticks) and `num_threads` from `/proc/<pid>/stat`.
+/// The command name may contain spaces and parentheses, so fields are counted
+/// from the last `)`.
+fn parse_stat(content: &str) -> Option<(u64, u32)> {
+    let rest = &content[content.rfind(')')? + 1..];
+    let fields: Vec<&str> = rest.split_whitespace().collect();
+    // `fields[0]` is field 3 (state) in proc(5) numbering
+    let utime: u64 = fields.get(11)?.parse().ok()?;
+    let stime: u64 = fields.get(12)?.parse().ok()?;
+    let threads: u32 = fields.get(17)?.parse().ok()?;
+    Some((utime + stime, threads))
+}
+
+/// Process group id: field 5 of `/proc/<pid>/stat`.
+fn parse_pgrp(content: &str) -> Option<u32> {
+    let rest = &content[content.rfind(')')? + 1..];
+    rest.split_whitespace().nth(2)?.parse().ok()
+}
+
+/// NUL-separated `KEY=value` entries; entries without `=` are skipped and
+/// invalid UTF-8 is replaced.
+fn parse_environ(content: &[u8]) -> BTreeMap<String, String> {
+    content
+        .split(|&b| b == 0)
+        .filter_map(|entry| {
+            let entry = String::from_utf8_lossy(entry);
+            let (key, value) = entry.split_once('=')?;
+            (!key.is_empty()).then(|| (key.to_string(), value.to_string()))
+        })
+        .collect()
+}
+
+/// Resident set size in pages: the second field of `/proc/<pid>/statm`.
+fn parse_statm(content: &str) -> Option<u64> {
+    content.split_whitespace().nth(1)?.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_proc_files() {
+        let stat = "4242 (my (odd) cmd) S 1 4242 4242 0 -1 4194560 1000 0 0 0 \
+                    150 50 0 0 20 0 3 0 123456 10000000 512 18446744073709551615";
+        assert_eq!(parse_stat(stat), Some((200, 3)));
+        assert_eq!(parse_stat("garbage"), None);
+        assert_eq!(parse_pgrp(stat), Some(4242));
+        assert_eq!(parse_statm("2500 512 300 10 0 400 0\n"), Some(512));
+
+        let env = parse_environ(b"PATH=/usr/bin\0EMPTY=\0EQ=a=b\0junk\0");
+        assert_eq!(env.len(), 3);
+        assert_eq!(env["EQ"], "a=b");
+        assert_eq!(env["EMPTY"], "");
+    }
+
+    #[tokio::test]
+    async fn test_read_own_stats() {
+        let stats = read_proc_stats(std::process::id()).await.unwrap();
+        assert!(stats.rss_bytes > 0);
+        assert!(stats.threads >= 1);
+    }
+}
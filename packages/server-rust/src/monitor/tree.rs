@@ -0,0 +1,190 @@
+//! The process tree as seen in `/proc`, including descendants the API did not
+//! spawn itself, such as the children of a shell running a build.
+
+use super::zombies::parse_state;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use tokio::fs;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProcessNode {
+    pub pid: u32,
+    pub ppid: u32,
+    /// `comm` from `/proc/<pid>/stat`
+    pub comm: String,
+    /// Ordered by PID
+    pub children: Vec<ProcessNode>,
+}
+
+/// One process from `/proc`: PID, parent PID and `comm`.
+type ProcEntry = (u32, u32, String);
+
+/// The tree rooted at `root`, or `None` if no such process is visible in
+/// `/proc`. Processes that exit while `/proc` is scanned are left out.
+pub async fn process_tree(root: u32) -> Option<ProcessNode> {
+    build_tree(&read_process_table().await, root)
+}
+
+/// `roots` and every process below them that is visible in `/proc`.
+pub async fn subtree_pids(roots: &[u32]) -> HashSet<u32> {
+    collect_subtrees(&read_process_table().await, roots)
+}
+
+/// `node` cut down to the processes in `visible` and their ancestors, or
+/// `None` if none of them is in the tree.
+pub fn retain_visible(node: ProcessNode, visible: &HashSet<u32>) -> Option<ProcessNode> {
+    if visible.contains(&node.pid) {
+        return Some(node);
+    }
+    let children: Vec<ProcessNode> = node
+        .children
+        .into_iter()
+        .filter_map(|child| retain_visible(child, visible))
+        .collect();
+    (!children.is_empty()).then_some(ProcessNode { children, ..node })
+}
+
+async fn read_process_table() -> Vec<ProcEntry> {
+    let mut table = Vec::new();
+    let Ok(mut procs) = fs::read_dir("/proc").await else {
+        return table;
+    };
+    while let Ok(Some(entry)) = procs.next_entry().await {
+        let Some(pid) = entry
+            .file_name()
+            .to_str()
+            .and_then(|s| s.parse::<u32>().ok())
+        else {
+            continue;
+        };
+        let Ok(stat) = fs::read_to_string(entry.path().join("stat")).await else {
+            continue; // Exited while scanning
+        };
+        if let Some((comm, _, ppid)) = parse_state(&stat) {
+            table.push((pid, ppid, comm));
+        }
+    }
+    table
+}
+
+fn build_tree(table: &[ProcEntry], root: u32) -> Option<ProcessNode> {
+    let (_, ppid, comm) = table.iter().find(|(pid, _, _)| *pid == root)?;
+    let mut children: HashMap<u32, Vec<&ProcEntry>> = HashMap::new();
+    for entry in table {
+        children.entry(entry.1).or_default().push(entry);
+    }
+    for siblings in children.values_mut() {
+        siblings.sort_unstable_by_key(|(pid, _, _)| *pid);
+    }
+    // A PID reused while scanning could in principle show up as its own
+    // ancestor; each process is placed in the tree at most once
+    let mut placed = HashSet::from([root]);
+    Some(ProcessNode {
+        pid: root,
+        ppid: *ppid,
+        comm: comm.clone(),
+        children: descendants(root, &children, &mut placed),
+    })
+}
+
+fn collect_subtrees(table: &[ProcEntry], roots: &[u32]) -> HashSet<u32> {
+    let mut found: HashSet<u32> = roots.iter().copied().collect();
+    let mut pending = roots.to_vec();
+    while let Some(parent) = pending.pop() {
+        for &(pid, ppid, _) in table {
+            if ppid == parent && found.insert(pid) {
+                pending.push(pid);
+            }
+        }
+    }
+    found
+}
+
+fn descendants(
+    pid: u32,
+    children: &HashMap<u32, Vec<&ProcEntry>>,
+    placed: &mut HashSet<u32>,
+) -> Vec<ProcessNode> {
+    let Some(kids) = children.get(&pid) else {
+        return Vec::new();
+    };
+    let mut nodes = Vec::with_capacity(kids.len());
+    for &&(child, ppid, ref comm) in kids {
+        if !placed.insert(child) {
+            continue;
+        }
+        nodes.push(ProcessNode {
+            pid: child,
+            ppid,
+            comm: comm.clone(),
+            children: descendants(child, children, placed),
+        });
+    }
+    nodes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_tree() {
+        let table: Vec<ProcEntry> = [
+            (1, 0, "init"),
+            (30, 10, "cargo"),
+            (10, 1, "bash"),
+            (20, 10, "sleep"),
+            (40, 30, "rustc"),
+            (50, 1, "sshd"),
+            // A cycle the scan could observe after PID reuse
+            (60, 70, "a"),
+            (70, 60, "b"),
+        ]
+        .into_iter()
+        .map(|(pid, ppid, comm)| (pid, ppid, comm.to_string()))
+        .collect();
+
+        let tree = build_tree(&table, 10).unwrap();
+        assert_eq!(tree.ppid, 1);
+        assert_eq!(tree.comm, "bash");
+        let pids: Vec<u32> = tree.children.iter().map(|c| c.pid).collect();
+        assert_eq!(pids, vec![20, 30]);
+        assert_eq!(tree.children[1].children[0].comm, "rustc");
+        assert!(tree.children[0].children.is_empty());
+
+        assert_eq!(build_tree(&table, 1).unwrap().children.len(), 2);
+        let cycle = build_tree(&table, 60).unwrap();
+        assert_eq!(cycle.children[0].pid, 70);
+        assert!(cycle.children[0].children.is_empty());
+        assert_eq!(build_tree(&table, 99), None);
+
+        let visible = collect_subtrees(&table, &[30]);
+        assert_eq!(visible, HashSet::from([30, 40]));
+        let pruned = retain_visible(build_tree(&table, 1).unwrap(), &visible).unwrap();
+        assert_eq!(pruned.children.len(), 1);
+        let bash = &pruned.children[0];
+        assert_eq!(
+            bash.children.iter().map(|c| c.pid).collect::<Vec<_>>(),
+            [30]
+        );
+        assert_eq!(bash.children[0].children[0].pid, 40);
+        assert_eq!(
+            retain_visible(build_tree(&table, 50).unwrap(), &visible),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn test_tree_includes_own_children() {
+        let mut child = tokio::process::Command::new("sleep")
+            .arg("5")
+            .spawn()
+            .unwrap();
+        let pid = child.id().unwrap();
+        let tree = process_tree(std::process::id()).await.unwrap();
+        let node = tree.children.iter().find(|c| c.pid == pid).unwrap();
+        assert_eq!(node.comm, "sleep");
+        child.kill().await.unwrap();
+    }
+}
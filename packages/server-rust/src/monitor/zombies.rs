@@ -0,0 +1,192 @@
+//! Defunct processes. Every child the server spawns is waited for by the task
+//! that spawned it, but a child whose handle was dropped early, or an orphan
+//! reparented to the server when it runs as PID 1, stays a zombie and holds
+//! its PID until someone waits for it. The reaper waits for those.
+
+use nix::sys::wait::{waitpid, WaitPidFlag, WaitStatus};
+use nix::unistd::Pid;
+use serde::Serialize;
+use std::collections::{HashSet, VecDeque};
+use std::time::Duration;
+use tokio::fs;
+use tokio::sync::Mutex;
+
+/// How often `/proc` is scanned for zombie children
+const REAP_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Reaped children remembered for `process/zombies`
+const MAX_REAPED_HISTORY: usize = 50;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Zombie {
+    pub pid: u32,
+    pub ppid: u32,
+    /// `comm` from `/proc/<pid>/stat`
+    pub command: String,
+    /// Whether the server is the parent, and so the one that should wait
+    pub is_child: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReapedChild {
+    pub pid: u32,
+    pub command: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exit_code: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub signal: Option<String>,
+    pub reaped_at: String,
+}
+
+#[derive(Default)]
+pub struct ZombieReaper {
+    reaped: Mutex<VecDeque<ReapedChild>>,
+}
+
+impl ZombieReaper {
+    /// Most recently reaped children, newest last.
+    pub async fn reaped(&self) -> Vec<ReapedChild> {
+        self.reaped.lock().await.iter().cloned().collect()
+    }
+
+    async fn record(&self, child: ReapedChild) {
+        let mut reaped = self.reaped.lock().await;
+        if reaped.len() == MAX_REAPED_HISTORY {
+            reaped.pop_front();
+        }
+        reaped.push_back(child);
+    }
+}
+
+/// Wait for zombie children nobody else waits for. A zombie is only reaped
+/// once it has survived a whole interval: a child a spawning task is about to
+/// wait for is reaped by that task within moments, and taking it first would
+/// cost the task its exit status.
+pub async fn run_reaper(reaper: std::sync::Arc<ZombieReaper>, quiet: bool) {
+    let own_pid = std::process::id();
+    let mut seen: HashSet<u32> = HashSet::new();
+    let mut interval = tokio::time::interval(REAP_INTERVAL);
+    loop {
+        interval.tick().await;
+        let children: Vec<Zombie> = list_zombies()
+            .await
+            .into_iter()
+            .filter(|z| z.ppid == own_pid)
+            .collect();
+        for zombie in children.iter().filter(|z| seen.contains(&z.pid)) {
+            let Some(child) = reap(zombie) else { continue };
+            if !quiet {
+                eprintln!(
+                    "Reaped zombie child {} ({}) that nothing waited for",
+                    child.pid, child.command
+                );
+            }
+            reaper.record(child).await;
+        }
+        seen = children.into_iter().map(|z| z.pid).collect();
+    }
+}
+
+fn reap(zombie: &Zombie) -> Option<ReapedChild> {
+    let (exit_code, signal) =
+        match waitpid(Pid::from_raw(zombie.pid as i32), Some(WaitPidFlag::WNOHANG)).ok()? {
+            WaitStatus::Exited(_, code) => (Some(code), None),
+            WaitStatus::Signaled(_, signal, _) => (None, Some(signal.as_str().to_string())),
+            _ => return None,
+        };
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default();
+    Some(ReapedChild {
+        pid: zombie.pid,
+        command: zombie.command.clone(),
+        exit_code,
+        signal,
+        reaped_at: crate::utils::common::format_time(now.as_secs()),
+    })
+}
+
+/// Every process in state `Z` visible in `/proc`.
+pub async fn list_zombies() -> Vec<Zombie> {
+    let own_pid = std::process::id();
+    let mut zombies = Vec::new();
+    let Ok(mut procs) = fs::read_dir("/proc").await else {
+        return zombies;
+    };
+    while let Ok(Some(entry)) = procs.next_entry().await {
+        let Some(pid) = entry
+            .file_name()
+            .to_str()
+            .and_then(|s| s.parse::<u32>().ok())
+        else {
+            continue;
+        };
+        let Ok(stat) = fs::read_to_string(entry.path().join("stat")).await else {
+            continue; // Exited while scanning
+        };
+        if let Some((command, 'Z', ppid)) = parse_state(&stat) {
+            zombies.push(Zombie {
+                pid,
+                ppid,
+                command,
+                is_child: ppid == own_pid,
+            });
+        }
+    }
+    zombies.sort_unstable_by_key(|z| z.pid);
+    zombies
+}
+
+/// `comm`, state and parent PID (fields 2 to 4) of `/proc/<pid>/stat`.
+pub(super) fn parse_state(content: &str) -> Option<(String, char, u32)> {
+    let open = content.find('(')?;
+    let close = content.rfind(')')?;
+    let command = content.get(open + 1..close)?.to_string();
+    let mut fields = content[close + 1..].split_whitespace();
+    let state = fields.next()?.chars().next()?;
+    let ppid = fields.next()?.parse().ok()?;
+    Some((command, state, ppid))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_state() {
+        assert_eq!(
+            parse_state("4242 (my (odd) cmd) Z 17 4242 4242 0 -1"),
+            Some(("my (odd) cmd".to_string(), 'Z', 17))
+        );
+        assert_eq!(
+            parse_state("1 (init) S 0 1 1"),
+            Some(("init".to_string(), 'S', 0))
+        );
+        assert_eq!(parse_state("garbage"), None);
+    }
+
+    #[tokio::test]
+    async fn test_lists_and_reaps_zombie_child() {
+        // A child that exits without anyone waiting stays a zombie
+        let child = std::process::Command::new("true").spawn().unwrap();
+        let pid = child.id();
+        std::mem::forget(child);
+        let mut zombie = None;
+        for _ in 0..50 {
+            zombie = list_zombies().await.into_iter().find(|z| z.pid == pid);
+            if zombie.is_some() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        let zombie = zombie.expect("child should be a zombie");
+        assert!(zombie.is_child);
+        assert_eq!(zombie.command, "true");
+
+        let reaped = reap(&zombie).unwrap();
+        assert_eq!(reaped.exit_code, Some(0));
+        assert!(!list_zombies().await.iter().any(|z| z.pid == pid));
+    }
+}
@@ -10,9 +10,12 @@ pub enum Status {
     Unauthorized = 1401,
     Forbidden = 1403,
     InvalidRequest = 1422,
+    PayloadTooLarge = 1413,
+    UnsupportedMediaType = 1415,
     InternalError = 1500,
     Conflict = 1409,
     OperationError = 1600,
+    ServiceUnavailable = 1503,
 }
 
 impl Serialize for Status {
@@ -1,8 +1,8 @@
-use crate::handlers::{file, health, port, process, session, websocket};
-use crate::middleware::{auth, logging};
-use crate::state::AppState;
+use crate::handlers::{config, env, file, git, health, logs, port, process, session, websocket};
+use crate::middleware::{auth, idle, limit, logging, methods, path_encoding, rejection};
+use crate::state::{upload_limit::UploadLimit, workspace::Workspace, AppState};
 use axum::{
-    extract::{FromRequest, Request},
+    extract::{FromRequest, FromRequestParts, Request},
     middleware,
     response::{IntoResponse, Response},
     routing::{get, post},
@@ -16,13 +16,26 @@ pub fn create_router(state: AppState) -> Router {
     let api_routes = Router::new()
         // File routes
         .route("/files/list", get(file::list_files))
+        .route("/files/tree", get(file::file_tree))
+        .route("/files/sync", get(file::sync_files))
+        .route("/files/list-watch", get(file::list_watch))
         .route("/files/read", get(file::read_file))
         .route("/files/download", get(file::read_file)) // Alias for read
+        .route("/files/mime", get(file::get_mime_type))
+        .route("/files/tail-lines", get(file::tail_file_lines))
+        .route("/files/head", get(file::head_file))
+        .route("/files/hash", get(file::hash_file))
+        .route("/files/read-batch", post(file::read_batch))
         .route("/files/delete", post(file::delete_file))
+        .route("/files/clean", post(file::clean_files))
         .route(
             "/files/write",
             post(handle_write_file).layer(axum::extract::DefaultBodyLimit::disable()),
         )
+        .route(
+            "/files/write-at",
+            post(file::write_file_at).layer(axum::extract::DefaultBodyLimit::disable()),
+        )
         .route(
             "/files/batch-upload",
             post(file::batch_upload).layer(axum::extract::DefaultBodyLimit::disable()),
@@ -30,10 +43,15 @@ pub fn create_router(state: AppState) -> Router {
         .route("/files/batch-download", post(file::batch_download))
         .route("/files/move", post(file::move_file))
         .route("/files/rename", post(file::rename_file))
+        .route("/files/symlink", post(file::create_symlink))
         .route("/files/chmod", post(file::change_permissions))
+        .route("/files/perm", get(file::get_permissions))
         .route("/files/search", post(file::search_files))
         .route("/files/find", post(file::find_in_files))
         .route("/files/replace", post(file::replace_in_files))
+        .route("/files/diff", post(file::diff_files))
+        .route("/files/patch", post(file::patch_file))
+        .route("/files/edit-lines", post(file::edit_lines))
         // Process routes
         .route("/process/exec", post(process::exec_process))
         .route("/process/exec-sync", post(process::exec_process_sync))
@@ -41,39 +59,87 @@ pub fn create_router(state: AppState) -> Router {
             "/process/sync-stream",
             post(process::exec_process_sync_stream),
         )
+        .route("/process/run-preset", post(process::run_preset))
         .route("/process/list", get(process::list_processes))
+        .route("/process/zombies", get(process::list_zombies))
+        .route("/process/tree", get(process::process_tree))
+        .route("/process/status", post(process::get_process_statuses))
         .route("/process/{id}/status", get(process::get_process_status))
         .route("/process/{id}/kill", post(process::kill_process))
+        .route("/process/{id}/cancel", post(process::cancel_process))
+        .route("/process/{id}/restart", post(process::restart_process))
         .route("/process/{id}/logs", get(process::get_process_logs))
+        .route("/process/{id}/env", get(process::get_process_env))
+        .route(
+            "/process/{id}/diagnostics",
+            get(process::get_process_diagnostics),
+        )
         // Session routes
         .route("/sessions/create", post(session::create_session))
         .route("/sessions", get(session::list_sessions))
         .route("/sessions/{id}", get(session::get_session))
         .route("/sessions/{id}/env", post(session::update_session_env))
         .route("/sessions/{id}/exec", post(session::session_exec))
+        .route(
+            "/sessions/{id}/exec-batch",
+            post(session::session_exec_batch),
+        )
         .route("/sessions/{id}/cd", post(session::session_cd))
         .route("/sessions/{id}/terminate", post(session::terminate_session))
+        .route("/sessions/{id}/signal", post(session::signal_session))
         .route("/sessions/{id}/logs", get(session::get_session_logs))
+        .route("/logs", get(logs::aggregate_logs))
+        // Git routes
+        .route("/git/status", get(git::git_status))
         // Port routes
-        .route("/ports", get(port::get_ports));
+        .route("/ports", get(port::get_ports))
+        .route("/ports/wait", get(port::wait_for_port))
+        .route("/ports/{port}/kill", post(port::kill_port))
+        // Server info
+        .route("/config", get(config::get_config))
+        .route(
+            "/env/defaults",
+            post(env::set_env_defaults)
+                .get(env::get_env_defaults)
+                .delete(env::clear_env_defaults),
+        )
+        .route("/capabilities", get(health::capabilities))
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            limit::limit_requests,
+        ));
 
-    Router::new()
+    let app = Router::new()
         .route("/health", get(health::health_check))
         .route("/health/ready", get(health::readiness_check))
         .route("/ws", get(websocket::ws_handler))
         .nest("/api/v1", api_routes)
+        .layer(middleware::from_fn(path_encoding::scope_path_encoding))
+        .layer(middleware::from_fn(rejection::normalize_rejections))
         .layer(middleware::from_fn_with_state(
             state.clone(),
             auth::auth_middleware,
         ))
         .layer(middleware::from_fn(logging::logging_middleware))
-        .with_state(state)
+        .layer(middleware::from_fn(idle::track_requests))
+        .with_state(state);
+
+    // As the only route of an outer router, `app` sees the method after
+    // `handle_methods` has rewritten it
+    Router::new()
+        .fallback_service(app)
+        .layer(middleware::from_fn(methods::handle_methods))
 }
 
 async fn handle_write_file(
     state: axum::extract::State<Arc<AppState>>,
     req: Request,
 ) -> Result<Response, crate::error::AppError> {
+    let (mut parts, body) = req.into_parts();
+    let workspace = Workspace::from_request_parts(&mut parts, &state.0).await?;
+    let limit = UploadLimit::from_request_parts(&mut parts, &state.0).await?;
+    let req = Request::from_parts(parts, body);
+
     let content_type = req
         .headers()
         .get(axum::http::header::CONTENT_TYPE)
@@ -81,11 +147,29 @@ async fn handle_write_file(
         .unwrap_or("");
 
     if content_type.starts_with("application/json") {
-        let json_body = axum::Json::<file::WriteFileRequest>::from_request(req, &state)
+        // The route disables the default body limit for streamed uploads, so
+        // bound the buffered JSON body by the upload limit instead
+        let body_limit = file::json_body_limit(limit.0);
+        let declared = req
+            .headers()
+            .get(axum::http::header::CONTENT_LENGTH)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.parse::<u64>().ok());
+        if let Some(len) = declared.filter(|&len| len > body_limit as u64) {
+            return Err(crate::error::AppError::BadRequest(format!(
+                "File too large: JSON body is {} bytes, limit is {} bytes",
+                len, body_limit
+            )));
+        }
+        let bytes = axum::body::to_bytes(req.into_body(), body_limit)
             .await
+            .map_err(|e| {
+                crate::error::AppError::BadRequest(format!("Failed to read request body: {}", e))
+            })?;
+        let json_body = axum::Json::<file::WriteFileRequest>::from_bytes(&bytes)
             .map_err(|e| crate::error::AppError::BadRequest(e.to_string()))?;
 
-        file::write_file_json(state, json_body)
+        file::write_file_json(workspace, limit, json_body)
             .await
             .map(|r| r.into_response())
     } else if content_type.starts_with("multipart/form-data") {
@@ -93,13 +177,14 @@ async fn handle_write_file(
             .await
             .map_err(|e| crate::error::AppError::BadRequest(e.to_string()))?;
 
-        file::write_file_multipart(state, multipart)
+        file::write_file_multipart(state, workspace, limit, multipart)
             .await
             .map(|r| r.into_response())
     } else {
         // Binary
         let (parts, body) = req.into_parts();
-        let req_for_query = Request::from_parts(parts.clone(), axum::body::Body::empty());
+        let headers = parts.headers.clone();
+        let req_for_query = Request::from_parts(parts, axum::body::Body::empty());
 
         let query =
             axum::extract::Query::<std::collections::HashMap<String, String>>::from_request(
@@ -109,7 +194,7 @@ async fn handle_write_file(
             .await
             .map_err(|e| crate::error::AppError::BadRequest(e.to_string()))?;
 
-        file::write_file_binary(state, query, body)
+        file::write_file_binary(workspace, limit, query, headers, body)
             .await
             .map(|r| r.into_response())
     }
@@ -0,0 +1,76 @@
+use std::collections::VecDeque;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Lines kept per process or session; older ones are dropped first
+pub const MAX_LOG_LINES: usize = 10000;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogLine {
+    /// When the line was recorded, in milliseconds since the Unix epoch
+    pub at_ms: u64,
+    /// The line as logged, e.g. `[stdout] hello`
+    pub text: String,
+}
+
+/// The in-memory log of a process or session, each line stamped with the
+/// time it was recorded.
+#[derive(Debug, Default)]
+pub struct LogBuffer {
+    lines: VecDeque<LogLine>,
+}
+
+impl LogBuffer {
+    pub fn push(&mut self, text: String) {
+        let at_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or_default();
+        self.push_at(at_ms, text);
+    }
+
+    fn push_at(&mut self, at_ms: u64, text: String) {
+        if self.lines.len() >= MAX_LOG_LINES {
+            self.lines.pop_front();
+        }
+        self.lines.push_back(LogLine { at_ms, text });
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &LogLine> {
+        self.lines.iter()
+    }
+
+    /// The last `tail` lines, or all of them, oldest first.
+    pub fn tail(&self, tail: Option<usize>) -> impl Iterator<Item = &LogLine> {
+        let skip = tail.map_or(0, |t| self.lines.len().saturating_sub(t));
+        self.lines.iter().skip(skip)
+    }
+
+    /// Text of the last `tail` lines, or all of them.
+    pub fn tail_texts(&self, tail: Option<usize>) -> Vec<String> {
+        self.tail(tail).map(|line| line.text.clone()).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_log_buffer() {
+        let mut logs = LogBuffer::default();
+        for i in 0..MAX_LOG_LINES + 2 {
+            logs.push_at(i as u64, format!("[stdout] {}", i));
+        }
+        assert_eq!(logs.iter().count(), MAX_LOG_LINES);
+        assert_eq!(logs.iter().next().unwrap().at_ms, 2);
+        assert_eq!(
+            logs.tail_texts(Some(2)),
+            vec![
+                format!("[stdout] {}", MAX_LOG_LINES),
+                format!("[stdout] {}", MAX_LOG_LINES + 1)
+            ]
+        );
+        assert_eq!(logs.tail(Some(usize::MAX)).count(), MAX_LOG_LINES);
+        assert_eq!(logs.tail(None).count(), MAX_LOG_LINES);
+    }
+}
@@ -1,5 +1,9 @@
+pub mod logs;
+pub mod persist;
 pub mod process;
 pub mod session;
+pub mod upload_limit;
+pub mod workspace;
 
 use std::collections::HashMap;
 use std::sync::Arc;
@@ -9,8 +13,18 @@ use tokio::sync::RwLock;
 pub struct AppState {
     pub config: Arc<crate::config::Config>,
     pub processes: process::ProcessStore,
+    /// Announces the ID of every process started by `exec_process`
+    pub process_created: tokio::sync::broadcast::Sender<String>,
+    /// Notified whenever the process registry changes, to rewrite the snapshot
+    pub registry_changed: Arc<tokio::sync::Notify>,
     pub sessions: session::SessionStore,
+    /// Set through `env/defaults`; exec and session requests' `env` goes on top
+    pub env_defaults: Arc<RwLock<HashMap<String, String>>>,
+    /// One permit per API request in progress, with `MAX_CONCURRENT_REQUESTS`
+    pub request_slots: Option<Arc<tokio::sync::Semaphore>>,
     pub port_monitor: Arc<crate::monitor::port::PortMonitor>,
+    /// Records the children `run_reaper` waited for
+    pub zombie_reaper: Arc<crate::monitor::zombies::ZombieReaper>,
     pub start_time: std::time::Instant,
 }
 
@@ -20,16 +34,58 @@ impl AppState {
         if let Ok(addr) = config.addr.parse::<std::net::SocketAddr>() {
             excluded_ports.push(addr.port());
         }
+        for &port in &config.excluded_ports {
+            if !excluded_ports.contains(&port) {
+                excluded_ports.push(port);
+            }
+        }
+
+        let processes: process::ProcessStore = Arc::new(RwLock::new(HashMap::new()));
+        let registry_changed = Arc::new(tokio::sync::Notify::new());
+        if let Some(state_dir) = &config.state_dir {
+            let restored = persist::load(state_dir);
+            if !restored.is_empty() && !config.quiet {
+                println!("Restored {} processes from {:?}", restored.len(), state_dir);
+            }
+            let mut store = processes.try_write().expect("registry is not shared yet");
+            for info in restored {
+                tokio::spawn(persist::watch_restored(
+                    info.id.clone(),
+                    processes.clone(),
+                    registry_changed.clone(),
+                ));
+                store.insert(info.id.clone(), info);
+            }
+            drop(store);
+            tokio::spawn(persist::run_writer(
+                state_dir.clone(),
+                processes.clone(),
+                registry_changed.clone(),
+            ));
+        }
+
+        let request_slots = (config.max_concurrent_requests > 0)
+            .then(|| Arc::new(tokio::sync::Semaphore::new(config.max_concurrent_requests)));
 
         Self {
             config: Arc::new(config),
-            processes: Arc::new(RwLock::new(HashMap::new())),
+            processes,
+            process_created: tokio::sync::broadcast::Sender::new(64),
+            registry_changed,
             sessions: Arc::new(RwLock::new(HashMap::new())),
+            env_defaults: Arc::new(RwLock::new(HashMap::new())),
+            request_slots,
             port_monitor: Arc::new(crate::monitor::port::PortMonitor::new(
                 std::time::Duration::from_millis(100),
                 excluded_ports,
             )),
+            zombie_reaper: Arc::default(),
             start_time: std::time::Instant::now(),
         }
     }
+
+    /// Record that the process registry changed (a no-op without `STATE_DIR`).
+    pub fn mark_registry_changed(&self) {
+        self.registry_changed.notify_one();
+    }
 }
@@ -0,0 +1,280 @@
+//! Optional on-disk snapshot of the process registry under `STATE_DIR`, so
+//! `list_processes` survives a server restart. Only metadata is kept; logs
+//! and child handles are not. Sessions are not persisted because a shell's
+//! stdin pipe cannot be reattached.
+
+use super::process::{ProcessInfo, ProcessStore};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::{broadcast, Notify};
+
+/// Snapshot file name inside the state directory.
+pub const SNAPSHOT_FILE: &str = "processes.json";
+
+/// How long finished processes stay listed before they are dropped.
+pub const FINISHED_RETENTION: Duration = Duration::from_secs(4 * 60 * 60);
+
+/// Changes arriving within this window are written as one snapshot.
+const WRITE_DEBOUNCE: Duration = Duration::from_millis(200);
+
+/// How often a restored process is checked for having exited.
+const LIVENESS_POLL: Duration = Duration::from_secs(1);
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ProcessRecord {
+    id: String,
+    pid: Option<u32>,
+    command: String,
+    status: String,
+    /// Unix seconds
+    start_time: u64,
+    end_time: Option<u64>,
+    exit_code: Option<i32>,
+    /// Kept so a process restored as running can still be cancelled
+    #[serde(default)]
+    cancel_token: Option<String>,
+    /// Entries written before tokens were labelled belong to the admin
+    #[serde(default)]
+    owner: Option<String>,
+}
+
+fn unix_secs(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+fn from_unix_secs(secs: u64) -> SystemTime {
+    UNIX_EPOCH + Duration::from_secs(secs)
+}
+
+fn pid_alive(pid: u32) -> bool {
+    match nix::sys::signal::kill(nix::unistd::Pid::from_raw(pid as i32), None) {
+        Ok(()) => true,
+        Err(errno) => errno == nix::errno::Errno::EPERM,
+    }
+}
+
+/// Load the snapshot and rebuild registry entries. Processes recorded as
+/// running keep that status only if their PID is still alive.
+pub fn load(state_dir: &Path) -> Vec<ProcessInfo> {
+    let path = state_dir.join(SNAPSHOT_FILE);
+    let records: Vec<ProcessRecord> = match std::fs::read(&path) {
+        Ok(data) => match serde_json::from_slice(&data) {
+            Ok(records) => records,
+            Err(e) => {
+                eprintln!("Ignoring unreadable state file {}: {}", path.display(), e);
+                return Vec::new();
+            }
+        },
+        Err(_) => return Vec::new(),
+    };
+
+    records
+        .into_iter()
+        .map(|record| {
+            let (log_broadcast, _) = broadcast::channel(100);
+            let mut info =
+                ProcessInfo::new(record.id, record.pid, record.command, None, log_broadcast);
+            info.status = record.status;
+            info.start_time = from_unix_secs(record.start_time);
+            info.end_time = record.end_time.map(from_unix_secs);
+            info.exit_code = record.exit_code;
+            if let Some(token) = record.cancel_token {
+                info.cancel_token = token;
+            }
+            if let Some(owner) = record.owner {
+                info.owner = owner;
+            }
+
+            let alive = info.pid.is_some_and(pid_alive);
+            if info.status == "running" && !alive {
+                // Exited while the server was down; the exit code is unknown
+                info.status = "completed".to_string();
+                info.end_time.get_or_insert_with(SystemTime::now);
+            }
+            if info.status != "running" {
+                info.exited.send_replace(true);
+            }
+            info
+        })
+        .collect()
+}
+
+/// Write the current registry to the snapshot file, replacing it atomically.
+async fn save(state_dir: &Path, processes: &ProcessStore) -> std::io::Result<()> {
+    let records: Vec<ProcessRecord> = processes
+        .read()
+        .await
+        .values()
+        .map(|p| ProcessRecord {
+            id: p.id.clone(),
+            pid: p.pid,
+            command: p.command.clone(),
+            status: p.status.clone(),
+            start_time: unix_secs(p.start_time),
+            end_time: p.end_time.map(unix_secs),
+            exit_code: p.exit_code,
+            cancel_token: Some(p.cancel_token.clone()),
+            owner: Some(p.owner.clone()),
+        })
+        .collect();
+
+    let data = serde_json::to_vec(&records).map_err(std::io::Error::other)?;
+    let tmp = state_dir.join(format!("{}.tmp", SNAPSHOT_FILE));
+    tokio::fs::write(&tmp, data).await?;
+    tokio::fs::rename(&tmp, state_dir.join(SNAPSHOT_FILE)).await
+}
+
+/// Rewrite the snapshot whenever `changed` is notified.
+pub async fn run_writer(state_dir: PathBuf, processes: ProcessStore, changed: Arc<Notify>) {
+    if let Err(e) = tokio::fs::create_dir_all(&state_dir).await {
+        eprintln!("Failed to create state dir {}: {}", state_dir.display(), e);
+        return;
+    }
+    loop {
+        changed.notified().await;
+        tokio::time::sleep(WRITE_DEBOUNCE).await;
+        if let Err(e) = save(&state_dir, &processes).await {
+            eprintln!("Failed to write process state: {}", e);
+        }
+    }
+}
+
+/// Follow a restored entry: wait for a still-running PID to go away, record
+/// it as finished, then drop the entry after the usual retention period.
+///
+/// A process started since may be registered under the same ID, as the
+/// startup command always is; the entry is only touched while it still holds
+/// the restored process, recognised by its cancel token.
+pub async fn watch_restored(id: String, processes: ProcessStore, changed: Arc<Notify>) {
+    let (pid, end_time, token) = match processes.read().await.get(&id) {
+        Some(p) => (
+            p.pid.filter(|_| p.status == "running"),
+            p.end_time,
+            p.cancel_token.clone(),
+        ),
+        None => return,
+    };
+
+    let end_time = match pid {
+        Some(pid) => {
+            while pid_alive(pid) {
+                tokio::time::sleep(LIVENESS_POLL).await;
+            }
+            let now = SystemTime::now();
+            let mut store = processes.write().await;
+            let Some(proc) = store.get_mut(&id).filter(|p| p.cancel_token == token) else {
+                return;
+            };
+            if proc.status == "running" {
+                proc.status = "completed".to_string();
+            }
+            proc.end_time.get_or_insert(now);
+            proc.exited.send_replace(true);
+            drop(store);
+            changed.notify_one();
+            now
+        }
+        None => end_time.unwrap_or_else(SystemTime::now),
+    };
+
+    let elapsed = end_time.elapsed().unwrap_or_default();
+    tokio::time::sleep(FINISHED_RETENTION.saturating_sub(elapsed)).await;
+    let mut store = processes.write().await;
+    if store.get(&id).is_some_and(|p| p.cancel_token == token) {
+        store.remove(&id);
+        changed.notify_one();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use tokio::sync::RwLock;
+
+    #[tokio::test]
+    async fn test_snapshot_round_trip() {
+        let dir = std::env::temp_dir().join(format!("devbox-persist-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let (tx, _) = broadcast::channel(1);
+        let mut finished =
+            ProcessInfo::new("done".into(), Some(1), "true".into(), None, tx.clone());
+        finished.status = "failed".to_string();
+        finished.exit_code = Some(2);
+        finished.end_time = Some(SystemTime::now());
+        // A PID that cannot exist on Linux (above pid_max)
+        let stale = ProcessInfo::new("gone".into(), Some(1 << 30), "sleep 9".into(), None, tx);
+        let stale_token = stale.cancel_token.clone();
+
+        let store: ProcessStore = Arc::new(RwLock::new(HashMap::from([
+            ("done".to_string(), finished),
+            ("gone".to_string(), stale),
+        ])));
+        save(&dir, &store).await.unwrap();
+
+        let mut restored = load(&dir);
+        restored.sort_by(|a, b| a.id.cmp(&b.id));
+        assert_eq!(restored.len(), 2);
+
+        assert_eq!(restored[0].id, "done");
+        assert_eq!(restored[0].status, "failed");
+        assert_eq!(restored[0].exit_code, Some(2));
+        assert!(*restored[0].exited.borrow());
+
+        assert_eq!(restored[1].id, "gone");
+        assert_eq!(restored[1].status, "completed");
+        assert_eq!(restored[1].exit_code, None);
+        assert!(restored[1].end_time.is_some());
+        assert_eq!(restored[1].cancel_token, stale_token);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_watcher_leaves_replaced_entry_alone() {
+        let mut old = std::process::Command::new("sleep")
+            .arg("30")
+            .spawn()
+            .unwrap();
+        let (tx, _) = broadcast::channel(1);
+        let restored = ProcessInfo::new(
+            "startup".into(),
+            Some(old.id()),
+            "sleep 30".into(),
+            None,
+            tx.clone(),
+        );
+        let store: ProcessStore = Arc::new(RwLock::new(HashMap::from([(
+            "startup".to_string(),
+            restored,
+        )])));
+        let watcher = tokio::spawn(watch_restored(
+            "startup".into(),
+            store.clone(),
+            Arc::new(Notify::new()),
+        ));
+
+        // Let the watcher pick up the restored entry, as it does at startup
+        tokio::task::yield_now().await;
+
+        // A new process takes over the ID while the old one is still running
+        let replacement = ProcessInfo::new("startup".into(), Some(1), "sh".into(), None, tx);
+        store.write().await.insert("startup".into(), replacement);
+        old.kill().unwrap();
+        old.wait().unwrap();
+
+        tokio::time::timeout(Duration::from_secs(5), watcher)
+            .await
+            .expect("watcher should stop once its entry is gone")
+            .unwrap();
+        let store = store.read().await;
+        assert_eq!(store["startup"].status, "running");
+        assert!(!*store["startup"].exited.borrow());
+    }
+}
@@ -1,9 +1,11 @@
+use super::logs::LogBuffer;
+use crate::utils::diagnostics::{Diagnostic, ParserKind};
 use serde::Serialize;
-use std::collections::{HashMap, VecDeque};
+use std::collections::HashMap;
 use std::sync::Arc;
 use std::time::SystemTime;
 use tokio::process::Child;
-use tokio::sync::{broadcast, RwLock};
+use tokio::sync::{broadcast, watch, RwLock};
 
 #[derive(Debug, Clone, Serialize)]
 #[serde(rename_all = "camelCase")]
@@ -11,10 +13,37 @@ pub struct ProcessStatus {
     pub process_id: String,
     pub pid: Option<u32>,
     pub command: String,
-    pub process_status: String, // "running", "completed", "failed", "killed"
+    pub process_status: String, // "running", "completed", "failed", "killed", "cancelled"
     pub start_time: String,
     pub end_time: Option<String>,
     pub exit_code: Option<i32>,
+    /// Why the server killed the process, when it did so on its own
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub kill_reason: Option<String>,
+    /// Resource usage, only filled for running processes when requested with `?stats=true`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cpu_time_ms: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rss_bytes: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub threads: Option<u32>,
+}
+
+impl ProcessStatus {
+    /// Attach `/proc` resource usage if the process is still running.
+    pub async fn with_stats(mut self) -> Self {
+        if self.process_status != "running" {
+            return self;
+        }
+        if let Some(pid) = self.pid {
+            if let Some(stats) = crate::monitor::proc_stats::read_proc_stats(pid).await {
+                self.cpu_time_ms = Some(stats.cpu_time_ms);
+                self.rss_bytes = Some(stats.rss_bytes);
+                self.threads = Some(stats.threads);
+            }
+        }
+        self
+    }
 }
 
 pub struct ProcessInfo {
@@ -26,10 +55,21 @@ pub struct ProcessInfo {
     pub start_time: SystemTime,
     pub end_time: Option<SystemTime>,
     pub exit_code: Option<i32>,
-    pub logs: Arc<RwLock<VecDeque<String>>>, // In-memory logs
+    pub kill_reason: Option<String>, // Set when a time limit or idle timeout killed it
+    pub logs: Arc<RwLock<LogBuffer>>, // In-memory logs
     pub log_broadcast: broadcast::Sender<String>, // Real-time log broadcasting
+    pub exited: watch::Sender<bool>, // Flipped to true once the final status is recorded
+    pub cancel_token: String,        // Required by `/process/{id}/cancel`
+    pub owner: String,               // Label of the token that started it
+    pub parser: Option<ParserKind>,  // Set by `parser` on exec
+    pub diagnostics: Arc<RwLock<Vec<Diagnostic>>>, // What `parser` found in the output
+    /// The request that started it, for `/process/{id}/restart`; not persisted
+    pub exec_spec: Option<Arc<crate::handlers::process::ExecSpec>>,
 }
 
+/// Length of the per-process cancel token handed out by `process/exec`
+const CANCEL_TOKEN_LENGTH: usize = 24;
+
 impl ProcessInfo {
     pub fn new(
         id: String,
@@ -47,8 +87,15 @@ impl ProcessInfo {
             start_time: SystemTime::now(),
             end_time: None,
             exit_code: None,
-            logs: Arc::new(RwLock::new(VecDeque::new())),
+            kill_reason: None,
+            logs: Arc::default(),
             log_broadcast,
+            exited: watch::Sender::new(false),
+            cancel_token: crate::utils::common::generate_nanoid(CANCEL_TOKEN_LENGTH),
+            owner: crate::config::ADMIN_LABEL.to_string(),
+            parser: None,
+            diagnostics: Arc::new(RwLock::new(Vec::new())),
+            exec_spec: None,
         }
     }
 
@@ -72,6 +119,10 @@ impl ProcessInfo {
                 )
             }),
             exit_code: self.exit_code,
+            kill_reason: self.kill_reason.clone(),
+            cpu_time_ms: None,
+            rss_bytes: None,
+            threads: None,
         }
     }
 }
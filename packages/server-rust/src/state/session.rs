@@ -1,5 +1,7 @@
+use super::logs::LogBuffer;
 use serde::Serialize;
-use std::collections::{HashMap, VecDeque};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::time::SystemTime;
 use tokio::process::{Child, ChildStdin};
@@ -15,6 +17,8 @@ pub struct SessionStatus {
     pub session_status: String, // "active", "terminated"
     pub created_at: String,     // RFC3339
     pub last_used_at: String,   // RFC3339
+    /// Output reading is paused until live subscribers catch up
+    pub throttled: bool,
 }
 
 pub struct SessionInfo {
@@ -28,8 +32,12 @@ pub struct SessionInfo {
     pub status: String,
     pub created_at: SystemTime,
     pub last_used_at: SystemTime,
-    pub logs: Arc<RwLock<VecDeque<String>>>,
+    pub logs: Arc<RwLock<LogBuffer>>,
     pub log_broadcast: broadcast::Sender<String>,
+    /// Label of the token that created it
+    pub owner: String,
+    /// Output readers currently paused for backpressure
+    pub throttled_readers: Arc<AtomicUsize>,
 }
 
 pub struct SessionInitParams {
@@ -41,6 +49,7 @@ pub struct SessionInitParams {
     pub child: Option<Child>,
     pub stdin: ChildStdin,
     pub log_broadcast: broadcast::Sender<String>,
+    pub owner: String,
 }
 
 impl SessionInfo {
@@ -57,8 +66,10 @@ impl SessionInfo {
             status: "active".to_string(),
             created_at: now,
             last_used_at: now,
-            logs: Arc::new(RwLock::new(VecDeque::new())),
+            logs: Arc::default(),
             log_broadcast: params.log_broadcast,
+            owner: params.owner,
+            throttled_readers: Arc::new(AtomicUsize::new(0)),
         }
     }
 
@@ -82,6 +93,7 @@ impl SessionInfo {
             session_status: self.status.clone(),
             created_at: crate::utils::common::format_time(created_secs),
             last_used_at: crate::utils::common::format_time(last_used_secs),
+            throttled: self.throttled_readers.load(Ordering::Relaxed) > 0,
         }
     }
 }
@@ -102,6 +114,7 @@ mod tests {
             session_status: "active".to_string(),
             created_at: "2023-01-01T00:00:00Z".to_string(),
             last_used_at: "2023-01-01T00:00:00Z".to_string(),
+            throttled: false,
         };
 
         let json = serde_json::to_string(&status).unwrap();
@@ -0,0 +1,32 @@
+use super::AppState;
+use crate::error::AppError;
+use axum::{extract::FromRequestParts, http::request::Parts};
+use std::sync::Arc;
+
+/// Request header lowering the upload size limit for a single request.
+pub const MAX_SIZE_HEADER: &str = "x-max-size";
+
+/// Effective maximum upload size in bytes: `Config::max_file_size`, or the
+/// `X-Max-Size` header value when it is smaller. The header can only tighten
+/// the limit, never raise it.
+pub struct UploadLimit(pub u64);
+
+impl FromRequestParts<Arc<AppState>> for UploadLimit {
+    type Rejection = AppError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &Arc<AppState>,
+    ) -> Result<Self, Self::Rejection> {
+        let ceiling = state.config.max_file_size;
+        let Some(value) = parts.headers.get(MAX_SIZE_HEADER) else {
+            return Ok(UploadLimit(ceiling));
+        };
+        let requested = value
+            .to_str()
+            .ok()
+            .and_then(|v| v.trim().parse::<u64>().ok())
+            .ok_or_else(|| AppError::BadRequest("Invalid X-Max-Size header".to_string()))?;
+        Ok(UploadLimit(requested.min(ceiling)))
+    }
+}
@@ -0,0 +1,36 @@
+use super::AppState;
+use crate::error::AppError;
+use axum::{extract::FromRequestParts, http::request::Parts};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+/// Request header selecting a named workspace from `Config::workspaces`.
+pub const WORKSPACE_HEADER: &str = "x-workspace";
+
+/// Base directory that relative request paths are resolved against.
+///
+/// Defaults to `Config::workspace_path`; an `X-Workspace: <name>` header selects
+/// one of the configured named workspaces instead.
+pub struct Workspace(pub PathBuf);
+
+impl FromRequestParts<Arc<AppState>> for Workspace {
+    type Rejection = AppError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &Arc<AppState>,
+    ) -> Result<Self, Self::Rejection> {
+        let name = match parts.headers.get(WORKSPACE_HEADER) {
+            Some(value) => Some(
+                value
+                    .to_str()
+                    .map_err(|_| AppError::BadRequest("Invalid X-Workspace header".to_string()))?,
+            ),
+            None => None,
+        };
+        state
+            .config
+            .resolve_workspace(name)
+            .map(|path| Workspace(path.to_path_buf()))
+    }
+}
@@ -1,4 +1,5 @@
 use rand::Rng;
+use std::sync::OnceLock;
 
 /// NanoID alphabet (38 characters, lowercase alphanumeric + _-)
 /// Compatible with URL paths: _-0123456789abcdefghijklmnopqrstuvwxyz
@@ -43,9 +44,103 @@ pub fn generate_nanoid(length: usize) -> String {
     id
 }
 
-/// Simple ISO 8601 UTC formatting (approximate)
-/// Replaces `chrono` for basic logging/listing needs.
+/// Milliseconds since `start`, as the `u64` that duration fields carry on the
+/// wire: JSON numbers above 2^53 lose precision in JavaScript clients, and
+/// `u64` milliseconds last far longer than any process.
+pub fn elapsed_ms(start: std::time::Instant) -> u64 {
+    u64::try_from(start.elapsed().as_millis()).unwrap_or(u64::MAX)
+}
+
+/// How `format_time` writes the date: `2026-10-14` or the ISO 8601 week
+/// date `2026-W42-3` (year of the week, week number, weekday from Monday).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TimeFormat {
+    Calendar,
+    IsoWeek,
+}
+
+impl TimeFormat {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value.trim() {
+            "" | "calendar" => Ok(Self::Calendar),
+            "iso-week" => Ok(Self::IsoWeek),
+            other => Err(format!("{} (expected calendar or iso-week)", other)),
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TimeFormat::Calendar => "calendar",
+            TimeFormat::IsoWeek => "iso-week",
+        }
+    }
+}
+
+static TIME_FORMAT: OnceLock<TimeFormat> = OnceLock::new();
+
+/// Write dates from `format_time` as `format` for the rest of the process.
+pub fn set_time_format(format: TimeFormat) {
+    let _ = TIME_FORMAT.set(format);
+}
+
+/// Simple ISO 8601 formatting (approximate)
+/// Replaces `chrono` for basic logging/listing needs. Times are UTC (`Z`)
+/// unless `TIMEZONE` is set, in which case they are local to that zone and
+/// carry its offset, e.g. `+08:00`. `TIME_FORMAT=iso-week` writes week dates.
 pub fn format_time(secs: u64) -> String {
+    let format = TIME_FORMAT.get().copied().unwrap_or(TimeFormat::Calendar);
+    match crate::utils::tz::display_zone() {
+        Some(zone) => format_time_at_offset(secs, zone.offset_at(secs as i64), format),
+        None => format!("{}Z", format_local(secs, format)),
+    }
+}
+
+/// Local time at `offset` seconds east of UTC, with the offset appended.
+fn format_time_at_offset(secs: u64, offset: i32, format: TimeFormat) -> String {
+    let local = (secs as i64 + offset as i64).max(0) as u64;
+    let sign = if offset < 0 { '-' } else { '+' };
+    let offset = offset.unsigned_abs();
+    format!(
+        "{}{}{:02}:{:02}",
+        format_local(local, format),
+        sign,
+        offset / 3600,
+        offset % 3600 / 60
+    )
+}
+
+fn format_local(secs: u64, format: TimeFormat) -> String {
+    match format {
+        TimeFormat::Calendar => format_civil(secs),
+        TimeFormat::IsoWeek => format_week_date(secs),
+    }
+}
+
+/// `YYYY-Www-DTHH:MM:SS` for Unix seconds, without a zone suffix. Weeks run
+/// Monday to Sunday and belong to the year holding their Thursday, so early
+/// January can fall in the previous year's last week.
+fn format_week_date(secs: u64) -> String {
+    use crate::utils::tz::{civil_from_days, days_from_civil};
+    let days = (secs / 86400) as i64;
+    let seconds_of_day = secs % 86400;
+    // 1970-01-01 was a Thursday; 0 is Monday
+    let weekday = (days + 3).rem_euclid(7);
+    let thursday = days - weekday + 3;
+    let (year, _, _) = civil_from_days(thursday);
+    let week = (thursday - days_from_civil(year, 1, 1)) / 7 + 1;
+    format!(
+        "{:04}-W{:02}-{}T{:02}:{:02}:{:02}",
+        year,
+        week,
+        weekday + 1,
+        seconds_of_day / 3600,
+        seconds_of_day % 3600 / 60,
+        seconds_of_day % 60
+    )
+}
+
+/// `YYYY-MM-DDTHH:MM:SS` for Unix seconds, without a zone suffix.
+fn format_civil(secs: u64) -> String {
     let days_since_epoch = secs / 86400;
     let seconds_of_day = secs % 86400;
     let hours = seconds_of_day / 3600;
@@ -92,7 +187,7 @@ pub fn format_time(secs: u64) -> String {
     }
 
     format!(
-        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
         year,
         month + 1,
         days + 1,
@@ -102,10 +197,179 @@ pub fn format_time(secs: u64) -> String {
     )
 }
 
+const HTTP_WEEKDAYS: [&str; 7] = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+const HTTP_MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+/// HTTP date (RFC 9110 IMF-fixdate) for Unix seconds, e.g.
+/// `Sun, 06 Nov 1994 08:49:37 GMT`. Always UTC, regardless of `TIMEZONE`.
+pub fn format_http_date(secs: u64) -> String {
+    let days = (secs / 86400) as i64;
+    let seconds_of_day = secs % 86400;
+    let (year, month, day) = crate::utils::tz::civil_from_days(days);
+    // 1970-01-01 was a Thursday
+    let weekday = HTTP_WEEKDAYS[(days + 3).rem_euclid(7) as usize];
+    format!(
+        "{}, {:02} {} {:04} {:02}:{:02}:{:02} GMT",
+        weekday,
+        day,
+        HTTP_MONTHS[month as usize - 1],
+        year,
+        seconds_of_day / 3600,
+        seconds_of_day % 3600 / 60,
+        seconds_of_day % 60
+    )
+}
+
+/// Parse an IMF-fixdate back into Unix seconds. The obsolete RFC 850 and
+/// asctime forms are not accepted; callers treat `None` as "no date".
+pub fn parse_http_date(s: &str) -> Option<u64> {
+    let parts: Vec<&str> = s.split_whitespace().collect();
+    let [weekday, day, month, year, time, "GMT"] = parts[..] else {
+        return None;
+    };
+    if !HTTP_WEEKDAYS.contains(&weekday.strip_suffix(',')?) {
+        return None;
+    }
+    let day: u32 = day.parse().ok()?;
+    let month = HTTP_MONTHS.iter().position(|&m| m == month)? as u32 + 1;
+    let year: i64 = year.parse().ok()?;
+    let mut hms = time.split(':').map(|p| p.parse::<u64>().ok());
+    let (Some(Some(h)), Some(Some(m)), Some(Some(sec)), None) =
+        (hms.next(), hms.next(), hms.next(), hms.next())
+    else {
+        return None;
+    };
+    if !(1..=31).contains(&day) || h > 23 || m > 59 || sec > 60 {
+        return None;
+    }
+    let days = crate::utils::tz::days_from_civil(year, month, day);
+    u64::try_from(days * 86400)
+        .ok()
+        .map(|d| d + h * 3600 + m * 60 + sec)
+}
+
+/// Log entry prefix marking a piece of an over-long line that continues in
+/// the next entry, e.g. `[stdout]+` instead of `[stdout]`.
+pub const CONTINUATION_MARK: &str = "+";
+
+/// Split a process or shell output entry, `[stdout] text` or with the mark
+/// `[stdout]+ text`, into its stream, its text and whether the line continues
+/// in the next entry. `None` for entries that are not stdout or stderr.
+pub fn split_output_entry(entry: &str) -> Option<(&'static str, &str, bool)> {
+    let (stream, rest) = if let Some(rest) = entry.strip_prefix("[stdout]") {
+        ("stdout", rest)
+    } else if let Some(rest) = entry.strip_prefix("[stderr]") {
+        ("stderr", rest)
+    } else {
+        return None;
+    };
+    let (continued, rest) = match rest.strip_prefix(CONTINUATION_MARK) {
+        Some(rest) => (true, rest),
+        None => (false, rest),
+    };
+    rest.strip_prefix(' ').map(|text| (stream, text, continued))
+}
+
+/// Read one line of output, or at most `max` bytes of it. Returns the text
+/// (lossily decoded, newline included when one was read) and whether the line
+/// was cut at `max` and continues, or `None` at end of input. `buf` carries a
+/// UTF-8 character split at the cut over to the next call, so reuse it.
+pub async fn read_line_capped<R: tokio::io::AsyncBufRead + Unpin>(
+    reader: &mut R,
+    buf: &mut Vec<u8>,
+    max: usize,
+) -> std::io::Result<Option<(String, bool)>> {
+    use tokio::io::AsyncBufReadExt;
+
+    let continued = loop {
+        let available = reader.fill_buf().await?;
+        if available.is_empty() {
+            if buf.is_empty() {
+                return Ok(None);
+            }
+            break false;
+        }
+        let window = &available[..available.len().min(max.saturating_sub(buf.len()).max(1))];
+        if let Some(i) = window.iter().position(|&b| b == b'\n') {
+            buf.extend_from_slice(&window[..=i]);
+            reader.consume(i + 1);
+            break false;
+        }
+        let n = window.len();
+        buf.extend_from_slice(window);
+        reader.consume(n);
+        if buf.len() >= max {
+            break true;
+        }
+    };
+
+    let keep = match std::str::from_utf8(buf) {
+        Err(e) if continued && e.error_len().is_none() && e.valid_up_to() > 0 => {
+            buf.len() - e.valid_up_to()
+        }
+        _ => 0,
+    };
+    let rest = buf.split_off(buf.len() - keep);
+    let line = String::from_utf8_lossy(buf).into_owned();
+    *buf = rest;
+    Ok(Some((line, continued)))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_format_time_at_offset() {
+        // 2026-01-01T00:00:00Z
+        assert_eq!(format_civil(1767225600), "2026-01-01T00:00:00");
+        assert_eq!(
+            format_time_at_offset(1767225600, 8 * 3600, TimeFormat::Calendar),
+            "2026-01-01T08:00:00+08:00"
+        );
+        assert_eq!(
+            format_time_at_offset(1767225600, -(3 * 3600 + 1800), TimeFormat::Calendar),
+            "2025-12-31T20:30:00-03:30"
+        );
+        assert_eq!(
+            format_time_at_offset(1767225600, 8 * 3600, TimeFormat::IsoWeek),
+            "2026-W01-4T08:00:00+08:00"
+        );
+    }
+
+    #[test]
+    fn test_format_week_date() {
+        // 2026-10-14, a Wednesday
+        assert_eq!(format_week_date(1791936000), "2026-W42-3T00:00:00");
+        // 2021-01-03 is a Sunday in the last week of 2020
+        assert_eq!(format_week_date(1609632000 + 3723), "2020-W53-7T01:02:03");
+        // 2024-12-30 is a Monday in the first week of 2025
+        assert_eq!(format_week_date(1735516800), "2025-W01-1T00:00:00");
+        assert_eq!(format_week_date(0), "1970-W01-4T00:00:00");
+
+        assert_eq!(TimeFormat::parse("iso-week"), Ok(TimeFormat::IsoWeek));
+        assert_eq!(TimeFormat::parse(""), Ok(TimeFormat::Calendar));
+        assert!(TimeFormat::parse("week").is_err());
+    }
+
+    #[test]
+    fn test_http_date() {
+        assert_eq!(format_http_date(784111777), "Sun, 06 Nov 1994 08:49:37 GMT");
+        assert_eq!(
+            format_http_date(1767225600),
+            "Thu, 01 Jan 2026 00:00:00 GMT"
+        );
+        assert_eq!(
+            parse_http_date("Sun, 06 Nov 1994 08:49:37 GMT"),
+            Some(784111777)
+        );
+        assert_eq!(parse_http_date("Sunday, 06-Nov-94 08:49:37 GMT"), None);
+        assert_eq!(parse_http_date("Sun, 06 Nov 1994 08:49:37 PST"), None);
+        assert_eq!(parse_http_date("garbage"), None);
+    }
+
     #[test]
     fn test_generate_id_length() {
         let id = generate_id();
@@ -149,4 +413,27 @@ mod tests {
             }
         }
     }
+
+    #[tokio::test]
+    async fn test_read_line_capped() {
+        let input = "short\nlonger line\nabcde\u{e9}!".as_bytes();
+        let mut reader = tokio::io::BufReader::new(input);
+        let mut buf = Vec::new();
+        let mut pieces = Vec::new();
+        while let Some(piece) = read_line_capped(&mut reader, &mut buf, 6).await.unwrap() {
+            pieces.push(piece);
+        }
+        let owned = |s: &str, c: bool| (s.to_string(), c);
+        assert_eq!(
+            pieces,
+            vec![
+                owned("short\n", false),
+                owned("longer", true),
+                owned(" line\n", false),
+                // The two-byte character is not split across pieces
+                owned("abcde", true),
+                owned("\u{e9}!", false),
+            ]
+        );
+    }
 }
@@ -0,0 +1,286 @@
+//! Line-by-line extraction of compiler and linter diagnostics from process
+//! output, for `process/exec` with a `parser`. Only the tools' default
+//! human-readable formats are understood; anything else passes through.
+
+use serde::Serialize;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Diagnostic {
+    pub file: String,
+    pub line: u32,
+    pub column: u32,
+    /// `error` or `warning`
+    pub severity: String,
+    pub message: String,
+    /// Error code or lint rule, e.g. `E0308`, `TS2322`, `no-unused-vars`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ParserKind {
+    Cargo,
+    Tsc,
+    Eslint,
+}
+
+impl ParserKind {
+    pub const NAMES: &'static [&'static str] = &["cargo", "eslint", "tsc"];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "cargo" => Some(Self::Cargo),
+            "tsc" => Some(Self::Tsc),
+            "eslint" => Some(Self::Eslint),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Cargo => "cargo",
+            Self::Tsc => "tsc",
+            Self::Eslint => "eslint",
+        }
+    }
+}
+
+/// Parser for one output stream; formats that span several lines keep
+/// their state between calls.
+pub struct DiagnosticParser {
+    kind: ParserKind,
+    /// cargo: the `error[E..]: message` header waiting for its `-->` line
+    pending: Option<(String, Option<String>, String)>,
+    /// eslint: the file the following entries belong to
+    current_file: Option<String>,
+}
+
+impl DiagnosticParser {
+    pub fn new(kind: ParserKind) -> Self {
+        Self {
+            kind,
+            pending: None,
+            current_file: None,
+        }
+    }
+
+    /// Feed one line of output, without its newline.
+    pub fn push_line(&mut self, line: &str) -> Option<Diagnostic> {
+        let line = strip_ansi(line);
+        let line = line.trim_end();
+        match self.kind {
+            ParserKind::Cargo => self.cargo(line),
+            ParserKind::Tsc => tsc(line),
+            ParserKind::Eslint => self.eslint(line),
+        }
+    }
+
+    /// `error[E0308]: mismatched types` followed a line later by
+    /// `  --> src/main.rs:4:18`.
+    fn cargo(&mut self, line: &str) -> Option<Diagnostic> {
+        if let Some(header) = cargo_header(line) {
+            self.pending = Some(header);
+            return None;
+        }
+        let location = line.trim_start().strip_prefix("--> ")?;
+        let (severity, code, message) = self.pending.take()?;
+        let (file, line, column) = split_location(location)?;
+        Some(Diagnostic {
+            file,
+            line,
+            column,
+            severity,
+            message,
+            code,
+        })
+    }
+
+    /// A file path on its own line, then entries such as
+    /// `  3:10  error  'x' is defined but never used  no-unused-vars`.
+    fn eslint(&mut self, line: &str) -> Option<Diagnostic> {
+        if line.is_empty() {
+            self.current_file = None;
+            return None;
+        }
+        if !line.starts_with(char::is_whitespace) {
+            // The summary line (`✖ 3 problems`) is not a file
+            self.current_file = (!line.starts_with('✖')).then(|| line.to_string());
+            return None;
+        }
+        let file = self.current_file.clone()?;
+        let entry = line.trim_start();
+        let (position, rest) = entry.split_once(char::is_whitespace)?;
+        let (line, column) = position.split_once(':')?;
+        let rest = rest.trim_start();
+        let (severity, rest) = rest.split_once(char::is_whitespace)?;
+        if severity != "error" && severity != "warning" {
+            return None;
+        }
+        // Message and rule are separated by two or more spaces
+        let rest = rest.trim();
+        let (message, code) = match rest.rsplit_once("  ") {
+            Some((message, rule)) => (message.trim_end(), Some(rule.trim().to_string())),
+            None => (rest, None),
+        };
+        Some(Diagnostic {
+            file,
+            line: line.parse().ok()?,
+            column: column.parse().ok()?,
+            severity: severity.to_string(),
+            message: message.to_string(),
+            code,
+        })
+    }
+}
+
+/// `src/a.ts(3,5): error TS2322: message`, or with `--pretty`
+/// `src/a.ts:3:5 - error TS2322: message`.
+fn tsc(line: &str) -> Option<Diagnostic> {
+    let (file, line_no, column, rest) = if let Some((head, rest)) = line.split_once("): ") {
+        let (file, coords) = head.rsplit_once('(')?;
+        let (line_no, column) = coords.split_once(',')?;
+        (
+            file.to_string(),
+            line_no.parse().ok()?,
+            column.parse().ok()?,
+            rest,
+        )
+    } else {
+        let (location, rest) = line.split_once(" - ")?;
+        let (file, line_no, column) = split_location(location)?;
+        (file, line_no, column, rest)
+    };
+    let (severity, rest) = rest.split_once(' ')?;
+    if severity != "error" && severity != "warning" {
+        return None;
+    }
+    let (code, message) = match rest.split_once(": ") {
+        Some((code, message)) if code.starts_with("TS") => (Some(code.to_string()), message),
+        _ => (None, rest),
+    };
+    Some(Diagnostic {
+        file,
+        line: line_no,
+        column,
+        severity: severity.to_string(),
+        message: message.to_string(),
+        code,
+    })
+}
+
+fn cargo_header(line: &str) -> Option<(String, Option<String>, String)> {
+    let (severity, rest) = ["error", "warning"]
+        .iter()
+        .find_map(|s| line.strip_prefix(s).map(|rest| (*s, rest)))?;
+    let (code, rest) = match rest.strip_prefix('[') {
+        Some(rest) => {
+            let (code, rest) = rest.split_once(']')?;
+            (Some(code.to_string()), rest)
+        }
+        None => (None, rest),
+    };
+    let message = rest.strip_prefix(": ")?;
+    Some((severity.to_string(), code, message.to_string()))
+}
+
+/// `path:line:column`, where the path itself may contain colons.
+fn split_location(location: &str) -> Option<(String, u32, u32)> {
+    let mut parts = location.trim().rsplitn(3, ':');
+    let column = parts.next()?.parse().ok()?;
+    let line = parts.next()?.parse().ok()?;
+    let file = parts.next().filter(|f| !f.is_empty())?;
+    Some((file.to_string(), line, column))
+}
+
+/// Drop ANSI escape sequences (colours) that tools emit when forced to.
+fn strip_ansi(line: &str) -> std::borrow::Cow<'_, str> {
+    if !line.contains('\x1b') {
+        return std::borrow::Cow::Borrowed(line);
+    }
+    let mut out = String::with_capacity(line.len());
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            if chars.next() == Some('[') {
+                for c in chars.by_ref() {
+                    if c.is_ascii_alphabetic() {
+                        break;
+                    }
+                }
+            }
+        } else {
+            out.push(c);
+        }
+    }
+    std::borrow::Cow::Owned(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(kind: ParserKind, output: &str) -> Vec<Diagnostic> {
+        let mut parser = DiagnosticParser::new(kind);
+        output.lines().filter_map(|l| parser.push_line(l)).collect()
+    }
+
+    #[test]
+    fn test_diagnostic_parsers() {
+        let cargo = parse(
+            ParserKind::Cargo,
+            "   Compiling app v0.1.0\n\
+             error[E0308]: mismatched types\n \
+             --> src/main.rs:4:18\n  |\n\
+             warning: unused variable: `x`\n  \x1b[1m-->\x1b[0m src/lib.rs:10:9\n\
+             warning: `app` (bin) generated 1 warning\n\
+             error: could not compile `app`\n",
+        );
+        assert_eq!(cargo.len(), 2);
+        assert_eq!(
+            cargo[0],
+            Diagnostic {
+                file: "src/main.rs".into(),
+                line: 4,
+                column: 18,
+                severity: "error".into(),
+                message: "mismatched types".into(),
+                code: Some("E0308".into()),
+            }
+        );
+        assert_eq!(cargo[1].severity, "warning");
+        assert_eq!(cargo[1].code, None);
+        assert_eq!((cargo[1].file.as_str(), cargo[1].line), ("src/lib.rs", 10));
+
+        let tsc = parse(
+            ParserKind::Tsc,
+            "src/a.ts(3,5): error TS2322: Type 'string' is not assignable to type 'number'.\n\
+             src/b.ts:7:1 - warning TS6133: 'y' is declared but never used.\n\
+             Found 2 errors.\n",
+        );
+        assert_eq!(tsc.len(), 2);
+        assert_eq!((tsc[0].line, tsc[0].column), (3, 5));
+        assert_eq!(tsc[0].code.as_deref(), Some("TS2322"));
+        assert_eq!(
+            tsc[0].message,
+            "Type 'string' is not assignable to type 'number'."
+        );
+        assert_eq!(
+            (tsc[1].file.as_str(), tsc[1].severity.as_str()),
+            ("src/b.ts", "warning")
+        );
+
+        let eslint = parse(
+            ParserKind::Eslint,
+            "\n/app/src/index.js\n  \
+             3:10  error    'x' is defined but never used  no-unused-vars\n  \
+             8:1   warning  Unexpected console statement   no-console\n\n\
+             ✖ 2 problems (1 error, 1 warning)\n",
+        );
+        assert_eq!(eslint.len(), 2);
+        assert_eq!(eslint[0].file, "/app/src/index.js");
+        assert_eq!(eslint[0].message, "'x' is defined but never used");
+        assert_eq!(eslint[0].code.as_deref(), Some("no-unused-vars"));
+        assert_eq!((eslint[1].line, eslint[1].column), (8, 1));
+    }
+}
@@ -0,0 +1,474 @@
+//! Minimal line-based unified diff (Myers' algorithm) and patch application.
+//! Replaces `similar`/`diffy` for the diff and patch endpoints to keep the binary small.
+
+/// Above this edit distance the diff falls back to replacing the whole changed
+/// region, bounding the O(D^2) trace memory.
+const MAX_EDIT_DISTANCE: usize = 2048;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum DiffOp {
+    Equal,
+    Delete,
+    Insert,
+}
+
+/// Outcome of diffing two texts
+pub struct UnifiedDiff {
+    pub diff: String,
+    pub additions: usize,
+    pub deletions: usize,
+}
+
+/// Split text into lines, keeping the trailing `\n` so a missing final newline
+/// is reported as a change.
+pub fn split_lines(text: &str) -> Vec<&str> {
+    text.split_inclusive('\n').collect()
+}
+
+/// Produce a unified diff of `old` against `new` with `context` lines around each change.
+/// Returns an empty diff when the texts are identical.
+pub fn unified_diff(
+    old: &str,
+    new: &str,
+    old_name: &str,
+    new_name: &str,
+    context: usize,
+) -> UnifiedDiff {
+    let a = split_lines(old);
+    let b = split_lines(new);
+    let ops = diff_lines(&a, &b);
+
+    let additions = ops.iter().filter(|op| **op == DiffOp::Insert).count();
+    let deletions = ops.iter().filter(|op| **op == DiffOp::Delete).count();
+    if additions == 0 && deletions == 0 {
+        return UnifiedDiff {
+            diff: String::new(),
+            additions,
+            deletions,
+        };
+    }
+
+    // Line positions (old, new) before each op
+    let mut positions = Vec::with_capacity(ops.len() + 1);
+    let (mut x, mut y) = (0usize, 0usize);
+    for op in &ops {
+        positions.push((x, y));
+        match op {
+            DiffOp::Equal => {
+                x += 1;
+                y += 1;
+            }
+            DiffOp::Delete => x += 1,
+            DiffOp::Insert => y += 1,
+        }
+    }
+    positions.push((x, y));
+
+    let changes: Vec<usize> = (0..ops.len())
+        .filter(|&i| ops[i] != DiffOp::Equal)
+        .collect();
+
+    let mut out = format!("--- {}\n+++ {}\n", old_name, new_name);
+    let mut c = 0;
+    while c < changes.len() {
+        let start = changes[c].saturating_sub(context);
+        let mut last = changes[c];
+        // Merge changes whose context windows overlap
+        while c + 1 < changes.len() && changes[c + 1] - last <= 2 * context + 1 {
+            c += 1;
+            last = changes[c];
+        }
+        c += 1;
+        let end = (last + context + 1).min(ops.len());
+
+        let (old_pos, new_pos) = positions[start];
+        let old_count = ops[start..end]
+            .iter()
+            .filter(|op| **op != DiffOp::Insert)
+            .count();
+        let new_count = ops[start..end]
+            .iter()
+            .filter(|op| **op != DiffOp::Delete)
+            .count();
+
+        out.push_str(&format!(
+            "@@ -{} +{} @@\n",
+            hunk_range(old_pos, old_count),
+            hunk_range(new_pos, new_count)
+        ));
+
+        for (i, op) in ops.iter().enumerate().take(end).skip(start) {
+            let (ox, ny) = positions[i];
+            let (prefix, line) = match op {
+                DiffOp::Equal => (' ', a[ox]),
+                DiffOp::Delete => ('-', a[ox]),
+                DiffOp::Insert => ('+', b[ny]),
+            };
+            out.push(prefix);
+            out.push_str(line);
+            if !line.ends_with('\n') {
+                out.push_str("\n\\ No newline at end of file\n");
+            }
+        }
+    }
+
+    UnifiedDiff {
+        diff: out,
+        additions,
+        deletions,
+    }
+}
+
+fn hunk_range(pos: usize, count: usize) -> String {
+    // An empty range points at the line before it
+    let start = if count == 0 { pos } else { pos + 1 };
+    if count == 1 {
+        start.to_string()
+    } else {
+        format!("{},{}", start, count)
+    }
+}
+
+/// Compute the edit script between two line slices.
+fn diff_lines(a: &[&str], b: &[&str]) -> Vec<DiffOp> {
+    // Trim the common prefix and suffix, which Myers would otherwise walk repeatedly
+    let prefix = a.iter().zip(b).take_while(|(x, y)| x == y).count();
+    let suffix = a[prefix..]
+        .iter()
+        .rev()
+        .zip(b[prefix..].iter().rev())
+        .take_while(|(x, y)| x == y)
+        .count();
+
+    let mut ops = vec![DiffOp::Equal; prefix];
+    let mid_a = &a[prefix..a.len() - suffix];
+    let mid_b = &b[prefix..b.len() - suffix];
+    match myers(mid_a, mid_b) {
+        Some(mid) => ops.extend(mid),
+        None => {
+            ops.extend(std::iter::repeat_n(DiffOp::Delete, mid_a.len()));
+            ops.extend(std::iter::repeat_n(DiffOp::Insert, mid_b.len()));
+        }
+    }
+    ops.extend(std::iter::repeat_n(DiffOp::Equal, suffix));
+    ops
+}
+
+/// Myers' O(ND) shortest edit script. Returns `None` if the edit distance
+/// exceeds `MAX_EDIT_DISTANCE`.
+fn myers(a: &[&str], b: &[&str]) -> Option<Vec<DiffOp>> {
+    let n = a.len() as isize;
+    let m = b.len() as isize;
+    let max = (a.len() + b.len()).min(MAX_EDIT_DISTANCE) as isize;
+    let offset = max + 1;
+    let mut v = vec![0isize; (2 * max + 3) as usize];
+    // trace[d] holds v[k] for k in -d..=d before step d
+    let mut trace: Vec<Vec<isize>> = Vec::new();
+
+    let mut found = None;
+    'outer: for d in 0..=max {
+        trace.push(v[(offset - d) as usize..=(offset + d) as usize].to_vec());
+        let mut k = -d;
+        while k <= d {
+            let idx = (offset + k) as usize;
+            let mut x = if k == -d || (k != d && v[idx - 1] < v[idx + 1]) {
+                v[idx + 1]
+            } else {
+                v[idx - 1] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && a[x as usize] == b[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            v[idx] = x;
+            if x >= n && y >= m {
+                found = Some(d);
+                break 'outer;
+            }
+            k += 2;
+        }
+    }
+    let depth = found?;
+
+    // Backtrack from (n, m) to (0, 0)
+    let mut ops = Vec::new();
+    let (mut x, mut y) = (n, m);
+    for d in (0..=depth).rev() {
+        let snapshot = &trace[d as usize];
+        let get = |k: isize| snapshot[(k + d) as usize];
+        let k = x - y;
+        let prev_k = if d == 0 {
+            0
+        } else if k == -d || (k != d && get(k - 1) < get(k + 1)) {
+            k + 1
+        } else {
+            k - 1
+        };
+        let (prev_x, prev_y) = if d == 0 {
+            (0, 0)
+        } else {
+            let px = get(prev_k);
+            (px, px - prev_k)
+        };
+        while x > prev_x && y > prev_y {
+            ops.push(DiffOp::Equal);
+            x -= 1;
+            y -= 1;
+        }
+        if d > 0 {
+            if x == prev_x {
+                ops.push(DiffOp::Insert);
+            } else {
+                ops.push(DiffOp::Delete);
+            }
+        }
+        x = prev_x;
+        y = prev_y;
+    }
+    ops.reverse();
+    Some(ops)
+}
+
+#[derive(Debug, PartialEq)]
+pub enum PatchError {
+    /// The patch text is not a valid unified diff
+    Malformed(String),
+    /// A hunk's context does not match the target text
+    Mismatch(String),
+}
+
+struct Hunk {
+    old_start: usize,
+    old_lines: Vec<String>,
+    new_lines: Vec<String>,
+}
+
+/// Apply a unified diff to `original`. Hunks must match exactly at their stated
+/// positions; no fuzz or offset search is attempted.
+pub fn apply_patch(original: &str, patch: &str) -> Result<String, PatchError> {
+    let hunks = parse_patch(patch)?;
+    if hunks.is_empty() {
+        return Err(PatchError::Malformed("No hunks found in patch".to_string()));
+    }
+
+    let lines = split_lines(original);
+    let mut out = String::with_capacity(original.len());
+    let mut cursor = 0;
+
+    for (i, hunk) in hunks.iter().enumerate() {
+        // An empty old range refers to the line *after which* text is inserted
+        let start = if hunk.old_lines.is_empty() {
+            hunk.old_start
+        } else {
+            hunk.old_start.saturating_sub(1)
+        };
+        if start < cursor {
+            return Err(PatchError::Malformed(format!(
+                "Hunk #{} overlaps the previous hunk",
+                i + 1
+            )));
+        }
+
+        let end = start + hunk.old_lines.len();
+        let matches = end <= lines.len()
+            && lines[start..end]
+                .iter()
+                .zip(&hunk.old_lines)
+                .all(|(a, b)| *a == b.as_str());
+        if !matches {
+            return Err(PatchError::Mismatch(format!(
+                "Hunk #{} does not apply at line {}",
+                i + 1,
+                hunk.old_start
+            )));
+        }
+
+        lines[cursor..start].iter().for_each(|l| out.push_str(l));
+        hunk.new_lines.iter().for_each(|l| out.push_str(l));
+        cursor = end;
+    }
+    lines[cursor..].iter().for_each(|l| out.push_str(l));
+
+    Ok(out)
+}
+
+fn parse_patch(patch: &str) -> Result<Vec<Hunk>, PatchError> {
+    let mut hunks = Vec::new();
+    let mut lines = patch.split_inclusive('\n').peekable();
+
+    while let Some(line) = lines.next() {
+        // File headers and other preamble lines are ignored
+        if !line.starts_with("@@") {
+            continue;
+        }
+        let (old_start, old_count, new_count) = parse_hunk_header(line)?;
+        let mut hunk = Hunk {
+            old_start,
+            old_lines: Vec::with_capacity(old_count),
+            new_lines: Vec::with_capacity(new_count),
+        };
+
+        // Which side(s) the previous body line belonged to, for "\ No newline" markers
+        let mut last = ' ';
+        while hunk.old_lines.len() < old_count || hunk.new_lines.len() < new_count {
+            let Some(body) = lines.next() else {
+                return Err(PatchError::Malformed(format!(
+                    "Hunk at line {} is truncated",
+                    old_start
+                )));
+            };
+            let (prefix, text) = match body.chars().next() {
+                Some(c @ (' ' | '-' | '+')) => (c, &body[1..]),
+                // Some editors strip the space from empty context lines
+                Some('\n') | Some('\r') => (' ', body),
+                Some('\\') => {
+                    strip_newline(&mut hunk, last);
+                    continue;
+                }
+                _ => {
+                    return Err(PatchError::Malformed(format!(
+                        "Unexpected line in hunk: {}",
+                        body.trim_end()
+                    )))
+                }
+            };
+            match prefix {
+                ' ' => {
+                    hunk.old_lines.push(text.to_string());
+                    hunk.new_lines.push(text.to_string());
+                }
+                '-' => hunk.old_lines.push(text.to_string()),
+                _ => hunk.new_lines.push(text.to_string()),
+            }
+            if hunk.old_lines.len() > old_count || hunk.new_lines.len() > new_count {
+                return Err(PatchError::Malformed(format!(
+                    "Hunk at line {} has more lines than its header declares",
+                    old_start
+                )));
+            }
+            last = prefix;
+        }
+
+        // A trailing marker belongs to the hunk's final line
+        if lines.peek().is_some_and(|l| l.starts_with('\\')) {
+            lines.next();
+            strip_newline(&mut hunk, last);
+        }
+
+        hunks.push(hunk);
+    }
+
+    Ok(hunks)
+}
+
+fn strip_newline(hunk: &mut Hunk, side: char) {
+    let trim = |lines: &mut Vec<String>| {
+        if let Some(l) = lines.last_mut() {
+            if l.ends_with('\n') {
+                l.pop();
+            }
+        }
+    };
+    match side {
+        '-' => trim(&mut hunk.old_lines),
+        '+' => trim(&mut hunk.new_lines),
+        _ => {
+            trim(&mut hunk.old_lines);
+            trim(&mut hunk.new_lines);
+        }
+    }
+}
+
+/// Parse "@@ -l[,s] +l[,s] @@" into (old start, old count, new count)
+fn parse_hunk_header(line: &str) -> Result<(usize, usize, usize), PatchError> {
+    let malformed = || PatchError::Malformed(format!("Invalid hunk header: {}", line.trim_end()));
+    let mut parts = line.split_whitespace();
+    if parts.next() != Some("@@") {
+        return Err(malformed());
+    }
+    let old = parts
+        .next()
+        .and_then(|p| p.strip_prefix('-'))
+        .ok_or_else(malformed)?;
+    let new = parts
+        .next()
+        .and_then(|p| p.strip_prefix('+'))
+        .ok_or_else(malformed)?;
+
+    let parse_range = |r: &str| -> Option<(usize, usize)> {
+        match r.split_once(',') {
+            Some((start, count)) => Some((start.parse().ok()?, count.parse().ok()?)),
+            None => Some((r.parse().ok()?, 1)),
+        }
+    };
+    let (old_start, old_count) = parse_range(old).ok_or_else(malformed)?;
+    let (_, new_count) = parse_range(new).ok_or_else(malformed)?;
+    Ok((old_start, old_count, new_count))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_identical_texts() {
+        let d = unified_diff("a\nb\n", "a\nb\n", "a", "b", 3);
+        assert!(d.diff.is_empty());
+        assert_eq!((d.additions, d.deletions), (0, 0));
+    }
+
+    #[test]
+    fn test_single_change_with_context() {
+        let old = "1\n2\n3\n4\n5\n6\n7\n";
+        let new = "1\n2\n3\nfour\n5\n6\n7\n";
+        let d = unified_diff(old, new, "old.txt", "new.txt", 1);
+        assert_eq!(
+            d.diff,
+            "--- old.txt\n+++ new.txt\n@@ -3,3 +3,3 @@\n 3\n-4\n+four\n 5\n"
+        );
+        assert_eq!((d.additions, d.deletions), (1, 1));
+    }
+
+    #[test]
+    fn test_separate_hunks_and_missing_newline() {
+        let old = "a\nb\nc\nd\ne\nf\ng\nh";
+        let new = "A\nb\nc\nd\ne\nf\ng\nH";
+        let d = unified_diff(old, new, "l", "r", 1);
+        assert_eq!(
+            d.diff,
+            "--- l\n+++ r\n@@ -1,2 +1,2 @@\n-a\n+A\n b\n@@ -7,2 +7,2 @@\n g\n-h\n\\ No newline at end of file\n+H\n\\ No newline at end of file\n"
+        );
+    }
+
+    #[test]
+    fn test_insert_into_empty() {
+        let d = unified_diff("", "x\n", "l", "r", 3);
+        assert_eq!(d.diff, "--- l\n+++ r\n@@ -0,0 +1 @@\n+x\n");
+    }
+
+    #[test]
+    fn test_patch_round_trip() {
+        let old = "a\nb\nc\nd\ne\nf\ng\nh";
+        let new = "A\nb\nc\nd\ne\nf\ng\nh\ni\n";
+        let d = unified_diff(old, new, "l", "r", 3);
+        assert_eq!(apply_patch(old, &d.diff).unwrap(), new);
+
+        let d = unified_diff(new, "", "l", "r", 3);
+        assert_eq!(apply_patch(new, &d.diff).unwrap(), "");
+    }
+
+    #[test]
+    fn test_patch_context_mismatch() {
+        let patch = "--- a\n+++ b\n@@ -2,2 +2,2 @@\n b\n-c\n+C\n";
+        assert_eq!(apply_patch("a\nb\nc\n", patch).unwrap(), "a\nb\nC\n");
+        assert!(matches!(
+            apply_patch("a\nx\nc\n", patch),
+            Err(PatchError::Mismatch(_))
+        ));
+        assert!(matches!(
+            apply_patch("a\n", "not a patch"),
+            Err(PatchError::Malformed(_))
+        ));
+    }
+}
@@ -0,0 +1,152 @@
+//! Shell-style wildcard matching for single path components: `*` matches any
+//! run of characters, `?` one character, and `[...]` a class with ranges and
+//! `!`/`^` negation. There is no `/` handling or `**`.
+
+#[derive(Debug, Clone)]
+enum Token {
+    Char(char),
+    Any,
+    Star,
+    Class {
+        ranges: Vec<(char, char)>,
+        negated: bool,
+    },
+}
+
+/// Compiled wildcard pattern matched against a whole name.
+#[derive(Debug, Clone)]
+pub struct Glob {
+    tokens: Vec<Token>,
+}
+
+impl Glob {
+    /// An unclosed `[` is an error rather than a literal, so typos don't
+    /// silently match nothing.
+    pub fn new(pattern: &str) -> Result<Self, String> {
+        let chars: Vec<char> = pattern.chars().collect();
+        let mut tokens = Vec::new();
+        let mut i = 0;
+        while i < chars.len() {
+            match chars[i] {
+                '*' => {
+                    // Consecutive stars are one star
+                    if !matches!(tokens.last(), Some(Token::Star)) {
+                        tokens.push(Token::Star);
+                    }
+                }
+                '?' => tokens.push(Token::Any),
+                '[' => {
+                    let (token, next) = parse_class(&chars, i + 1)
+                        .ok_or_else(|| format!("unclosed '[' in pattern: {}", pattern))?;
+                    tokens.push(token);
+                    i = next;
+                    continue;
+                }
+                '\\' if i + 1 < chars.len() => {
+                    i += 1;
+                    tokens.push(Token::Char(chars[i]));
+                }
+                c => tokens.push(Token::Char(c)),
+            }
+            i += 1;
+        }
+        Ok(Glob { tokens })
+    }
+
+    pub fn is_match(&self, name: &str) -> bool {
+        let input: Vec<char> = name.chars().collect();
+        let (mut t, mut n) = (0, 0);
+        // Position after the last star and the input index it is matched up to
+        let mut backtrack: Option<(usize, usize)> = None;
+        while n < input.len() {
+            match self.tokens.get(t) {
+                Some(Token::Star) => {
+                    backtrack = Some((t + 1, n));
+                    t += 1;
+                    continue;
+                }
+                Some(token) if token_matches(token, input[n]) => {
+                    t += 1;
+                    n += 1;
+                    continue;
+                }
+                _ => {}
+            }
+            // Mismatch: let the last star swallow one more character
+            match backtrack {
+                Some((star_next, star_n)) => {
+                    t = star_next;
+                    n = star_n + 1;
+                    backtrack = Some((star_next, star_n + 1));
+                }
+                None => return false,
+            }
+        }
+        self.tokens[t..]
+            .iter()
+            .all(|token| matches!(token, Token::Star))
+    }
+}
+
+fn token_matches(token: &Token, c: char) -> bool {
+    match token {
+        Token::Char(expected) => *expected == c,
+        Token::Any => true,
+        Token::Star => false,
+        Token::Class { ranges, negated } => {
+            ranges.iter().any(|&(lo, hi)| lo <= c && c <= hi) != *negated
+        }
+    }
+}
+
+/// Parse a class body starting after `[`; returns the token and the index
+/// after the closing `]`. A `]` right after the opening (or negation) is literal.
+fn parse_class(chars: &[char], mut i: usize) -> Option<(Token, usize)> {
+    let negated = matches!(chars.get(i), Some('!') | Some('^'));
+    if negated {
+        i += 1;
+    }
+    let mut ranges = Vec::new();
+    let start = i;
+    loop {
+        let c = *chars.get(i)?;
+        if c == ']' && i > start {
+            return Some((Token::Class { ranges, negated }, i + 1));
+        }
+        if chars.get(i + 1) == Some(&'-') && chars.get(i + 2).is_some_and(|&hi| hi != ']') {
+            ranges.push((c, chars[i + 2]));
+            i += 3;
+        } else {
+            ranges.push((c, c));
+            i += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(pattern: &str, name: &str) -> bool {
+        Glob::new(pattern).unwrap().is_match(name)
+    }
+
+    #[test]
+    fn test_glob_matching() {
+        assert!(matches("*.log", "server.log"));
+        assert!(matches("*.log", ".log"));
+        assert!(!matches("*.log", "server.log.1"));
+        assert!(matches("node_modules", "node_modules"));
+        assert!(!matches("node_modules", "node_modules2"));
+        assert!(matches("*cache*", "__pycache__"));
+        assert!(matches("a*b*c", "aXXbYYbc"));
+        assert!(matches("file?.txt", "file1.txt"));
+        assert!(!matches("file?.txt", "file.txt"));
+        assert!(matches("[a-c]*.tmp", "b1.tmp"));
+        assert!(!matches("[!a-c]*.tmp", "b1.tmp"));
+        assert!(matches("[]x]", "]"));
+        assert!(matches(r"\*", "*"));
+        assert!(!matches(r"\*", "a"));
+        assert!(Glob::new("[abc").is_err());
+    }
+}
@@ -0,0 +1,108 @@
+//! Content-type detection from file extensions and leading magic bytes. The
+//! extension table covers common web, document, media and archive types; it
+//! is small on purpose and not a full registry.
+
+use std::path::Path;
+
+const BY_EXTENSION: &[(&str, &str)] = &[
+    ("7z", "application/x-7z-compressed"),
+    ("avif", "image/avif"),
+    ("bmp", "image/bmp"),
+    ("bz2", "application/x-bzip2"),
+    ("c", "text/x-c"),
+    ("cpp", "text/x-c++"),
+    ("css", "text/css"),
+    ("csv", "text/csv"),
+    ("gif", "image/gif"),
+    ("go", "text/x-go"),
+    ("gz", "application/gzip"),
+    ("h", "text/x-c"),
+    ("htm", "text/html"),
+    ("html", "text/html"),
+    ("ico", "image/x-icon"),
+    ("java", "text/x-java"),
+    ("jpeg", "image/jpeg"),
+    ("jpg", "image/jpeg"),
+    ("js", "text/javascript"),
+    ("json", "application/json"),
+    ("jsx", "text/javascript"),
+    ("md", "text/markdown"),
+    ("mjs", "text/javascript"),
+    ("mp3", "audio/mpeg"),
+    ("mp4", "video/mp4"),
+    ("ogg", "audio/ogg"),
+    ("otf", "font/otf"),
+    ("pdf", "application/pdf"),
+    ("png", "image/png"),
+    ("py", "text/x-python"),
+    ("rs", "text/x-rust"),
+    ("sh", "application/x-sh"),
+    ("svg", "image/svg+xml"),
+    ("tar", "application/x-tar"),
+    ("tgz", "application/gzip"),
+    ("toml", "application/toml"),
+    ("ts", "text/typescript"),
+    ("tsx", "text/typescript"),
+    ("ttf", "font/ttf"),
+    ("txt", "text/plain"),
+    ("wasm", "application/wasm"),
+    ("wav", "audio/wav"),
+    ("webm", "video/webm"),
+    ("webp", "image/webp"),
+    ("woff", "font/woff"),
+    ("woff2", "font/woff2"),
+    ("xml", "application/xml"),
+    ("yaml", "application/yaml"),
+    ("yml", "application/yaml"),
+    ("zip", "application/zip"),
+];
+
+/// Signatures checked against the start of the content.
+const BY_MAGIC: &[(&[u8], &str)] = &[
+    (b"\x89PNG\r\n\x1a\n", "image/png"),
+    (b"\xff\xd8\xff", "image/jpeg"),
+    (b"GIF87a", "image/gif"),
+    (b"GIF89a", "image/gif"),
+    (b"%PDF-", "application/pdf"),
+    (b"PK\x03\x04", "application/zip"),
+    (b"\x1f\x8b", "application/gzip"),
+    (b"BZh", "application/x-bzip2"),
+    (b"7z\xbc\xaf\x27\x1c", "application/x-7z-compressed"),
+    (b"\0asm", "application/wasm"),
+    (b"\x7fELF", "application/x-elf"),
+    (b"wOFF", "font/woff"),
+    (b"wOF2", "font/woff2"),
+];
+
+/// Type for the extension of `path`, matched case-insensitively.
+pub fn from_extension(path: &Path) -> Option<&'static str> {
+    let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+    BY_EXTENSION
+        .binary_search_by(|(e, _)| e.cmp(&ext.as_str()))
+        .ok()
+        .map(|i| BY_EXTENSION[i].1)
+}
+
+/// Type recognised from the leading bytes of a file.
+pub fn from_magic(header: &[u8]) -> Option<&'static str> {
+    BY_MAGIC
+        .iter()
+        .find(|(magic, _)| header.starts_with(magic))
+        .map(|(_, mime)| *mime)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mime_detection() {
+        assert!(BY_EXTENSION.windows(2).all(|w| w[0].0 < w[1].0));
+        assert_eq!(from_extension(Path::new("a/logo.PNG")), Some("image/png"));
+        assert_eq!(from_extension(Path::new("main.rs")), Some("text/x-rust"));
+        assert_eq!(from_extension(Path::new("Makefile")), None);
+        assert_eq!(from_magic(b"%PDF-1.7\n"), Some("application/pdf"));
+        assert_eq!(from_magic(b"\x7fELF\x02\x01"), Some("application/x-elf"));
+        assert_eq!(from_magic(b"hello"), None);
+    }
+}
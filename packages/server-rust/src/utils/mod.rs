@@ -1,2 +1,11 @@
 pub mod common;
+pub mod diagnostics;
+pub mod diff;
+pub mod glob;
+pub mod hash;
+pub mod mime;
 pub mod path;
+pub mod regex;
+pub mod tz;
+pub mod validate;
+pub mod watch;
@@ -1,6 +1,35 @@
 use crate::error::AppError;
+use std::collections::HashMap;
+use std::ffi::{OsStr, OsString};
+use std::future::Future;
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
 use std::path::{Component, Path, PathBuf};
 
+/// Marks a path parameter as percent-encoded bytes, for names that are not
+/// valid UTF-8 and so cannot be sent as plain JSON or query strings.
+pub const ENCODED_PATH_PREFIX: &str = "encoded:";
+
+/// Request header that turns on `encoded:` path parameters; without it they
+/// are ordinary names, so files called `encoded:...` stay reachable.
+pub const PATH_ENCODING_HEADER: &str = "x-path-encoding";
+
+tokio::task_local! {
+    /// Whether the request being handled sent `X-Path-Encoding: percent`
+    static DECODE_PATHS: bool;
+}
+
+/// Run `f` with `encoded:` path parameters decoded (`enabled`) or taken literally.
+pub async fn with_path_decoding<F: Future>(enabled: bool, f: F) -> F::Output {
+    DECODE_PATHS.scope(enabled, f).await
+}
+
+/// Whether `encoded:` path parameters are decoded here; work that outlives
+/// the handler, such as a streamed response, carries this into its own
+/// `with_path_decoding`.
+pub fn decoding_paths() -> bool {
+    DECODE_PATHS.try_with(|enabled| *enabled).unwrap_or(false)
+}
+
 pub fn normalize_path(path: &Path) -> PathBuf {
     let mut ret = PathBuf::new();
     for component in path.components() {
@@ -18,7 +47,8 @@ pub fn normalize_path(path: &Path) -> PathBuf {
 }
 
 pub fn validate_path(base_path: &Path, user_path: &str) -> Result<PathBuf, AppError> {
-    let p = Path::new(user_path);
+    let decoded = decode_path(user_path)?;
+    let p = decoded.as_path();
 
     // WARNING: This is insecure. The user has explicitly requested this behavior,
     // which mirrors the Go implementation. It allows any absolute path to be accessed.
@@ -48,6 +78,135 @@ pub fn validate_path(base_path: &Path, user_path: &str) -> Result<PathBuf, AppEr
     })
 }
 
+/// A path parameter as given, or the bytes of an `encoded:` one when the
+/// request opted in.
+fn decode_path(user_path: &str) -> Result<PathBuf, AppError> {
+    let Some(encoded) = user_path
+        .strip_prefix(ENCODED_PATH_PREFIX)
+        .filter(|_| decoding_paths())
+    else {
+        return Ok(PathBuf::from(user_path));
+    };
+    percent_decode(encoded)
+        .map(|bytes| PathBuf::from(OsString::from_vec(bytes)))
+        .ok_or_else(|| {
+            AppError::BadRequest(format!("Invalid percent-encoding in path: {}", user_path))
+        })
+}
+
+/// `%XX` escapes for `%`, control characters and every byte above ASCII;
+/// everything else is kept as is.
+fn percent_encode(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len());
+    for &b in bytes {
+        if b == b'%' || !(0x20..0x7f).contains(&b) {
+            out.push_str(&format!("%{:02X}", b));
+        } else {
+            out.push(b as char);
+        }
+    }
+    out
+}
+
+fn percent_decode(s: &str) -> Option<Vec<u8>> {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = std::str::from_utf8(bytes.get(i + 1..i + 3)?).ok()?;
+            out.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            out.push(bytes[i]);
+            i += 1;
+        }
+    }
+    Some(out)
+}
+
+/// Percent-encoded bytes of a name that is not valid UTF-8, or `None`
+/// when the plain name already round-trips.
+pub fn encoded_name(name: &OsStr) -> Option<String> {
+    match name.to_str() {
+        Some(_) => None,
+        None => Some(percent_encode(name.as_bytes())),
+    }
+}
+
+/// `path` as a string that `validate_path` maps back to the same path: the
+/// path itself when it is UTF-8, otherwise its `encoded:` form.
+pub fn path_string(path: &Path) -> String {
+    match encoded_name(path.as_os_str()) {
+        Some(encoded) => format!("{}{}", ENCODED_PATH_PREFIX, encoded),
+        None => path.to_string_lossy().to_string(),
+    }
+}
+
+/// Path relative to the workspace, or `None` if `path` lies outside it.
+/// The workspace root itself is reported as ".".
+pub fn relative_to_workspace(base_path: &Path, path: &Path) -> Option<String> {
+    let base = normalize_path(base_path);
+    let rel = normalize_path(path).strip_prefix(&base).ok()?.to_path_buf();
+    Some(if rel.as_os_str().is_empty() {
+        ".".to_string()
+    } else {
+        rel.to_string_lossy().to_string()
+    })
+}
+
+/// Expand a leading `~` to `home` and `$VAR` / `${VAR}` references, shell style.
+/// Variables are looked up in `env` first, then the server environment; unset
+/// variables expand to an empty string.
+///
+/// Expansion happens *before* `validate_path`, so expanded values are subject to
+/// the same (non-)confinement rules as literal paths.
+pub fn expand_path(input: &str, home: &Path, env: Option<&HashMap<String, String>>) -> String {
+    let lookup = |name: &str| -> String {
+        env.and_then(|e| e.get(name).cloned())
+            .or_else(|| std::env::var(name).ok())
+            .unwrap_or_default()
+    };
+
+    let mut out = String::with_capacity(input.len());
+    let rest = if input == "~" || input.starts_with("~/") {
+        out.push_str(&home.to_string_lossy());
+        &input[1..]
+    } else {
+        input
+    };
+
+    let mut chars = rest.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '$' {
+            out.push(c);
+            continue;
+        }
+        let name: String = if chars.peek() == Some(&'{') {
+            chars.next();
+            let name: String = chars.by_ref().take_while(|&ch| ch != '}').collect();
+            name
+        } else {
+            let mut name = String::new();
+            while let Some(&ch) = chars.peek() {
+                if ch.is_ascii_alphanumeric() || ch == '_' {
+                    name.push(ch);
+                    chars.next();
+                } else {
+                    break;
+                }
+            }
+            name
+        };
+        if name.is_empty() {
+            out.push('$');
+        } else {
+            out.push_str(&lookup(&name));
+        }
+    }
+    out
+}
+
 // Helper to ensure directory exists
 pub async fn ensure_directory(path: &Path) -> Result<(), AppError> {
     if !path.exists() {
@@ -58,9 +217,77 @@ pub async fn ensure_directory(path: &Path) -> Result<(), AppError> {
     Ok(())
 }
 
+/// A hidden, uniquely named path next to `path` for staging its new contents.
+pub fn temp_sibling(path: &Path) -> PathBuf {
+    let parent = path.parent().unwrap_or_else(|| Path::new("."));
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    parent.join(format!(
+        ".{}.{}.tmp",
+        file_name,
+        crate::utils::common::generate_id()
+    ))
+}
+
+/// A uniquely named path for staging new contents of `path`: inside
+/// `temp_dir` when that is on the same filesystem, so the staged file can be
+/// renamed into place, otherwise a hidden sibling of `path`.
+pub async fn staging_path(temp_dir: &Path, path: &Path) -> PathBuf {
+    use std::os::unix::fs::MetadataExt;
+    let parent = match path.parent() {
+        Some(p) if !p.as_os_str().is_empty() => p,
+        _ => Path::new("."),
+    };
+    if tokio::fs::create_dir_all(temp_dir).await.is_ok() {
+        let same_device = match (
+            tokio::fs::metadata(temp_dir).await,
+            tokio::fs::metadata(parent).await,
+        ) {
+            (Ok(a), Ok(b)) => a.dev() == b.dev(),
+            _ => false,
+        };
+        if same_device {
+            let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+            return temp_dir.join(format!(
+                "{}.{}.tmp",
+                file_name,
+                crate::utils::common::generate_id()
+            ));
+        }
+    }
+    temp_sibling(path)
+}
+
+/// Rename a staged file over `path`, carrying over the permissions of an
+/// existing target.
+pub async fn replace_with(staged: &Path, path: &Path) -> std::io::Result<()> {
+    if let Ok(metadata) = tokio::fs::metadata(path).await {
+        tokio::fs::set_permissions(staged, metadata.permissions()).await?;
+    }
+    tokio::fs::rename(staged, path).await
+}
+
+/// Write a file atomically by writing a temp file (see `staging_path`) and renaming it over
+/// the target. Permissions of an existing target are carried over.
+pub async fn write_atomic(temp_dir: &Path, path: &Path, contents: &[u8]) -> Result<(), AppError> {
+    let tmp_path = staging_path(temp_dir, path).await;
+
+    let result = async {
+        tokio::fs::write(&tmp_path, contents).await?;
+        replace_with(&tmp_path, path).await
+    }
+    .await;
+
+    if let Err(e) = result {
+        tokio::fs::remove_file(&tmp_path).await.ok();
+        return Err(e.into());
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::os::unix::fs::MetadataExt;
 
     #[test]
     fn test_normalize_path() {
@@ -114,8 +341,115 @@ mod tests {
         let res = validate_path(base, "src/../lib.rs").unwrap();
         assert_eq!(res, PathBuf::from("/home/devbox/project/lib.rs"));
 
-        // Test traversal escaping workspace (allowed as per insecure policy)
-        let res = validate_path(base, "../../etc/passwd").unwrap();
+        // Test traversal escaping workspace (allowed as per insecure policy);
+        // the workspace is three levels deep, so reaching `/` takes three `..`
+        let res = validate_path(base, "../../../etc/passwd").unwrap();
         assert_eq!(res, PathBuf::from("/etc/passwd"));
     }
+
+    #[test]
+    fn test_encoded_paths() {
+        let base = Path::new("/ws");
+        let latin1 = Path::new(OsStr::from_bytes(b"/ws/caf\xe9 100%.txt"));
+        assert_eq!(path_string(latin1), "encoded:/ws/caf%E9 100%25.txt");
+        DECODE_PATHS.sync_scope(true, || {
+            assert_eq!(validate_path(base, &path_string(latin1)).unwrap(), latin1);
+            assert_eq!(
+                validate_path(base, "encoded:docs/caf%e9").unwrap(),
+                Path::new(OsStr::from_bytes(b"/ws/docs/caf\xe9"))
+            );
+            assert!(validate_path(base, "encoded:bad%G1").is_err());
+            assert!(validate_path(base, "encoded:cut%E").is_err());
+        });
+        // Without the header the prefix is part of an ordinary name
+        assert_eq!(
+            validate_path(base, "encoded:docs/caf%e9").unwrap(),
+            PathBuf::from("/ws/encoded:docs/caf%e9")
+        );
+        DECODE_PATHS.sync_scope(false, || {
+            assert!(validate_path(base, "encoded:bad%G1").is_ok());
+        });
+
+        assert_eq!(path_string(Path::new("/ws/naïve.txt")), "/ws/naïve.txt");
+        assert_eq!(encoded_name(OsStr::new("naïve.txt")), None);
+        assert_eq!(
+            encoded_name(OsStr::from_bytes(b"\xff\xfe")).as_deref(),
+            Some("%FF%FE")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_staging_path() {
+        let root = std::env::temp_dir().join(format!(
+            "devbox-staging-{}",
+            crate::utils::common::generate_id()
+        ));
+        let temp_dir = root.join(".devbox-tmp");
+        let target = root.join("src/main.rs");
+        std::fs::create_dir_all(target.parent().unwrap()).unwrap();
+
+        let staged = staging_path(&temp_dir, &target).await;
+        assert_eq!(staged.parent(), Some(temp_dir.as_path()));
+        assert!(staged
+            .file_name()
+            .unwrap()
+            .to_string_lossy()
+            .starts_with("main.rs."));
+        write_atomic(&temp_dir, &target, b"fn main() {}")
+            .await
+            .unwrap();
+        assert_eq!(std::fs::read(&target).unwrap(), b"fn main() {}");
+        assert_eq!(std::fs::read_dir(&temp_dir).unwrap().count(), 0);
+
+        // A rename cannot cross filesystems, so such targets stage beside themselves
+        let shm = Path::new("/dev/shm");
+        let other_device = std::fs::metadata(shm)
+            .is_ok_and(|m| m.dev() != std::fs::metadata(&root).unwrap().dev());
+        if other_device {
+            let staged = staging_path(&temp_dir, &shm.join("out.bin")).await;
+            assert_eq!(staged.parent(), Some(shm));
+        }
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_relative_to_workspace() {
+        let base = Path::new("/home/devbox/project");
+        assert_eq!(
+            relative_to_workspace(base, Path::new("/home/devbox/project/src/main.rs")),
+            Some("src/main.rs".to_string())
+        );
+        assert_eq!(
+            relative_to_workspace(base, Path::new("/home/devbox/project")),
+            Some(".".to_string())
+        );
+        assert_eq!(
+            relative_to_workspace(base, Path::new("/home/devbox/project-other/a")),
+            None
+        );
+        assert_eq!(relative_to_workspace(base, Path::new("/etc/passwd")), None);
+    }
+
+    #[test]
+    fn test_expand_path() {
+        let home = Path::new("/home/devbox");
+        let mut env = HashMap::new();
+        env.insert("PROJECT".to_string(), "app".to_string());
+
+        assert_eq!(expand_path("~", home, None), "/home/devbox");
+        assert_eq!(
+            expand_path("~/$PROJECT/src", home, Some(&env)),
+            "/home/devbox/app/src"
+        );
+        assert_eq!(
+            expand_path("/srv/${PROJECT}-data", home, Some(&env)),
+            "/srv/app-data"
+        );
+        assert_eq!(expand_path("a/~b/$", home, Some(&env)), "a/~b/$");
+        assert_eq!(
+            expand_path("$DEVBOX_TEST_UNSET_VAR/x", home, Some(&env)),
+            "/x"
+        );
+    }
 }
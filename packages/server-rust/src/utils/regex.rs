@@ -0,0 +1,522 @@
+//! Small regular expression matcher for operator-supplied patterns (command
+//! allow/deny lists). Supports literals, `.`, `[...]` classes with ranges and
+//! negation, `\d \w \s` (and their negations), `^`/`$` anchors, groups, `|`
+//! and the `* + ? {n} {n,} {n,m}` quantifiers.
+//!
+//! Patterns are compiled to a small instruction program that is run as a
+//! Thompson NFA simulation: every alternative is tracked in lockstep, so
+//! matching takes time linear in the input and never recurses, however the
+//! pattern nests its repetitions.
+
+#[derive(Debug, Clone)]
+enum Node {
+    Char(char),
+    Any,
+    Class {
+        ranges: Vec<(char, char)>,
+        negated: bool,
+    },
+    Start,
+    End,
+    Group(Vec<Vec<Node>>),
+    Repeat {
+        node: Box<Node>,
+        min: usize,
+        max: Option<usize>,
+    },
+}
+
+const DIGIT: &[(char, char)] = &[('0', '9')];
+const WORD: &[(char, char)] = &[('a', 'z'), ('A', 'Z'), ('0', '9'), ('_', '_')];
+const SPACE: &[(char, char)] = &[(' ', ' '), ('\t', '\r')];
+
+/// Instructions a pattern may compile to, bounding counted repetitions like
+/// `(a{100}){100}`
+const MAX_PROGRAM_SIZE: usize = 10_000;
+
+/// Compiled pattern; `is_match` searches anywhere in the input unless anchored.
+#[derive(Debug, Clone)]
+pub struct Regex {
+    source: String,
+    program: Vec<Inst>,
+}
+
+impl Regex {
+    pub fn new(pattern: &str) -> Result<Self, String> {
+        let mut parser = Parser {
+            chars: pattern.chars().collect(),
+            pos: 0,
+        };
+        let alternatives = parser.parse_alternatives()?;
+        if parser.pos < parser.chars.len() {
+            return Err("unmatched ')'".to_string());
+        }
+        let mut compiler = Compiler::default();
+        compiler.alternatives(&alternatives)?;
+        compiler.push(Inst::Match)?;
+        Ok(Self {
+            source: pattern.to_string(),
+            program: compiler.program,
+        })
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.source
+    }
+
+    pub fn is_match(&self, text: &str) -> bool {
+        let program = &self.program;
+        let mut current = Threads::new(program.len());
+        let mut next = Threads::new(program.len());
+        let mut stack = Vec::new();
+        let mut chars = text.chars().peekable();
+        let mut at_start = true;
+        loop {
+            let at_end = chars.peek().is_none();
+            // A new attempt starts at every position
+            if add_thread(program, &mut current, &mut stack, 0, at_start, at_end) {
+                return true;
+            }
+            let Some(c) = chars.next() else {
+                return false;
+            };
+            let at_end = chars.peek().is_none();
+            next.clear();
+            for &pc in &current.pcs {
+                if program[pc].consumes(c)
+                    && add_thread(program, &mut next, &mut stack, pc + 1, false, at_end)
+                {
+                    return true;
+                }
+            }
+            std::mem::swap(&mut current, &mut next);
+            at_start = false;
+        }
+    }
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek();
+        self.pos += 1;
+        c
+    }
+
+    fn parse_alternatives(&mut self) -> Result<Vec<Vec<Node>>, String> {
+        let mut alternatives = vec![self.parse_sequence()?];
+        while self.peek() == Some('|') {
+            self.pos += 1;
+            alternatives.push(self.parse_sequence()?);
+        }
+        Ok(alternatives)
+    }
+
+    fn parse_sequence(&mut self) -> Result<Vec<Node>, String> {
+        let mut nodes = Vec::new();
+        while let Some(c) = self.peek() {
+            if c == '|' || c == ')' {
+                break;
+            }
+            let atom = self.parse_atom()?;
+            nodes.push(self.parse_quantifier(atom)?);
+        }
+        Ok(nodes)
+    }
+
+    fn parse_atom(&mut self) -> Result<Node, String> {
+        match self.next() {
+            Some('(') => {
+                if self.chars[self.pos..].starts_with(&['?', ':']) {
+                    self.pos += 2;
+                }
+                let group = self.parse_alternatives()?;
+                if self.next() != Some(')') {
+                    return Err("missing ')'".to_string());
+                }
+                Ok(Node::Group(group))
+            }
+            Some('[') => self.parse_class(),
+            Some('.') => Ok(Node::Any),
+            Some('^') => Ok(Node::Start),
+            Some('$') => Ok(Node::End),
+            Some('\\') => self.parse_escape(),
+            Some(c @ ('*' | '+' | '?' | '{')) => Err(format!("nothing to repeat before '{}'", c)),
+            Some(c) => Ok(Node::Char(c)),
+            None => Err("unexpected end of pattern".to_string()),
+        }
+    }
+
+    fn parse_escape(&mut self) -> Result<Node, String> {
+        let class = |ranges: &[(char, char)], negated| Node::Class {
+            ranges: ranges.to_vec(),
+            negated,
+        };
+        Ok(match self.next() {
+            Some('d') => class(DIGIT, false),
+            Some('D') => class(DIGIT, true),
+            Some('w') => class(WORD, false),
+            Some('W') => class(WORD, true),
+            Some('s') => class(SPACE, false),
+            Some('S') => class(SPACE, true),
+            Some(c) => Node::Char(unescape(c)),
+            None => return Err("trailing '\\' in pattern".to_string()),
+        })
+    }
+
+    fn parse_class(&mut self) -> Result<Node, String> {
+        let negated = self.peek() == Some('^');
+        if negated {
+            self.pos += 1;
+        }
+        let mut ranges = Vec::new();
+        let mut first = true;
+        loop {
+            let c = match self.next() {
+                Some(']') if !first => break,
+                Some('\\') => match self.next() {
+                    Some('d') => {
+                        ranges.extend_from_slice(DIGIT);
+                        first = false;
+                        continue;
+                    }
+                    Some('w') => {
+                        ranges.extend_from_slice(WORD);
+                        first = false;
+                        continue;
+                    }
+                    Some('s') => {
+                        ranges.extend_from_slice(SPACE);
+                        first = false;
+                        continue;
+                    }
+                    Some(c) => unescape(c),
+                    None => return Err("unterminated character class".to_string()),
+                },
+                Some(c) => c,
+                None => return Err("unterminated character class".to_string()),
+            };
+            first = false;
+            if self.peek() == Some('-') && self.chars.get(self.pos + 1).is_some_and(|&n| n != ']') {
+                self.pos += 1;
+                let end = match self.next() {
+                    Some('\\') => self.next().map(unescape),
+                    other => other,
+                }
+                .ok_or_else(|| "unterminated character class".to_string())?;
+                if end < c {
+                    return Err(format!("invalid range {}-{}", c, end));
+                }
+                ranges.push((c, end));
+            } else {
+                ranges.push((c, c));
+            }
+        }
+        Ok(Node::Class { ranges, negated })
+    }
+
+    fn parse_quantifier(&mut self, atom: Node) -> Result<Node, String> {
+        let (min, max) = match self.peek() {
+            Some('*') => (0, None),
+            Some('+') => (1, None),
+            Some('?') => (0, Some(1)),
+            Some('{') => return self.parse_braces(atom),
+            _ => return Ok(atom),
+        };
+        self.pos += 1;
+        repeat(atom, min, max)
+    }
+
+    fn parse_braces(&mut self, atom: Node) -> Result<Node, String> {
+        let close = self.chars[self.pos..]
+            .iter()
+            .position(|&c| c == '}')
+            .ok_or_else(|| "missing '}'".to_string())?;
+        let body: String = self.chars[self.pos + 1..self.pos + close].iter().collect();
+        let parse = |s: &str| {
+            s.trim()
+                .parse::<usize>()
+                .map_err(|_| format!("invalid repetition {{{}}}", body))
+        };
+        let (min, max) = match body.split_once(',') {
+            None => {
+                let n = parse(&body)?;
+                (n, Some(n))
+            }
+            Some((min, "")) => (parse(min)?, None),
+            Some((min, max)) => (parse(min)?, Some(parse(max)?)),
+        };
+        if max.is_some_and(|max| max < min) {
+            return Err(format!("invalid repetition {{{}}}", body));
+        }
+        self.pos += close + 1;
+        repeat(atom, min, max)
+    }
+}
+
+fn repeat(atom: Node, min: usize, max: Option<usize>) -> Result<Node, String> {
+    if matches!(atom, Node::Start | Node::End | Node::Repeat { .. }) {
+        return Err("nothing to repeat".to_string());
+    }
+    Ok(Node::Repeat {
+        node: Box::new(atom),
+        min,
+        max,
+    })
+}
+
+fn unescape(c: char) -> char {
+    match c {
+        'n' => '\n',
+        't' => '\t',
+        'r' => '\r',
+        c => c,
+    }
+}
+
+/// One step of the compiled program. `Char`, `Any` and `Class` consume a
+/// character; the rest are followed without consuming input.
+#[derive(Debug, Clone)]
+enum Inst {
+    Char(char),
+    Any,
+    Class {
+        ranges: Vec<(char, char)>,
+        negated: bool,
+    },
+    Start,
+    End,
+    Split(usize, usize),
+    Jmp(usize),
+    Match,
+}
+
+impl Inst {
+    fn consumes(&self, c: char) -> bool {
+        match self {
+            Inst::Char(x) => *x == c,
+            Inst::Any => c != '\n',
+            Inst::Class { ranges, negated } => {
+                ranges.iter().any(|&(lo, hi)| lo <= c && c <= hi) != *negated
+            }
+            _ => false,
+        }
+    }
+}
+
+#[derive(Default)]
+struct Compiler {
+    program: Vec<Inst>,
+}
+
+impl Compiler {
+    fn push(&mut self, inst: Inst) -> Result<usize, String> {
+        if self.program.len() >= MAX_PROGRAM_SIZE {
+            return Err("pattern is too large once repetitions are expanded".to_string());
+        }
+        self.program.push(inst);
+        Ok(self.program.len() - 1)
+    }
+
+    fn patch(&mut self, pc: usize, target: usize) {
+        match &mut self.program[pc] {
+            Inst::Split(_, to) | Inst::Jmp(to) => *to = target,
+            _ => unreachable!("only jumps are patched"),
+        }
+    }
+
+    fn alternatives(&mut self, alternatives: &[Vec<Node>]) -> Result<(), String> {
+        let Some((last, rest)) = alternatives.split_last() else {
+            return Ok(());
+        };
+        let mut jumps = Vec::new();
+        for seq in rest {
+            let split = self.push(Inst::Split(self.program.len() + 1, 0))?;
+            self.sequence(seq)?;
+            jumps.push(self.push(Inst::Jmp(0))?);
+            self.patch(split, self.program.len());
+        }
+        self.sequence(last)?;
+        for jump in jumps {
+            self.patch(jump, self.program.len());
+        }
+        Ok(())
+    }
+
+    fn sequence(&mut self, nodes: &[Node]) -> Result<(), String> {
+        nodes.iter().try_for_each(|node| self.node(node))
+    }
+
+    fn node(&mut self, node: &Node) -> Result<(), String> {
+        match node {
+            Node::Char(c) => self.push(Inst::Char(*c)).map(drop),
+            Node::Any => self.push(Inst::Any).map(drop),
+            Node::Class { ranges, negated } => self
+                .push(Inst::Class {
+                    ranges: ranges.clone(),
+                    negated: *negated,
+                })
+                .map(drop),
+            Node::Start => self.push(Inst::Start).map(drop),
+            Node::End => self.push(Inst::End).map(drop),
+            Node::Group(alternatives) => self.alternatives(alternatives),
+            Node::Repeat { node, min, max } => {
+                for _ in 0..*min {
+                    self.node(node)?;
+                }
+                match max {
+                    None => {
+                        let split = self.push(Inst::Split(self.program.len() + 1, 0))?;
+                        self.node(node)?;
+                        self.push(Inst::Jmp(split))?;
+                        self.patch(split, self.program.len());
+                    }
+                    Some(max) => {
+                        let mut splits = Vec::new();
+                        for _ in *min..*max {
+                            splits.push(self.push(Inst::Split(self.program.len() + 1, 0))?);
+                            self.node(node)?;
+                        }
+                        for split in splits {
+                            self.patch(split, self.program.len());
+                        }
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Threads alive at one input position: the consuming instructions they wait
+/// at, each at most once.
+struct Threads {
+    pcs: Vec<usize>,
+    /// Every instruction visited at this position, so it is followed only once
+    seen: Vec<bool>,
+    visited: Vec<usize>,
+}
+
+impl Threads {
+    fn new(size: usize) -> Self {
+        Self {
+            pcs: Vec::new(),
+            seen: vec![false; size],
+            visited: Vec::new(),
+        }
+    }
+
+    fn visit(&mut self, pc: usize) -> bool {
+        if self.seen[pc] {
+            return false;
+        }
+        self.seen[pc] = true;
+        self.visited.push(pc);
+        true
+    }
+
+    fn clear(&mut self) {
+        for &pc in &self.visited {
+            self.seen[pc] = false;
+        }
+        self.visited.clear();
+        self.pcs.clear();
+    }
+}
+
+/// Follow every non-consuming instruction reachable from `pc` and add the
+/// consuming ones to `threads`. Returns whether `Match` is reachable. Each
+/// instruction is visited at most once per position, so empty loops such as
+/// `(a*)*` terminate.
+fn add_thread(
+    program: &[Inst],
+    threads: &mut Threads,
+    stack: &mut Vec<usize>,
+    pc: usize,
+    at_start: bool,
+    at_end: bool,
+) -> bool {
+    stack.push(pc);
+    while let Some(pc) = stack.pop() {
+        if !threads.visit(pc) {
+            continue;
+        }
+        match &program[pc] {
+            Inst::Match => {
+                stack.clear();
+                return true;
+            }
+            Inst::Jmp(to) => stack.push(*to),
+            Inst::Split(first, second) => {
+                stack.push(*second);
+                stack.push(*first);
+            }
+            Inst::Start => {
+                if at_start {
+                    stack.push(pc + 1);
+                }
+            }
+            Inst::End => {
+                if at_end {
+                    stack.push(pc + 1);
+                }
+            }
+            _ => threads.pcs.push(pc),
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(pattern: &str, text: &str) -> bool {
+        Regex::new(pattern).unwrap().is_match(text)
+    }
+
+    #[test]
+    fn test_regex_matching() {
+        assert!(matches(r"rm\s+-rf\s+/$", "sudo rm -rf /"));
+        assert!(!matches(r"rm\s+-rf\s+/$", "rm -rf /tmp/x"));
+        assert!(matches(r"^(npm|yarn|pnpm)( |$)", "pnpm install"));
+        assert!(!matches(r"^(npm|yarn|pnpm)( |$)", "npx foo"));
+        assert!(matches(r"[^a-z]", "abc1"));
+        assert!(!matches(r"^[a-z_]+$", "abc-def"));
+        assert!(matches(r"^a{2,3}$", "aaa"));
+        assert!(!matches(r"^a{2,3}$", "aaaa"));
+        assert!(matches(r"^x\d{3}\.y$", "x123.y"));
+        assert!(matches(r"^(a*)*b$", "aaab"));
+        assert!(!matches(r"^(a*)*b$", "aaac"));
+        assert!(matches(r"curl .*\| *(ba)?sh", "curl https://x.sh | bash"));
+
+        assert!(Regex::new("(abc").is_err());
+        assert!(Regex::new("abc)").is_err());
+        assert!(Regex::new("*a").is_err());
+        assert!(Regex::new("[a-").is_err());
+        assert!(Regex::new("a{3,1}").is_err());
+        assert!(Regex::new("(a{100}){200}").is_err());
+    }
+
+    #[test]
+    fn test_regex_is_linear() {
+        // Nested repetitions that make a backtracking matcher exponential
+        let input = format!("{}c", "a".repeat(5000));
+        assert!(!matches(r"^(a+)+b$", &input));
+        assert!(!matches(r"^(a|a)*b$", &input));
+        assert!(matches(r"^(a+)+c$", &input));
+
+        // Long inputs neither recurse nor overflow the stack
+        let command = format!("echo {}", "x".repeat(200_000));
+        assert!(matches(r"^echo x+$", &command));
+        assert!(!matches(r"rm\s+-rf", &command));
+    }
+}
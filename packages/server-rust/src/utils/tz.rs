@@ -0,0 +1,474 @@
+//! Minimal IANA time zone support in place of `chrono-tz`: reads TZif files
+//! from the system zoneinfo directory and evaluates their POSIX TZ footer for
+//! instants past the last listed transition. Only UTC offsets are computed;
+//! abbreviations and leap seconds are ignored.
+
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+#[derive(Debug, Clone)]
+pub struct TimeZone {
+    name: String,
+    /// Transition instants in Unix seconds, ascending
+    transitions: Vec<i64>,
+    /// UTC offset in effect from the transition at the same index
+    offsets: Vec<i32>,
+    /// UTC offset before the first transition
+    initial_offset: i32,
+    /// Footer rule for instants after the last transition
+    rule: Option<Rule>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Rule {
+    std_offset: i32,
+    dst: Option<DstRule>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct DstRule {
+    offset: i32,
+    /// Start date and time of day, in local standard time
+    start: (DateRule, i32),
+    /// End date and time of day, in local daylight time
+    end: (DateRule, i32),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum DateRule {
+    /// `Jn`: day 1..=365, February 29 never counted
+    Julian(u16),
+    /// `n`: zero-based day of the year, counting February 29
+    Ordinal(u16),
+    /// `Mm.w.d`: day `d` (0 = Sunday) of week `w` (5 = last) of month `m`
+    MonthWeekDay(u32, u32, u32),
+}
+
+static DISPLAY_ZONE: OnceLock<TimeZone> = OnceLock::new();
+
+/// Render timestamps from `format_time` in `zone` for the rest of the process.
+pub fn set_display_zone(zone: TimeZone) {
+    let _ = DISPLAY_ZONE.set(zone);
+}
+
+pub fn display_zone() -> Option<&'static TimeZone> {
+    DISPLAY_ZONE.get()
+}
+
+impl TimeZone {
+    /// Load an IANA zone such as `Asia/Shanghai` from `$TZDIR` (default
+    /// `/usr/share/zoneinfo`).
+    pub fn load(name: &str) -> Result<Self, String> {
+        if name.is_empty() || name.starts_with('/') || name.split('/').any(|part| part == "..") {
+            return Err(format!("invalid time zone name: {}", name));
+        }
+        let dir = std::env::var_os("TZDIR")
+            .map(PathBuf::from)
+            .unwrap_or_else(|| PathBuf::from("/usr/share/zoneinfo"));
+        let data = std::fs::read(dir.join(name)).map_err(|e| format!("{}: {}", name, e))?;
+        Self::parse(name, &data).map_err(|e| format!("{}: {}", name, e))
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Seconds east of UTC at the Unix time `secs`.
+    pub fn offset_at(&self, secs: i64) -> i32 {
+        let n = self.transitions.partition_point(|&t| t <= secs);
+        if n == self.transitions.len() {
+            if let Some(rule) = &self.rule {
+                return rule.offset_at(secs);
+            }
+        }
+        match n {
+            0 => self.initial_offset,
+            n => self.offsets[n - 1],
+        }
+    }
+
+    fn parse(name: &str, data: &[u8]) -> Result<Self, String> {
+        let mut reader = Reader { data, pos: 0 };
+        let (version, mut counts) = reader.header()?;
+        let mut time_size = 4;
+        if version >= b'2' {
+            // Skip the 32-bit block; the 64-bit one after it covers every date
+            reader.skip(counts.block_len(4))?;
+            counts = reader.header()?.1;
+            time_size = 8;
+        }
+
+        let mut transitions = Vec::with_capacity(counts.time);
+        for _ in 0..counts.time {
+            transitions.push(reader.int(time_size)?);
+        }
+        let indices = reader.take(counts.time)?.to_vec();
+        let mut type_offsets = Vec::with_capacity(counts.types);
+        for _ in 0..counts.types {
+            type_offsets.push(reader.int(4)? as i32);
+            reader.skip(2)?; // isdst, abbreviation index
+        }
+        if type_offsets.is_empty() {
+            return Err("no local time types".to_string());
+        }
+        let offsets = indices
+            .iter()
+            .map(|&i| type_offsets.get(i as usize).copied())
+            .collect::<Option<Vec<_>>>()
+            .ok_or("transition refers to an unknown time type")?;
+        reader.skip(counts.chars + counts.leaps * (time_size + 4) + counts.std + counts.ut)?;
+
+        let rule = if version >= b'2' {
+            let footer = reader.data.get(reader.pos..).unwrap_or_default();
+            let footer = String::from_utf8_lossy(footer);
+            // A rule this parser can't read falls back to the last transition
+            parse_rule(footer.trim_matches('\n'))
+        } else {
+            None
+        };
+
+        Ok(TimeZone {
+            name: name.to_string(),
+            transitions,
+            offsets,
+            initial_offset: type_offsets[0],
+            rule,
+        })
+    }
+}
+
+struct Counts {
+    ut: usize,
+    std: usize,
+    leaps: usize,
+    time: usize,
+    types: usize,
+    chars: usize,
+}
+
+impl Counts {
+    fn block_len(&self, time_size: usize) -> usize {
+        self.time * (time_size + 1)
+            + self.types * 6
+            + self.chars
+            + self.leaps * (time_size + 4)
+            + self.std
+            + self.ut
+    }
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], String> {
+        let bytes = self
+            .data
+            .get(self.pos..self.pos + len)
+            .ok_or("truncated TZif data")?;
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn skip(&mut self, len: usize) -> Result<(), String> {
+        self.take(len).map(|_| ())
+    }
+
+    /// Big-endian signed integer of 4 or 8 bytes.
+    fn int(&mut self, size: usize) -> Result<i64, String> {
+        let bytes = self.take(size)?;
+        Ok(match size {
+            4 => i32::from_be_bytes(bytes.try_into().expect("4 bytes")) as i64,
+            _ => i64::from_be_bytes(bytes.try_into().expect("8 bytes")),
+        })
+    }
+
+    fn header(&mut self) -> Result<(u8, Counts), String> {
+        if self.take(4)? != b"TZif" {
+            return Err("not a TZif file".to_string());
+        }
+        let version = self.take(1)?[0];
+        self.skip(15)?;
+        let mut count = || self.int(4).map(|n| n.max(0) as usize);
+        Ok((
+            version,
+            Counts {
+                ut: count()?,
+                std: count()?,
+                leaps: count()?,
+                time: count()?,
+                types: count()?,
+                chars: count()?,
+            },
+        ))
+    }
+}
+
+impl Rule {
+    fn offset_at(&self, secs: i64) -> i32 {
+        let Some(dst) = &self.dst else {
+            return self.std_offset;
+        };
+        let year = civil_from_days((secs + self.std_offset as i64).div_euclid(86400)).0;
+        let start = dst.start.0.day(year) * 86400 + dst.start.1 as i64 - self.std_offset as i64;
+        let end = dst.end.0.day(year) * 86400 + dst.end.1 as i64 - dst.offset as i64;
+        let in_dst = if start <= end {
+            start <= secs && secs < end
+        } else {
+            // Southern hemisphere: daylight time spans the new year
+            !(end <= secs && secs < start)
+        };
+        if in_dst {
+            dst.offset
+        } else {
+            self.std_offset
+        }
+    }
+}
+
+impl DateRule {
+    /// Days since the Unix epoch of this date in `year`.
+    fn day(self, year: i64) -> i64 {
+        let jan1 = days_from_civil(year, 1, 1);
+        match self {
+            DateRule::Julian(n) => {
+                let skip_leap_day = is_leap(year) && n >= 60;
+                jan1 + n as i64 - 1 + skip_leap_day as i64
+            }
+            DateRule::Ordinal(n) => jan1 + n as i64,
+            DateRule::MonthWeekDay(month, week, weekday) => {
+                let first = days_from_civil(year, month, 1);
+                let first_weekday = (first + 4).rem_euclid(7); // 1970-01-01 was a Thursday
+                let mut offset =
+                    (weekday as i64 - first_weekday).rem_euclid(7) + (week as i64 - 1) * 7;
+                while offset >= days_in_month(year, month) {
+                    offset -= 7;
+                }
+                first + offset
+            }
+        }
+    }
+}
+
+/// Parse a POSIX TZ string such as `CET-1CEST,M3.5.0,M10.5.0/3`. POSIX
+/// offsets count west of UTC, so their sign is flipped here.
+fn parse_rule(s: &str) -> Option<Rule> {
+    let mut p = RuleParser {
+        s: s.as_bytes(),
+        pos: 0,
+    };
+    p.name()?;
+    let std_offset = -p.seconds()?;
+    if p.done() {
+        return Some(Rule {
+            std_offset,
+            dst: None,
+        });
+    }
+
+    p.name()?;
+    let offset = match p.peek() {
+        None | Some(b',') => std_offset + 3600,
+        _ => -p.seconds()?,
+    };
+    let (start, end) = if p.done() {
+        // POSIX leaves the default unspecified; use the US rules like glibc
+        (
+            (DateRule::MonthWeekDay(3, 2, 0), 7200),
+            (DateRule::MonthWeekDay(11, 1, 0), 7200),
+        )
+    } else {
+        p.eat(b',').then_some(())?;
+        let start = p.transition()?;
+        p.eat(b',').then_some(())?;
+        (start, p.transition()?)
+    };
+    p.done().then_some(Rule {
+        std_offset,
+        dst: Some(DstRule { offset, start, end }),
+    })
+}
+
+struct RuleParser<'a> {
+    s: &'a [u8],
+    pos: usize,
+}
+
+impl RuleParser<'_> {
+    fn peek(&self) -> Option<u8> {
+        self.s.get(self.pos).copied()
+    }
+
+    fn done(&self) -> bool {
+        self.pos == self.s.len()
+    }
+
+    fn eat(&mut self, c: u8) -> bool {
+        let found = self.peek() == Some(c);
+        self.pos += found as usize;
+        found
+    }
+
+    /// Zone abbreviation, either alphabetic or `<...>` quoted.
+    fn name(&mut self) -> Option<()> {
+        let start = self.pos;
+        if self.eat(b'<') {
+            while self.peek()? != b'>' {
+                self.pos += 1;
+            }
+            self.pos += 1;
+        } else {
+            while self.peek().is_some_and(|c| c.is_ascii_alphabetic()) {
+                self.pos += 1;
+            }
+        }
+        (self.pos > start).then_some(())
+    }
+
+    fn number(&mut self) -> Option<i64> {
+        let start = self.pos;
+        while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            self.pos += 1;
+        }
+        std::str::from_utf8(&self.s[start..self.pos])
+            .ok()?
+            .parse()
+            .ok()
+    }
+
+    /// `[+-]hh[:mm[:ss]]` as signed seconds.
+    fn seconds(&mut self) -> Option<i32> {
+        let sign = if self.eat(b'-') {
+            -1
+        } else {
+            self.eat(b'+');
+            1
+        };
+        let mut total = self.number()? * 3600;
+        if self.eat(b':') {
+            total += self.number()? * 60;
+            if self.eat(b':') {
+                total += self.number()?;
+            }
+        }
+        i32::try_from(sign * total).ok()
+    }
+
+    /// `date[/time]`, with the time defaulting to 02:00.
+    fn transition(&mut self) -> Option<(DateRule, i32)> {
+        let date = if self.eat(b'J') {
+            DateRule::Julian(self.number()?.try_into().ok()?)
+        } else if self.eat(b'M') {
+            let month = self.number()?;
+            self.eat(b'.').then_some(())?;
+            let week = self.number()?;
+            self.eat(b'.').then_some(())?;
+            let weekday = self.number()?;
+            if !(1..=12).contains(&month) || !(1..=5).contains(&week) || weekday > 6 {
+                return None;
+            }
+            DateRule::MonthWeekDay(month as u32, week as u32, weekday as u32)
+        } else {
+            DateRule::Ordinal(self.number()?.try_into().ok()?)
+        };
+        let time = if self.eat(b'/') {
+            self.seconds()?
+        } else {
+            7200
+        };
+        Some((date, time))
+    }
+}
+
+fn is_leap(year: i64) -> bool {
+    (year % 4 == 0 && year % 100 != 0) || year % 400 == 0
+}
+
+fn days_in_month(year: i64, month: u32) -> i64 {
+    match month {
+        2 if is_leap(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Days since 1970-01-01 of a proleptic Gregorian date.
+pub(crate) fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month as i64 + 9) % 12) + 2) / 5 + day as i64 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+/// Inverse of `days_from_civil`: (year, month, day).
+pub(crate) fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days - era * 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = year_of_era + era * 400 + (month <= 2) as i64;
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule_zone(rule: &str) -> TimeZone {
+        TimeZone {
+            name: "test".to_string(),
+            transitions: Vec::new(),
+            offsets: Vec::new(),
+            initial_offset: 0,
+            rule: parse_rule(rule),
+        }
+    }
+
+    #[test]
+    fn test_civil_days() {
+        assert_eq!(days_from_civil(1970, 1, 1), 0);
+        assert_eq!(days_from_civil(2024, 2, 29), 19782);
+        assert_eq!(civil_from_days(19782), (2024, 2, 29));
+        assert_eq!(civil_from_days(-1), (1969, 12, 31));
+    }
+
+    #[test]
+    fn test_posix_rules() {
+        let berlin = rule_zone("CET-1CEST,M3.5.0,M10.5.0/3");
+        // 2026-03-29 00:59:59Z / 01:00:00Z straddle the spring change
+        assert_eq!(berlin.offset_at(1774745999), 3600);
+        assert_eq!(berlin.offset_at(1774746000), 7200);
+        // 2026-10-25 00:59:59Z / 01:00:00Z straddle the autumn change
+        assert_eq!(berlin.offset_at(1792889999), 7200);
+        assert_eq!(berlin.offset_at(1792890000), 3600);
+
+        let sydney = rule_zone("AEST-10AEDT,M10.1.0,M4.1.0/3");
+        assert_eq!(sydney.offset_at(1767225600), 11 * 3600); // 2026-01-01
+        assert_eq!(sydney.offset_at(1782864000), 10 * 3600); // 2026-07-01
+
+        assert_eq!(rule_zone("<+0530>-5:30").offset_at(0), 19800);
+        assert_eq!(rule_zone("EST5").offset_at(0), -5 * 3600);
+        assert!(parse_rule("CET-1CEST,M13.1.0,M10.5.0").is_none());
+    }
+
+    #[test]
+    fn test_load_system_zone() {
+        // zoneinfo may be missing from minimal images
+        let Ok(shanghai) = TimeZone::load("Asia/Shanghai") else {
+            return;
+        };
+        assert_eq!(shanghai.name(), "Asia/Shanghai");
+        assert_eq!(shanghai.offset_at(1767225600), 8 * 3600);
+        assert!(TimeZone::load("../etc/passwd").is_err());
+    }
+}
@@ -0,0 +1,98 @@
+//! Field-level checks on request bodies that deserialized fine but make no
+//! sense, collected so one `AppError::Validation` reports all of them.
+
+use crate::error::AppError;
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// Longest timeout a request may ask for, in seconds (one week)
+pub const MAX_TIMEOUT_SECS: u64 = 7 * 24 * 60 * 60;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FieldError {
+    /// The field's name in the JSON body, e.g. `idleTimeoutSecs` or `args[2]`
+    pub field: String,
+    pub message: String,
+}
+
+pub trait Validate {
+    fn validate(&self, v: &mut Validator);
+}
+
+#[derive(Default)]
+pub struct Validator {
+    errors: Vec<FieldError>,
+}
+
+impl Validator {
+    /// Record `message` against `field` unless `ok`.
+    pub fn check(&mut self, ok: bool, field: &str, message: impl Into<String>) {
+        if !ok {
+            self.errors.push(FieldError {
+                field: field.to_string(),
+                message: message.into(),
+            });
+        }
+    }
+
+    /// A command line: a non-empty command and no NUL bytes anywhere, which
+    /// exec(2) cannot pass.
+    pub fn command(&mut self, command: &str, args: Option<&[String]>) {
+        self.check(!command.trim().is_empty(), "command", "must not be empty");
+        self.check(!command.contains('\0'), "command", "must not contain NUL");
+        for (i, arg) in args.unwrap_or_default().iter().enumerate() {
+            self.check(
+                !arg.contains('\0'),
+                &format!("args[{}]", i),
+                "must not contain NUL",
+            );
+        }
+    }
+
+    pub fn env(&mut self, env: Option<&HashMap<String, String>>) {
+        for (key, value) in env.into_iter().flatten() {
+            self.check(
+                is_env_name(key),
+                &format!("env.{}", key),
+                "name must be non-empty without '=' or NUL",
+            );
+            self.check(
+                !value.contains('\0'),
+                &format!("env.{}", key),
+                "value must not contain NUL",
+            );
+        }
+    }
+
+    /// Seconds in `1..=MAX_TIMEOUT_SECS`, or `0..` with `allow_zero` for
+    /// options where 0 means off.
+    pub fn timeout(&mut self, field: &str, secs: Option<u64>, allow_zero: bool) {
+        let Some(secs) = secs else { return };
+        let min = if allow_zero { 0 } else { 1 };
+        self.check(
+            (min..=MAX_TIMEOUT_SECS).contains(&secs),
+            field,
+            format!("must be between {} and {} seconds", min, MAX_TIMEOUT_SECS),
+        );
+    }
+
+    pub fn finish(self) -> Result<(), AppError> {
+        if self.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(AppError::Validation(self.errors))
+        }
+    }
+}
+
+/// Run `req`'s checks, failing with every problem found.
+pub fn validate<T: Validate>(req: &T) -> Result<(), AppError> {
+    let mut v = Validator::default();
+    req.validate(&mut v);
+    v.finish()
+}
+
+/// Whether `name` can be set as an environment variable.
+pub fn is_env_name(name: &str) -> bool {
+    !name.is_empty() && !name.contains(['=', '\0'])
+}
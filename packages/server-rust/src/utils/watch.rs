@@ -0,0 +1,293 @@
+//! Recursive directory watching on top of inotify. Every directory under the
+//! root gets its own watch; directories that appear later are picked up as
+//! their creation events arrive. Symbolic links and ignored directory names
+//! are never descended into.
+
+use nix::sys::inotify::{AddWatchFlags, InitFlags, Inotify, WatchDescriptor};
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::os::fd::{AsFd, AsRawFd, RawFd};
+use std::path::{Path, PathBuf};
+use tokio::fs;
+use tokio::io::unix::AsyncFd;
+
+/// `AsyncFd` needs `AsRawFd`, which `Inotify` only offers through `AsFd`.
+struct InotifyFd(Inotify);
+
+impl AsRawFd for InotifyFd {
+    fn as_raw_fd(&self) -> RawFd {
+        self.0.as_fd().as_raw_fd()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ChangeKind {
+    Created,
+    Modified,
+    Deleted,
+}
+
+impl ChangeKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ChangeKind::Created => "created",
+            ChangeKind::Modified => "modified",
+            ChangeKind::Deleted => "deleted",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum WatchEvent {
+    Change {
+        path: PathBuf,
+        kind: ChangeKind,
+        is_dir: bool,
+    },
+    /// The kernel queue overflowed and events were lost; callers should rescan.
+    Overflow,
+}
+
+pub struct DirWatcher {
+    inotify: AsyncFd<InotifyFd>,
+    dirs: HashMap<WatchDescriptor, PathBuf>,
+    ignored_dirs: HashSet<String>,
+}
+
+fn watch_flags() -> AddWatchFlags {
+    AddWatchFlags::IN_CREATE
+        | AddWatchFlags::IN_CLOSE_WRITE
+        | AddWatchFlags::IN_DELETE
+        | AddWatchFlags::IN_MOVED_FROM
+        | AddWatchFlags::IN_MOVED_TO
+        | AddWatchFlags::IN_DELETE_SELF
+        | AddWatchFlags::IN_ONLYDIR
+        | AddWatchFlags::IN_DONT_FOLLOW
+}
+
+impl DirWatcher {
+    pub fn new(ignored_dirs: HashSet<String>) -> io::Result<Self> {
+        let inotify = Inotify::init(InitFlags::IN_NONBLOCK | InitFlags::IN_CLOEXEC)?;
+        Ok(Self {
+            inotify: AsyncFd::new(InotifyFd(inotify))?,
+            dirs: HashMap::new(),
+            ignored_dirs,
+        })
+    }
+
+    /// Watch `dir` and every directory below it. Returns the entries found on
+    /// the way (files and directories, symlinks excluded), so callers can
+    /// report the current contents without walking the tree twice.
+    pub async fn watch_tree(&mut self, dir: &Path) -> Vec<fs::DirEntry> {
+        let mut found = Vec::new();
+        let mut stack = vec![dir.to_path_buf()];
+        while let Some(current) = stack.pop() {
+            match self.inotify.get_ref().0.add_watch(&current, watch_flags()) {
+                Ok(wd) => {
+                    self.dirs.insert(wd, current.clone());
+                }
+                Err(e) => {
+                    eprintln!("Failed to watch {}: {}", current.display(), e);
+                    continue;
+                }
+            }
+            let mut entries = match fs::read_dir(&current).await {
+                Ok(e) => e,
+                Err(_) => continue, // Removed or unreadable
+            };
+            while let Ok(Some(entry)) = entries.next_entry().await {
+                let Ok(file_type) = entry.file_type().await else {
+                    continue;
+                };
+                if file_type.is_symlink() {
+                    continue;
+                }
+                if file_type.is_dir() {
+                    if self.is_ignored(&entry.file_name().to_string_lossy()) {
+                        continue;
+                    }
+                    stack.push(entry.path());
+                }
+                found.push(entry);
+            }
+        }
+        found
+    }
+
+    /// Watch `dir` alone, including attribute changes (`chmod`, `touch`) of
+    /// its entries, which recursive watches leave out.
+    pub fn watch_dir(&mut self, dir: &Path) -> io::Result<()> {
+        let wd = self
+            .inotify
+            .get_ref()
+            .0
+            .add_watch(dir, watch_flags() | AddWatchFlags::IN_ATTRIB)?;
+        self.dirs.insert(wd, dir.to_path_buf());
+        Ok(())
+    }
+
+    /// False once every watched directory, including the root, has been removed.
+    pub fn is_watching(&self) -> bool {
+        !self.dirs.is_empty()
+    }
+
+    fn is_ignored(&self, name: &str) -> bool {
+        self.ignored_dirs.contains(name)
+    }
+
+    /// Stop watching `dir` and the directories below it after it moved away;
+    /// if it moved within the tree, its new path arrives as `Created`.
+    fn unwatch_tree(&mut self, dir: &Path) {
+        let inotify = &self.inotify.get_ref().0;
+        self.dirs.retain(|wd, path| {
+            if !path.starts_with(dir) {
+                return true;
+            }
+            let _ = inotify.rm_watch(*wd);
+            false
+        });
+    }
+
+    /// Wait for the next batch of events. New directories are reported as
+    /// `Created` but not yet watched; pass them to `watch_tree`. Returns an
+    /// empty batch once nothing is left to watch.
+    pub async fn next_events(&mut self) -> io::Result<Vec<WatchEvent>> {
+        loop {
+            let mut guard = self.inotify.readable().await?;
+            let raw = match guard.try_io(|fd| fd.get_ref().0.read_events().map_err(io::Error::from))
+            {
+                Ok(result) => result?,
+                Err(_would_block) => continue,
+            };
+
+            let mut events = Vec::new();
+            for event in raw {
+                if event.mask.contains(AddWatchFlags::IN_Q_OVERFLOW) {
+                    events.push(WatchEvent::Overflow);
+                    continue;
+                }
+                if event
+                    .mask
+                    .intersects(AddWatchFlags::IN_IGNORED | AddWatchFlags::IN_DELETE_SELF)
+                {
+                    // Watched directory is gone
+                    self.dirs.remove(&event.wd);
+                    continue;
+                }
+                let (Some(dir), Some(name)) = (self.dirs.get(&event.wd), event.name) else {
+                    continue;
+                };
+                let is_dir = event.mask.contains(AddWatchFlags::IN_ISDIR);
+                if is_dir && self.is_ignored(&name.to_string_lossy()) {
+                    continue;
+                }
+                let kind = if event
+                    .mask
+                    .intersects(AddWatchFlags::IN_CREATE | AddWatchFlags::IN_MOVED_TO)
+                {
+                    ChangeKind::Created
+                } else if event
+                    .mask
+                    .intersects(AddWatchFlags::IN_CLOSE_WRITE | AddWatchFlags::IN_ATTRIB)
+                {
+                    ChangeKind::Modified
+                } else {
+                    ChangeKind::Deleted
+                };
+                let path = dir.join(name);
+                if is_dir && event.mask.contains(AddWatchFlags::IN_MOVED_FROM) {
+                    self.unwatch_tree(&path);
+                }
+                events.push(WatchEvent::Change { path, kind, is_dir });
+            }
+            if !events.is_empty() || !self.is_watching() {
+                return Ok(events);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_dir_watcher() {
+        let root = std::env::temp_dir().join(format!(
+            "devbox-watch-{}",
+            crate::utils::common::generate_id()
+        ));
+        std::fs::create_dir_all(root.join("sub")).unwrap();
+        std::fs::create_dir_all(root.join("node_modules")).unwrap();
+        std::fs::write(root.join("sub/a.txt"), "a").unwrap();
+
+        let mut watcher = DirWatcher::new(HashSet::from(["node_modules".to_string()])).unwrap();
+        let mut found: Vec<_> = watcher
+            .watch_tree(&root)
+            .await
+            .iter()
+            .map(|e| e.file_name().to_string_lossy().to_string())
+            .collect();
+        found.sort();
+        assert_eq!(found, vec!["a.txt", "sub"]);
+
+        std::fs::write(root.join("sub/b.txt"), "b").unwrap();
+        std::fs::write(root.join("node_modules/x.js"), "x").unwrap();
+        std::fs::remove_file(root.join("sub/a.txt")).unwrap();
+
+        let mut changes = Vec::new();
+        while changes.len() < 3 {
+            let events =
+                tokio::time::timeout(std::time::Duration::from_secs(5), watcher.next_events())
+                    .await
+                    .unwrap()
+                    .unwrap();
+            for event in events {
+                if let WatchEvent::Change { path, kind, .. } = event {
+                    changes.push((path.strip_prefix(&root).unwrap().to_path_buf(), kind));
+                }
+            }
+        }
+        assert_eq!(
+            changes,
+            vec![
+                (PathBuf::from("sub/b.txt"), ChangeKind::Created),
+                (PathBuf::from("sub/b.txt"), ChangeKind::Modified),
+                (PathBuf::from("sub/a.txt"), ChangeKind::Deleted),
+            ]
+        );
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_moved_dir_is_unwatched() {
+        let root = std::env::temp_dir().join(format!(
+            "devbox-watch-move-{}",
+            crate::utils::common::generate_id()
+        ));
+        let outside = root.with_extension("moved");
+        std::fs::create_dir_all(root.join("tree/sub/inner")).unwrap();
+
+        let mut watcher = DirWatcher::new(HashSet::new()).unwrap();
+        watcher.watch_tree(&root.join("tree")).await;
+        assert_eq!(watcher.dirs.len(), 3);
+
+        std::fs::rename(root.join("tree/sub"), &outside).unwrap();
+        let events = tokio::time::timeout(std::time::Duration::from_secs(5), watcher.next_events())
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(matches!(
+            &events[0],
+            WatchEvent::Change { path, kind: ChangeKind::Deleted, is_dir: true }
+                if path == &root.join("tree/sub")
+        ));
+        // Neither the moved directory nor its children keep their old paths
+        let watched: Vec<&PathBuf> = watcher.dirs.values().collect();
+        assert_eq!(watched, vec![&root.join("tree")]);
+
+        std::fs::remove_dir_all(&root).unwrap();
+        std::fs::remove_dir_all(&outside).unwrap();
+    }
+}